@@ -17,29 +17,29 @@ fn setup(
     mut scheduler: UndoScheduler<UndoCallbackEvent>,
     asset: Res<AssetServer>,
 ) {
-    commands.spawn(Camera2dBundle::default());
+    commands.spawn(Camera2d);
     let text = commands
-        .spawn(Text2dBundle {
-            text: Text::from_section("Please Press [R]: Delete text", TextStyle {
-                font: asset.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 31.,
+        .spawn((
+            Text2d::new("Please Press [R]: Delete text"),
+            TextFont {
+                font: asset.load("fonts/FiraSans-Bold.ttf").into(),
+                font_size: FontSize::Px(31.),
                 ..default()
-            }),
-            ..default()
-        })
+            },
+        ))
         .id();
 
-    scheduler.register(UndoCallbackEvent::new(move |cmd| {
-        cmd.entity(text).despawn();
+    scheduler.register(UndoCallbackEvent::entity(text, |entity| {
+        entity.despawn();
     }));
 }
 
 
 fn keyboard_input_system(
     mut requester: UndoRequester,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    if key.pressed(KeyCode::R) {
+    if key.pressed(KeyCode::KeyR) {
         requester.undo();
     }
 }
@@ -2,18 +2,20 @@ use std::time::Duration;
 use bevy::app::{App, Startup, Update};
 use bevy::asset::AssetServer;
 use bevy::DefaultPlugins;
-use bevy::hierarchy::BuildChildren;
-use bevy::input::Input;
+use bevy::input::ButtonInput;
 use bevy::math::{Vec2, Vec3};
-use bevy::prelude::{any_with_component, Camera2dBundle, Children, Color, Commands, Component, Entity, Event, EventReader, IntoSystemConfigs, KeyCode, Query, Res, Sprite, Text, Text2dBundle, TextStyle, Transform, With};
-use bevy::sprite::SpriteBundle;
-use bevy::utils::default;
-use bevy_tweening::{Animator, EaseMethod, Tween, TweenCompleted, TweeningPlugin};
+use bevy::math::curve::EaseFunction;
+use bevy::prelude::{any_with_component, Camera2d, Children, Color, Commands, Component, default, Entity, FontSize, IntoScheduleConfigs, KeyCode, Message, MessageReader, Query, Res, Sprite, Text2d, TextColor, TextFont, Transform, With};
+use bevy_tweening::{AnimCompletedEvent, Tween, TweenAnim, TweeningPlugin};
 use bevy_tweening::lens::TransformPositionLens;
 
 use bevy_undo2::prelude::{AppUndoEx, UndoRequester, UndoScheduler};
 use bevy_undo2::UndoPlugin;
 
+const RED: Color = Color::srgb(1., 0., 0.);
+const GREEN: Color = Color::srgb(0., 1., 0.);
+const BLUE: Color = Color::srgb(0., 0., 1.);
+
 fn main() {
     App::new()
         .add_plugins((DefaultPlugins, TweeningPlugin))
@@ -27,7 +29,7 @@ fn main() {
             move_system,
             undo_color_system,
             undo_move_system,
-            tween_completed_system.run_if(any_with_component::<UndoMoveEvent>())
+            tween_completed_system.run_if(any_with_component::<UndoMoveEvent>)
         ))
         .run();
 }
@@ -40,56 +42,55 @@ struct ColorBox;
 #[derive(Component)]
 struct Movable;
 
-#[derive(Event, Copy, Clone)]
+#[derive(Message, Copy, Clone)]
 struct ChangeColorEvent(Color);
 
-#[derive(Event, Copy, Clone, Component)]
+#[derive(Message, Copy, Clone, Component)]
 struct UndoMoveEvent(Vec3);
 
 fn setup(mut commands: Commands, asset: Res<AssetServer>) {
-    commands.spawn(Camera2dBundle::default());
+    commands.spawn(Camera2d);
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
+        .spawn((
+            Sprite {
                 custom_size: Some(Vec2::new(100., 100.)),
-                color: Color::RED,
+                color: RED,
                 ..default()
             },
-            transform: Transform::from_xyz(-300., 0., 0.),
-            ..default()
-        })
+            Transform::from_xyz(-300., 0., 0.),
+        ))
         .insert(ColorBox);
 
     commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
+        .spawn((
+            Sprite {
                 custom_size: Some(Vec2::new(100., 100.)),
                 ..default()
             },
-            transform: Transform::from_xyz(0., 0., 0.),
-            ..default()
-        })
+            Transform::from_xyz(0., 0., 0.),
+        ))
 
         .insert(Movable)
         .with_children(|parent| {
-            parent.spawn(Text2dBundle {
-                text: Text::from_section("Stop", TextStyle {
-                    font: asset.load("fonts/FiraSans-Bold.ttf"),
-                    font_size: 31.,
-                    color: Color::BLACK,
-                }),
-                transform: Transform::from_xyz(0., 0., 1.),
-                ..default()
-            });
+            parent.spawn((
+                Text2d::new("Stop"),
+                TextFont {
+                    font: asset.load("fonts/FiraSans-Bold.ttf").into(),
+                    font_size: FontSize::Px(31.),
+                    ..default()
+                },
+                TextColor(Color::BLACK),
+                Transform::from_xyz(0., 0., 1.),
+            ));
         });
 }
 
 
 fn request_undo_system(
     mut requester: UndoRequester,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    if key.just_pressed(KeyCode::R) {
+    if key.just_pressed(KeyCode::KeyR) {
         requester.undo();
     }
 }
@@ -98,56 +99,55 @@ fn request_undo_system(
 fn change_color_system(
     mut scheduler: UndoScheduler<ChangeColorEvent>,
     mut color_box: Query<&mut Sprite, With<ColorBox>>,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    let mut color_box = color_box.single_mut();
+    let mut color_box = color_box.single_mut().unwrap();
     let mut register = move |new_color: Color| {
         let color = color_box.color;
         color_box.color = new_color;
         scheduler.register(ChangeColorEvent(color));
     };
 
-    if key.just_pressed(KeyCode::Key1) {
-        register(Color::RED);
-    } else if key.just_pressed(KeyCode::Key2) {
-        register(Color::GREEN);
-    } else if key.just_pressed(KeyCode::Key3) {
-        register(Color::BLUE);
+    if key.just_pressed(KeyCode::Digit1) {
+        register(RED);
+    } else if key.just_pressed(KeyCode::Digit2) {
+        register(GREEN);
+    } else if key.just_pressed(KeyCode::Digit3) {
+        register(BLUE);
     }
 }
 
 
 fn move_system(
     mut commands: Commands,
-    mut text: Query<&mut Text>,
+    mut text: Query<&mut Text2d>,
     mov: Query<(Entity, &Transform, &Children), With<Movable>>,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    let (me, mt, children) = mov.single();
+    let (me, mt, children) = mov.single().unwrap();
 
     let start = mt.translation;
     let mut start_move = |message: &str, relative: Vec3| {
         let tween = Tween::new(
-            EaseMethod::Linear,
+            EaseFunction::Linear,
             Duration::from_millis(500),
             TransformPositionLens {
                 start,
                 end: start + relative * 100.,
             },
-        )
-            .with_completed_event(0);
+        );
 
-        text.get_mut(*children.first().unwrap()).unwrap().sections[0].value = message.to_string();
-        commands.entity(me).insert(Animator::new(tween)).insert(UndoMoveEvent(start));
+        text.get_mut(*children.first().unwrap()).unwrap().0 = message.to_string();
+        commands.entity(me).insert(TweenAnim::new(tween)).insert(UndoMoveEvent(start));
     };
 
-    if key.just_pressed(KeyCode::Left) {
+    if key.just_pressed(KeyCode::ArrowLeft) {
         start_move("Left", Vec3::NEG_X);
-    } else if key.just_pressed(KeyCode::Up) {
+    } else if key.just_pressed(KeyCode::ArrowUp) {
         start_move("Up", Vec3::Y);
-    } else if key.just_pressed(KeyCode::Right) {
+    } else if key.just_pressed(KeyCode::ArrowRight) {
         start_move("Right", Vec3::X);
-    } else if key.just_pressed(KeyCode::Down) {
+    } else if key.just_pressed(KeyCode::ArrowDown) {
         start_move("Down", Vec3::NEG_Y);
     }
 }
@@ -155,14 +155,14 @@ fn move_system(
 
 fn tween_completed_system(
     mut commands: Commands,
-    mut er: EventReader<TweenCompleted>,
+    mut er: MessageReader<AnimCompletedEvent>,
     mut scheduler: UndoScheduler<UndoMoveEvent>,
-    mut text: Query<&mut Text>,
+    mut text: Query<&mut Text2d>,
     mov: Query<(Entity, &Children, &UndoMoveEvent), With<Movable>>,
 ) {
-    for _ in er.iter() {
-        let (me, children, undo_event) = mov.single();
-        text.get_mut(*children.first().unwrap()).unwrap().sections[0].value = "Stop".to_string();
+    for _ in er.read() {
+        let (me, children, undo_event) = mov.single().unwrap();
+        text.get_mut(*children.first().unwrap()).unwrap().0 = "Stop".to_string();
         commands.entity(me).remove::<UndoMoveEvent>();
         scheduler.register(*undo_event);
     }
@@ -170,19 +170,19 @@ fn tween_completed_system(
 
 
 fn undo_move_system(
-    mut er: EventReader<UndoMoveEvent>,
+    mut er: MessageReader<UndoMoveEvent>,
     mut mov: Query<&mut Transform, With<Movable>>,
 ) {
-    for e in er.iter() {
-        mov.single_mut().translation = e.0;
+    for e in er.read() {
+        mov.single_mut().unwrap().translation = e.0;
     }
 }
 
 
 fn undo_color_system(
-    mut er: EventReader<ChangeColorEvent>,
+    mut er: MessageReader<ChangeColorEvent>,
     mut color_box: Query<&mut Sprite, With<ColorBox>>,
 ) {
-    let Some(ChangeColorEvent(color)) = er.iter().next() else { return; };
-    color_box.single_mut().color = *color;
+    let Some(ChangeColorEvent(color)) = er.read().next() else { return; };
+    color_box.single_mut().unwrap().color = *color;
 }
\ No newline at end of file
@@ -2,7 +2,7 @@ use bevy::prelude::*;
 use bevy_undo2::prelude::*;
 
 
-#[derive(Event, Debug, Clone)]
+#[derive(Message, Debug, Clone)]
 enum UndoColorEvent {
     Red,
     Blue,
@@ -26,9 +26,9 @@ fn main() {
 
 fn reserve_red_system(
     mut scheduler: UndoScheduler<UndoColorEvent>,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    if key.just_pressed(KeyCode::Key1) {
+    if key.just_pressed(KeyCode::Digit1) {
         println!("Reserved {:?}", UndoColorEvent::Red);
         scheduler.reserve(UndoColorEvent::Red);
     }
@@ -37,9 +37,9 @@ fn reserve_red_system(
 
 fn reserve_blue_system(
     mut scheduler: UndoScheduler<UndoColorEvent>,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    if key.just_pressed(KeyCode::Key2) {
+    if key.just_pressed(KeyCode::Digit2) {
         println!("Reserved {:?}", UndoColorEvent::Blue);
         scheduler.reserve(UndoColorEvent::Blue);
     }
@@ -48,9 +48,9 @@ fn reserve_blue_system(
 
 fn register_all_reserved_system(
     mut committer: UndoReserveCommitter,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    if key.just_pressed(KeyCode::Key3) {
+    if key.just_pressed(KeyCode::Digit3) {
         println!("Register all reserved");
         committer.commit();
     }
@@ -59,16 +59,16 @@ fn register_all_reserved_system(
 
 fn request_undo_system(
     mut requester: UndoRequester,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    if key.just_pressed(KeyCode::R) {
+    if key.just_pressed(KeyCode::KeyR) {
         requester.undo();
     }
 }
 
 
-fn undo_system(mut er: EventReader<UndoColorEvent>) {
-    for event in er.iter() {
+fn undo_system(mut er: MessageReader<UndoColorEvent>) {
+    for event in er.read() {
         println!("{:?}", event);
     }
 }
\ No newline at end of file
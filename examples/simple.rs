@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_undo2::prelude::*;
 
-#[derive(Event, Clone)]
+#[derive(Message, Clone)]
 struct GreetEvent(String);
 
 
@@ -21,34 +21,34 @@ fn setup(
     mut scheduler: UndoScheduler<GreetEvent>,
     asset: Res<AssetServer>,
 ) {
-    commands.spawn(Camera2dBundle::default());
-    commands.spawn(Text2dBundle {
-        text: Text::from_section("Please Press [R]", TextStyle {
-            font: asset.load("fonts/FiraSans-Bold.ttf"),
-            font_size: 31.,
+    commands.spawn(Camera2d);
+    commands.spawn((
+        Text2d::new("Please Press [R]"),
+        TextFont {
+            font: asset.load("fonts/FiraSans-Bold.ttf").into(),
+            font_size: FontSize::Px(31.),
             ..default()
-        }),
-        ..default()
-    });
+        },
+    ));
     scheduler.register(GreetEvent("Undo!".to_string()));
 }
 
 
 fn keyboard_input_system(
     mut requester: UndoRequester,
-    key: Res<Input<KeyCode>>,
+    key: Res<ButtonInput<KeyCode>>,
 ) {
-    if key.pressed(KeyCode::R) {
+    if key.pressed(KeyCode::KeyR) {
         requester.undo();
     }
 }
 
 
 fn read_undo_event_system(
-    mut er: EventReader<GreetEvent>,
-    mut text: Query<&mut Text>,
+    mut er: MessageReader<GreetEvent>,
+    mut text: Query<&mut Text2d>,
 ) {
-    for GreetEvent(message) in er.iter() {
-        text.single_mut().sections[0].value = message.clone();
+    for GreetEvent(message) in er.read() {
+        text.single_mut().unwrap().0 = message.clone();
     }
 }
\ No newline at end of file
@@ -0,0 +1,273 @@
+use bevy::prelude::{Res, ResMut, Resource};
+
+use crate::{lock, UndoAreas, UndoTypeAreas};
+use crate::history::UndoHistory;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEntry;
+
+type UndoAmendment<E> = Box<dyn FnOnce(&mut E) + Send + Sync + 'static>;
+
+
+/// The amendments requested via [`UndoScheduler::amend_latest`](crate::prelude::UndoScheduler::amend_latest),
+/// applied once the entries registered in the same frame are recorded.
+pub(crate) struct UndoAmendments<E: UndoPayload>(pub Vec<UndoAmendment<E>>);
+
+
+impl<E: UndoPayload> Default for UndoAmendments<E> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+
+/// The payload requested via [`UndoScheduler::replace_latest`](crate::prelude::UndoScheduler::replace_latest),
+/// the last one requested in a frame taking effect.
+pub(crate) struct UndoReplacement<E: UndoPayload>(pub Option<E>);
+
+
+impl<E: UndoPayload> Default for UndoReplacement<E> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+
+/// The slots and types of the payloads replaced by another type, to be dropped from the area of their type.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoReplacedEntries(Vec<(usize, &'static str)>);
+
+
+#[inline]
+pub(crate) fn has_amendments<E: UndoPayload>(areas: Res<UndoAreas>) -> bool {
+    areas.get::<E>().is_some_and(|areas| !lock(&areas.amendments).0.is_empty())
+}
+
+
+#[inline]
+pub(crate) fn has_replacement<E: UndoPayload>(areas: Res<UndoAreas>) -> bool {
+    areas.get::<E>().is_some_and(|areas| lock(&areas.replacement).0.is_some())
+}
+
+
+#[inline]
+pub(crate) fn has_replaced_entries(replaced: Res<UndoReplacedEntries>) -> bool {
+    !replaced.0.is_empty()
+}
+
+
+/// Applies the amendments to the most recent entry of `E`, dropping them if there is none.
+pub(crate) fn amend_latest_system<E: UndoPayload>(
+    mut areas: ResMut<UndoAreas>,
+    history: Res<UndoHistory>,
+) {
+    let areas = areas.areas_mut::<E>();
+    let amendments = std::mem::take(&mut lock(&areas.amendments).0);
+    let registered_area = &mut areas.registered;
+    if amendments.is_empty() {
+        return;
+    }
+
+    let type_name = std::any::type_name::<E>();
+    let latest = history
+        .entries()
+        .rev()
+        .find(|entry| entry.type_name == type_name)
+        .and_then(|entry| registered_area.pop_entry(entry.no));
+    let Some(mut entry) = latest else {
+        return;
+    };
+    for amend in amendments {
+        amend(&mut entry.inner);
+    }
+    registered_area.push(entry);
+}
+
+
+/// Swaps the payload of the most recent entry with the replacement, keeping its slot and metadata.
+pub(crate) fn replace_latest_system<E: UndoPayload>(
+    mut areas: ResMut<UndoAreas>,
+    mut replaced: ResMut<UndoReplacedEntries>,
+    mut history: ResMut<UndoHistory>,
+) {
+    let areas = areas.areas_mut::<E>();
+    let replacement = lock(&areas.replacement).0.take();
+    let Some(event) = replacement else {
+        return;
+    };
+    let type_name = std::any::type_name::<E>();
+    let Some((no, previous)) = history.replace_latest(type_name) else {
+        return;
+    };
+    if previous == type_name {
+        pop_replaced(areas, no);
+    } else {
+        replaced.0.push((no, previous));
+    }
+    areas.registered.push(UndoEntry {
+        inner: event,
+        redo: None,
+        no,
+    });
+}
+
+
+/// Drops the payloads of `E` replaced by another type.
+pub(crate) fn drop_replaced_entries_system<E: UndoPayload>(
+    mut replaced: ResMut<UndoReplacedEntries>,
+    mut areas: ResMut<UndoAreas>,
+) {
+    let areas = areas.areas_mut::<E>();
+    if replaced.0.is_empty() {
+        return;
+    }
+    let type_name = std::any::type_name::<E>();
+    replaced.0.retain(|(no, previous)| {
+        if *previous != type_name {
+            return true;
+        }
+        pop_replaced(areas, *no);
+        false
+    });
+}
+
+
+/// Drops the replaced payload of the slot, which is a committed reservation if no entry was registered to it.
+#[inline]
+fn pop_replaced<E: UndoPayload>(areas: &mut UndoTypeAreas<E>, no: usize) {
+    #[cfg(feature = "reserve")]
+    if areas.registered.pop_entry(no).is_none() {
+        areas.reserved.pop_entry(no);
+    }
+    #[cfg(not(feature = "reserve"))]
+    areas.registered.pop_entry(no);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Local, Message, Messages, Update};
+
+    use crate::prelude::{AppUndoEx, UndoScheduler};
+    use crate::UndoAreas;
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Drag(i32);
+
+
+    #[test]
+    fn amend_latest_entry_in_place() {
+        let mut harness = UndoTestHarness::<Drag>::new();
+        harness.setup(|app| {
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Drag>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.register(Drag(1));
+                    scheduler.register(Drag(2));
+                    scheduler.amend_latest(|drag| drag.0 += 10);
+                } else if *frame == 2 {
+                    scheduler.amend_latest(|drag| drag.0 += 10);
+                }
+            });
+        });
+        harness.frames(3);
+        harness.undo();
+        harness.expect([Drag(22)]);
+        harness.undo();
+        harness.expect([Drag(1)]);
+        harness.undo();
+        harness.expect([]);
+    }
+
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Transform(i32);
+
+
+    #[test]
+    fn replace_latest_entry_across_types() {
+        let mut harness = UndoTestHarness::<Drag>::new();
+        harness.setup(|app| {
+            app.add_undo_event::<Transform>();
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Drag>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.register(Drag(1));
+                } else if *frame == 3 {
+                    scheduler.replace_latest(Drag(2));
+                }
+            });
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Transform>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 2 {
+                    scheduler.register(Transform(1));
+                }
+            });
+        });
+        harness.frames(4);
+        assert_eq!(harness.app().world().resource::<UndoAreas>().registered::<Transform>().0.len(), 0);
+
+        harness.undo();
+        harness.expect([Drag(2)]);
+        harness.undo();
+        harness.expect([Drag(1)]);
+        assert!(harness.app().world().resource::<Messages<Transform>>().is_empty());
+    }
+
+
+    #[cfg(feature = "reserve")]
+    #[test]
+    fn replace_latest_reserved_entry() {
+        let mut harness = UndoTestHarness::<Drag>::new();
+        harness.setup(|app| {
+            app.add_undo_event::<Transform>();
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Drag>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.reserve(Drag(1));
+                    scheduler.register_all_reserved();
+                } else if *frame == 3 {
+                    scheduler.replace_latest(Drag(2));
+                }
+            });
+        });
+        harness.frames(4);
+        assert_eq!(harness.app().world().resource::<UndoAreas>().areas::<Drag>().reserved.0.len(), 0);
+
+        harness.undo();
+        harness.expect([Drag(2)]);
+        harness.undo();
+        harness.expect([]);
+    }
+
+
+    #[cfg(feature = "reserve")]
+    #[test]
+    fn drop_reserved_entry_replaced_by_other_type() {
+        let mut harness = UndoTestHarness::<Transform>::new();
+        harness.setup(|app| {
+            app.add_undo_event::<Drag>();
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Drag>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.reserve(Drag(1));
+                    scheduler.register_all_reserved();
+                }
+            });
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Transform>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 3 {
+                    scheduler.replace_latest(Transform(1));
+                }
+            });
+        });
+        harness.frames(4);
+        assert_eq!(harness.app().world().resource::<UndoAreas>().areas::<Drag>().reserved.0.len(), 0);
+
+        harness.undo();
+        harness.expect([Transform(1)]);
+        assert!(harness.app().world().resource::<Messages<Drag>>().is_empty());
+    }
+}
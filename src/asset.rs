@@ -0,0 +1,118 @@
+use bevy::asset::{Asset, Assets, Handle};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Message, MessageReader, ResMut};
+
+use crate::meta::UndoMeta;
+use crate::undo_event::UndoScheduler;
+
+/// Restores the asset of the handle to the captured value when undone or redone.
+///
+/// It is registered by [`UndoAssets::modify`],
+/// and applied automatically if the asset type is set up via [`AppUndoEx::add_undo_asset`](crate::prelude::AppUndoEx::add_undo_asset).
+#[derive(Message, Clone)]
+pub struct UndoAssetEvent<A: Asset + Clone> {
+    pub handle: Handle<A>,
+    pub asset: A,
+}
+
+
+/// Mutates [`Assets`] while registering the restoration of the previous value.
+#[derive(SystemParam)]
+pub struct UndoAssets<'w, A: Asset + Clone> {
+    assets: ResMut<'w, Assets<A>>,
+    scheduler: UndoScheduler<'w, UndoAssetEvent<A>>,
+}
+
+
+impl<'w, A: Asset + Clone> UndoAssets<'w, A> {
+    /// Mutates the asset and registers an entry which restores the value before the mutation.
+    ///
+    /// The value after the mutation is kept for redo.
+    /// Returns false without registering anything if the asset does not exist.
+    #[inline]
+    pub fn modify(&mut self, handle: &Handle<A>, f: impl FnOnce(&mut A)) -> bool {
+        self.modify_with_meta(handle, UndoMeta::default(), f)
+    }
+
+
+    /// Same as [`UndoAssets::modify`], but the entry carries the metadata.
+    pub fn modify_with_meta(&mut self, handle: &Handle<A>, meta: UndoMeta, f: impl FnOnce(&mut A)) -> bool {
+        let Some(mut asset) = self.assets.get_mut(handle) else {
+            return false;
+        };
+        let before = asset.clone();
+        f(&mut asset);
+        let after = asset.clone();
+
+        self.scheduler.push(
+            UndoAssetEvent { handle: handle.clone(), asset: before },
+            Some(UndoAssetEvent { handle: handle.clone(), asset: after }),
+            meta,
+        );
+        true
+    }
+
+
+    /// Returns the asset without registering anything.
+    #[inline(always)]
+    pub fn get(&self, handle: &Handle<A>) -> Option<&A> {
+        self.assets.get(handle)
+    }
+}
+
+
+pub(crate) fn restore_asset_system<A: Asset + Clone>(
+    mut er: MessageReader<UndoAssetEvent<A>>,
+    mut assets: ResMut<Assets<A>>,
+) {
+    for event in er.read() {
+        let _ = assets.insert(&event.handle, event.asset.clone());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::asset::{Asset, AssetApp, AssetPlugin, Assets, Handle};
+    use bevy::prelude::{Local, MinimalPlugins, Reflect, Res, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoAssets, UndoChannel};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Asset, Reflect, Clone, Debug, PartialEq)]
+    struct Material(usize);
+
+    #[derive(Resource)]
+    struct Target(Handle<Material>);
+
+
+    #[test]
+    fn restore_modified_asset() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), UndoPlugin));
+        app.init_asset::<Material>();
+        app.add_undo_asset::<Material>();
+
+        let handle = app.world_mut().resource_mut::<Assets<Material>>().add(Material(1));
+        app.insert_resource(Target(handle.clone()));
+        app.add_systems(Update, |mut assets: UndoAssets<Material>, target: Res<Target>, mut done: Local<bool>| {
+            if !*done {
+                *done = true;
+                assert!(assets.modify(&target.0, |material| material.0 = 2));
+            }
+        });
+        app.update();
+        app.update();
+        assert_eq!(app.world().resource::<Assets<Material>>().get(&handle), Some(&Material(2)));
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Assets<Material>>().get(&handle), Some(&Material(1)));
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Assets<Material>>().get(&handle), Some(&Material(2)));
+    }
+}
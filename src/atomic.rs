@@ -0,0 +1,264 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{DetectChanges, Message, MessageWriter, Res, ResMut, Resource};
+use bevy::platform::collections::HashMap;
+
+use crate::channel::UndoChannel;
+use crate::history::{UndoHistory, UndoHistoryEntry};
+
+/// Groups entries across channels so they are undone or redone all at once, or not at all.
+///
+/// Entries join the group by registering them with [`UndoMeta::in_atomic_group`](crate::prelude::UndoMeta::in_atomic_group).
+/// Unlike [`UndoLink`](crate::prelude::UndoLink), the group is only processed while each of its entries is the next
+/// to undo or redo in its channel, and while none of them has been evicted or discarded.
+/// Otherwise requests reaching the group are dropped and [`UndoAtomicBlocked`] is sent.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+#[repr(transparent)]
+pub struct UndoAtomicGroup(pub u64);
+
+
+impl UndoAtomicGroup {
+    /// Creates a group distinct from the other groups created by this.
+    #[inline]
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+
+impl Default for UndoAtomicGroup {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// Sent when a request is dropped since the atomic group it reached cannot be processed as a whole.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoAtomicBlocked {
+    pub group: UndoAtomicGroup,
+}
+
+
+/// Coordinates the atomic groups, remembering the count of slots each group was registered with.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoAtomicGroups {
+    slots: HashMap<UndoAtomicGroup, usize>,
+}
+
+
+impl UndoAtomicGroups {
+    /// Extends the slots with the other members of their atomic groups.
+    ///
+    /// Returns the group which blocks the slots if any group cannot be processed as a whole.
+    pub fn resolve(&self, history: &UndoHistory, slots: Vec<usize>, redo: bool) -> Result<Vec<usize>, UndoAtomicGroup> {
+        let entries: Vec<&UndoHistoryEntry> = if redo {
+            history.redo_entries().collect()
+        } else {
+            history.entries().rev().collect()
+        };
+        let groups: Vec<UndoAtomicGroup> = entries
+            .iter()
+            .filter(|entry| slots.contains(&entry.no))
+            .filter_map(|entry| entry.meta.atomic_group)
+            .fold(Vec::new(), |mut groups, group| {
+                if !groups.contains(&group) {
+                    groups.push(group);
+                }
+                groups
+            });
+        if groups.is_empty() {
+            return Ok(slots);
+        }
+
+        let mut resolved = slots;
+        for group in groups {
+            let members = distinct_slots(entries.iter().filter(|entry| entry.meta.atomic_group == Some(group)));
+            if members.len() < self.slots.get(&group).copied().unwrap_or_default() {
+                return Err(group);
+            }
+
+            let channels = member_channels(&entries, &members);
+            for channel in channels {
+                let in_channel = distinct_slots(entries.iter().filter(|entry| entry.meta.channel == channel));
+                let members_in_channel = members
+                    .iter()
+                    .filter(|no| entries.iter().any(|entry| entry.no == **no && entry.meta.channel == channel))
+                    .count();
+                if !in_channel[..members_in_channel].iter().all(|no| members.contains(no)) {
+                    return Err(group);
+                }
+            }
+            for no in members {
+                if !resolved.contains(&no) {
+                    resolved.push(no);
+                }
+            }
+        }
+
+        if redo {
+            resolved.sort_unstable();
+        } else {
+            resolved.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        Ok(resolved)
+    }
+}
+
+
+/// Checks the slots to undo or redo against their atomic groups, reporting the groups which block them.
+#[derive(SystemParam)]
+pub(crate) struct UndoAtomicCoordinator<'w> {
+    groups: Res<'w, UndoAtomicGroups>,
+    blocked: MessageWriter<'w, UndoAtomicBlocked>,
+}
+
+
+impl<'w> UndoAtomicCoordinator<'w> {
+    /// Returns the slots extended with the members of their groups, or `None` after sending [`UndoAtomicBlocked`].
+    pub fn resolve(&mut self, history: &UndoHistory, slots: Vec<usize>, redo: bool) -> Option<Vec<usize>> {
+        match self.groups.resolve(history, slots, redo) {
+            Ok(slots) => Some(slots),
+            Err(group) => {
+                self.blocked.write(UndoAtomicBlocked { group });
+                None
+            }
+        }
+    }
+}
+
+
+/// Returns the slot numbers of the entries without duplicates, in the order they appear.
+fn distinct_slots<'a>(entries: impl Iterator<Item = &'a &'a UndoHistoryEntry>) -> Vec<usize> {
+    let mut slots = Vec::new();
+    for entry in entries {
+        if !slots.contains(&entry.no) {
+            slots.push(entry.no);
+        }
+    }
+    slots
+}
+
+
+fn member_channels(entries: &[&UndoHistoryEntry], members: &[usize]) -> Vec<UndoChannel> {
+    let mut channels = Vec::new();
+    for entry in entries.iter().filter(|entry| members.contains(&entry.no)) {
+        if !channels.contains(&entry.meta.channel) {
+            channels.push(entry.meta.channel);
+        }
+    }
+    channels
+}
+
+
+/// Records the count of slots each group is registered with, and forgets the groups no longer in the history.
+pub(crate) fn track_atomic_groups_system(
+    mut groups: ResMut<UndoAtomicGroups>,
+    history: Res<UndoHistory>,
+) {
+    if !history.is_changed() {
+        return;
+    }
+
+    let mut observed: HashMap<UndoAtomicGroup, Vec<usize>> = HashMap::default();
+    for entry in history.entries().chain(history.redo_entries()) {
+        if let Some(group) = entry.meta.atomic_group {
+            let slots = observed.entry(group).or_default();
+            if !slots.contains(&entry.no) {
+                slots.push(entry.no);
+            }
+        }
+    }
+    groups.slots.retain(|group, _| observed.contains_key(group));
+    for (group, slots) in observed {
+        let count = groups.slots.entry(group).or_default();
+        *count = (*count).max(slots.len());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoAtomicBlocked, UndoAtomicGroup, UndoChannel, UndoMeta, UndoScheduler, UndoStackConfig};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Edit(&'static str);
+
+
+    fn new_app() -> (App, SystemState<UndoScheduler<'static, Edit>>) {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        let state = SystemState::new(app.world_mut());
+        (app, state)
+    }
+
+
+    fn drain(app: &mut App) -> Vec<Edit> {
+        app.world_mut().resource_mut::<Messages<Edit>>().drain().collect()
+    }
+
+
+    #[test]
+    fn undo_group_as_a_whole() {
+        let (mut app, mut state) = new_app();
+        let group = UndoAtomicGroup::new();
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.register_with_meta(Edit("scene"), UndoMeta::default().in_atomic_group(group));
+        scheduler.register_with_meta(Edit("graph"), UndoMeta::default().with_channel(1).in_atomic_group(group));
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel(1)));
+        app.update();
+        assert_eq!(drain(&mut app), vec![Edit("graph"), Edit("scene")]);
+    }
+
+
+    #[test]
+    fn block_group_not_on_top_of_channel() {
+        let (mut app, mut state) = new_app();
+        let group = UndoAtomicGroup::new();
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.register_with_meta(Edit("scene"), UndoMeta::default().in_atomic_group(group));
+        scheduler.register_with_meta(Edit("graph"), UndoMeta::default().with_channel(1).in_atomic_group(group));
+        scheduler.register_to(1, Edit("other"));
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert!(drain(&mut app).is_empty());
+        let blocked: Vec<UndoAtomicBlocked> = app.world_mut().resource_mut::<Messages<UndoAtomicBlocked>>().drain().collect();
+        assert_eq!(blocked, vec![UndoAtomicBlocked { group }]);
+    }
+
+
+    #[test]
+    fn block_group_with_evicted_member() {
+        let (mut app, mut state) = new_app();
+        app.configure_undo_channel(1, UndoStackConfig::with_capacity(1));
+        let group = UndoAtomicGroup::new();
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.register_with_meta(Edit("scene"), UndoMeta::default().in_atomic_group(group));
+        scheduler.register_with_meta(Edit("graph"), UndoMeta::default().with_channel(1).in_atomic_group(group));
+        state.apply(app.world_mut());
+        app.update();
+        state.get_mut(app.world_mut()).unwrap().register_to(1, Edit("other"));
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert!(drain(&mut app).is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::log::warn;
+
+use crate::hooks::UndoHookContext;
+
+/// Configures the audit log enabled via [`AppUndoEx::enable_undo_audit`](crate::prelude::AppUndoEx::enable_undo_audit).
+///
+/// Once the file exceeds `max_bytes`, it is renamed to `<path>.1`, the older files being shifted up to `<path>.<max_files>`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoAuditConfig {
+    pub path: PathBuf,
+
+    /// The user written on each line, such as the account name of the developer editing the shared assets.
+    pub user: String,
+
+    pub max_bytes: u64,
+
+    /// The count of rotated files kept besides the current one.
+    pub max_files: usize,
+}
+
+
+impl UndoAuditConfig {
+    /// Returns the config writing to the path, rotating at 10 MiB and keeping 5 rotated files.
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            user: String::new(),
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+
+
+    #[inline]
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = user.into();
+        self
+    }
+
+
+    #[inline]
+    pub fn with_rotation(mut self, max_bytes: u64, max_files: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self.max_files = max_files;
+        self
+    }
+}
+
+
+/// The log file shared by the hooks of the registrations, undos and redos.
+#[derive(Clone)]
+pub(crate) struct UndoAuditLog(Arc<Mutex<UndoAuditFile>>);
+
+
+struct UndoAuditFile {
+    config: UndoAuditConfig,
+    file: Option<File>,
+    written: u64,
+}
+
+
+impl UndoAuditLog {
+    #[inline]
+    pub fn new(config: UndoAuditConfig) -> Self {
+        Self(Arc::new(Mutex::new(UndoAuditFile {
+            config,
+            file: None,
+            written: 0,
+        })))
+    }
+
+
+    /// Appends the line of the operation, warning instead of failing if the file can't be written.
+    pub fn append(&self, operation: &str, cx: &UndoHookContext) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let mut log = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let line = format!(
+            "{timestamp}\t{operation}\t{}\t{}\t{}\t{}\n",
+            cx.no,
+            cx.type_name,
+            escape(&cx.meta.tag),
+            escape(&log.config.user)
+        );
+        if let Err(error) = log.write(line.as_bytes()) {
+            warn!("undo audit log: {error}");
+        }
+    }
+}
+
+
+impl UndoAuditFile {
+    fn write(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        if 0 < self.written && self.config.max_bytes < self.written + line.len() as u64 {
+            self.file = None;
+            rotate(&self.config.path, self.config.max_files)?;
+            self.open()?;
+        }
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line)?;
+            self.written += line.len() as u64;
+        }
+        Ok(())
+    }
+
+
+    fn open(&mut self) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)?;
+        self.written = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+
+fn rotate(path: &Path, max_files: usize) -> std::io::Result<()> {
+    if max_files == 0 {
+        return std::fs::remove_file(path);
+    }
+    for n in (1..max_files).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+            std::fs::rename(from, rotated_path(path, n + 1))?;
+        }
+    }
+    std::fs::rename(path, rotated_path(path, 1))
+}
+
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_os_string();
+    rotated.push(format!(".{n}"));
+    PathBuf::from(rotated)
+}
+
+
+/// Keeps each operation on its own line and its fields apart.
+fn escape(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Message;
+
+    use crate::audit::rotated_path;
+    use crate::prelude::{AppUndoEx, UndoAuditConfig, UndoMeta, UndoScheduler};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[test]
+    fn append_operations_and_rotate() {
+        let dir = std::env::temp_dir().join(format!("bevy_undo2_audit_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("undo.log");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(rotated_path(&path, 1));
+
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.enable_undo_audit(UndoAuditConfig::new(&path)
+                .with_user("alice")
+                .with_rotation(150, 1));
+        });
+        let mut state = SystemState::<UndoScheduler<Move>>::new(harness.app().world_mut());
+        state.get_mut(harness.app().world_mut()).unwrap().push(Move(1), Some(Move(2)), UndoMeta::tagged("brush"));
+        state.apply(harness.app().world_mut());
+        harness.frames(1);
+        harness.undo();
+        harness.redo();
+
+        let rotated = std::fs::read_to_string(rotated_path(&path, 1)).unwrap();
+        let current = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<Vec<&str>> = rotated
+            .lines()
+            .chain(current.lines())
+            .map(|line| line.split('\t').collect())
+            .collect();
+        let operations: Vec<&str> = lines.iter().map(|line| line[1]).collect();
+        assert_eq!(operations, vec!["register", "undo", "redo"]);
+        assert!(lines.iter().all(|line| line[4] == "brush" && line[5] == "alice"));
+        assert!(lines[0][3].ends_with("Move"));
+        assert_eq!(rotated.lines().count(), 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
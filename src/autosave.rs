@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use bevy::prelude::{Message, MessageWriter, Res, ResMut, Resource, Time};
+
+use crate::history::UndoHistory;
+
+/// Sent when enough undoable actions have been registered since the last suggestion.
+///
+/// It is configured via [`AppUndoEx::configure_undo_autosave`](crate::prelude::AppUndoEx::configure_undo_autosave).
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct AutosaveSuggested {
+    /// The count of actions registered since the last suggestion.
+    pub actions: usize,
+}
+
+
+/// When to suggest autosave, whichever comes first.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct UndoAutosaveConfig {
+    /// Suggests after this count of actions, entries registered together are counted once.
+    pub every: Option<usize>,
+
+    /// Suggests once this time has passed since the first action after the last suggestion.
+    ///
+    /// This requires [`Time`], so it does nothing without [`TimePlugin`](bevy::time::TimePlugin).
+    pub after: Option<Duration>,
+}
+
+
+impl UndoAutosaveConfig {
+    #[inline(always)]
+    pub const fn every(actions: usize) -> Self {
+        Self {
+            every: Some(actions),
+            after: None,
+        }
+    }
+
+
+    #[inline(always)]
+    pub const fn after(mut self, duration: Duration) -> Self {
+        self.after = Some(duration);
+        self
+    }
+}
+
+
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoAutosave {
+    pub config: UndoAutosaveConfig,
+    seen: usize,
+    actions: usize,
+    since: Option<Duration>,
+}
+
+
+impl UndoAutosave {
+    #[inline(always)]
+    pub fn new(config: UndoAutosaveConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+}
+
+
+pub(crate) fn suggest_autosave_system(
+    mut ew: MessageWriter<AutosaveSuggested>,
+    mut autosave: ResMut<UndoAutosave>,
+    history: Res<UndoHistory>,
+    time: Option<Res<Time>>,
+) {
+    let now = time.map(|time| time.elapsed());
+    let registered = history.registered_slots();
+    if autosave.seen < registered {
+        autosave.actions += registered - autosave.seen;
+        autosave.seen = registered;
+        if autosave.since.is_none() {
+            autosave.since = now;
+        }
+    }
+    if autosave.actions == 0 {
+        return;
+    }
+
+    let by_count = autosave.config.every.is_some_and(|every| every <= autosave.actions);
+    let by_time = match (autosave.config.after, autosave.since, now) {
+        (Some(after), Some(since), Some(now)) => after <= now.saturating_sub(since),
+        _ => false
+    };
+    if by_count || by_time {
+        ew.write(AutosaveSuggested { actions: autosave.actions });
+        autosave.actions = 0;
+        autosave.since = None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, AutosaveSuggested, UndoAutosaveConfig, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Edit;
+
+
+    #[test]
+    fn suggest_every_n_actions() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        app.configure_undo_autosave(UndoAutosaveConfig::every(2));
+        app.add_systems(Startup, |mut s: UndoScheduler<Edit>| {
+            s.register(Edit);
+            s.register_all_grouped([Edit, Edit]);
+            s.register(Edit);
+        });
+        app.update();
+
+        let events = app.world().resource::<Messages<AutosaveSuggested>>();
+        assert_eq!(events.iter_current_update_messages().next(), Some(&AutosaveSuggested { actions: 3 }));
+        app.update();
+        let events = app.world().resource::<Messages<AutosaveSuggested>>();
+        assert_eq!(events.iter_current_update_messages().count(), 0);
+    }
+}
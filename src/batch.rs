@@ -0,0 +1,59 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Message, MessageWriter};
+
+/// Sent before a single request undoes or redoes more than one entry, such as a group.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoBatchStarted {
+    /// The count of entries the batch processes.
+    pub total: usize,
+}
+
+
+/// Sent after each slot of a batch is processed.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoBatchProgress {
+    /// The count of entries processed so far.
+    pub completed: usize,
+    pub total: usize,
+}
+
+
+/// Sent after all entries of a batch are processed.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoBatchFinished {
+    pub total: usize,
+}
+
+
+#[derive(SystemParam)]
+pub(crate) struct UndoBatchWriter<'w> {
+    started: MessageWriter<'w, UndoBatchStarted>,
+    progress: MessageWriter<'w, UndoBatchProgress>,
+    finished: MessageWriter<'w, UndoBatchFinished>,
+}
+
+
+impl<'w> UndoBatchWriter<'w> {
+    #[inline]
+    pub fn start(&mut self, total: usize) {
+        if 1 < total {
+            self.started.write(UndoBatchStarted { total });
+        }
+    }
+
+
+    #[inline]
+    pub fn progress(&mut self, completed: usize, total: usize) {
+        if 1 < total {
+            self.progress.write(UndoBatchProgress { completed, total });
+        }
+    }
+
+
+    #[inline]
+    pub fn finish(&mut self, total: usize) {
+        if 1 < total {
+            self.finished.write(UndoBatchFinished { total });
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use bevy::ecs::message::MessageCursor;
+use bevy::ecs::system::SystemParam;
+use bevy::ecs::world::EntityWorldMut;
+use bevy::prelude::{Component, Entity, Local, Message, Messages, Mut, Query, ResMut, Resource, With, World};
+
+use crate::meta::UndoMeta;
+use crate::partial::UndoOutcomeReports;
+use crate::undo_event::UndoScheduler;
+
+type UndoBulkRestoreFn<S> = Box<dyn Fn(&mut EntityWorldMut, &S) + Send + Sync + 'static>;
+
+
+/// Restores the snapshots of several entities as a single entry, see [`UndoBulkEdit`].
+#[derive(Message, Debug, Clone, PartialEq)]
+pub struct UndoBulkEvent<S: Clone + Send + Sync + 'static> {
+    pub snapshots: Vec<(Entity, S)>,
+}
+
+
+/// Applies a snapshot back onto its entity, set via [`AppUndoEx::add_undo_bulk`](crate::prelude::AppUndoEx::add_undo_bulk).
+#[derive(Resource)]
+pub(crate) struct UndoBulkRestore<S: Clone + Send + Sync + 'static>(pub UndoBulkRestoreFn<S>);
+
+
+/// The snapshots captured by [`UndoBulkEdit::begin`].
+#[derive(Resource)]
+pub(crate) struct UndoBulkStarts<S: Clone + Send + Sync + 'static>(Option<Vec<(Entity, S)>>);
+
+
+impl<S: Clone + Send + Sync + 'static> Default for UndoBulkStarts<S> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+
+/// Registers an edit of all the entities selected via the marker component `M` as a single entry.
+///
+/// The capture closure returns the snapshot of an entity, or `None` to leave it out, such as when it lacks the component.
+/// Entities despawned by the time the entry is undone or redone are skipped and reported as failed via
+/// [`UndoPartial`](crate::prelude::UndoPartial), while the others are still restored.
+/// The snapshot type must be set up via [`AppUndoEx::add_undo_bulk`](crate::prelude::AppUndoEx::add_undo_bulk).
+#[derive(SystemParam)]
+pub struct UndoBulkEdit<'w, 's, M: Component, S: Clone + Send + Sync + 'static> {
+    selected: Query<'w, 's, Entity, With<M>>,
+    starts: ResMut<'w, UndoBulkStarts<S>>,
+    scheduler: UndoScheduler<'w, UndoBulkEvent<S>>,
+}
+
+
+impl<'w, 's, M: Component, S: Clone + Send + Sync + 'static> UndoBulkEdit<'w, 's, M, S> {
+    /// Returns the currently selected entities.
+    #[inline]
+    pub fn selected(&self) -> Vec<Entity> {
+        let mut selected: Vec<Entity> = self.selected.iter().collect();
+        selected.sort_unstable();
+        selected
+    }
+
+
+    /// Snapshots the selected entities and registers an entry restoring all of them, which is not redoable.
+    ///
+    /// Returns false without registering anything if no entity was captured.
+    pub fn register(&mut self, capture: impl FnMut(Entity) -> Option<S>) -> bool {
+        let snapshots = snapshot(self.selected(), capture);
+        if snapshots.is_empty() {
+            return false;
+        }
+        let meta = meta_of(&snapshots);
+        self.scheduler.push(UndoBulkEvent { snapshots }, None, meta);
+        true
+    }
+
+
+    /// Snapshots the selected entities at the start of the edit.
+    ///
+    /// Beginning again before [`UndoBulkEdit::end`] keeps the first snapshots.
+    #[inline]
+    pub fn begin(&mut self, capture: impl FnMut(Entity) -> Option<S>) {
+        if self.starts.0.is_none() {
+            self.starts.0 = Some(snapshot(self.selected(), capture));
+        }
+    }
+
+
+    /// Returns true while the edit is in progress.
+    #[inline(always)]
+    pub fn is_editing(&self) -> bool {
+        self.starts.0.is_some()
+    }
+
+
+    /// Finishes the edit and registers an entry which restores the snapshots taken by [`UndoBulkEdit::begin`],
+    /// redone by snapshotting the same entities again now.
+    ///
+    /// Entities despawned during the edit are only left out of the redo.
+    /// Returns false without registering anything if the edit has not begun or nothing was captured.
+    pub fn end(&mut self, capture: impl FnMut(Entity) -> Option<S>) -> bool {
+        let Some(starts) = self.starts.0.take() else {
+            return false;
+        };
+        if starts.is_empty() {
+            return false;
+        }
+
+        let ends = snapshot(starts.iter().map(|(entity, _)| *entity), capture);
+        let meta = meta_of(&starts);
+        self.scheduler.push(UndoBulkEvent { snapshots: starts }, Some(UndoBulkEvent { snapshots: ends }), meta);
+        true
+    }
+
+
+    /// Aborts the edit without registering anything, returning the snapshots taken by [`UndoBulkEdit::begin`].
+    #[inline]
+    pub fn cancel(&mut self) -> Option<Vec<(Entity, S)>> {
+        self.starts.0.take()
+    }
+}
+
+
+fn snapshot<S>(entities: impl IntoIterator<Item = Entity>, mut capture: impl FnMut(Entity) -> Option<S>) -> Vec<(Entity, S)> {
+    entities
+        .into_iter()
+        .filter_map(|entity| Some((entity, capture(entity)?)))
+        .collect()
+}
+
+
+fn meta_of<S>(snapshots: &[(Entity, S)]) -> UndoMeta {
+    UndoMeta {
+        entities: snapshots.iter().map(|(entity, _)| *entity).collect(),
+        ..UndoMeta::default()
+    }
+}
+
+
+/// Restores the snapshots onto the entities which still exist, reporting the others as failed.
+pub(crate) fn restore_bulk_system<S: Clone + Send + Sync + 'static>(
+    world: &mut World,
+    mut reader: Local<MessageCursor<UndoBulkEvent<S>>>,
+) {
+    let events: Vec<UndoBulkEvent<S>> = reader
+        .read(world.resource::<Messages<UndoBulkEvent<S>>>())
+        .cloned()
+        .collect();
+    if events.is_empty() {
+        return;
+    }
+
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+    world.resource_scope(|world, restore: Mut<UndoBulkRestore<S>>| {
+        for (entity, snapshot) in events.iter().flat_map(|event| event.snapshots.iter()) {
+            match world.get_entity_mut(*entity) {
+                Ok(mut entity_mut) => {
+                    (restore.0)(&mut entity_mut, snapshot);
+                    succeeded.push(*entity);
+                }
+                Err(_) => failed.push(*entity),
+            }
+        }
+    });
+
+    let mut reports = world.resource_mut::<UndoOutcomeReports>();
+    let report = reports.report(std::any::type_name::<UndoBulkEvent<S>>());
+    report.succeeded.extend(succeeded);
+    report.failed.extend(failed);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::prelude::{Component, Messages, Query, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoBulkEdit, UndoChannel, UndoPartial};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Component)]
+    struct Selected;
+
+    #[derive(Resource, Default)]
+    struct Frame(usize);
+
+
+    fn positions(app: &mut App) -> Vec<i32> {
+        let mut positions: Vec<i32> = app
+            .world_mut()
+            .query::<&Position>()
+            .iter(app.world())
+            .map(|position| position.0)
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+
+    #[test]
+    fn restore_selected_entities_in_one_entry() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_bulk::<Position>(|entity, position| {
+            entity.insert(position.clone());
+        });
+        app.init_resource::<Frame>();
+        let a = app.world_mut().spawn((Position(0), Selected)).id();
+        app.world_mut().spawn((Position(10), Selected));
+        app.world_mut().spawn(Position(20));
+        app.add_systems(Update, |mut edit: UndoBulkEdit<Selected, Position>, mut positions: Query<&mut Position>, mut frame: ResMut<Frame>| {
+            match frame.0 {
+                0 => edit.begin(|entity| positions.get(entity).ok().cloned()),
+                1 => {
+                    for entity in edit.selected() {
+                        positions.get_mut(entity).unwrap().0 += 5;
+                    }
+                }
+                2 => assert!(edit.end(|entity| positions.get(entity).ok().cloned())),
+                _ => {}
+            }
+            frame.0 += 1;
+        });
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(positions(&mut app), vec![5, 15, 20]);
+
+        app.world_mut().despawn(a);
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(positions(&mut app), vec![10, 20]);
+        let partial: Vec<UndoPartial> = app.world_mut().resource_mut::<Messages<UndoPartial>>().drain().collect();
+        assert_eq!(partial.len(), 1);
+        assert_eq!((partial[0].restored(), partial[0].failed.clone()), (1, vec![a]));
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(positions(&mut app), vec![15, 20]);
+    }
+}
@@ -0,0 +1,282 @@
+use std::any::TypeId;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use bevy::asset::UntypedAssetId;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Entity, Message, MessageWriter, Res, ResMut, Resource};
+use bevy::platform::collections::HashSet;
+use bevy::platform::hash::FixedHasher;
+use bevy::platform::collections::HashMap;
+
+use crate::history::UndoHistory;
+use crate::payload::UndoPayload;
+
+/// Runtime id of an isolated undo stream.
+///
+/// Entries registered via [`UndoScheduler::register`](crate::prelude::UndoScheduler::register) go to [`UndoChannel::DEFAULT`],
+/// and [`UndoRequester::undo`](crate::prelude::UndoRequester::undo) only undoes the entries of it.
+/// Dynamically created documents or tools can use their own channels instead.
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+#[repr(transparent)]
+pub struct UndoChannel(pub u64);
+
+
+/// The highest bit, set on the channels of windows.
+const WINDOW_TAG: u64 = 1 << 63;
+
+
+/// The second highest bit, set on the channels of scenes with [`WINDOW_TAG`] cleared.
+const SCENE_TAG: u64 = 1 << 62;
+
+
+/// The third highest bit, set on the channels of collaboration sites with the other tags cleared.
+pub(crate) const SITE_TAG: u64 = 1 << 61;
+
+
+/// All the tags, cleared on the ids tagged with the lower ones, so the derived channels never collide with each other.
+pub(crate) const CHANNEL_TAGS: u64 = WINDOW_TAG | SCENE_TAG | SITE_TAG;
+
+
+const _: () = assert!(WINDOW_TAG & SCENE_TAG == 0 && WINDOW_TAG & SITE_TAG == 0 && SCENE_TAG & SITE_TAG == 0);
+
+
+impl UndoChannel {
+    pub const DEFAULT: UndoChannel = UndoChannel(0);
+
+
+    /// Returns the channel of the window, see [`AppUndoEx::enable_undo_windows`](crate::prelude::AppUndoEx::enable_undo_windows).
+    ///
+    /// The highest bit is set, so it does not collide with small channel ids.
+    #[inline(always)]
+    pub const fn for_window(window: Entity) -> Self {
+        Self(window.to_bits() | WINDOW_TAG)
+    }
+
+
+    /// Returns the channel of the scene, see [`AppUndoEx::enable_undo_scenes`](crate::prelude::AppUndoEx::enable_undo_scenes).
+    ///
+    /// The id is hashed with the second highest bit set, so it does not collide with small channel ids nor windows.
+    #[inline]
+    pub fn for_scene(scene: impl Into<UntypedAssetId>) -> Self {
+        Self(FixedHasher.hash_one(scene.into()) & !WINDOW_TAG | SCENE_TAG)
+    }
+}
+
+
+impl From<u64> for UndoChannel {
+    #[inline(always)]
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+
+/// How to make room when a channel exceeds its capacity.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum UndoEviction {
+    /// The oldest entries are dropped.
+    #[default]
+    DropOldest,
+
+    /// The newly registered entries are dropped, so the oldest history is kept.
+    RejectNewest,
+
+    /// The entries with the lowest [`UndoMeta::importance`](crate::prelude::UndoMeta::importance) are dropped, the oldest first among the same importance.
+    LowestImportance,
+
+    /// The entries whose [`UndoMeta::tag`](crate::prelude::UndoMeta::tag) has been registered least recently are dropped, the oldest first.
+    LeastRecentTag,
+}
+
+
+/// Sent for each entry of `E` dropped because its channel exceeded the capacity.
+///
+/// The payloads are handed over so that side resources referenced by them, such as temp files, can be released.
+#[derive(Message, Debug)]
+pub struct UndoEvicted<E: UndoPayload> {
+    pub payload: E,
+
+    /// The redo-event of the entry, if it was registered with one.
+    pub redo: Option<E>,
+}
+
+
+/// What a capacity is counted over.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum UndoCapacityScope {
+    /// The slots of the channel, see [`AppUndoEx::configure_undo_channel`](crate::prelude::AppUndoEx::configure_undo_channel).
+    Channel(UndoChannel),
+
+    /// The slots containing entries of the payload type, see [`AppUndoEx::configure_undo_type`](crate::prelude::AppUndoEx::configure_undo_type).
+    Type(TypeId),
+}
+
+
+/// Sent when a channel or payload type reaches its capacity and starts evicting entries,
+/// so the app can warn users that older edits are no longer recoverable.
+///
+/// It is sent once per crossing, and again only after the count of slots falls below the capacity.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoHistoryFull {
+    pub scope: UndoCapacityScope,
+    pub capacity: usize,
+
+    /// The name of the payload type for a [`UndoCapacityScope::Type`], to be displayed.
+    pub type_name: Option<&'static str>,
+}
+
+
+/// Capacity and eviction settings of a channel.
+///
+/// It is configured via [`AppUndoEx::configure_undo_channel`](crate::prelude::AppUndoEx::configure_undo_channel).
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct UndoStackConfig {
+    /// The maximum count of slots kept in the channel, `None` means unlimited.
+    ///
+    /// Entries reserved together share one slot.
+    pub capacity: Option<usize>,
+
+    pub eviction: UndoEviction,
+
+    /// Entries registered longer ago than this are dropped regardless of the capacity.
+    ///
+    /// This requires [`Time`](bevy::prelude::Time), so it does nothing without [`TimePlugin`](bevy::time::TimePlugin).
+    pub max_age: Option<Duration>,
+}
+
+
+impl UndoStackConfig {
+    /// Creates the config with the given capacity, dropping the oldest entries when exceeded.
+    #[inline(always)]
+    pub const fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            eviction: UndoEviction::DropOldest,
+            max_age: None,
+        }
+    }
+
+
+    #[inline(always)]
+    pub const fn eviction(mut self, eviction: UndoEviction) -> Self {
+        self.eviction = eviction;
+        self
+    }
+
+
+    #[inline(always)]
+    pub const fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+
+    /// Returns which of the slots, ordered from the oldest, to drop according to this config,
+    /// and whether the capacity was exceeded.
+    pub(crate) fn evicted_slots(&self, history: &UndoHistory, mut slots: Vec<usize>, now: Option<Duration>) -> (Vec<usize>, bool) {
+        let mut evicted = Vec::new();
+        if let (Some(max_age), Some(now)) = (self.max_age, now) {
+            slots.retain(|no| {
+                let expired = history
+                    .slot_entries(*no)
+                    .all(|entry| max_age < now.saturating_sub(entry.registered_at));
+                if expired {
+                    evicted.push(*no);
+                }
+                !expired
+            });
+        }
+
+        let Some(capacity) = self.capacity else {
+            return (evicted, false);
+        };
+        if slots.len() <= capacity {
+            return (evicted, false);
+        }
+        let overflow = slots.len() - capacity;
+        match self.eviction {
+            UndoEviction::DropOldest => {}
+            UndoEviction::RejectNewest => slots.reverse(),
+            UndoEviction::LowestImportance => slots.sort_by_key(|no| {
+                let importance = history.slot_entries(*no).map(|entry| entry.meta.importance).max();
+                (importance, *no)
+            }),
+            UndoEviction::LeastRecentTag => {
+                let mut last_use: HashMap<&str, usize> = HashMap::default();
+                for no in slots.iter() {
+                    for entry in history.slot_entries(*no) {
+                        last_use.insert(entry.meta.tag.as_str(), *no);
+                    }
+                }
+                slots.sort_by_key(|no| {
+                    let last_use = history
+                        .slot_entries(*no)
+                        .map(|entry| last_use[entry.meta.tag.as_str()])
+                        .max();
+                    (last_use, *no)
+                });
+            }
+        }
+        evicted.extend(slots.into_iter().take(overflow));
+        (evicted, true)
+    }
+}
+
+
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoStackConfigs(pub HashMap<UndoChannel, UndoStackConfig>);
+
+
+/// Capacity and eviction settings keyed by the payload type, along with its name.
+///
+/// The capacity counts the slots containing entries of the type, across all channels.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoTypeConfigs(pub HashMap<TypeId, (&'static str, UndoStackConfig)>);
+
+
+/// The scopes which are evicting entries over their capacity.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoFullScopes(HashSet<UndoCapacityScope>);
+
+
+/// The capacity configs of the channels and the payload types, notifying when they become full.
+#[derive(SystemParam)]
+pub(crate) struct UndoCapacities<'w> {
+    channels: Res<'w, UndoStackConfigs>,
+    types: Res<'w, UndoTypeConfigs>,
+    full: ResMut<'w, UndoFullScopes>,
+    ew: MessageWriter<'w, UndoHistoryFull>,
+}
+
+
+impl<'w> UndoCapacities<'w> {
+    /// Returns the configs of the channels followed by the ones of the payload types.
+    pub fn scopes(&self) -> Vec<(UndoCapacityScope, UndoStackConfig)> {
+        self.channels.0
+            .iter()
+            .map(|(channel, config)| (UndoCapacityScope::Channel(*channel), *config))
+            .chain(self.types.0.iter().map(|(type_id, (_, config))| (UndoCapacityScope::Type(*type_id), *config)))
+            .collect()
+    }
+
+
+    /// Sends [`UndoHistoryFull`] when the scope starts exceeding the capacity,
+    /// and rearms it once the remaining slots fall below the capacity.
+    pub fn notify(&mut self, scope: UndoCapacityScope, config: &UndoStackConfig, over_capacity: bool, remaining: usize) {
+        let Some(capacity) = config.capacity else {
+            return;
+        };
+        if over_capacity {
+            if self.full.0.insert(scope) {
+                let type_name = match scope {
+                    UndoCapacityScope::Channel(_) => None,
+                    UndoCapacityScope::Type(type_id) => self.types.0.get(&type_id).map(|(type_name, _)| *type_name),
+                };
+                self.ew.write(UndoHistoryFull { scope, capacity, type_name });
+            }
+        } else if remaining < capacity {
+            self.full.0.remove(&scope);
+        }
+    }
+}
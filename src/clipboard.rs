@@ -0,0 +1,186 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{AppTypeRegistry, Commands, Entity, Res, Resource, World};
+
+use crate::entity_snapshot::UndoEntitySnapshot;
+use crate::meta::UndoMeta;
+use crate::spawn::{despawned, live, send, set_spawned, UndoSpawnedEntity, UndoSpawnEvent, with_descendants};
+use crate::undo_event::UndoScheduler;
+
+/// The snapshots copied or cut last.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoClipboardContents(Vec<UndoEntitySnapshot>);
+
+
+/// Cuts, copies, pastes and duplicates entities as reflected snapshots, the changes to the world being undoable.
+///
+/// Only components registered in [`AppTypeRegistry`] with [`ReflectComponent`](bevy::ecs::reflect::ReflectComponent)
+/// are kept, and respawned entities get new ids, so other entries referring to the former ids no longer apply to them.
+/// The operations are applied together with the [`Commands`] of the system.
+/// It must be set up via [`AppUndoEx::add_undo_clipboard`](crate::prelude::AppUndoEx::add_undo_clipboard).
+#[derive(SystemParam)]
+pub struct UndoClipboard<'w, 's> {
+    commands: Commands<'w, 's>,
+    contents: Res<'w, UndoClipboardContents>,
+    scheduler: UndoScheduler<'w, UndoSpawnEvent>,
+}
+
+
+impl<'w, 's> UndoClipboard<'w, 's> {
+    /// Returns the snapshots copied or cut until the previous commands were applied.
+    #[inline(always)]
+    pub fn contents(&self) -> &[UndoEntitySnapshot] {
+        &self.contents.0
+    }
+
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.contents.0.is_empty()
+    }
+
+
+    /// Copies the entities and their descendants to the clipboard, which registers nothing.
+    pub fn copy(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        let entities: Vec<Entity> = entities.into_iter().collect();
+        self.commands.queue(move |world: &mut World| {
+            let snapshots = capture(world, &entities);
+            world.resource_mut::<UndoClipboardContents>().0 = snapshots;
+        });
+    }
+
+
+    /// Copies the entities and their descendants to the clipboard and despawns them, registering an entry which respawns them.
+    pub fn cut(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        let entities: Vec<Entity> = entities.into_iter().collect();
+        let (no, meta) = self.next_slot(&entities);
+        self.commands.queue(move |world: &mut World| {
+            let cut = live(&with_descendants(world, &entities));
+            set_spawned(world, &cut, false);
+            let snapshots: Vec<UndoEntitySnapshot> = cut
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .iter()
+                .filter_map(|entity| match entity {
+                    UndoSpawnedEntity::Despawned(snapshot) => Some(snapshot.clone()),
+                    UndoSpawnedEntity::Live(_) => None,
+                })
+                .collect();
+            world.resource_mut::<UndoClipboardContents>().0 = snapshots;
+            send(world, cut, true, no, meta);
+        });
+    }
+
+
+    /// Spawns the entities of the clipboard under their former parents, registering an entry which despawns them.
+    pub fn paste(&mut self) {
+        let (no, meta) = self.next_slot(&[]);
+        self.commands.queue(move |world: &mut World| {
+            let snapshots = world.resource::<UndoClipboardContents>().0.clone();
+            spawn_and_send(world, snapshots, no, meta);
+        });
+    }
+
+
+    /// Spawns copies of the entities and their descendants without touching the clipboard,
+    /// registering an entry which despawns them.
+    pub fn duplicate(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        let entities: Vec<Entity> = entities.into_iter().collect();
+        let (no, meta) = self.next_slot(&entities);
+        self.commands.queue(move |world: &mut World| {
+            let snapshots = capture(world, &entities);
+            spawn_and_send(world, snapshots, no, meta);
+        });
+    }
+
+
+    #[inline]
+    fn next_slot(&mut self, entities: &[Entity]) -> (usize, UndoMeta) {
+        let meta = UndoMeta {
+            entities: entities.to_vec(),
+            ..UndoMeta::default()
+        };
+        let meta = self.scheduler.routed(meta);
+        (self.scheduler.next_slot(meta.channel), meta)
+    }
+}
+
+
+fn capture(world: &World, entities: &[Entity]) -> Vec<UndoEntitySnapshot> {
+    let type_registry = world.resource::<AppTypeRegistry>();
+    with_descendants(world, entities)
+        .iter()
+        .filter_map(|entity| UndoEntitySnapshot::capture(world, type_registry, *entity))
+        .collect()
+}
+
+
+fn spawn_and_send(world: &mut World, snapshots: Vec<UndoEntitySnapshot>, no: usize, meta: UndoMeta) {
+    let spawned = despawned(snapshots);
+    set_spawned(world, &spawned, true);
+    send(world, spawned, false, no, meta);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Component, Reflect, ReflectComponent};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoClipboard};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Component, Reflect, Default, Debug, PartialEq)]
+    #[reflect(Component)]
+    struct Position(i32);
+
+
+    fn positions(app: &mut App) -> Vec<i32> {
+        let mut positions: Vec<i32> = app
+            .world_mut()
+            .query::<&Position>()
+            .iter(app.world())
+            .map(|position| position.0)
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+
+    fn request(app: &mut App, request: RequestUndoEvent) -> Vec<i32> {
+        app.world_mut().write_message(request);
+        app.update();
+        positions(app)
+    }
+
+
+    #[test]
+    fn undo_cut_paste_and_duplicate() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.register_type::<Position>();
+        app.add_undo_clipboard();
+        let a = app.world_mut().spawn(Position(1)).id();
+        let b = app.world_mut().spawn(Position(2)).id();
+
+        let mut state = SystemState::<UndoClipboard>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().cut([a]);
+        state.apply(app.world_mut());
+        app.update();
+        assert_eq!(positions(&mut app), vec![2]);
+        assert_eq!(request(&mut app, RequestUndoEvent::Latest(UndoChannel::DEFAULT)), vec![1, 2]);
+        assert_eq!(request(&mut app, RequestUndoEvent::Redo(UndoChannel::DEFAULT)), vec![2]);
+
+        state.get_mut(app.world_mut()).unwrap().paste();
+        state.get_mut(app.world_mut()).unwrap().duplicate([b]);
+        state.apply(app.world_mut());
+        app.update();
+        assert_eq!(positions(&mut app), vec![1, 2, 2]);
+        assert_eq!(request(&mut app, RequestUndoEvent::Latest(UndoChannel::DEFAULT)), vec![1, 2]);
+        assert_eq!(request(&mut app, RequestUndoEvent::Latest(UndoChannel::DEFAULT)), vec![2]);
+        assert_eq!(request(&mut app, RequestUndoEvent::Redo(UndoChannel::DEFAULT)), vec![1, 2]);
+        assert_eq!(request(&mut app, RequestUndoEvent::Latest(UndoChannel::DEFAULT)), vec![2]);
+        assert_eq!(request(&mut app, RequestUndoEvent::Latest(UndoChannel::DEFAULT)), vec![1, 2]);
+    }
+}
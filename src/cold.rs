@@ -0,0 +1,352 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use bevy::log::warn;
+use bevy::prelude::{MessageReader, Res, ResMut, Resource, Time};
+use bevy::tasks::{AsyncComputeTaskPool, Task, TaskPool};
+use bevy::platform::collections::HashMap;
+use futures_lite::future;
+
+use crate::{DispatchUndoEvent, UndoAreas};
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use crate::export::UndoCompression;
+use crate::history::UndoHistory;
+use crate::undo_event::UndoEntry;
+use crate::version::{UndoVersioned, UndoVersionedBytes};
+
+/// An entry encoded via [`UndoVersioned`], and compressed if the area is configured so.
+struct UndoColdEntry {
+    inner: UndoVersionedBytes,
+    redo: Option<UndoVersionedBytes>,
+    no: usize,
+}
+
+
+/// Encodes the payloads via [`UndoVersioned`], compressing them if configured so.
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct UndoColdCodec {
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    pub compression: Option<UndoCompression>,
+}
+
+
+impl UndoColdCodec {
+    fn encode<E: UndoVersioned>(self, payload: &E) -> UndoVersionedBytes {
+        #[allow(unused_mut)]
+        let mut bytes = UndoVersionedBytes::encode(payload);
+        #[cfg(any(feature = "lz4", feature = "zstd"))]
+        if let Some(compression) = self.compression {
+            bytes.bytes = compression.compress(&bytes.bytes);
+        }
+        bytes
+    }
+
+
+    fn decode<E: UndoVersioned>(self, bytes: UndoVersionedBytes) -> Option<E> {
+        #[cfg(any(feature = "lz4", feature = "zstd"))]
+        if let Some(compression) = self.compression {
+            return UndoVersionedBytes {
+                version: bytes.version,
+                bytes: compression.decompress(&bytes.bytes)?,
+            }.decode();
+        }
+        bytes.decode()
+    }
+}
+
+
+/// Keeps the entries of `E` registered longer ago than `after` as encoded bytes, in place of the registered area.
+#[derive(Resource)]
+pub(crate) struct UndoColdArea<E: UndoVersioned> {
+    after: Duration,
+    pub codec: UndoColdCodec,
+    /// The encoded entries by slot, from the oldest registered.
+    entries: HashMap<usize, Vec<UndoColdEntry>>,
+    /// The encoding tasks along with the slots of their entries.
+    encoding: Vec<(Vec<usize>, Task<Vec<UndoColdEntry>>)>,
+    _marker: PhantomData<E>,
+}
+
+
+impl<E: UndoVersioned> UndoColdArea<E> {
+    #[inline]
+    pub fn new(after: Duration) -> Self {
+        Self {
+            after,
+            codec: UndoColdCodec::default(),
+            entries: HashMap::new(),
+            encoding: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+
+    /// Takes the results of the finished encoding tasks.
+    fn collect_finished(&mut self) {
+        for (slots, task) in std::mem::take(&mut self.encoding) {
+            if task.is_finished() {
+                self.insert(future::block_on(task));
+            } else {
+                self.encoding.push((slots, task));
+            }
+        }
+    }
+
+
+    /// Waits for the encoding tasks holding entries of the slot, if any.
+    fn wait_for(&mut self, no: usize) {
+        let (slot, encoding): (Vec<_>, Vec<_>) = std::mem::take(&mut self.encoding)
+            .into_iter()
+            .partition(|(slots, _)| slots.contains(&no));
+        self.encoding = encoding;
+        for (_, task) in slot {
+            self.insert(future::block_on(task));
+        }
+    }
+
+
+    fn insert(&mut self, entries: Vec<UndoColdEntry>) {
+        for entry in entries {
+            self.entries.entry(entry.no).or_default().push(entry);
+        }
+    }
+
+}
+/// Moves the cold entries out of the registered area and encodes them on [`AsyncComputeTaskPool`].
+pub(crate) fn compact_cold_entries_system<E: UndoVersioned>(
+    mut areas: ResMut<UndoAreas>,
+    mut cold_area: ResMut<UndoColdArea<E>>,
+    history: Res<UndoHistory>,
+    time: Option<Res<Time>>,
+) {
+    let registered_area = areas.registered_mut::<E>();
+    cold_area.collect_finished();
+    let Some(now) = time.map(|time| time.elapsed()) else {
+        return;
+    };
+
+    let after = cold_area.after;
+    let mut slots = Vec::new();
+    registered_area.0.for_each(&mut |entry| {
+        if history
+            .slot_entries(entry.no)
+            .all(|history_entry| after < now.saturating_sub(history_entry.registered_at)) {
+            slots.push(entry.no);
+        }
+    });
+    if slots.is_empty() {
+        return;
+    }
+    slots.sort_unstable();
+    slots.dedup();
+
+    let mut cold = Vec::new();
+    for &no in &slots {
+        let start = cold.len();
+        while let Some(entry) = registered_area.pop_entry(no) {
+            cold.push(entry);
+        }
+        cold[start..].reverse();
+    }
+    let codec = cold_area.codec;
+    let pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+    cold_area.encoding.push((slots, pool.spawn(async move {
+        cold
+            .into_iter()
+            .map(|entry: UndoEntry<E>| UndoColdEntry {
+                inner: codec.encode(&entry.inner),
+                redo: entry.redo.as_ref().map(|redo| codec.encode(redo)),
+                no: entry.no,
+            })
+            .collect()
+    })));
+}
+
+
+/// Decodes the cold entries of the dispatched slots back into the registered area, right before they are dispatched.
+///
+/// Only the slots still being encoded are waited for.
+pub(crate) fn rehydrate_cold_entries_system<E: UndoVersioned>(
+    mut er: MessageReader<DispatchUndoEvent>,
+    mut areas: ResMut<UndoAreas>,
+    mut cold_area: ResMut<UndoColdArea<E>>,
+) {
+    let registered_area = areas.registered_mut::<E>();
+    for dispatch in er.read() {
+        let (DispatchUndoEvent::Undo(no)
+        | DispatchUndoEvent::Discard(no)
+        | DispatchUndoEvent::Evict(no)
+        | DispatchUndoEvent::DryRun(no)) = *dispatch else {
+            continue;
+        };
+        cold_area.wait_for(no);
+        let Some(slot) = cold_area.entries.remove(&no) else {
+            continue;
+        };
+        if matches!(dispatch, DispatchUndoEvent::Discard(_)) {
+            continue;
+        }
+
+        let codec = cold_area.codec;
+        for entry in slot {
+            let Some(inner) = codec.decode::<E>(entry.inner) else {
+                warn!("failed to decode a cold undo entry of {}", std::any::type_name::<E>());
+                continue;
+            };
+            registered_area.push(UndoEntry {
+                inner,
+                redo: entry.redo.and_then(|redo| codec.decode::<E>(redo)),
+                no: entry.no,
+            });
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages, Time};
+
+    use crate::cold::UndoColdArea;
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    use crate::prelude::UndoCompression;
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoEntry, UndoMemoryStorage, UndoScheduler, UndoStorage, UndoVersioned};
+    use crate::request::RequestUndoEvent;
+    use crate::{UndoAreas, UndoPlugin};
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(u8);
+
+
+    /// Counts the entries ever pushed, delegating the rest to the memory storage.
+    #[derive(Default)]
+    struct CountingStorage {
+        pushed: Arc<AtomicUsize>,
+        inner: UndoMemoryStorage<Move>,
+    }
+
+
+    impl UndoStorage<Move> for CountingStorage {
+        fn push(&mut self, entry: UndoEntry<Move>) {
+            self.pushed.fetch_add(1, Ordering::Relaxed);
+            self.inner.push(entry);
+        }
+
+        fn pop(&mut self, no: usize) -> Option<UndoEntry<Move>> {
+            self.inner.pop(no)
+        }
+
+        fn for_each(&self, f: &mut dyn FnMut(&UndoEntry<Move>)) {
+            self.inner.for_each(f);
+        }
+
+        fn drain(&mut self) -> Vec<UndoEntry<Move>> {
+            self.inner.drain()
+        }
+
+        fn len(&self) -> usize {
+            self.inner.len()
+        }
+    }
+
+
+    impl UndoVersioned for Move {
+        const VERSION: u32 = 1;
+
+        fn encode(&self) -> Vec<u8> {
+            vec![self.0]
+        }
+
+        fn decode(bytes: &[u8]) -> Option<Self> {
+            match bytes {
+                [x] => Some(Self(*x)),
+                _ => None
+            }
+        }
+    }
+
+
+    #[test]
+    fn rehydrate_cold_entries_on_undo() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.compact_cold_undo_entries::<Move>(Duration::from_secs(60));
+        app.init_resource::<Time>();
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register_all([Move(1), Move(2)]);
+        app.update();
+
+        app.world_mut().resource_mut::<Time>().advance_to(Duration::from_secs(120));
+        state.get_mut(app.world_mut()).unwrap().register(Move(3));
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<Move>().0.len(), 1);
+
+        app.world_mut().write_message_batch((0..2).map(|_| RequestUndoEvent::Latest(UndoChannel::DEFAULT)));
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(3), Move(2)]);
+        assert!(app.world().resource::<UndoColdArea<Move>>().encoding.is_empty());
+    }
+
+
+    #[test]
+    fn leave_hot_entries_in_place() {
+        let storage = CountingStorage::default();
+        let pushed = storage.pushed.clone();
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.set_undo_storage::<Move>(storage);
+        app.add_undo_event::<Move>();
+        app.compact_cold_undo_entries::<Move>(Duration::from_secs(60));
+        app.init_resource::<Time>();
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(Move(1));
+        app.update();
+
+        app.world_mut().resource_mut::<Time>().advance_to(Duration::from_secs(120));
+        state.get_mut(app.world_mut()).unwrap().register(Move(2));
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(pushed.load(Ordering::Relaxed), 2);
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<Move>().0.len(), 1);
+    }
+
+
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    #[test]
+    fn compress_cold_entries() {
+        let compression = [
+            #[cfg(feature = "lz4")]
+            UndoCompression::Lz4,
+            #[cfg(feature = "zstd")]
+            UndoCompression::Zstd(0),
+        ][0];
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.compact_cold_undo_entries_compressed::<Move>(Duration::from_secs(60), compression);
+        app.init_resource::<Time>();
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(Move(1));
+        app.update();
+
+        app.world_mut().resource_mut::<Time>().advance_to(Duration::from_secs(120));
+        app.update();
+        let mut cold_area = app.world_mut().resource_mut::<UndoColdArea<Move>>();
+        cold_area.wait_for(1);
+        assert_eq!(cold_area.entries[&1][0].inner.bytes, compression.compress(&[1]));
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(1)]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeSet;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{ResMut, Resource, World};
+use bevy::platform::collections::HashMap;
+
+use crate::channel::{CHANNEL_TAGS, SITE_TAG, UndoChannel};
+use crate::counter::UndoCounter;
+use crate::erased::UndoAnyEvent;
+use crate::history::UndoHistory;
+use crate::meta::UndoMeta;
+
+/// Identifies a client of a collaborative editing session, enabled via [`AppUndoEx::enable_undo_collab`](crate::prelude::AppUndoEx::enable_undo_collab).
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+#[repr(transparent)]
+pub struct UndoSiteId(pub u64);
+
+
+impl UndoSiteId {
+    /// Returns the channel the entries merged from the site are recorded in,
+    /// kept apart from the local channels so that undo only reverts the edits of this client.
+    ///
+    /// The third highest bit is set, so it does not collide with small channel ids, windows nor scenes.
+    /// The highest three bits of the id are dropped.
+    #[inline(always)]
+    pub const fn channel(self) -> UndoChannel {
+        UndoChannel(self.0 & !CHANNEL_TAGS | SITE_TAG)
+    }
+}
+
+
+/// The logical clock and site of an entry, ordering the entries of all clients the same way on each of them.
+///
+/// Ordered by the clock first, so concurrent entries are ordered by their site.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+pub struct UndoStamp {
+    pub clock: u64,
+    pub site: UndoSiteId,
+}
+
+
+/// An entry registered by another client, merged via [`UndoCollab::merge`].
+pub struct UndoRemoteEntry {
+    pub stamp: UndoStamp,
+    pub event: UndoAnyEvent,
+    pub meta: UndoMeta,
+}
+
+
+#[derive(Resource, Default)]
+pub(crate) struct UndoCollabClock {
+    pub site: UndoSiteId,
+    clock: u64,
+    known: BTreeSet<UndoStamp>,
+    incoming: Vec<UndoRemoteEntry>,
+}
+
+
+impl UndoCollabClock {
+    #[inline]
+    pub fn new(site: UndoSiteId) -> Self {
+        Self {
+            site,
+            ..Self::default()
+        }
+    }
+}
+
+
+/// Merges the histories of other clients and exposes the order they converge to.
+#[derive(SystemParam)]
+pub struct UndoCollab<'w> {
+    clock: ResMut<'w, UndoCollabClock>,
+}
+
+
+impl<'w> UndoCollab<'w> {
+    /// Returns the site of this client.
+    #[inline(always)]
+    pub fn site(&self) -> UndoSiteId {
+        self.clock.site
+    }
+
+
+    /// Queues the remote entries, recorded at the start of the next frame in the order of their stamps.
+    ///
+    /// Entries already known are ignored, so merging the same history again or in another order has no effect.
+    #[inline]
+    pub fn merge(&mut self, entries: impl IntoIterator<Item = UndoRemoteEntry>) {
+        self.clock.incoming.extend(entries);
+    }
+
+
+    /// Returns the stamps of all entries known to this client, local and merged, in the order shared by all clients.
+    #[inline]
+    pub fn converged(&self) -> Vec<UndoStamp> {
+        self.clock.known.iter().copied().collect()
+    }
+}
+
+
+/// Records the merged remote entries in the channels of their sites, advancing the logical clock past them.
+pub(crate) fn merge_remote_entries_system(world: &mut World) {
+    let mut collab = world.resource_mut::<UndoCollabClock>();
+    if collab.incoming.is_empty() {
+        return;
+    }
+    let mut incoming = std::mem::take(&mut collab.incoming);
+    incoming.sort_by_key(|entry| entry.stamp);
+    incoming.dedup_by_key(|entry| entry.stamp);
+    incoming.retain(|entry| entry.stamp.site != collab.site && collab.known.insert(entry.stamp));
+    if let Some(latest) = incoming.last() {
+        collab.clock = collab.clock.max(latest.stamp.clock);
+    }
+
+    for UndoRemoteEntry { stamp, event, mut meta } in incoming {
+        meta.channel = stamp.site.channel();
+        meta.stamp = Some(stamp);
+        let mut counter = world.resource_mut::<UndoCounter>();
+        counter.increment();
+        let no = **counter;
+        event.send(world, no, meta);
+    }
+}
+
+
+/// Stamps the entries registered by this client this frame, entries registered together sharing the stamp.
+pub(crate) fn stamp_local_entries_system(
+    mut collab: ResMut<UndoCollabClock>,
+    mut history: ResMut<UndoHistory>,
+) {
+    let collab = &mut *collab;
+    let mut stamps = HashMap::new();
+    history.stamp_unstamped(|no| *stamps.entry(no).or_insert_with(|| {
+        collab.clock += 1;
+        let stamp = UndoStamp {
+            clock: collab.clock,
+            site: collab.site,
+        };
+        collab.known.insert(stamp);
+        stamp
+    }));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::asset::AssetId;
+    use bevy::asset::uuid::Uuid;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Entity, Message, Messages};
+    use bevy::world_serialization::WorldAsset;
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoCollab, UndoMeta, UndoRemoteEntry, UndoScheduler, UndoSiteId, UndoStamp};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    fn remote(clock: u64, site: u64, x: i32) -> UndoRemoteEntry {
+        UndoRemoteEntry {
+            stamp: UndoStamp {
+                clock,
+                site: UndoSiteId(site),
+            },
+            event: Move(x).into(),
+            meta: UndoMeta::default(),
+        }
+    }
+
+
+    fn new_app(site: u64) -> App {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.enable_undo_collab(UndoSiteId(site));
+
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(Move(site as i32));
+        state.apply(app.world_mut());
+        app.update();
+        app
+    }
+
+
+    fn merge(app: &mut App, remotes: Vec<UndoRemoteEntry>) {
+        let mut state = SystemState::<UndoCollab>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().merge(remotes);
+        app.update();
+    }
+
+
+    fn converged(app: &mut App) -> Vec<UndoStamp> {
+        SystemState::<UndoCollab>::new(app.world_mut()).get_mut(app.world_mut()).unwrap().converged()
+    }
+
+
+    #[test]
+    fn converge_regardless_of_merge_order() {
+        let mut first = new_app(1);
+        let mut second = new_app(2);
+        merge(&mut first, vec![remote(1, 3, 3), remote(1, 2, 2)]);
+        merge(&mut second, vec![remote(1, 1, 1), remote(1, 3, 3), remote(1, 1, 1)]);
+        assert_eq!(converged(&mut first), converged(&mut second));
+        assert_eq!(converged(&mut first).len(), 3);
+    }
+
+
+    #[test]
+    fn keep_site_channels_apart_from_other_channels() {
+        let mut channels: Vec<UndoChannel> = (0..3).map(UndoChannel).collect();
+        channels.extend([0, 1, u32::MAX - 1].map(|index| UndoChannel::for_window(Entity::from_raw_u32(index).unwrap())));
+        channels.push(UndoChannel::for_window(Entity::from_bits((u64::MAX >> 1) - 1)));
+        channels.extend((0..3).map(|_| UndoChannel::for_scene(AssetId::<WorldAsset>::from(Uuid::new_v4()))));
+        let sites = [0, 1, 2, u64::MAX >> 3].map(|site| UndoSiteId(site).channel());
+        for site in sites {
+            assert!(!channels.contains(&site));
+            channels.push(site);
+        }
+        channels.sort();
+        channels.dedup();
+        assert_eq!(channels.len(), 3 + 4 + 3 + 4);
+    }
+
+
+    #[test]
+    fn undo_only_local_entries() {
+        let mut app = new_app(1);
+        merge(&mut app, vec![remote(5, 2, 2)]);
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(1)]);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert!(app.world_mut().resource_mut::<Messages<Move>>().drain().next().is_none());
+
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(Move(10));
+        state.apply(app.world_mut());
+        app.update();
+        assert_eq!(converged(&mut app).last().map(|stamp| stamp.clock), Some(6));
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::prelude::{Mut, Resource, World};
+use bevy::platform::collections::HashMap;
+
+use crate::{DispatchUndoEvent, UndoAreas};
+use crate::counter::UndoCounter;
+use crate::history::UndoHistory;
+use crate::payload::UndoPayload;
+
+type NoopHook<E> = Box<dyn Fn(&E, &World) -> bool + Send + Sync + 'static>;
+
+
+/// Tells whether undoing the payload would have no effect in the current world.
+#[derive(Resource)]
+pub(crate) struct UndoNoopHook<E: UndoPayload>(pub NoopHook<E>);
+
+
+/// Drops the slots whose entries are all payloads of `E` judged as no-op by the hook.
+///
+/// Slots which also contain entries of other types or reservations are kept.
+/// It only runs in the frames where the areas have changed, so a history left alone costs nothing.
+pub(crate) fn compact_noops_system<E: UndoPayload>(world: &mut World) {
+    world.resource_scope(|world, hook: Mut<UndoNoopHook<E>>| {
+        let mut slots: HashMap<usize, (usize, bool)> = HashMap::default();
+        world.resource::<UndoAreas>().registered::<E>().0.for_each(&mut |entry| {
+            let (count, noop) = slots.entry(entry.no).or_insert((0, true));
+            *count += 1;
+            *noop = *noop && (hook.0)(&entry.inner, world);
+        });
+
+        let history = world.resource::<UndoHistory>();
+        let mut noops: Vec<usize> = slots
+            .into_iter()
+            .filter(|(no, (count, noop))| *noop && *count == history.slot_len(*no))
+            .map(|(no, _)| no)
+            .collect();
+        if noops.is_empty() {
+            return;
+        }
+        noops.sort_unstable();
+
+        let mut history = world.resource_mut::<UndoHistory>();
+        for no in noops.iter() {
+            history.remove_slot(*no);
+        }
+        let max_no = history.max_no().unwrap_or_default();
+        world.resource_mut::<UndoCounter>().set(max_no);
+        world.write_message_batch(noops.into_iter().map(DispatchUndoEvent::Discard));
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Entity, Message, World};
+
+    use crate::prelude::{AppUndoEx, UndoScheduler};
+    use crate::UndoAreas;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Despawn(Entity);
+
+
+    #[test]
+    fn drop_entries_of_despawned_entities() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Despawn>();
+        app.compact_undo_noops(|event: &Despawn, world: &World| world.get_entity(event.0).is_err());
+        let e1 = app.world_mut().spawn_empty().id();
+        let e2 = app.world_mut().spawn_empty().id();
+        app.add_systems(Startup, move |mut s: UndoScheduler<Despawn>| {
+            s.register(Despawn(e1));
+            s.register(Despawn(e2));
+        });
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<Despawn>().0.len(), 2);
+
+        app.world_mut().despawn(e1);
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<Despawn>().0.len(), 2);
+
+        let e3 = app.world_mut().spawn_empty().id();
+        let mut scheduler = SystemState::<UndoScheduler<Despawn>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register(Despawn(e3));
+        scheduler.apply(app.world_mut());
+        app.update();
+        let remaining: Vec<Entity> = app.world().resource::<UndoAreas>().registered::<Despawn>()
+            .events()
+            .iter()
+            .map(|entry| entry.0)
+            .collect();
+        assert_eq!(remaining, vec![e2, e3]);
+    }
+}
@@ -0,0 +1,173 @@
+//! Names of the bevy-undo v1 API mapped onto this crate.
+//!
+//! Spawning [`Undo`] requests an undo like [`UndoRequester::undo`](crate::prelude::UndoRequester::undo),
+//! and `on_undo` registers an [`UndoCallbackEvent`] to the default channel.
+//! The `on_undo_builder` API of v1 is not provided, use [`UndoCallbackEvent::for_entity`] instead.
+
+use bevy::app::{App, Plugin, PreUpdate};
+use bevy::ecs::system::{EntityCommands, SystemState};
+use bevy::prelude::{Commands, Component, Entity, IntoScheduleConfigs, MessageWriter, Query, With, World};
+
+use crate::UndoSystemSet;
+use crate::channel::UndoChannel;
+use crate::prelude::{UndoCallbackEvent, UndoScheduler};
+use crate::request::RequestUndoEvent;
+
+pub mod prelude {
+    pub use crate::compat::{CommandsOnUndoExt, CommandsUndoExt, EntityCommandsOnUndoExt, Processing, Undo};
+    pub use crate::UndoPlugin;
+}
+
+
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Debug, Default)]
+pub(crate) struct UndoCompatPlugin;
+
+
+impl Plugin for UndoCompatPlugin {
+    #[inline]
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, undo_component_system.in_set(UndoSystemSet::Commit));
+    }
+}
+
+
+/// Component to request undo action.
+///
+/// It is despawned when the request is sent, and each spawned one undoes one entry.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash, Component)]
+pub struct Undo;
+
+
+/// Undo is ignored while there is one or more `Processing` in the world.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash, Component)]
+pub struct Processing;
+
+
+pub trait CommandsUndoExt {
+    /// Spawns an empty entity with [`Undo`] inserted.
+    fn undo(&mut self);
+}
+
+
+impl<'w, 's> CommandsUndoExt for Commands<'w, 's> {
+    #[inline]
+    fn undo(&mut self) {
+        self.spawn(Undo);
+    }
+}
+
+
+impl<'a> CommandsUndoExt for EntityCommands<'a> {
+    #[inline]
+    fn undo(&mut self) {
+        self.commands().undo();
+    }
+}
+
+
+impl CommandsUndoExt for App {
+    #[inline]
+    fn undo(&mut self) {
+        self.world_mut().spawn(Undo);
+    }
+}
+
+
+pub trait CommandsOnUndoExt {
+    /// Add a process to be executed at the time of the undo operation.
+    fn on_undo(&mut self, on_undo: impl Fn(&mut Commands) + Send + Sync + 'static);
+}
+
+
+impl<'w, 's> CommandsOnUndoExt for Commands<'w, 's> {
+    #[inline]
+    fn on_undo(&mut self, on_undo: impl Fn(&mut Commands) + Send + Sync + 'static) {
+        let callback = UndoCallbackEvent::new(on_undo);
+        self.queue(move |world: &mut World| register(world, callback));
+    }
+}
+
+
+pub trait EntityCommandsOnUndoExt {
+    fn on_undo(&mut self, undo: impl Fn(&mut Commands, Entity) + Send + Sync + 'static);
+
+
+    fn on_undo_with_entity_commands(&mut self, undo: impl Fn(&mut EntityCommands) + Send + Sync + 'static) {
+        self.on_undo(move |cmd, entity| {
+            undo(&mut cmd.entity(entity));
+        });
+    }
+}
+
+
+impl<'a> EntityCommandsOnUndoExt for EntityCommands<'a> {
+    #[inline]
+    fn on_undo(&mut self, undo: impl Fn(&mut Commands, Entity) + Send + Sync + 'static) {
+        let entity = self.id();
+        self.commands().on_undo(move |cmd| undo(cmd, entity));
+    }
+}
+
+
+fn register(world: &mut World, callback: UndoCallbackEvent) {
+    let mut state = SystemState::<UndoScheduler<UndoCallbackEvent>>::new(world);
+    state.get_mut(world).expect("the resources of UndoPlugin are missing").register(callback);
+    state.apply(world);
+}
+
+
+fn undo_component_system(
+    mut commands: Commands,
+    mut ew: MessageWriter<RequestUndoEvent>,
+    undo: Query<Entity, With<Undo>>,
+    processing: Query<(), With<Processing>>,
+) {
+    for entity in undo.iter() {
+        commands.entity(entity).despawn();
+        if processing.is_empty() {
+            ew.write(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Commands, Component, With, World};
+
+    use crate::compat::prelude::*;
+
+    #[derive(Component)]
+    struct Undone;
+
+
+    #[test]
+    fn undo_v1_callbacks() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_systems(Startup, |mut commands: Commands| {
+            commands.on_undo(|cmd| { cmd.spawn(Undone); });
+            commands.spawn_empty().on_undo_with_entity_commands(|entity| { entity.insert(Undone); });
+        });
+        app.update();
+
+        let processing = app.world_mut().spawn(Processing).id();
+        app.undo();
+        app.update();
+        app.update();
+        assert_eq!(count(app.world_mut()), 0);
+
+        app.world_mut().despawn(processing);
+        app.undo();
+        app.undo();
+        app.update();
+        app.update();
+        assert_eq!(count(app.world_mut()), 2);
+    }
+
+
+    fn count(world: &mut World) -> usize {
+        world.query_filtered::<(), With<Undone>>().iter(world).count()
+    }
+}
@@ -0,0 +1,23 @@
+use bevy::ecs::component::Mutable;
+use bevy::prelude::{Component, Entity, Message, MessageReader, Query};
+
+/// Restores the component of the entity to the value when undone or redone.
+///
+/// Applied automatically if the component type is set up via [`AppUndoEx::add_undo_component`](crate::prelude::AppUndoEx::add_undo_component).
+#[derive(Message, Debug, Clone)]
+pub struct UndoComponentEvent<C: Component<Mutability = Mutable> + Clone> {
+    pub entity: Entity,
+    pub value: C,
+}
+
+
+pub(crate) fn restore_component_system<C: Component<Mutability = Mutable> + Clone>(
+    mut er: MessageReader<UndoComponentEvent<C>>,
+    mut components: Query<&mut C>,
+) {
+    for event in er.read() {
+        if let Ok(mut component) = components.get_mut(event.entity) {
+            *component = event.value.clone();
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::{In, Res, ResMut, Resource};
+
+use crate::payload::UndoPayload;
+
+/// Whether the condition given to [`AppUndoEx::add_undo_event_with_condition`](crate::prelude::AppUndoEx::add_undo_event_with_condition)
+/// held in the frame, the entries of `E` being recorded only if it did.
+#[derive(Resource)]
+pub(crate) struct UndoEventCondition<E: UndoPayload>(pub bool, PhantomData<fn() -> E>);
+
+
+impl<E: UndoPayload> Default for UndoEventCondition<E> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(true, PhantomData)
+    }
+}
+
+
+impl<E: UndoPayload> UndoEventCondition<E> {
+    #[inline(always)]
+    pub fn holds(&self) -> bool {
+        self.0
+    }
+}
+
+
+/// Whether the entries of `E` are captured and sent to the handlers, types without a condition always being.
+#[inline]
+pub(crate) fn event_condition_holds<E: UndoPayload>(condition: Option<Res<UndoEventCondition<E>>>) -> bool {
+    condition.is_none_or(|condition| condition.holds())
+}
+
+
+pub(crate) fn update_event_condition_system<E: UndoPayload>(
+    In(holds): In<bool>,
+    mut condition: ResMut<UndoEventCondition<E>>,
+) {
+    condition.0 = holds;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages, resource_equals, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Sculpt(i32);
+
+
+    #[derive(Resource, Debug, PartialEq)]
+    enum EditorMode {
+        Terrain,
+        Play,
+    }
+
+
+    #[test]
+    fn record_only_while_condition_holds() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.insert_resource(EditorMode::Terrain);
+        app.add_undo_event_with_condition::<Sculpt, _>(resource_equals(EditorMode::Terrain));
+        let mut scheduler = SystemState::<UndoScheduler<Sculpt>>::new(app.world_mut());
+
+        scheduler.get_mut(app.world_mut()).unwrap().register(Sculpt(1));
+        app.update();
+        *app.world_mut().resource_mut::<EditorMode>() = EditorMode::Play;
+        scheduler.get_mut(app.world_mut()).unwrap().register(Sculpt(2));
+        app.update();
+
+        *app.world_mut().resource_mut::<EditorMode>() = EditorMode::Terrain;
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<Sculpt> = app.world_mut().resource_mut::<Messages<Sculpt>>().drain().collect();
+        assert_eq!(undone, vec![Sculpt(1)]);
+    }
+
+
+    #[test]
+    fn dispatch_only_while_condition_holds() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.insert_resource(EditorMode::Terrain);
+        app.add_undo_event_with_condition::<Sculpt, _>(resource_equals(EditorMode::Terrain));
+        let mut scheduler = SystemState::<UndoScheduler<Sculpt>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register(Sculpt(1));
+        app.update();
+
+        *app.world_mut().resource_mut::<EditorMode>() = EditorMode::Play;
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert!(app.world_mut().resource_mut::<Messages<Sculpt>>().drain().next().is_none());
+    }
+}
@@ -0,0 +1,164 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Message, MessageReader, MessageWriter, Res, ResMut, Resource};
+
+use crate::history::UndoHistory;
+use crate::meta::UndoMeta;
+use crate::request::RequestUndoEvent;
+
+/// Sent instead of undoing an entry [requiring confirmation](UndoMeta::requires_confirmation),
+/// such as to show "This will discard generated content — continue?".
+///
+/// The undo is applied once [`ConfirmUndoEvent`] is sent, or forgotten on [`CancelUndoEvent`].
+#[derive(Message, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoNeedsConfirmation {
+    /// The slot of the entry.
+    pub no: usize,
+    pub meta: UndoMeta,
+}
+
+
+/// Applies the undo awaiting confirmation, see [`UndoNeedsConfirmation`].
+#[derive(Message, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ConfirmUndoEvent;
+
+
+/// Forgets the undo awaiting confirmation, leaving its entries in the history.
+#[derive(Message, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CancelUndoEvent;
+
+
+#[derive(Resource, Default)]
+pub(crate) struct UndoConfirmations {
+    /// The request awaiting confirmation and the slots it reached.
+    pending: Option<(RequestUndoEvent, Vec<usize>)>,
+
+    /// The slots confirmed, undone when the request is resolved again.
+    confirmed: Option<Vec<usize>>,
+}
+
+
+/// Holds back the undos reaching entries which require confirmation.
+#[derive(SystemParam)]
+pub(crate) struct UndoConfirmation<'w> {
+    confirmations: ResMut<'w, UndoConfirmations>,
+    ew: MessageWriter<'w, UndoNeedsConfirmation>,
+}
+
+
+impl<'w> UndoConfirmation<'w> {
+    /// Returns true after sending [`UndoNeedsConfirmation`] if undoing the slots has to wait for a confirmation.
+    pub fn awaits(&mut self, history: &UndoHistory, request: &RequestUndoEvent, slots: &[usize]) -> bool {
+        let Some(entry) = history
+            .entries()
+            .rev()
+            .find(|entry| slots.contains(&entry.no) && entry.meta.requires_confirmation) else {
+            return false;
+        };
+        if self.confirmations.confirmed.as_deref() == Some(slots) {
+            self.confirmations.confirmed = None;
+            return false;
+        }
+
+        self.confirmations.pending = Some((request.clone(), slots.to_vec()));
+        self.ew.write(UndoNeedsConfirmation {
+            no: entry.no,
+            meta: entry.meta.clone(),
+        });
+        true
+    }
+}
+
+
+/// Sends the request awaiting confirmation again once confirmed, to be resolved in the same frame.
+pub(crate) fn resolve_confirmations_system(
+    mut confirmations: ResMut<UndoConfirmations>,
+    mut confirm: MessageReader<ConfirmUndoEvent>,
+    mut cancel: MessageReader<CancelUndoEvent>,
+    mut requests: MessageWriter<RequestUndoEvent>,
+    history: Res<UndoHistory>,
+) {
+    if !cancel.is_empty() {
+        cancel.clear();
+        confirmations.pending = None;
+    }
+    if confirm.is_empty() {
+        return;
+    }
+    confirm.clear();
+
+    let Some((request, slots)) = confirmations.pending.take() else {
+        return;
+    };
+    if slots.iter().all(|no| history.slot_len(*no) > 0) {
+        confirmations.confirmed = Some(slots);
+        requests.write(request);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, CancelUndoEvent, ConfirmUndoEvent, UndoChannel, UndoMeta, UndoNeedsConfirmation, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Generate(&'static str);
+
+
+    fn new_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Generate>();
+        let mut state = SystemState::<UndoScheduler<Generate>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register_with_meta(Generate("image"), UndoMeta::default().requiring_confirmation());
+        state.apply(app.world_mut());
+        app.update();
+        app
+    }
+
+
+    fn undo(app: &mut App) -> Vec<Generate> {
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().resource_mut::<Messages<Generate>>().drain().collect()
+    }
+
+
+    fn confirmations(app: &mut App) -> usize {
+        app.world_mut().resource_mut::<Messages<UndoNeedsConfirmation>>().drain().count()
+    }
+
+
+    #[test]
+    fn undo_once_confirmed() {
+        let mut app = new_app();
+        assert!(undo(&mut app).is_empty());
+        assert_eq!(confirmations(&mut app), 1);
+
+        app.world_mut().write_message(ConfirmUndoEvent);
+        app.update();
+        assert_eq!(app.world_mut().resource_mut::<Messages<Generate>>().drain().collect::<Vec<_>>(), vec![Generate("image")]);
+        assert_eq!(confirmations(&mut app), 0);
+    }
+
+
+    #[test]
+    fn keep_entry_when_cancelled() {
+        let mut app = new_app();
+        assert!(undo(&mut app).is_empty());
+        assert_eq!(confirmations(&mut app), 1);
+        app.world_mut().write_message(CancelUndoEvent);
+        app.update();
+        app.world_mut().write_message(ConfirmUndoEvent);
+        app.update();
+        assert!(app.world_mut().resource_mut::<Messages<Generate>>().drain().next().is_none());
+
+        assert!(undo(&mut app).is_empty());
+        assert_eq!(confirmations(&mut app), 1);
+    }
+}
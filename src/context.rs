@@ -0,0 +1,71 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::Res;
+
+use crate::payload::UndoPayload;
+use crate::request::UndoRequester;
+use crate::state::UndoState;
+use crate::undo_event::UndoScheduler;
+#[cfg(feature = "reserve")]
+use crate::undo_event::UndoReserveCommitter;
+use crate::view::UndoView;
+
+/// Bundles the params a tool system usually needs to register entries of `E`, commit reservations,
+/// request undos and read the history, in place of taking them one by one.
+#[derive(SystemParam)]
+pub struct UndoContext<'w, E: UndoPayload> {
+    pub scheduler: UndoScheduler<'w, E>,
+    #[cfg(feature = "reserve")]
+    pub committer: UndoReserveCommitter<'w>,
+    pub requester: UndoRequester<'w>,
+    pub view: UndoView<'w>,
+    pub state: Res<'w, UndoState>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::prelude::{Local, Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoContext, UndoState};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Stamp(u32);
+
+
+    #[test]
+    fn register_and_request_through_one_param() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Stamp>();
+        app.add_systems(Update, |mut cx: UndoContext<Stamp>, mut frame: Local<u32>| {
+            *frame += 1;
+            match *frame {
+                1 => cx.scheduler.register(Stamp(1)),
+                #[cfg(feature = "reserve")]
+                2 => {
+                    cx.scheduler.reserve(Stamp(2));
+                    cx.scheduler.reserve(Stamp(3));
+                    cx.committer.commit();
+                }
+                3 => {
+                    assert_eq!(*cx.state, UndoState::Idle);
+                    assert_eq!(cx.view.depth::<Stamp>(), if cfg!(feature = "reserve") { 3 } else { 1 });
+                    cx.requester.undo();
+                }
+                _ => {}
+            }
+        });
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let undone: Vec<Stamp> = app.world_mut().resource_mut::<Messages<Stamp>>().drain().collect();
+        if cfg!(feature = "reserve") {
+            assert_eq!(undone, vec![Stamp(3), Stamp(2)]);
+        } else {
+            assert_eq!(undone, vec![Stamp(1)]);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use bevy::prelude::Resource;
+
+/// Drops undo and redo requests arriving too soon after the last one processed.
+///
+/// It is configured via [`AppUndoEx::configure_undo_cooldown`](crate::prelude::AppUndoEx::configure_undo_cooldown).
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoCooldown {
+    pub duration: Option<Duration>,
+    last: Option<Duration>,
+    suppressed: usize,
+}
+
+
+impl UndoCooldown {
+    /// Returns true if the requests sent in a frame at `now` may be processed, otherwise counts them as suppressed.
+    ///
+    /// The requests of a frame come from the same input, such as a single [`UndoRequester::undo_count`](crate::prelude::UndoRequester::undo_count),
+    /// so they are admitted or suppressed together.
+    /// Without [`Time`](bevy::prelude::Time), all requests are processed.
+    pub fn admit(&mut self, now: Option<Duration>, requests: usize) -> bool {
+        let (Some(duration), Some(now)) = (self.duration, now) else {
+            return true;
+        };
+        if self.last.is_some_and(|last| now.saturating_sub(last) < duration) {
+            self.suppressed += requests;
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+
+
+    /// The total count of requests dropped by the cooldown.
+    #[inline(always)]
+    pub fn suppressed(&self) -> usize {
+        self.suppressed
+    }
+}
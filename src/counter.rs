@@ -1,7 +1,6 @@
-use std::ops::{AddAssign, Deref};
+use std::ops::Deref;
 
 use bevy::prelude::Resource;
-use crate::reserve::ReserveCounter;
 
 #[derive(Resource, Default, Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
 #[repr(transparent)]
@@ -9,12 +8,6 @@ pub(crate) struct UndoCounter(usize);
 
 
 impl UndoCounter {
-    #[inline(always)]
-    pub const fn new() -> Self {
-        Self(0)
-    }
-
-
     #[inline(always)]
     pub fn increment(&mut self) {
         self.0 += 1;
@@ -22,16 +15,8 @@ impl UndoCounter {
 
 
     #[inline(always)]
-    pub fn decrement(&mut self) {
-        self.0 = self.0.checked_sub(1).unwrap_or_default();
-    }
-}
-
-
-
-impl AddAssign<ReserveCounter> for UndoCounter{
-    fn add_assign(&mut self, rhs: ReserveCounter) {
-        self.0 += *rhs;
+    pub fn set(&mut self, no: usize) {
+        self.0 = no;
     }
 }
 
@@ -44,4 +29,4 @@ impl Deref for UndoCounter {
     fn deref(&self) -> &Self::Target {
         &self.0
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,125 @@
+use bevy::prelude::{MessageReader, Res, ResMut, Resource};
+
+use crate::UndoAreas;
+use crate::counter::UndoCounter;
+use crate::history::UndoHistory;
+use crate::merge::mergeable_slot;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEvent;
+
+type DuplicateHook<E> = Box<dyn Fn(&E, &E) -> bool + Send + Sync + 'static>;
+
+
+/// Tells whether the newer payload duplicates the previous one.
+#[derive(Resource)]
+pub(crate) struct UndoDuplicateHook<E: UndoPayload>(pub DuplicateHook<E>);
+
+
+/// Drops the entries of `E` registered in this frame which duplicate the previous entry of their channel for the same entities.
+pub(crate) fn dedup_undo_entries_system<E: UndoPayload>(
+    mut er: MessageReader<UndoEvent<E>>,
+    mut areas: ResMut<UndoAreas>,
+    mut history: ResMut<UndoHistory>,
+    mut counter: ResMut<UndoCounter>,
+    hook: Res<UndoDuplicateHook<E>>,
+) {
+    let registered_area = areas.registered_mut::<E>();
+    let mut registered: Vec<usize> = er.read().map(|e| e.no).collect();
+    if registered.is_empty() {
+        return;
+    }
+    registered.sort_unstable();
+    registered.dedup();
+
+    let type_name = std::any::type_name::<E>();
+    let mut dropped = false;
+    for newer in registered {
+        let Some(previous) = mergeable_slot(&history, newer, type_name) else {
+            continue;
+        };
+        if history.entities_of_slot(previous) != history.entities_of_slot(newer) {
+            continue;
+        }
+        let (Some(previous_event), Some(newer_event)) = (
+            registered_area.slot_events(previous).pop(),
+            registered_area.slot_events(newer).pop()
+        ) else {
+            continue;
+        };
+        if !(hook.0)(&previous_event, &newer_event) {
+            continue;
+        }
+
+        registered_area.pop_slot(newer);
+        history.remove_slot(newer);
+        dropped = true;
+    }
+    if dropped {
+        counter.set(history.max_no().unwrap_or_default());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Local, Message, Update};
+
+    use crate::prelude::{AppUndoEx, UndoScheduler};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move {
+        id: u32,
+        to: i32,
+    }
+
+
+    fn register_moves(harness: &mut UndoTestHarness<Move>) {
+        harness.app().add_systems(Update, |mut scheduler: UndoScheduler<Move>, mut frame: Local<usize>| {
+            *frame += 1;
+            if *frame == 1 {
+                scheduler.register(Move { id: 1, to: 0 });
+                scheduler.register(Move { id: 1, to: 0 });
+            } else if *frame == 2 {
+                scheduler.register(Move { id: 1, to: 0 });
+                scheduler.register(Move { id: 1, to: 1 });
+                scheduler.register(Move { id: 2, to: 1 });
+            }
+        });
+        harness.frames(3);
+    }
+
+
+    #[test]
+    fn drop_identical_consecutive_entries() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.dedup_undo_entries::<Move>();
+        });
+        register_moves(&mut harness);
+        harness.undo();
+        harness.expect([Move { id: 2, to: 1 }]);
+        harness.undo();
+        harness.expect([Move { id: 1, to: 1 }]);
+        harness.undo();
+        harness.expect([Move { id: 1, to: 0 }]);
+        harness.undo();
+        harness.expect([]);
+    }
+
+
+    #[test]
+    fn drop_entries_with_same_hash() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.dedup_undo_entries_by_hash::<Move>(|event| event.id as u64);
+        });
+        register_moves(&mut harness);
+        harness.undo();
+        harness.expect([Move { id: 2, to: 1 }]);
+        harness.undo();
+        harness.expect([Move { id: 1, to: 0 }]);
+        harness.undo();
+        harness.expect([]);
+    }
+}
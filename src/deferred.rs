@@ -0,0 +1,214 @@
+use bevy::ecs::schedule::BoxedCondition;
+use bevy::prelude::{IntoSystem, Resource, SystemCondition, World};
+
+use crate::counter::UndoCounter;
+use crate::erased::UndoAnyEvent;
+use crate::meta::UndoMeta;
+
+/// Identifies a registration deferred via [`UndoScheduler::register_after_frames`](crate::prelude::UndoScheduler::register_after_frames)
+/// or [`UndoScheduler::register_when`](crate::prelude::UndoScheduler::register_when), so it can be cancelled.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+#[repr(transparent)]
+pub struct UndoDeferredId(pub u64);
+
+
+pub(crate) enum UndoDeferralTrigger {
+    Frames(usize),
+    Condition {
+        condition: BoxedCondition,
+        initialized: bool,
+    },
+}
+
+
+impl UndoDeferralTrigger {
+    #[inline]
+    pub fn condition<M>(condition: impl SystemCondition<M>) -> Self {
+        Self::Condition {
+            condition: Box::new(IntoSystem::into_system(condition)),
+            initialized: false,
+        }
+    }
+
+
+    /// Returns true once the registration is due, evaluated once per frame.
+    fn is_due(&mut self, world: &mut World) -> bool {
+        match self {
+            Self::Frames(frames) => {
+                *frames = frames.saturating_sub(1);
+                *frames == 0
+            }
+            Self::Condition { condition, initialized } => {
+                if !*initialized {
+                    condition.initialize(world);
+                    *initialized = true;
+                }
+                // A condition whose parameters are missing does not hold.
+                condition.run((), world).unwrap_or(false)
+            }
+        }
+    }
+}
+
+
+/// The event of a deferred registration, or the function capturing it once due.
+pub(crate) enum UndoDeferredEvent {
+    Ready(UndoAnyEvent),
+    Capture(Box<dyn FnOnce(&World) -> UndoAnyEvent + Send + Sync + 'static>),
+}
+
+
+struct UndoDeferred {
+    id: UndoDeferredId,
+    trigger: UndoDeferralTrigger,
+    event: UndoDeferredEvent,
+    meta: UndoMeta,
+}
+
+
+/// The registrations waiting for their trigger, checked at the start of each frame.
+#[derive(Resource, Default)]
+pub(crate) struct UndoDeferredRegistrations {
+    pending: Vec<UndoDeferred>,
+    last_id: u64,
+}
+
+
+impl UndoDeferredRegistrations {
+    pub fn push(&mut self, trigger: UndoDeferralTrigger, event: UndoDeferredEvent, meta: UndoMeta) -> UndoDeferredId {
+        self.last_id += 1;
+        let id = UndoDeferredId(self.last_id);
+        self.pending.push(UndoDeferred {
+            id,
+            trigger,
+            event,
+            meta,
+        });
+        id
+    }
+
+
+    /// Drops the registration, returning false if it has already been registered or cancelled.
+    pub fn cancel(&mut self, id: UndoDeferredId) -> bool {
+        let len = self.pending.len();
+        self.pending.retain(|deferred| deferred.id != id);
+        self.pending.len() != len
+    }
+}
+
+
+/// Registers the deferred events whose trigger fired, each into its own slot.
+pub(crate) fn register_deferred_events_system(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<UndoDeferredRegistrations>().pending);
+    if pending.is_empty() {
+        return;
+    }
+
+    let mut waiting = Vec::with_capacity(pending.len());
+    for mut deferred in pending {
+        if !deferred.trigger.is_due(world) {
+            waiting.push(deferred);
+            continue;
+        }
+        let event = match deferred.event {
+            UndoDeferredEvent::Ready(event) => event,
+            UndoDeferredEvent::Capture(capture) => capture(world),
+        };
+        let mut counter = world.resource_mut::<UndoCounter>();
+        counter.increment();
+        let no = **counter;
+        event.send(world, no, deferred.meta);
+    }
+
+    let mut registrations = world.resource_mut::<UndoDeferredRegistrations>();
+    waiting.append(&mut registrations.pending);
+    registrations.pending = waiting;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Commands, Component, Entity, Local, Message, Res, Resource, Update};
+
+    use crate::prelude::UndoScheduler;
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Save(i32);
+
+
+    #[derive(Resource, Default)]
+    struct Saved(bool);
+
+
+    #[test]
+    fn register_after_frames() {
+        let mut harness = UndoTestHarness::<Save>::new();
+        harness.setup(|app| {
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Save>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.register_after_frames(Save(1), 2);
+                    let cancelled = scheduler.register_after_frames(Save(2), 2);
+                    assert!(scheduler.cancel_deferred(cancelled));
+                }
+            });
+        });
+        harness.frames(1);
+        harness.undo();
+        harness.expect([]);
+        harness.undo();
+        harness.expect([Save(1)]);
+        harness.undo();
+        harness.expect([]);
+    }
+
+
+    #[test]
+    fn register_when_condition_holds() {
+        let mut harness = UndoTestHarness::<Save>::new();
+        harness.setup(|app| {
+            app.init_resource::<Saved>();
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Save>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.register_when(Save(1), |saved: Res<Saved>| saved.0);
+                }
+            });
+        });
+        harness.frames(3);
+        harness.undo();
+        harness.expect([]);
+
+        harness.app().world_mut().resource_mut::<Saved>().0 = true;
+        harness.frames(1);
+        harness.undo();
+        harness.expect([Save(1)]);
+    }
+
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Name(&'static str);
+
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Despawn(Option<Name>);
+
+
+    #[test]
+    fn capture_after_commands_applied() {
+        let mut harness = UndoTestHarness::<Despawn>::new();
+        harness.setup(|app| {
+            app.add_systems(Update, |mut commands: Commands, mut scheduler: UndoScheduler<Despawn>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    let entity: Entity = commands.spawn(Name("cube")).id();
+                    scheduler.register_after_commands(move |world| Despawn(world.get::<Name>(entity).cloned()));
+                }
+            });
+        });
+        harness.frames(1);
+        harness.undo();
+        harness.expect([Despawn(Some(Name("cube")))]);
+    }
+}
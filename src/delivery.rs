@@ -0,0 +1,119 @@
+use bevy::prelude::{Local, Messages, Res, ResMut, Resource};
+
+use crate::payload::UndoPayload;
+
+/// The count of frames the undone and redone events are kept readable at least.
+///
+/// It is configured via [`AppUndoEx::configure_undo_delivery_window`](crate::prelude::AppUndoEx::configure_undo_delivery_window).
+#[derive(Resource, Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct UndoDeliveryWindow(pub u32);
+
+
+impl Default for UndoDeliveryWindow {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+
+/// Updates `Messages<E>` in place of [`message_update_system`](bevy::ecs::message::message_update_system),
+/// swapping the buffers once every window instead of every frame, so the events are readable for at least the window and cleared within twice the window.
+///
+/// As the readers keep their own cursors, each of them still reads every event once.
+pub(crate) fn update_undo_events_system<E: UndoPayload>(
+    mut events: ResMut<Messages<E>>,
+    window: Res<UndoDeliveryWindow>,
+    mut age: Local<u32>,
+) {
+    *age += 1;
+    if *age < window.0 {
+        return;
+    }
+    *age = 0;
+    events.update();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, First, Update};
+    use bevy::prelude::{IntoScheduleConfigs, Local, Message, MessageReader, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoRequester, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Paint(u32);
+
+
+    #[derive(Resource, Default)]
+    struct Received(Vec<(&'static str, u32)>);
+
+
+    fn reader(name: &'static str) -> impl FnMut(MessageReader<Paint>, ResMut<Received>) {
+        move |mut er: MessageReader<Paint>, mut received: ResMut<Received>| {
+            received.0.extend(er.read().map(|paint| (name, paint.0)));
+        }
+    }
+
+
+    #[test]
+    fn deliver_once_to_readers_in_any_schedule() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Paint>();
+        app.configure_undo_delivery_window(3);
+        app.init_resource::<Received>();
+        app.add_systems(First, reader("first"));
+        app.add_systems(Update, reader("every third").run_if(|mut frame: Local<u32>| {
+            *frame += 1;
+            frame.is_multiple_of(3)
+        }));
+        app.add_systems(Update, |mut frame: Local<u32>, mut scheduler: UndoScheduler<Paint>, mut requester: UndoRequester| {
+            *frame += 1;
+            match *frame {
+                1 => scheduler.register(Paint(1)),
+                2 => scheduler.register(Paint(2)),
+                3 | 4 => requester.undo(),
+                _ => {}
+            }
+        });
+        for _ in 0..12 {
+            app.update();
+        }
+
+        let received = &app.world().resource::<Received>().0;
+        assert_eq!(received.iter().filter(|(name, _)| *name == "first").map(|(_, no)| *no).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(received.iter().filter(|(name, _)| *name == "every third").map(|(_, no)| *no).collect::<Vec<_>>(), vec![2, 1]);
+    }
+
+
+    #[test]
+    fn clear_undone_events_for_late_readers() {
+        for window in [1, 3] {
+            let mut app = App::new();
+            app.add_plugins(UndoPlugin);
+            app.add_undo_event::<Paint>();
+            app.configure_undo_delivery_window(window);
+            app.init_resource::<Received>();
+            app.add_systems(Update, reader("late").run_if(|mut frame: Local<u32>| {
+                *frame += 1;
+                10 < *frame
+            }));
+            app.add_systems(Update, |mut frame: Local<u32>, mut scheduler: UndoScheduler<Paint>, mut requester: UndoRequester| {
+                *frame += 1;
+                match *frame {
+                    1 => scheduler.register(Paint(1)),
+                    2 => requester.undo(),
+                    _ => {}
+                }
+            });
+            for _ in 0..12 {
+                app.update();
+            }
+
+            assert!(app.world().resource::<Received>().0.is_empty());
+        }
+    }
+}
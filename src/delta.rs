@@ -0,0 +1,265 @@
+use std::hash::Hash;
+
+use bevy::prelude::{MessageReader, ResMut, Resource};
+
+use crate::{DispatchUndoEvent, UndoAreas};
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEntry;
+
+/// Snapshot payloads which can be stored as the difference from the previous snapshot of the same target.
+///
+/// Enabled per type via [`AppUndoEx::compress_undo_deltas`](crate::prelude::AppUndoEx::compress_undo_deltas).
+pub trait UndoDelta: UndoPayload {
+    /// Identifies what the snapshot is taken of, such as an entity or an asset handle.
+    type Target: Eq + Hash + Clone + Send + Sync + 'static;
+
+    type Delta: Send + Sync + 'static;
+
+    fn target(&self) -> Self::Target;
+
+
+    /// Returns the difference needed to rebuild this snapshot from `base`.
+    fn delta(&self, base: &Self) -> Self::Delta;
+
+
+    /// Rebuilds the snapshot from the previous one of the same target and the difference.
+    fn apply(base: &Self, delta: &Self::Delta) -> Self;
+}
+
+
+enum StoredSnapshot<E: UndoDelta> {
+    Full(E),
+    Delta(E::Delta),
+}
+
+
+struct UndoDeltaEntry<E: UndoDelta> {
+    target: E::Target,
+    stored: StoredSnapshot<E>,
+    redo: Option<E>,
+    no: usize,
+}
+
+
+/// Keeps the registered entries of `E` in place of the registered area, most of them as deltas.
+///
+/// Every `keyframe_interval`th snapshot of a target is kept in full, which bounds the count of deltas applied to rebuild one.
+#[derive(Resource)]
+pub(crate) struct UndoDeltaArea<E: UndoDelta> {
+    keyframe_interval: usize,
+    entries: Vec<UndoDeltaEntry<E>>,
+}
+
+
+impl<E: UndoDelta> UndoDeltaArea<E> {
+    #[inline]
+    pub fn new(keyframe_interval: usize) -> Self {
+        Self {
+            keyframe_interval: keyframe_interval.max(1),
+            entries: Vec::new(),
+        }
+    }
+
+
+    pub fn push(&mut self, entry: UndoEntry<E>) {
+        let target = entry.inner.target();
+        let previous = self.entries.iter().rposition(|stored| stored.target == target);
+        let stored = match previous {
+            Some(previous) if self.deltas_since_keyframe(previous) + 1 < self.keyframe_interval => {
+                StoredSnapshot::Delta(entry.inner.delta(&self.snapshot(previous)))
+            }
+            _ => StoredSnapshot::Full(entry.inner)
+        };
+        self.entries.push(UndoDeltaEntry {
+            target,
+            stored,
+            redo: entry.redo,
+            no: entry.no,
+        });
+    }
+
+
+    /// Removes the entry, storing the next snapshot of the same target in full if it was based on the removed one.
+    pub fn remove(&mut self, index: usize) -> UndoEntry<E> {
+        let inner = self.snapshot(index);
+        if let Some(next) = self.next_of_target(index) {
+            if matches!(self.entries[next].stored, StoredSnapshot::Delta(_)) {
+                let snapshot = self.snapshot(next);
+                self.entries[next].stored = StoredSnapshot::Full(snapshot);
+            }
+        }
+
+        let entry = self.entries.remove(index);
+        UndoEntry {
+            inner,
+            redo: entry.redo,
+            no: entry.no,
+        }
+    }
+
+
+    /// Rebuilds the snapshot of the entry from the nearest full one of the same target.
+    fn snapshot(&self, index: usize) -> E {
+        let mut deltas = Vec::new();
+        let mut index = index;
+        loop {
+            match &self.entries[index].stored {
+                StoredSnapshot::Full(snapshot) => {
+                    return deltas
+                        .into_iter()
+                        .rev()
+                        .fold(snapshot.duplicate(), |base, delta| E::apply(&base, delta));
+                }
+                StoredSnapshot::Delta(delta) => {
+                    deltas.push(delta);
+                    // A delta always has a previous entry of the same target, since removing its base stores it in full.
+                    index = self.previous_of_target(index).expect("delta without a base snapshot");
+                }
+            }
+        }
+    }
+
+
+    fn deltas_since_keyframe(&self, index: usize) -> usize {
+        let mut count = 0;
+        let mut index = index;
+        while let StoredSnapshot::Delta(_) = self.entries[index].stored {
+            count += 1;
+            let Some(previous) = self.previous_of_target(index) else {
+                break;
+            };
+            index = previous;
+        }
+        count
+    }
+
+
+    #[inline]
+    fn previous_of_target(&self, index: usize) -> Option<usize> {
+        let target = &self.entries[index].target;
+        self.entries[..index].iter().rposition(|entry| entry.target == *target)
+    }
+
+
+    #[inline]
+    fn next_of_target(&self, index: usize) -> Option<usize> {
+        let target = &self.entries[index].target;
+        self.entries[index + 1..]
+            .iter()
+            .position(|entry| entry.target == *target)
+            .map(|i| index + 1 + i)
+    }
+}
+
+
+/// Moves the newly registered entries into the delta area.
+pub(crate) fn compress_deltas_system<E: UndoDelta>(
+    mut areas: ResMut<UndoAreas>,
+    mut delta_area: ResMut<UndoDeltaArea<E>>,
+) {
+    let registered_area = areas.registered_mut::<E>();
+    for entry in registered_area.0.drain() {
+        delta_area.push(entry);
+    }
+}
+
+
+/// Rebuilds the entries of the dispatched slots back into the registered area, right before they are dispatched.
+pub(crate) fn expand_deltas_system<E: UndoDelta>(
+    mut er: MessageReader<DispatchUndoEvent>,
+    mut areas: ResMut<UndoAreas>,
+    mut delta_area: ResMut<UndoDeltaArea<E>>,
+) {
+    let registered_area = areas.registered_mut::<E>();
+    for dispatch in er.read() {
+        let (DispatchUndoEvent::Undo(no)
+        | DispatchUndoEvent::Discard(no)
+        | DispatchUndoEvent::Evict(no)
+        | DispatchUndoEvent::DryRun(no)) = *dispatch else {
+            continue;
+        };
+        while let Some(index) = delta_area.entries.iter().position(|entry| entry.no == no) {
+            let entry = delta_area.remove(index);
+            registered_area.push(entry);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Message, Messages};
+
+    use crate::delta::{StoredSnapshot, UndoDeltaArea};
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoDelta, UndoScheduler, UndoStackConfig};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Canvas {
+        id: u32,
+        pixels: Vec<u8>,
+    }
+
+
+    impl UndoDelta for Canvas {
+        type Target = u32;
+
+        type Delta = Vec<(usize, u8)>;
+
+        fn target(&self) -> u32 {
+            self.id
+        }
+
+        fn delta(&self, base: &Self) -> Self::Delta {
+            self.pixels
+                .iter()
+                .zip(base.pixels.iter())
+                .enumerate()
+                .filter(|(_, (pixel, base))| pixel != base)
+                .map(|(i, (pixel, _))| (i, *pixel))
+                .collect()
+        }
+
+        fn apply(base: &Self, delta: &Self::Delta) -> Self {
+            let mut pixels = base.pixels.clone();
+            for (i, pixel) in delta.iter() {
+                pixels[*i] = *pixel;
+            }
+            Self { id: base.id, pixels }
+        }
+    }
+
+
+    fn canvas(painted: usize) -> Canvas {
+        Canvas {
+            id: 1,
+            pixels: (0..4).map(|i| u8::from(i < painted)).collect(),
+        }
+    }
+
+
+    #[test]
+    fn undo_delta_compressed_snapshots() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Canvas>();
+        app.compress_undo_deltas::<Canvas>(3);
+        app.configure_undo_type::<Canvas>(UndoStackConfig::with_capacity(3));
+        app.add_systems(Startup, |mut s: UndoScheduler<Canvas>| s.register_all((0..4).map(canvas)));
+        app.update();
+
+        let full = app.world().resource::<UndoDeltaArea<Canvas>>()
+            .entries
+            .iter()
+            .filter(|entry| matches!(entry.stored, StoredSnapshot::Full(_)))
+            .count();
+        assert_eq!(full, 2);
+
+        app.world_mut().write_message_batch((0..3).map(|_| RequestUndoEvent::Latest(UndoChannel::DEFAULT)));
+        app.update();
+        let undone: Vec<Canvas> = app.world_mut().resource_mut::<Messages<Canvas>>().drain().collect();
+        assert_eq!(undone, vec![canvas(3), canvas(2), canvas(1)]);
+    }
+}
@@ -0,0 +1,324 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{MessageWriter, ResMut, Resource};
+
+use crate::channel::UndoChannel;
+use crate::request::RequestUndoEvent;
+
+/// Which document is active, [`UndoChannel::DEFAULT`] standing for none.
+///
+/// It is switched only through [`UndoDocuments`], which takes it as [`ResMut`],
+/// so the systems routing entries and requests never run in parallel with a switch.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoDocumentState {
+    active: UndoChannel,
+}
+
+
+impl UndoDocumentState {
+    /// Returns the channel of the active document in place of [`UndoChannel::DEFAULT`].
+    #[inline]
+    pub fn route(&self, channel: UndoChannel) -> UndoChannel {
+        if channel == UndoChannel::DEFAULT {
+            self.active
+        } else {
+            channel
+        }
+    }
+
+
+    #[inline]
+    fn active(&self) -> Option<UndoChannel> {
+        Some(self.route(UndoChannel::DEFAULT)).filter(|active| *active != UndoChannel::DEFAULT)
+    }
+
+
+    #[inline]
+    fn set_active(&mut self, active: Option<UndoChannel>) {
+        self.active = active.unwrap_or(UndoChannel::DEFAULT);
+    }
+}
+
+
+/// The open documents in the order created.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoOpenDocuments(Vec<UndoChannel>);
+
+
+/// Keeps a separate history per document, such as the tabs of an editor.
+///
+/// Each document is identified by its own [`UndoChannel`].
+/// While a document is active, entries registered to [`UndoChannel::DEFAULT`] go to it,
+/// and requests and queries for [`UndoChannel::DEFAULT`] refer to it,
+/// so the rest of the app does not need to know which document is active.
+///
+/// To switch documents in a system also registering entries or sending requests, take them in a [`ParamSet`](bevy::prelude::ParamSet).
+#[derive(SystemParam)]
+pub struct UndoDocuments<'w> {
+    state: ResMut<'w, UndoDocumentState>,
+    documents: ResMut<'w, UndoOpenDocuments>,
+    ew: MessageWriter<'w, RequestUndoEvent>,
+}
+
+
+impl<'w> UndoDocuments<'w> {
+    /// Opens the document, returns false if it is already open.
+    #[inline]
+    pub fn create(&mut self, id: impl Into<UndoChannel>) -> bool {
+        let id = id.into();
+        if self.contains(id) {
+            return false;
+        }
+        self.documents.0.push(id);
+        true
+    }
+
+
+    /// Makes the document active, returns false if it is not open.
+    ///
+    /// Entries registered before switching stay in the previously active document.
+    #[inline]
+    pub fn set_active(&mut self, id: impl Into<UndoChannel>) -> bool {
+        let id = id.into();
+        if !self.contains(id) {
+            return false;
+        }
+        self.state.set_active(Some(id));
+        true
+    }
+
+
+    /// Closes the document and drops its whole history, returns false if it is not open.
+    ///
+    /// The entries are dropped in the next frame, and each of them is sent as [`UndoEvicted`](crate::prelude::UndoEvicted)
+    /// so that side resources referenced by them can be released.
+    /// If the document is active, no document is active afterwards.
+    pub fn close(&mut self, id: impl Into<UndoChannel>) -> bool {
+        let id = id.into();
+        let Some(index) = self.documents.0.iter().position(|document| *document == id) else {
+            return false;
+        };
+        self.documents.0.remove(index);
+        if self.active() == Some(id) {
+            self.state.set_active(None);
+        }
+        self.ew.write(RequestUndoEvent::CloseChannel(id));
+        true
+    }
+
+
+    /// Deactivates the active document, so [`UndoChannel::DEFAULT`] refers to itself again.
+    #[inline(always)]
+    pub fn deactivate(&mut self) {
+        self.state.set_active(None);
+    }
+
+
+    #[inline(always)]
+    pub fn active(&self) -> Option<UndoChannel> {
+        self.state.active()
+    }
+
+
+    #[inline]
+    pub fn contains(&self, id: impl Into<UndoChannel>) -> bool {
+        self.documents.0.contains(&id.into())
+    }
+
+
+    /// Iterates the open documents in the order created.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = UndoChannel> + '_ {
+        self.documents.0.iter().copied()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages, ParamSet};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoDocuments, UndoEvicted, UndoGestureGrouping, UndoMeta, UndoRequester, UndoScheduler, UndoView};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Edit(&'static str);
+
+
+    fn run<P: bevy::ecs::system::SystemParam + 'static>(app: &mut App, f: impl FnOnce(&mut P::Item<'_, '_>)) {
+        let mut state = SystemState::<P>::new(app.world_mut());
+        f(&mut state.get_mut(app.world_mut()).unwrap());
+        state.apply(app.world_mut());
+    }
+
+
+    fn undone(app: &mut App) -> Vec<Edit> {
+        app.world_mut().resource_mut::<Messages<Edit>>().drain().collect()
+    }
+
+
+    #[test]
+    fn route_to_active_document() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+
+        run::<UndoDocuments>(&mut app, |documents| {
+            assert!(documents.create(1));
+            assert!(documents.create(2));
+            assert!(!documents.create(2));
+            assert!(documents.set_active(1));
+            assert!(!documents.set_active(3));
+        });
+        run::<UndoScheduler<Edit>>(&mut app, |s| s.register(Edit("first tab")));
+        app.update();
+        run::<UndoDocuments>(&mut app, |documents| {
+            documents.set_active(2);
+        });
+        run::<UndoScheduler<Edit>>(&mut app, |s| s.register(Edit("second tab")));
+        app.update();
+        app.world_mut().resource_mut::<Messages<Edit>>().clear();
+
+        run::<UndoRequester>(&mut app, |requester| {
+            assert_eq!(requester.len(), 1);
+            assert_eq!(requester.len_channel(1), 1);
+            requester.undo();
+        });
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("second tab")]);
+
+        run::<UndoDocuments>(&mut app, |documents| {
+            documents.set_active(1);
+        });
+        run::<UndoRequester>(&mut app, |requester| {
+            assert!(requester.can_undo());
+            requester.undo();
+        });
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("first tab")]);
+    }
+
+
+    #[test]
+    fn show_next_undo_targets_of_active_document() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        let sketch = app.world_mut().spawn_empty().id();
+        let model = app.world_mut().spawn_empty().id();
+
+        run::<UndoDocuments>(&mut app, |documents| {
+            documents.create(1);
+            documents.create(2);
+            documents.set_active(1);
+        });
+        run::<UndoScheduler<Edit>>(&mut app, |s| s.register_with_meta(Edit("sketch"), UndoMeta::for_entity(sketch)));
+        app.update();
+        run::<UndoDocuments>(&mut app, |documents| {
+            documents.set_active(2);
+        });
+        run::<UndoScheduler<Edit>>(&mut app, |s| s.register_with_meta(Edit("model"), UndoMeta::for_entity(model)));
+        app.update();
+
+        run::<UndoRequester>(&mut app, |requester| {
+            assert_eq!(requester.next_undo_targets(UndoChannel::DEFAULT), vec![model]);
+            assert_eq!(requester.next_undo_targets(1), vec![sketch]);
+        });
+    }
+
+
+    #[test]
+    fn keep_entries_registered_before_switching() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        run::<UndoDocuments>(&mut app, |documents| {
+            documents.create(1);
+            documents.create(2);
+            documents.set_active(1);
+        });
+
+        run::<ParamSet<(UndoScheduler<Edit>, UndoDocuments)>>(&mut app, |params| {
+            params.p0().register(Edit("first tab"));
+            params.p1().set_active(2);
+            params.p0().register(Edit("second tab"));
+        });
+        app.update();
+
+        run::<UndoRequester>(&mut app, |requester| {
+            assert_eq!(requester.len_channel(1), 1);
+            assert_eq!(requester.len_channel(2), 1);
+            requester.undo();
+        });
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("second tab")]);
+    }
+
+
+    #[test]
+    fn group_entries_by_strategy_of_active_document() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        app.configure_undo_grouping(1, UndoGestureGrouping);
+        run::<UndoDocuments>(&mut app, |documents| {
+            documents.create(1);
+            documents.set_active(1);
+        });
+
+        run::<UndoScheduler<Edit>>(&mut app, |s| {
+            s.begin_gesture();
+            s.register(Edit("press"));
+            s.register(Edit("drag"));
+            s.end_gesture();
+        });
+        app.update();
+        run::<UndoRequester>(&mut app, |requester| requester.undo());
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("drag"), Edit("press")]);
+    }
+
+
+    #[test]
+    fn close_document() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+
+        run::<UndoDocuments>(&mut app, |documents| {
+            documents.create(1);
+            documents.create(2);
+            documents.set_active(1);
+        });
+        run::<UndoScheduler<Edit>>(&mut app, |s| {
+            s.register(Edit("a"));
+            s.push(Edit("b"), Some(Edit("b")), UndoMeta::default());
+            s.register_to(2, Edit("other"));
+        });
+        app.update();
+        run::<UndoRequester>(&mut app, |requester| requester.undo());
+        app.update();
+        app.world_mut().resource_mut::<Messages<Edit>>().clear();
+
+        run::<UndoDocuments>(&mut app, |documents| {
+            assert!(documents.close(1));
+            assert!(!documents.close(1));
+            assert_eq!(documents.active(), None);
+        });
+        app.update();
+
+        let mut evicted: Vec<&str> = app.world_mut().resource_mut::<Messages<UndoEvicted<Edit>>>()
+            .drain()
+            .map(|e| e.payload.0)
+            .collect();
+        evicted.sort_unstable();
+        assert_eq!(evicted, vec!["a", "b"]);
+        assert!(undone(&mut app).is_empty());
+
+        let mut state = SystemState::<UndoView>::new(app.world_mut());
+        let channels: Vec<u64> = state.get(app.world()).unwrap().iter().map(|entry| entry.meta.channel.0).collect();
+        assert_eq!(channels, vec![2]);
+        assert_eq!(state.get(app.world()).unwrap().iter_redo().count(), 0);
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::ecs::component::Mutable;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Component, Entity, ResMut, Resource};
+use bevy::platform::collections::HashMap;
+
+use crate::component::UndoComponentEvent;
+use crate::meta::UndoMeta;
+use crate::undo_event::UndoScheduler;
+
+/// The component values captured when the drags started.
+#[derive(Resource)]
+pub(crate) struct UndoDragStarts<C: Component>(HashMap<Entity, C>);
+
+
+impl<C: Component> Default for UndoDragStarts<C> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+
+
+/// Registers a drag as a single entry instead of one per frame of movement.
+///
+/// Call [`UndoDrag::begin`] on press and [`UndoDrag::end`] on release,
+/// the component type must be set up via [`AppUndoEx::add_undo_component`](crate::prelude::AppUndoEx::add_undo_component).
+#[derive(SystemParam)]
+pub struct UndoDrag<'w, C: Component<Mutability = Mutable> + Clone + PartialEq> {
+    starts: ResMut<'w, UndoDragStarts<C>>,
+    scheduler: UndoScheduler<'w, UndoComponentEvent<C>>,
+}
+
+
+impl<'w, C: Component<Mutability = Mutable> + Clone + PartialEq> UndoDrag<'w, C> {
+    /// Captures the value at the start of the drag.
+    ///
+    /// Beginning again before [`UndoDrag::end`] keeps the first value.
+    #[inline]
+    pub fn begin(&mut self, entity: Entity, value: &C) {
+        self.starts.0.entry(entity).or_insert_with(|| value.clone());
+    }
+
+
+    /// Returns true while the drag of the entity is in progress.
+    #[inline(always)]
+    pub fn is_dragging(&self, entity: Entity) -> bool {
+        self.starts.0.contains_key(&entity)
+    }
+
+
+    /// Finishes the drag and registers an entry which restores the value captured by [`UndoDrag::begin`].
+    ///
+    /// Returns false without registering anything if the drag has not begun or the value is unchanged.
+    pub fn end(&mut self, entity: Entity, value: &C) -> bool {
+        let Some(start) = self.starts.0.remove(&entity) else {
+            return false;
+        };
+        if start == *value {
+            return false;
+        }
+
+        self.scheduler.push(
+            UndoComponentEvent { entity, value: start },
+            Some(UndoComponentEvent { entity, value: value.clone() }),
+            UndoMeta::for_entity(entity),
+        );
+        true
+    }
+
+
+    /// Aborts the drag without registering anything, returning the value captured at its start.
+    #[inline]
+    pub fn cancel(&mut self, entity: Entity) -> Option<C> {
+        self.starts.0.remove(&entity)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::prelude::{Component, Entity, Query, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoDrag};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Resource)]
+    struct Frame(usize, Entity);
+
+
+    #[test]
+    fn register_whole_drag_once() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_component::<Position>();
+        let entity = app.world_mut().spawn(Position(0)).id();
+        app.insert_resource(Frame(0, entity));
+        app.add_systems(Update, |mut drag: UndoDrag<Position>, mut frame: ResMut<Frame>, mut positions: Query<&mut Position>| {
+            let entity = frame.1;
+            let mut position = positions.get_mut(entity).unwrap();
+            match frame.0 {
+                0 => drag.begin(entity, &position),
+                1..=3 => position.0 += 1,
+                4 => assert!(drag.end(entity, &position)),
+                _ => {}
+            }
+            frame.0 += 1;
+        });
+        for _ in 0..6 {
+            app.update();
+        }
+        assert_eq!(app.world().get::<Position>(entity), Some(&Position(3)));
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().get::<Position>(entity), Some(&Position(0)));
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().get::<Position>(entity), Some(&Position(0)));
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().get::<Position>(entity), Some(&Position(3)));
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::Message;
+
+use crate::payload::UndoPayload;
+
+/// Sent instead of `E` for the entries a dry-run would undo, see [`UndoRequester::dry_run`](crate::prelude::UndoRequester::dry_run).
+///
+/// The events are sent in the same order as the real undo, while the entries stay in the history.
+#[derive(Message, Debug)]
+pub struct DryRun<E: UndoPayload>(pub E);
+
+
+/// Sent for the redo-events a redo would send, see [`UndoRequester::preview_redo`](crate::prelude::UndoRequester::preview_redo),
+/// so games can render ghosts of what the redo would bring back before the player commits to it.
+///
+/// The events are sent in the same order as the real redo, while the entries keep waiting for redo.
+#[derive(Message, Debug)]
+pub struct Preview<E: UndoPayload>(pub E);
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, DryRun, Preview, UndoMeta, UndoRequester, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(usize);
+
+
+    #[test]
+    fn dry_run_keeps_history() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>| {
+            s.register(Move(1));
+            s.register_all_grouped([Move(2), Move(3)]);
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().dry_run();
+        app.update();
+        let previewed: Vec<usize> = app.world().resource::<Messages<DryRun<Move>>>().iter_current_update_messages().map(|e| e.0.0).collect();
+        assert_eq!(previewed, vec![3, 2]);
+        assert_eq!(app.world().resource::<Messages<Move>>().iter_current_update_messages().count(), 0);
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().len(), 2);
+
+        state.get_mut(app.world_mut()).unwrap().undo();
+        app.update();
+        let undone: Vec<usize> = app.world().resource::<Messages<Move>>().iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(undone, previewed);
+    }
+
+
+    #[test]
+    fn preview_redo_keeps_redo_history() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>| {
+            s.push(Move(1), Some(Move(10)), UndoMeta::default());
+            s.push(Move(2), Some(Move(20)), UndoMeta::default());
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().undo();
+        app.update();
+        state.get_mut(app.world_mut()).unwrap().preview_redo();
+        app.update();
+        let previewed: Vec<usize> = app.world().resource::<Messages<Preview<Move>>>().iter_current_update_messages().map(|e| e.0.0).collect();
+        assert_eq!(previewed, vec![20]);
+        assert_eq!(app.world().resource::<Messages<Move>>().iter_current_update_messages().count(), 0);
+
+        state.get_mut(app.world_mut()).unwrap().redo();
+        app.update();
+        let redone: Vec<usize> = app.world().resource::<Messages<Move>>().iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(redone, previewed);
+    }
+}
@@ -0,0 +1,317 @@
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::SystemState;
+use bevy::prelude::{Component, Entity, Resource, With, World};
+use bevy_egui::egui::{self, ScrollArea, Ui};
+use bevy_egui::{EguiContext, EguiPrimaryContextPass, PrimaryEguiContext};
+
+use crate::channel::UndoChannel;
+use crate::document::UndoDocumentState;
+use crate::history::{UndoHistory, UndoHistoryEntry};
+use crate::request::UndoRequester;
+
+/// Shows the history in an egui window, with buttons to undo, redo and jump to any slot.
+///
+/// The window requires the `EguiPlugin` to be added.
+/// Editors hosting their own windows call [`undo_history_ui`] from their window instead of adding this plugin.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoEditorPlugin;
+
+
+impl Plugin for UndoEditorPlugin {
+    #[inline]
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<UndoEditorState>()
+            .add_systems(EguiPrimaryContextPass, show_history_window_system);
+    }
+}
+
+
+/// Inserted on the entities affected by the slot hovered or selected in the history panel,
+/// so that hierarchies and viewports can highlight them.
+#[derive(Component, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoHighlighted;
+
+
+#[derive(Resource, Debug, Default)]
+struct UndoEditorState {
+    selected: Option<usize>,
+    highlighted: Vec<Entity>,
+}
+
+
+/// A slot listed by the panel, entries registered together being shown as one row.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct UndoPanelRow {
+    no: usize,
+    channel: UndoChannel,
+    redo: bool,
+    labels: Vec<String>,
+    entities: Vec<Entity>,
+
+    /// The count of undos, or redos for undone slots, needed to get to the state right after this slot.
+    steps: usize,
+}
+
+
+enum UndoPanelAction {
+    Undo(UndoChannel, usize),
+    Redo(UndoChannel, usize),
+}
+
+
+/// Shows the history panel in the ui: the slots waiting for redo dimmed on top, then the slots to undo from the most recent.
+///
+/// Hovering or selecting a slot inserts [`UndoHighlighted`] on the entities it affects.
+pub fn undo_history_ui(world: &mut World, ui: &mut Ui) {
+    world.init_resource::<UndoEditorState>();
+    let rows = panel_rows(world.resource::<UndoHistory>());
+    let channel = world.resource::<UndoDocumentState>().route(UndoChannel::DEFAULT);
+    let mut selected = world.resource::<UndoEditorState>().selected.filter(|no| rows.iter().any(|row| row.no == *no));
+    let mut hovered = None;
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        let (can_undo, can_redo) = panel_buttons(&rows, channel);
+        if ui.add_enabled(can_undo, egui::Button::new("Undo")).clicked() {
+            action = Some(UndoPanelAction::Undo(UndoChannel::DEFAULT, 1));
+        }
+        if ui.add_enabled(can_redo, egui::Button::new("Redo")).clicked() {
+            action = Some(UndoPanelAction::Redo(UndoChannel::DEFAULT, 1));
+        }
+    });
+    ui.separator();
+
+    ScrollArea::vertical().show(ui, |ui| {
+        for row in &rows {
+            ui.horizontal(|ui| {
+                let text = egui::RichText::new(format!("#{} {}", row.no, row.labels.join(", ")));
+                let text = if row.redo { text.weak() } else { text };
+                let response = ui.selectable_label(selected == Some(row.no), text);
+                if response.clicked() {
+                    selected = (selected != Some(row.no)).then_some(row.no);
+                }
+                if response.hovered() {
+                    hovered = Some(row.no);
+                }
+                if 0 < row.steps && ui.small_button("Jump").clicked() {
+                    action = Some(if row.redo {
+                        UndoPanelAction::Redo(row.channel, row.steps)
+                    } else {
+                        UndoPanelAction::Undo(row.channel, row.steps)
+                    });
+                }
+            });
+        }
+        if rows.is_empty() {
+            ui.weak("No entries");
+        }
+    });
+
+    let highlighted = hovered
+        .or(selected)
+        .and_then(|no| rows.iter().find(|row| row.no == no))
+        .map(|row| row.entities.clone())
+        .unwrap_or_default();
+    highlight(world, highlighted);
+    world.resource_mut::<UndoEditorState>().selected = selected;
+
+    if let Some(action) = action {
+        let mut state = SystemState::<UndoRequester>::new(world);
+        let mut requester = state.get_mut(world).expect("the resources of UndoPlugin are missing");
+        match action {
+            UndoPanelAction::Undo(channel, n) => requester.undo_channel_count(channel, n),
+            UndoPanelAction::Redo(channel, n) => requester.redo_channel_count(channel, n),
+        }
+        state.apply(world);
+    }
+}
+
+
+fn show_history_window_system(world: &mut World) {
+    let Ok(mut context) = world
+        .query_filtered::<&mut EguiContext, With<PrimaryEguiContext>>()
+        .single_mut(world) else {
+        return;
+    };
+    let context = context.get_mut().clone();
+    egui::Window::new("Undo History").show(&context, |ui| undo_history_ui(world, ui));
+}
+
+
+/// Moves [`UndoHighlighted`] from the entities highlighted so far onto the given ones.
+fn highlight(world: &mut World, entities: Vec<Entity>) {
+    let previous = std::mem::take(&mut world.resource_mut::<UndoEditorState>().highlighted);
+    for entity in previous.iter().filter(|entity| !entities.contains(entity)) {
+        if let Ok(mut entity) = world.get_entity_mut(*entity) {
+            entity.remove::<UndoHighlighted>();
+        }
+    }
+    for entity in &entities {
+        if let Ok(mut entity) = world.get_entity_mut(*entity) {
+            entity.insert(UndoHighlighted);
+        }
+    }
+    world.resource_mut::<UndoEditorState>().highlighted = entities;
+}
+
+
+/// Returns whether the undo and redo buttons are enabled, the rows holding the channels the entries were routed to.
+#[inline]
+fn panel_buttons(rows: &[UndoPanelRow], channel: UndoChannel) -> (bool, bool) {
+    (
+        rows.iter().any(|row| !row.redo && row.channel == channel),
+        rows.iter().any(|row| row.redo && row.channel == channel),
+    )
+}
+
+
+fn panel_rows(history: &UndoHistory) -> Vec<UndoPanelRow> {
+    let mut redo_rows = rows_of(history.redo_entries(), true);
+    redo_rows.reverse();
+    let mut rows = redo_rows;
+    rows.extend(rows_of(history.entries().rev(), false));
+    rows
+}
+
+
+/// Groups the entries, ordered from the slot reached in the fewest steps, into rows.
+fn rows_of<'a>(entries: impl Iterator<Item = &'a UndoHistoryEntry>, redo: bool) -> Vec<UndoPanelRow> {
+    let mut rows: Vec<UndoPanelRow> = Vec::new();
+    for entry in entries {
+        let label = if entry.meta.tag.is_empty() {
+            short_type_name(entry.type_name).to_string()
+        } else {
+            entry.meta.tag.clone()
+        };
+        match rows.last_mut() {
+            Some(row) if row.no == entry.no => {
+                if !row.labels.contains(&label) {
+                    row.labels.push(label);
+                }
+                row.entities.extend(entry.meta.entities.iter().copied());
+                row.entities.sort_unstable();
+                row.entities.dedup();
+            }
+            _ => {
+                let channel = entry.meta.channel;
+                let before = rows.iter().filter(|row| row.channel == channel).count();
+                rows.push(UndoPanelRow {
+                    no: entry.no,
+                    channel,
+                    redo,
+                    labels: vec![label],
+                    entities: entry.meta.entities.clone(),
+                    steps: if redo { before + 1 } else { before },
+                });
+            }
+        }
+    }
+    rows
+}
+
+
+fn short_type_name(type_name: &str) -> &str {
+    let name = type_name.split('<').next().unwrap_or(type_name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Entity, Message};
+    use bevy_egui::egui;
+
+    use crate::channel::UndoChannel;
+    use crate::document::UndoDocumentState;
+    use crate::editor::{panel_buttons, panel_rows, undo_history_ui, UndoEditorState};
+    use crate::history::UndoHistory;
+    use crate::prelude::{AppUndoEx, UndoDocuments, UndoHighlighted, UndoMeta, UndoRequester, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    fn new_app(n: usize) -> (App, Vec<Entity>) {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        let entities: Vec<Entity> = (0..n).map(|_| app.world_mut().spawn_empty().id()).collect();
+        for (x, entity) in entities.iter().enumerate() {
+            let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+            let meta = UndoMeta {
+                entities: vec![*entity],
+                ..UndoMeta::default()
+            };
+            state.get_mut(app.world_mut()).unwrap().push(Move(x as i32), Some(Move(x as i32)), meta);
+            state.apply(app.world_mut());
+            app.update();
+        }
+        (app, entities)
+    }
+
+
+    fn steps(app: &App) -> Vec<(bool, usize)> {
+        panel_rows(app.world().resource::<UndoHistory>())
+            .iter()
+            .map(|row| (row.redo, row.steps))
+            .collect()
+    }
+
+
+    #[test]
+    fn count_steps_to_jump_to_each_slot() {
+        let (mut app, entities) = new_app(3);
+        assert_eq!(steps(&app), vec![(false, 0), (false, 1), (false, 2)]);
+        assert_eq!(panel_rows(app.world().resource::<UndoHistory>())[2].entities, vec![entities[0]]);
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().undo_count(2);
+        state.apply(app.world_mut());
+        app.update();
+        assert_eq!(steps(&app), vec![(true, 2), (true, 1), (false, 0)]);
+    }
+
+
+    #[test]
+    fn highlight_entities_of_selected_slot() {
+        let (mut app, entities) = new_app(2);
+        let no = panel_rows(app.world().resource::<UndoHistory>())[1].no;
+        app.world_mut().insert_resource(UndoEditorState {
+            selected: Some(no),
+            highlighted: Vec::new(),
+        });
+        let context = egui::Context::default();
+        let mut output = context.run_ui(Default::default(), |ui| {
+            egui::CentralPanel::default().show(ui, |ui| undo_history_ui(app.world_mut(), ui));
+        });
+        output.textures_delta.clear();
+        assert!(app.world().get::<UndoHighlighted>(entities[0]).is_some());
+        assert!(app.world().get::<UndoHighlighted>(entities[1]).is_none());
+    }
+
+
+    #[test]
+    fn enable_buttons_for_active_document() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        let mut documents = SystemState::<UndoDocuments>::new(app.world_mut());
+        let mut document = documents.get_mut(app.world_mut()).unwrap();
+        document.create(1);
+        document.set_active(1);
+        documents.apply(app.world_mut());
+        let mut scheduler = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register(Move(0));
+        scheduler.apply(app.world_mut());
+        app.update();
+
+        let rows = panel_rows(app.world().resource::<UndoHistory>());
+        let channel = app.world().resource::<UndoDocumentState>().route(UndoChannel::DEFAULT);
+        assert_eq!(panel_buttons(&rows, channel), (true, false));
+        assert_eq!(panel_buttons(&rows, UndoChannel::DEFAULT), (false, false));
+    }
+}
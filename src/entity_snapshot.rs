@@ -0,0 +1,81 @@
+use std::any::TypeId;
+
+use bevy::ecs::reflect::ReflectComponent;
+use bevy::ecs::world::EntityWorldMut;
+use bevy::prelude::{AppTypeRegistry, ChildOf, Children, Entity, World};
+use bevy::reflect::{PartialReflect, TypeRegistry};
+
+/// The reflected components of an entity.
+///
+/// Only components registered in [`AppTypeRegistry`] with [`ReflectComponent`] are captured.
+/// The hierarchy is kept as the parent instead of [`ChildOf`] and [`Children`], which refer to entities by id.
+#[derive(Debug)]
+pub struct UndoEntitySnapshot {
+    pub entity: Entity,
+    pub parent: Option<Entity>,
+    pub components: Vec<(TypeId, Box<dyn PartialReflect>)>,
+}
+
+
+impl Clone for UndoEntitySnapshot {
+    fn clone(&self) -> Self {
+        Self {
+            entity: self.entity,
+            parent: self.parent,
+            components: self
+                .components
+                .iter()
+                .map(|(type_id, component)| (*type_id, component.to_dynamic()))
+                .collect(),
+        }
+    }
+}
+
+
+impl UndoEntitySnapshot {
+    /// Captures the reflected components of the entity, `None` if it doesn't exist.
+    pub(crate) fn capture(world: &World, type_registry: &AppTypeRegistry, entity: Entity) -> Option<Self> {
+        let entity_ref = world.get_entity(entity).ok()?;
+        let type_registry = type_registry.read();
+        let components = entity_ref
+            .archetype()
+            .components()
+            .iter()
+            .filter_map(|component_id| world.components().get_info(*component_id)?.type_id())
+            .filter(|type_id| *type_id != TypeId::of::<ChildOf>() && *type_id != TypeId::of::<Children>())
+            .filter_map(|type_id| {
+                let reflect_component = type_registry.get_type_data::<ReflectComponent>(type_id)?;
+                let component = reflect_component.reflect(entity_ref)?;
+                Some((type_id, component.to_dynamic()))
+            })
+            .collect();
+        Some(Self {
+            entity,
+            parent: entity_ref.get::<ChildOf>().map(ChildOf::parent),
+            components,
+        })
+    }
+
+
+    /// Applies the captured components to the entity, inserting the missing ones.
+    pub(crate) fn apply(&self, entity: &mut EntityWorldMut, type_registry: &TypeRegistry) {
+        for (type_id, component) in self.components.iter() {
+            let Some(reflect_component) = type_registry.get_type_data::<ReflectComponent>(*type_id) else {
+                continue;
+            };
+            if entity.contains_type_id(*type_id) {
+                reflect_component.apply(&mut *entity, &**component);
+            } else {
+                reflect_component.insert(entity, &**component, type_registry);
+            }
+        }
+    }
+
+
+    /// Spawns a new entity with the captured components, without its parent.
+    pub(crate) fn spawn(&self, world: &mut World, type_registry: &TypeRegistry) -> Entity {
+        let mut entity = world.spawn_empty();
+        self.apply(&mut entity, type_registry);
+        entity.id()
+    }
+}
@@ -0,0 +1,140 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Commands, Res, ResMut, World};
+
+use crate::channel::UndoChannel;
+use crate::counter::UndoCounter;
+use crate::document::UndoDocumentState;
+use crate::grouping::UndoGroupings;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEvent;
+
+type SendUndoEvent = Box<dyn FnOnce(&mut World, usize, UndoMeta) + Send + Sync + 'static>;
+
+
+/// An undo-event of any type registered via [`AppUndoEx::add_undo_event`](crate::prelude::AppUndoEx::add_undo_event),
+/// for tool frameworks collecting edits generically.
+///
+/// Created from the event via [`From`].
+pub struct UndoAnyEvent(SendUndoEvent);
+
+
+impl UndoAnyEvent {
+    #[inline(always)]
+    pub(crate) fn send(self, world: &mut World, no: usize, meta: UndoMeta) {
+        (self.0)(world, no, meta);
+    }
+}
+
+
+impl<E: UndoPayload> From<E> for UndoAnyEvent {
+    fn from(event: E) -> Self {
+        Self(Box::new(move |world, no, meta| {
+            world.write_message(UndoEvent {
+                inner: event,
+                redo: None,
+                no,
+                meta,
+                #[cfg(feature = "serde")]
+                registered_on: None,
+            });
+        }))
+    }
+}
+
+
+/// Registers undo-events of mixed types.
+#[derive(SystemParam)]
+pub struct UndoAnyScheduler<'w, 's> {
+    commands: Commands<'w, 's>,
+    counter: ResMut<'w, UndoCounter>,
+    groupings: Option<ResMut<'w, UndoGroupings>>,
+    documents: Res<'w, UndoDocumentState>,
+}
+
+
+impl<'w, 's> UndoAnyScheduler<'w, 's> {
+    /// Registers the events as a single slot, so all of them are undone together.
+    ///
+    /// They are recorded after the commands of this frame are applied.
+    #[inline(always)]
+    pub fn register_batch_atomic(&mut self, events: impl IntoIterator<Item = UndoAnyEvent>) {
+        self.register_batch_atomic_with_meta(events, UndoMeta::default());
+    }
+
+
+    /// Registers the events as a single slot together with the [`UndoMeta`] shared by all of them.
+    pub fn register_batch_atomic_with_meta(&mut self, events: impl IntoIterator<Item = UndoAnyEvent>, meta: UndoMeta) {
+        let events: Vec<UndoAnyEvent> = events.into_iter().collect();
+        if events.is_empty() {
+            return;
+        }
+        let meta = self.routed(meta);
+        let no = self.next_slot(meta.channel);
+        self.commands.queue(move |world: &mut World| {
+            for event in events {
+                event.send(world, no, meta.clone());
+            }
+        });
+    }
+
+
+    #[inline]
+    pub(crate) fn next_slot(&mut self, channel: UndoChannel) -> usize {
+        match self.groupings.as_mut() {
+            Some(groupings) => groupings.next_slot(channel, &mut self.counter),
+            None => {
+                self.counter.increment();
+                **self.counter
+            }
+        }
+    }
+
+
+    /// Like [`UndoScheduler::routed`](crate::undo_event::UndoScheduler::routed).
+    #[inline]
+    pub(crate) fn routed(&self, meta: UndoMeta) -> UndoMeta {
+        let channel = self.documents.route(meta.channel);
+        meta.with_channel(channel)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoAnyScheduler, UndoChannel};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Rename(&'static str);
+
+
+    #[test]
+    fn undo_mixed_events_together() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.add_undo_event::<Rename>();
+
+        let mut state = SystemState::<UndoAnyScheduler>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register_batch_atomic([Move(1).into(), Rename("cube").into()]);
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let moved: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        let renamed: Vec<Rename> = app.world_mut().resource_mut::<Messages<Rename>>().drain().collect();
+        assert_eq!(moved, vec![Move(1)]);
+        assert_eq!(renamed, vec![Rename("cube")]);
+    }
+}
@@ -0,0 +1,807 @@
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use bevy::log::warn;
+use bevy::prelude::{Entity, Resource, World};
+use bevy::platform::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use crate::{DispatchUndoEvent, UndoAreas};
+use crate::atomic::UndoAtomicGroup;
+use crate::channel::UndoChannel;
+use crate::collab::{UndoSiteId, UndoStamp};
+use crate::counter::UndoCounter;
+use crate::history::{UndoHistory, UndoHistoryEntry};
+use crate::link::UndoLink;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::policy::UndoPolicy;
+use crate::request::RequestUndoEvent;
+use crate::turn::UndoTurnClock;
+use crate::undo_event::{UndoEntry, UndoEvent};
+use crate::weak::WeakEntityRef;
+
+/// The compression of a history file written by [`UndoHistoryExport::export_compressed`].
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UndoCompression {
+    /// The LZ4 frame format, the fastest to write and read.
+    #[cfg(feature = "lz4")]
+    Lz4,
+
+    /// Zstandard at the level, smaller files at the cost of speed; 0 picks the default level.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+impl UndoCompression {
+    /// Runs `write` with an encoder over the writer, returning the writer once the encoding is finished.
+    fn write<W: Write>(self, writer: W, write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>) -> std::io::Result<W> {
+        match self {
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+                write(&mut encoder)?;
+                encoder.finish().map_err(std::io::Error::from)
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd(level) => {
+                let mut encoder = zstd::stream::write::Encoder::new(writer, level)?;
+                write(&mut encoder)?;
+                encoder.finish()
+            }
+        }
+    }
+
+
+    /// Wraps the reader with a decoder.
+    fn reader<'a>(self, reader: impl std::io::BufRead + 'a) -> std::io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(_) => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+        })
+    }
+
+
+    /// Compresses the bytes in memory, in the same format as the files.
+    pub(crate) fn compress(self, bytes: &[u8]) -> Vec<u8> {
+        self.write(Vec::new(), |writer| writer.write_all(bytes))
+            .expect("writing to memory does not fail")
+    }
+
+
+    /// Decompresses bytes returned by [`UndoCompression::compress`], or `None` if they are corrupted.
+    pub(crate) fn decompress(self, bytes: &[u8]) -> Option<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        self.reader(bytes).ok()?.read_to_end(&mut decompressed).ok()?;
+        Some(decompressed)
+    }
+}
+
+
+/// A payload as RON paired with its slot number and the payload for redo.
+type ExportedPayload = (usize, String, Option<String>);
+
+/// The payloads of a type, the registered ones and then the ones waiting for redo.
+type ExportedPayloads = (Vec<ExportedPayload>, Vec<ExportedPayload>);
+
+type PayloadExporter = fn(&World) -> ExportedPayloads;
+
+/// Registers the decoded payloads of the entry at the slot, returning false if they cannot be decoded.
+type PayloadImporter = fn(&mut World, &UndoExportEntry, usize) -> bool;
+
+
+/// The payload types exported along with the metadata, see [`AppUndoEx::export_undo_payloads`](crate::prelude::AppUndoEx::export_undo_payloads).
+#[derive(Resource, Default)]
+pub(crate) struct UndoPayloadExporters(pub HashMap<&'static str, PayloadExporter>);
+
+
+/// The payload types replayed by [`UndoHistoryExport::replay`], see [`AppUndoEx::import_undo_payloads`](crate::prelude::AppUndoEx::import_undo_payloads).
+#[derive(Resource, Default)]
+pub(crate) struct UndoPayloadImporters(pub HashMap<&'static str, PayloadImporter>);
+
+
+pub(crate) fn export_payloads<E: UndoPayload + Serialize>(world: &World) -> ExportedPayloads {
+    let Some(areas) = world.get_resource::<UndoAreas>().and_then(UndoAreas::get::<E>) else {
+        return Default::default();
+    };
+    let mut registered = Vec::new();
+    areas.registered.0.for_each(&mut |entry| registered.extend(export_entry(entry)));
+    let redo = areas.redo.0.iter().filter_map(export_entry).collect();
+    (registered, redo)
+}
+
+
+#[inline]
+fn export_entry<E: UndoPayload + Serialize>(entry: &UndoEntry<E>) -> Option<ExportedPayload> {
+    let redo = entry.redo.as_ref().and_then(to_ron);
+    to_ron(&entry.inner).map(|ron| (entry.no, ron, redo))
+}
+
+
+pub(crate) fn import_payload<E: UndoPayload + DeserializeOwned>(world: &mut World, entry: &UndoExportEntry, no: usize) -> bool {
+    let Some(inner) = entry.payload.as_deref().and_then(from_ron::<E>) else {
+        return false;
+    };
+    world.write_message(UndoEvent {
+        inner,
+        redo: entry.redo_payload.as_deref().and_then(from_ron),
+        no,
+        meta: entry.meta(),
+        registered_on: entry.registered_on,
+    });
+    true
+}
+
+
+#[inline]
+fn from_ron<E: DeserializeOwned>(ron: &str) -> Option<E> {
+    ron::from_str(ron).ok()
+}
+
+
+#[inline]
+fn to_ron(payload: &impl Serialize) -> Option<String> {
+    ron::to_string(payload).ok()
+}
+
+
+/// A registered entry written by [`UndoHistoryExport`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoExportEntry {
+    pub group: usize,
+    pub type_name: String,
+    pub registered_at: Duration,
+
+    /// The wall-clock time when registered, by which [`UndoHistoryExport::merge`] interleaves histories.
+    #[serde(default)]
+    pub registered_on: Option<SystemTime>,
+
+    pub redoable: bool,
+
+    /// True if the entry has been undone and waits for redo.
+    pub undone: bool,
+
+    pub tag: String,
+    pub tags: Vec<String>,
+
+    /// The entities as [`Entity::to_bits`](bevy::prelude::Entity::to_bits).
+    pub entities: Vec<u64>,
+
+    pub channel: u64,
+    pub importance: i32,
+
+    #[serde(default)]
+    pub link: Option<u64>,
+
+    #[serde(default)]
+    pub atomic_group: Option<u64>,
+
+    #[serde(default)]
+    pub sticky: bool,
+
+    #[serde(default)]
+    pub requires_confirmation: bool,
+
+    /// The entities of [`UndoMeta::weak_refs`] as [`Entity::to_bits`](bevy::prelude::Entity::to_bits).
+    #[serde(default)]
+    pub weak_refs: Vec<u64>,
+
+    /// The entities of [`UndoMeta::checked_entities`] as [`Entity::to_bits`](bevy::prelude::Entity::to_bits).
+    #[serde(default)]
+    pub checked_entities: Vec<u64>,
+
+    /// The [`UndoMeta::stamp`] as its clock and site.
+    #[serde(default)]
+    pub stamp: Option<(u64, u64)>,
+
+    /// The payload as RON, present only for the types exported via [`AppUndoEx::export_undo_payloads`](crate::prelude::AppUndoEx::export_undo_payloads).
+    pub payload: Option<String>,
+
+    /// The payload sent when redone as RON, if the entry is redoable and its payload is exported.
+    #[serde(default)]
+    pub redo_payload: Option<String>,
+}
+
+
+impl UndoExportEntry {
+    fn new(entry: &UndoHistoryEntry, undone: bool, payload: Option<ExportedPayload>) -> Self {
+        let (payload, redo_payload) = payload
+            .map(|(_, payload, redo)| (Some(payload), redo))
+            .unwrap_or_default();
+        Self {
+            group: entry.no,
+            type_name: entry.type_name.to_string(),
+            registered_at: entry.registered_at,
+            registered_on: Some(entry.registered_on),
+            redoable: entry.redoable,
+            undone,
+            tag: entry.meta.tag.clone(),
+            tags: entry.meta.tags.clone(),
+            entities: entry.meta.entities.iter().map(|entity| entity.to_bits()).collect(),
+            channel: entry.meta.channel.0,
+            importance: entry.meta.importance,
+            link: entry.meta.link.map(|link| link.0),
+            atomic_group: entry.meta.atomic_group.map(|group| group.0),
+            sticky: entry.meta.sticky,
+            requires_confirmation: entry.meta.requires_confirmation,
+            weak_refs: entry.meta.weak_refs.iter().map(|weak| weak.0.to_bits()).collect(),
+            checked_entities: entry.meta.checked_entities.iter().map(|entity| entity.to_bits()).collect(),
+            stamp: entry.meta.stamp.map(|stamp| (stamp.clock, stamp.site.0)),
+            payload,
+            redo_payload,
+        }
+    }
+
+
+    /// Rebuilds the metadata, the entities being the ones with the same bits in the current world.
+    pub fn meta(&self) -> UndoMeta {
+        UndoMeta {
+            tag: self.tag.clone(),
+            tags: self.tags.clone(),
+            entities: self.entities.iter().map(|bits| Entity::from_bits(*bits)).collect(),
+            channel: UndoChannel(self.channel),
+            importance: self.importance,
+            link: self.link.map(UndoLink),
+            atomic_group: self.atomic_group.map(UndoAtomicGroup),
+            sticky: self.sticky,
+            requires_confirmation: self.requires_confirmation,
+            weak_refs: self.weak_refs.iter().map(|bits| WeakEntityRef(Entity::from_bits(*bits))).collect(),
+            checked_entities: self.checked_entities.iter().map(|bits| Entity::from_bits(*bits)).collect(),
+            stamp: self.stamp.map(|(clock, site)| UndoStamp {
+                clock,
+                site: UndoSiteId(site),
+            }),
+            user_data: None,
+        }
+    }
+}
+
+
+/// A human-readable dump of the history, for attaching the exact actions to bug reports.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoHistoryExport {
+    /// The entries which can be undone, ordered from the oldest.
+    pub entries: Vec<UndoExportEntry>,
+
+    /// The entries which can be redone, ordered from the most recently undone.
+    pub redo: Vec<UndoExportEntry>,
+}
+
+
+impl UndoHistoryExport {
+    /// Copies the history of the world.
+    ///
+    /// This takes the world, so call it from a system taking `&World` or an exclusive one.
+    pub fn capture(world: &World) -> Self {
+        let history = world.resource::<UndoHistory>();
+        let mut payloads: HashMap<&str, ExportedPayloads> = HashMap::default();
+        if let Some(exporters) = world.get_resource::<UndoPayloadExporters>() {
+            for (type_name, exporter) in exporters.0.iter() {
+                payloads.insert(type_name, exporter(world));
+            }
+        }
+
+        let mut take_payload = |entry: &UndoHistoryEntry, undone: bool| {
+            let (registered, redo) = payloads.get_mut(entry.type_name)?;
+            let payloads = if undone { redo } else { registered };
+            let index = payloads.iter().position(|(no, ..)| *no == entry.no)?;
+            Some(payloads.remove(index))
+        };
+        let entries = history
+            .entries()
+            .map(|entry| UndoExportEntry::new(entry, false, take_payload(entry, false)))
+            .collect();
+        let redo = history
+            .redo_entries()
+            .map(|entry| UndoExportEntry::new(entry, true, take_payload(entry, true)))
+            .collect();
+        Self {
+            entries,
+            redo,
+        }
+    }
+
+
+    #[inline]
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+
+    /// Writes the history of the world to the file as pretty RON.
+    pub fn export(world: &World, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let ron = Self::capture(world)
+            .to_ron()
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        std::fs::write(path, ron)
+    }
+
+
+    #[inline]
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(ron)
+    }
+
+
+    /// Reads a history written by [`UndoHistoryExport::export`].
+    pub fn import(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let ron = std::fs::read_to_string(path)?;
+        Self::from_ron(&ron).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+
+
+    /// Streams the history as compact RON into the writer, without building the whole text first.
+    #[inline]
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), ron::Error> {
+        ron::ser::to_writer(writer, self)
+    }
+
+
+    #[inline]
+    pub fn from_reader(reader: impl Read) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_reader(reader)
+    }
+
+
+    /// Writes the history of the world to the file, encoding the RON while it is written.
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    pub fn export_compressed(world: &World, path: impl AsRef<Path>, compression: UndoCompression) -> std::io::Result<()> {
+        let export = Self::capture(world);
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut file = compression.write(file, |writer| export
+            .to_writer(writer)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error)))?;
+        file.flush()
+    }
+
+
+    /// Reads a history written by [`UndoHistoryExport::export_compressed`] with the same compression,
+    /// decoding the file while it is read.
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    pub fn import_compressed(path: impl AsRef<Path>, compression: UndoCompression) -> std::io::Result<Self> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Self::from_reader(compression.reader(file)?)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+
+
+    /// Interleaves the entries of both histories by the wall-clock time they were registered, the ones of `self` first among the same time.
+    ///
+    /// Entries exported without the time, by earlier versions, come first.
+    ///
+    /// The groups are numbered again in the merged order, so the slots of both histories never collide.
+    /// The entries waiting for redo are kept in the order of `self` and then `other`.
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut entries: Vec<(usize, &UndoExportEntry)> = self
+            .entries
+            .iter()
+            .map(|entry| (0, entry))
+            .chain(other.entries.iter().map(|entry| (1, entry)))
+            .collect();
+        entries.sort_by_key(|(session, entry)| (entry.registered_on, *session, entry.group));
+        let redo = self
+            .redo
+            .iter()
+            .map(|entry| (0, entry))
+            .chain(other.redo.iter().map(|entry| (1, entry)));
+
+        let mut last = None;
+        let mut group = 0;
+        let mut renumber = |(session, entry): (usize, &UndoExportEntry)| {
+            if last != Some((session, entry.group)) {
+                last = Some((session, entry.group));
+                group += 1;
+            }
+            UndoExportEntry {
+                group,
+                ..entry.clone()
+            }
+        };
+        let entries = entries.into_iter().map(&mut renumber).collect();
+        let redo = redo.map(&mut renumber).collect();
+        Self {
+            entries,
+            redo,
+        }
+    }
+
+
+    /// Merges the history into the one of the world, such as to recover a history saved before a crash.
+    ///
+    /// The history of the world is captured, dropped and replayed together with this one via [`UndoHistoryExport::merge`],
+    /// so the slots are numbered again. The slots of the world holding entries whose payloads are not both exported and imported
+    /// can't be replayed, so they are kept as they are, before the merged ones.
+    ///
+    /// Returns the count of the replayed entries.
+    pub fn merge_into(&self, world: &mut World) -> usize {
+        let current = Self::capture(world);
+        let importers = world.get_resource::<UndoPayloadImporters>();
+        let kept: HashSet<usize> = current.entries
+            .iter()
+            .chain(current.redo.iter())
+            .filter(|entry| entry.payload.is_none() || !importers.is_some_and(|importers| importers.0.contains_key(entry.type_name.as_str())))
+            .map(|entry| entry.group)
+            .collect();
+        let current = Self {
+            entries: current.entries.into_iter().filter(|entry| !kept.contains(&entry.group)).collect(),
+            redo: current.redo.into_iter().filter(|entry| !kept.contains(&entry.group)).collect(),
+        };
+
+        let mut history = world.resource_mut::<UndoHistory>();
+        let mut registered: Vec<usize> = current.entries.iter().map(|entry| entry.group).collect();
+        registered.dedup();
+        for no in registered.iter() {
+            history.remove_slot(*no);
+        }
+        let mut redo: Vec<usize> = current.redo.iter().map(|entry| entry.group).collect();
+        redo.dedup();
+        for no in redo.iter() {
+            history.remove_redo_slot(*no);
+        }
+        world.write_message_batch(registered.into_iter().map(DispatchUndoEvent::Discard));
+        world.write_message_batch(redo.into_iter().map(DispatchUndoEvent::DiscardRedo));
+        current.merge(self).replay(world)
+    }
+
+
+    /// Registers the recorded entries into the world, for reproducing a reported history.
+    ///
+    /// The entries are registered in their original order, sharing slots as they did, and the undone entries are undone again,
+    /// so the handlers receive them as they did when recorded.
+    /// This takes effect on the next update, and only the types set via
+    /// [`AppUndoEx::import_undo_payloads`](crate::prelude::AppUndoEx::import_undo_payloads) are replayed.
+    ///
+    /// Returns the count of the replayed entries.
+    pub fn replay(&self, world: &mut World) -> usize {
+        let importers = world.get_resource::<UndoPayloadImporters>().map(|importers| importers.0.clone()).unwrap_or_default();
+        let mut replayed = 0;
+        let mut undone_channels = Vec::new();
+        let mut slot: Option<(usize, usize)> = None;
+        for entry in self.entries.iter().chain(self.redo.iter()) {
+            let no = match slot {
+                Some((group, no)) if group == entry.group => no,
+                _ => {
+                    let mut counter = world.resource_mut::<UndoCounter>();
+                    counter.increment();
+                    slot = Some((entry.group, **counter));
+                    if entry.undone {
+                        undone_channels.push(UndoChannel(entry.channel));
+                    }
+                    **counter
+                }
+            };
+
+            let Some(import) = importers.get(entry.type_name.as_str()) else {
+                warn!("skipped replaying an undo entry of {}, its payloads are not imported", entry.type_name);
+                continue;
+            };
+            if import(world, entry, no) {
+                replayed += 1;
+            } else {
+                warn!("failed to decode a replayed undo entry of {}", entry.type_name);
+            }
+        }
+
+        // The entry undone most recently was registered the earliest, so undo from the back.
+        for channel in undone_channels.into_iter().rev() {
+            world.write_message(RequestUndoEvent::Restore(channel));
+        }
+        replayed
+    }
+}
+
+
+/// A self-contained copy of the undo state to embed in save games, handed back via [`UndoSnapshot::restore`] on load.
+///
+/// The history covers the types whose payloads are both exported and imported,
+/// see [`AppUndoEx::export_undo_payloads`](crate::prelude::AppUndoEx::export_undo_payloads).
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoSnapshot {
+    pub history: UndoHistoryExport,
+
+    /// The number of the last slot allocated.
+    pub counter: usize,
+
+    /// The count of turns ended, see [`UndoTurns`](crate::prelude::UndoTurns).
+    #[serde(default)]
+    pub turn: u64,
+
+    /// The undos allowed so far by the [`UndoPolicy`](crate::prelude::UndoPolicy).
+    #[serde(default)]
+    pub undos_used: usize,
+}
+
+
+impl UndoSnapshot {
+    /// Copies the undo state of the world.
+    pub fn capture(world: &World) -> Self {
+        Self {
+            history: UndoHistoryExport::capture(world),
+            counter: **world.resource::<UndoCounter>(),
+            turn: world.get_resource::<UndoTurnClock>().map(UndoTurnClock::turn).unwrap_or_default(),
+            undos_used: world.get_resource::<UndoPolicy>().map(UndoPolicy::undos_used).unwrap_or_default(),
+        }
+    }
+
+
+    /// Replaces the undo state of the world with the snapshot, replaying its history via [`UndoHistoryExport::replay`].
+    ///
+    /// The entries undone when captured are undone again regardless of the policy and the cooldown,
+    /// without being charged to the policy.
+    ///
+    /// Returns the count of the replayed entries.
+    pub fn restore(&self, world: &mut World) -> usize {
+        let (registered, redo) = world.resource_mut::<UndoHistory>().drain_slots();
+        world.write_message_batch(registered.into_iter().map(DispatchUndoEvent::Discard));
+        world.write_message_batch(redo.into_iter().map(DispatchUndoEvent::DiscardRedo));
+        let replayed = self.history.replay(world);
+
+        let mut counter = world.resource_mut::<UndoCounter>();
+        let no = (**counter).max(self.counter);
+        counter.set(no);
+        if let Some(turns) = world.get_resource::<UndoTurnClock>() {
+            turns.set(self.turn);
+        }
+        if let Some(mut policy) = world.get_resource_mut::<UndoPolicy>() {
+            policy.used = self.undos_used;
+        }
+        replayed
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{IntoScheduleConfigs, Message, Messages};
+    use serde::{Deserialize, Serialize};
+
+    use crate::counter::UndoCounter;
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    use crate::prelude::UndoCompression;
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoHistoryExport, UndoMeta, UndoPolicy, UndoScheduler, UndoSnapshot, UndoTurns};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Move(i32);
+
+    #[derive(Message, Clone)]
+    struct Opaque;
+
+
+    #[test]
+    fn export_metadata_and_payloads() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.add_undo_event::<Opaque>();
+        app.export_undo_payloads::<Move>();
+        app.add_systems(Startup, (
+            |mut s: UndoScheduler<Move>| {
+                s.register_with_meta(Move(1), UndoMeta::tagged("move"));
+                s.push(Move(2), Some(Move(3)), UndoMeta::default());
+            },
+            |mut s: UndoScheduler<Opaque>| s.register(Opaque),
+        ).chain());
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+
+        let export = UndoHistoryExport::capture(app.world());
+        assert_eq!(export.entries.len(), 1);
+        assert_eq!(export.entries[0].tag, "move");
+        assert_eq!(export.entries[0].payload.as_deref(), Some("(1)"));
+        assert_eq!(export.redo.len(), 1);
+        assert_eq!(export.redo[0].payload.as_deref(), Some("(2)"));
+
+        let path = std::env::temp_dir().join(format!("bevy_undo2_export_{}.ron", std::process::id()));
+        UndoHistoryExport::export(app.world(), &path).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(written.contains("\"move\""));
+    }
+
+
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    #[test]
+    fn import_compressed_history() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.export_undo_payloads::<Move>();
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>| {
+            for x in 0..100 {
+                s.register_with_meta(Move(x), UndoMeta::tagged("move"));
+            }
+        });
+        app.update();
+        let ron = UndoHistoryExport::capture(app.world()).to_ron().unwrap();
+
+        let compressions = [
+            #[cfg(feature = "lz4")]
+            UndoCompression::Lz4,
+            #[cfg(feature = "zstd")]
+            UndoCompression::Zstd(0),
+        ];
+        for compression in compressions {
+            let path = std::env::temp_dir().join(format!("bevy_undo2_compressed_{}_{compression:?}", std::process::id()));
+            UndoHistoryExport::export_compressed(app.world(), &path, compression).unwrap();
+            let written = std::fs::metadata(&path).unwrap().len();
+            let export = UndoHistoryExport::import_compressed(&path, compression);
+            let _ = std::fs::remove_file(&path);
+            let export = export.unwrap();
+            assert!((written as usize) < ron.len() / 4);
+            assert_eq!(export.entries.len(), 100);
+            assert_eq!(export.entries[99].payload.as_deref(), Some("(99)"));
+        }
+    }
+
+
+    #[test]
+    fn replay_imported_history() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.export_undo_payloads::<Move>();
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>| {
+            s.register_all_grouped([Move(1), Move(2)]);
+            s.push(Move(3), Some(Move(4)), UndoMeta::tagged("redoable"));
+        });
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let ron = UndoHistoryExport::capture(app.world()).to_ron().unwrap();
+
+        let mut replay = App::new();
+        replay.add_plugins(UndoPlugin);
+        replay.add_undo_event::<Move>();
+        replay.import_undo_payloads::<Move>();
+        let export = UndoHistoryExport::from_ron(&ron).unwrap();
+        assert_eq!(export.replay(replay.world_mut()), 3);
+        replay.update();
+        let undone: Vec<Move> = replay.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(3)]);
+
+        replay.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        replay.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        replay.update();
+        let redone: Vec<Move> = replay.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(redone, vec![Move(4), Move(3)]);
+    }
+
+
+    fn register(app: &mut App, event: Move) {
+        let mut scheduler = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register(event);
+        scheduler.apply(app.world_mut());
+        app.update();
+    }
+
+
+    #[test]
+    fn merge_saved_history() {
+        let mut saved = App::new();
+        saved.add_plugins(UndoPlugin);
+        saved.add_undo_event::<Move>();
+        saved.export_undo_payloads::<Move>();
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.export_undo_payloads::<Move>();
+        app.import_undo_payloads::<Move>();
+        register(&mut saved, Move(1));
+        register(&mut app, Move(2));
+        register(&mut saved, Move(3));
+
+        let saved = UndoHistoryExport::capture(saved.world());
+        let merged = UndoHistoryExport::capture(app.world()).merge(&saved);
+        let groups: Vec<(usize, Option<&str>)> = merged.entries.iter().map(|entry| (entry.group, entry.payload.as_deref())).collect();
+        assert_eq!(groups, vec![(1, Some("(1)")), (2, Some("(2)")), (3, Some("(3)"))]);
+
+        assert_eq!(saved.merge_into(app.world_mut()), 3);
+        app.update();
+        // The replayed entries keep the time they were first registered.
+        let times: Vec<_> = UndoHistoryExport::capture(app.world()).entries.iter().map(|entry| entry.registered_on).collect();
+        assert_eq!(times, merged.entries.iter().map(|entry| entry.registered_on).collect::<Vec<_>>());
+
+        for _ in 0..3 {
+            app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        }
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(3), Move(2), Move(1)]);
+    }
+
+
+    #[test]
+    fn keep_entries_not_exported_when_merging() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.add_undo_event::<Opaque>();
+        app.export_undo_payloads::<Move>();
+        app.import_undo_payloads::<Move>();
+        let mut scheduler = SystemState::<UndoScheduler<Opaque>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register(Opaque);
+        scheduler.apply(app.world_mut());
+        app.update();
+        register(&mut app, Move(1));
+
+        let mut saved = App::new();
+        saved.add_plugins(UndoPlugin);
+        saved.add_undo_event::<Move>();
+        saved.export_undo_payloads::<Move>();
+        register(&mut saved, Move(2));
+        let saved = UndoHistoryExport::capture(saved.world());
+
+        assert_eq!(saved.merge_into(app.world_mut()), 2);
+        app.update();
+        for _ in 0..3 {
+            app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        }
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(2), Move(1)]);
+        assert_eq!(app.world_mut().resource_mut::<Messages<Opaque>>().drain().count(), 1);
+    }
+
+
+    #[derive(Serialize, Deserialize)]
+    struct SaveGame {
+        level: u32,
+        undo: UndoSnapshot,
+    }
+
+
+    #[test]
+    fn restore_snapshot_embedded_in_save() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.export_undo_payloads::<Move>();
+        app.enable_undo_turns();
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>, mut turns: UndoTurns| {
+            s.register(Move(1));
+            turns.end_turn();
+            s.push(Move(2), Some(Move(3)), UndoMeta::default());
+            turns.end_turn();
+        });
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let save = ron::to_string(&SaveGame {
+            level: 3,
+            undo: UndoSnapshot::capture(app.world()),
+        }).unwrap();
+
+        let mut loaded = App::new();
+        loaded.add_plugins(UndoPlugin);
+        loaded.add_undo_event::<Move>();
+        loaded.import_undo_payloads::<Move>();
+        loaded.enable_undo_turns();
+        loaded.insert_resource(UndoPolicy::disabled());
+        let save: SaveGame = ron::from_str(&save).unwrap();
+        assert_eq!(save.level, 3);
+        assert_eq!(save.undo.restore(loaded.world_mut()), 2);
+        loaded.update();
+        let undone: Vec<Move> = loaded.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(2)]);
+        assert_eq!(UndoSnapshot::capture(loaded.world()).turn, 2);
+        assert_eq!(**loaded.world().resource::<UndoCounter>(), 2);
+
+        loaded.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        loaded.update();
+        let redone: Vec<Move> = loaded.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(redone, vec![Move(3)]);
+    }
+}
@@ -1,10 +1,91 @@
-use bevy::app::{App, Update};
-use bevy::prelude::{Event, EventReader, EventWriter, IntoSystemConfigs, ResMut};
-use crate::{CommitReservationsEvent, DecrementCounterEvent, UndoRegisteredArea};
-use crate::prelude::UndoRequester;
-use crate::request::RequestUndoEvent;
-use crate::reserve::{ReserveCounter, UndoReservedArea, UndoReserveEvent};
-use crate::undo_event::UndoEvent;
+use std::any::TypeId;
+use std::hash::Hash;
+use std::time::Duration;
+
+use bevy::app::{App, First, Last, PostUpdate, PreUpdate, Update};
+use bevy::asset::Asset;
+use bevy::ecs::component::Mutable;
+use bevy::ecs::system::{In as SystemIn, ScheduleSystem, System};
+use bevy::ecs::world::EntityWorldMut;
+#[cfg(feature = "thumbnails")]
+use bevy::prelude::{Handle, Image, resource_changed};
+use bevy::prelude::{Component, IntoScheduleConfigs, IntoSystem, MessageReader, MessageWriter, Messages, on_message, Reflect, Res, ResMut, States, SystemCondition, Time, Transform, World};
+use bevy::state::state::FreelyMutableState;
+use crate::amend::{amend_latest_system, drop_replaced_entries_system, has_amendments, has_replaced_entries, has_replacement, replace_latest_system};
+use crate::asset::{restore_asset_system, UndoAssetEvent};
+#[cfg(feature = "audit")]
+use crate::audit::{UndoAuditConfig, UndoAuditLog};
+use crate::autosave::{AutosaveSuggested, suggest_autosave_system, UndoAutosave, UndoAutosaveConfig};
+use crate::bulk::{restore_bulk_system, UndoBulkEvent, UndoBulkRestore, UndoBulkStarts};
+use crate::{DispatchUndoEvent, evict_over_capacity_system, lock, UndoAreas, UndoRegisteredArea, UndoSystemSet, UndoTypeAreas};
+use crate::channel::{UndoChannel, UndoEvicted, UndoStackConfig, UndoStackConfigs, UndoTypeConfigs};
+use crate::clipboard::UndoClipboardContents;
+use crate::collab::{merge_remote_entries_system, stamp_local_entries_system, UndoCollabClock, UndoSiteId};
+use crate::cold::{compact_cold_entries_system, rehydrate_cold_entries_system, UndoColdArea};
+use crate::condition::{event_condition_holds, update_event_condition_system, UndoEventCondition};
+use crate::compaction::{compact_noops_system, UndoNoopHook};
+use crate::cooldown::UndoCooldown;
+use crate::counter::UndoCounter;
+use crate::dedup::{dedup_undo_entries_system, UndoDuplicateHook};
+use crate::delivery::{update_undo_events_system, UndoDeliveryWindow};
+use crate::delta::{compress_deltas_system, expand_deltas_system, UndoDelta, UndoDeltaArea};
+use crate::component::{restore_component_system, UndoComponentEvent};
+use crate::drag::UndoDragStarts;
+#[cfg(feature = "serde")]
+use crate::export::{export_payloads, import_payload, UndoPayloadExporters, UndoPayloadImporters};
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+use crate::export::UndoCompression;
+use crate::dry_run::{DryRun, Preview};
+use crate::failure::{run_fallible_handler_system, UndoError, UndoFailurePolicy, UndoFallibleHandler};
+use crate::grouping::{init_undo_groupings, UndoFrameGrouping, UndoGranularity, UndoGroupingStrategy};
+use crate::handle::{release_handles_system, retain_handles_system, UndoRetainedHandles};
+use crate::history::UndoHistory;
+use crate::hooks::{init_undo_hooks, UndoAuthorization, UndoHookContext, UndoHooks, UndoVerdict};
+#[cfg(feature = "audit")]
+use crate::hooks::UndoHookPhase;
+use crate::hot_reload::{fix_hot_reloaded_entries_system, has_hot_reload_work};
+#[cfg(feature = "debug_invariants")]
+use crate::invariants::check_type_invariants_system;
+use crate::lifecycle::{apply_lifecycle_events_system, track_component_lifecycle_system, UndoComponentShadow, UndoLifecycleEvent};
+use crate::merge::{merge_undo_entries_system, MergeUndo};
+use crate::mapped::{map_undo_event_system, UndoEventMapper};
+use crate::pacing::{start_handler_timer_system, stop_handler_timer_system, UndoRequestQueue};
+use crate::payload::UndoPayload;
+#[cfg(feature = "rapier")]
+use crate::physics::{restore_physics_system, UndoPhysicsEvent};
+use crate::priority::{configure_priority, UndoAllHandlersSet, UndoHandlerSet};
+use crate::recorder::{record_macro_steps_system, UndoMacroLibrary};
+use crate::registry::UndoTypeRegistry;
+use crate::scene::track_loaded_scenes_system;
+#[cfg(any(feature = "lua", feature = "rhai"))]
+use crate::scripting::{apply_script_commands_system, UndoScriptApi, UndoScriptEvent};
+use crate::selection::{restore_selection_system, UndoSelectionEvent};
+use crate::spawn::{apply_spawn_events_system, UndoSpawnEvent};
+use crate::storage::UndoStorage;
+use crate::suspend::{update_suspension_system, UndoRecording, UndoSuspendedStates};
+use crate::strict::{check_register_during_dispatch_system, store_dispatched_counters_system, UndoStrictMode, UndoStrictness};
+#[cfg(feature = "reserve")]
+use crate::strict::{check_empty_commit_system, check_request_with_reservations_system};
+use crate::stroke::{UndoStrokeBuffer, UndoStrokeEvent};
+#[cfg(feature = "tilemap")]
+use crate::tilemap::{restore_tiles_system, UndoTileKey};
+use crate::telemetry::{report_undo_telemetry_system, UndoTelemetry, UndoUsage};
+use crate::transform::{transform_local_entries_system, UndoTransform};
+#[cfg(feature = "thumbnails")]
+use crate::thumbnail::{capture_thumbnails_system, UndoThumbnailCapture};
+use crate::tween::{advance_tweens_system, interpolate_transform, start_tweens_system, UndoTweenConfig, UndoTweenFinished, UndoTweenStarted};
+use crate::text::{commit_text_edits_system, UndoTextEvent, UndoTextPending};
+use crate::transition::{apply_transition_events_system, track_state_transition_system, UndoTrackedState, UndoTransitionEvent};
+use crate::turn::{UndoTurnClock, UndoTurnGrouping};
+#[cfg(feature = "reserve")]
+use crate::reserve::CommitReservationsEvent;
+use crate::undo_event::{UndoEntry, UndoEvent};
+use crate::version::UndoVersioned;
+use crate::unhandled::{detect_unhandled_system, UndoHandlers};
+use crate::weak::UndoWeakRefs;
+use crate::window::track_focused_window_system;
+#[cfg(feature = "xpbd")]
+use crate::xpbd::{restore_xpbd_system, UndoXpbdEvent};
 
 
 pub trait AppUndoEx {
@@ -12,91 +93,1341 @@ pub trait AppUndoEx {
     ///
     /// In order to use undo-action, you must call [`UndoScheduler::register`](UndoScheduler::register).
     /// then call [`UndoRequester::undo`](UndoRequester::undo) when you need.
-    fn add_undo_event<T: Event + Clone>(&mut self) -> &mut App;
+    fn add_undo_event<T: UndoPayload>(&mut self) -> &mut App;
+
+
+    /// Setup the app like [`AppUndoEx::add_undo_event`], recording the entries of `T` only in the frames where the condition holds,
+    /// such as `in_state(EditorMode::Terrain)`.
+    ///
+    /// While it does not hold, entries registered are dropped and the entries recorded before are not sent to the handlers
+    /// when undone or redone, such as to keep the sculpts of the terrain mode from being applied during play.
+    fn add_undo_event_with_condition<T: UndoPayload, M>(&mut self, condition: impl SystemCondition<M>) -> &mut App;
+
+
+    /// Ignores all registrations while the state of `S` is one of the states, such as cutscenes, loading or replay playback,
+    /// so the mutations driven by the game during them never end up in the history.
+    ///
+    /// It can be called for several state types, registrations being ignored while any of them is in a listed state.
+    /// Undo and redo requests are not affected.
+    fn suspend_undo_in_states<S: States>(&mut self, states: impl IntoIterator<Item = S>) -> &mut App;
+
+
+    /// Keeps a separate history per window, such as for multi-window editors, via [`UndoDocuments`](crate::prelude::UndoDocuments).
+    ///
+    /// The document of the focused window is active, so entries registered and requests made to [`UndoChannel::DEFAULT`]
+    /// go to it, while [`UndoChannel::for_window`] and [`UndoRequester::undo_for_window`](crate::prelude::UndoRequester::undo_for_window)
+    /// refer to a given window. The history of a window is dropped once it is closed.
+    /// Activating other documents is overridden whenever the focus changes.
+    fn enable_undo_windows(&mut self) -> &mut App;
+
+
+    /// Keeps a separate history per scene via [`UndoDocuments`](crate::prelude::UndoDocuments), keyed by the handle of the scene,
+    /// such as for editors loading and unloading levels.
+    ///
+    /// Once a [`WorldInstance`](bevy::world_serialization::WorldInstance) is spawned, the document of its scene is activated,
+    /// so entries registered and requests made to [`UndoChannel::DEFAULT`] go to it,
+    /// while [`UndoChannel::for_scene`] refers to a given scene.
+    /// The history of an unloaded scene is kept and resumed when the scene is spawned again,
+    /// and the scene loaded before it is activated in the meantime, if any.
+    fn enable_undo_scenes(&mut self) -> &mut App;
+
+
+    /// Lets the entries of `T` registered with a redo-event be recorded into macros and replayed via
+    /// [`UndoMacros`](crate::prelude::UndoMacros).
+    fn add_undo_macros<T: UndoPayload>(&mut self) -> &mut App;
+
+
+    /// Adds the system to [`Update`] as the handler of the undo events of `E`.
+    ///
+    /// This is the same as adding the system directly, but also marks `E` as handled for [`AppUndoEx::warn_unhandled_undo`].
+    fn add_undo_handler<E: UndoPayload, M>(&mut self, handler: impl IntoScheduleConfigs<ScheduleSystem, M>) -> &mut App;
+
+
+    /// Adds the system to [`Update`] as a fallible handler of the undo events of `E`.
+    ///
+    /// The handler takes each event as its input, and when it returns an error,
+    /// [`UndoFailed`](crate::prelude::UndoFailed) is sent and the event is treated according to the policy.
+    /// The count of failures is available via [`UndoRequester::failures`](crate::prelude::UndoRequester::failures).
+    fn add_undo_handler_fallible<E: UndoPayload, M>(
+        &mut self,
+        policy: UndoFailurePolicy,
+        handler: impl IntoSystem<SystemIn<E>, Result<(), UndoError>, M>,
+    ) -> &mut App;
+
+
+    /// Marks `E` as handled for [`AppUndoEx::warn_unhandled_undo`], for handlers not added via [`AppUndoEx::add_undo_handler`].
+    fn mark_undo_handled<E: UndoPayload>(&mut self) -> &mut App;
+
+
+    /// Sends [`UndoUnhandled`](crate::prelude::UndoUnhandled) and logs a warning
+    /// when events of a type which is neither added via [`AppUndoEx::add_undo_handler`] nor marked via [`AppUndoEx::mark_undo_handled`] are undone or redone.
+    ///
+    /// This catches handlers forgotten to be added, which would otherwise make undos silently disappear.
+    fn warn_unhandled_undo(&mut self) -> &mut App;
+
+
+    /// Setup the app to register undo events as a lightweight `In`, while storing `Stored` built by the `converter`.
+    ///
+    /// The converter is a system taking `In` as its input, so it can capture the current component values at registration time.
+    /// Messages are registered via `UndoScheduler<In>::register`, and `Stored` is sent when undone.
+    /// Reservations of `In` are not supported.
+    fn add_undo_event_mapped<In, Stored, M>(&mut self, converter: impl IntoSystem<SystemIn<In>, Stored, M>) -> &mut App
+        where
+            In: UndoPayload,
+            Stored: UndoPayload;
+
+
+    /// Keeps the assets referenced by the stored events of `T` alive while the entries live.
+    ///
+    /// The [`Handle`](bevy::asset::Handle)s are detected via [`Reflect`], so the handle types must be registered by
+    /// [`register_asset_reflect`](bevy::asset::AssetApp::register_asset_reflect).
+    /// This is useful when the events only hold handle ids, or the last strong handle may be dropped before undo re-adds the asset.
+    fn retain_undo_handles<T: UndoPayload + Reflect>(&mut self) -> &mut App;
+
+
+    /// Setup the app to undo mutations of `Assets<A>` made via [`UndoAssets::modify`](crate::prelude::UndoAssets::modify).
+    ///
+    /// The captured values are written back to the assets in [`PreUpdate`], right after they are undone or redone.
+    fn add_undo_asset<A: Asset + Clone>(&mut self) -> &mut App;
+
+
+    /// Setup the app to restore values of the component `C` via [`UndoComponentEvent`](crate::prelude::UndoComponentEvent).
+    ///
+    /// This also enables [`UndoDrag`](crate::prelude::UndoDrag) for the component.
+    fn add_undo_component<C: Component<Mutability = Mutable> + Clone>(&mut self) -> &mut App;
+
+
+    /// Registers the insertions and removals of the component `C` as entries, undoing an insertion by removing the component
+    /// and a removal by inserting the last known value again, via [`UndoLifecycleEvent`](crate::prelude::UndoLifecycleEvent).
+    ///
+    /// Changes are detected in [`Last`], so the ones made anywhere in the frame are registered in the next frame.
+    /// The components of despawned entities are not registered, and neither are the ones inserted or removed by undo or redo.
+    fn track_component_lifecycle_undo<C: Component + Clone>(&mut self) -> &mut App;
+
+
+    /// Registers the transitions of the state `S` as entries, undoing a transition by queuing the one back
+    /// to the previous state via [`NextState`](bevy::prelude::NextState), such as for editor modes or wizard steps,
+    /// via [`UndoTransitionEvent`](crate::prelude::UndoTransitionEvent).
+    ///
+    /// Transitions are detected in [`Last`], and the ones queued by undo and redo are applied in the same frame.
+    /// The transitions made by undo or redo are not registered.
+    fn track_state_undo<S: FreelyMutableState>(&mut self) -> &mut App;
+
+
+    /// Interpolates the component `C` restored via [`UndoComponentEvent`](crate::prelude::UndoComponentEvent)
+    /// from its current value over the duration, instead of snapping to it.
+    ///
+    /// `interpolate` receives the progress from 0 to 1, the value when the undo or redo started and the restored value.
+    /// The tween is kept in [`UndoTween`](crate::prelude::UndoTween) while running, and is framed by
+    /// [`UndoTweenStarted`](crate::prelude::UndoTweenStarted) and [`UndoTweenFinished`](crate::prelude::UndoTweenFinished).
+    /// This also sets up `C` via [`AppUndoEx::add_undo_component`] if not done yet.
+    fn animate_undo_component<C: Component<Mutability = Mutable> + Clone>(
+        &mut self,
+        duration: Duration,
+        interpolate: impl Fn(f32, &C, &C) -> C + Send + Sync + 'static,
+    ) -> &mut App;
+
+
+    /// Same as [`AppUndoEx::animate_undo_component`] for [`Transform`](bevy::prelude::Transform),
+    /// interpolating the rotation spherically.
+    fn animate_undo_transforms(&mut self, duration: Duration) -> &mut App;
+
+
+    /// Setup the app to undo bulk edits made via [`UndoBulkEdit`](crate::prelude::UndoBulkEdit) with snapshots of type `S`.
+    ///
+    /// `restore` applies a snapshot back onto its entity, which is skipped if it has been despawned.
+    fn add_undo_bulk<S: Clone + Send + Sync + 'static>(&mut self, restore: impl Fn(&mut EntityWorldMut, &S) + Send + Sync + 'static) -> &mut App;
+
+
+    /// Setup the app to undo the cuts, pastes and duplicates made via [`UndoClipboard`](crate::prelude::UndoClipboard).
+    ///
+    /// This also sets up [`AppUndoEx::add_undo_spawns`].
+    fn add_undo_clipboard(&mut self) -> &mut App;
+
+
+    /// Setup the app to undo the instantiations registered via [`UndoSpawns`](crate::prelude::UndoSpawns).
+    fn add_undo_spawns(&mut self) -> &mut App;
+
+
+    /// Setup the app to undo selection changes made via [`UndoSelection`](crate::prelude::UndoSelection).
+    ///
+    /// `M` is the marker component of the selected entities.
+    fn add_undo_selection<M: Component + Default>(&mut self) -> &mut App;
+
+
+    /// Setup the app to register strokes made via [`UndoStroke`](crate::prelude::UndoStroke).
+    ///
+    /// Strokes with more distinct keys than `max_changes` are split into several entries.
+    /// The app applies [`UndoStrokeEvent`](crate::prelude::UndoStrokeEvent) to its own storage.
+    fn add_undo_stroke<K, V>(&mut self, max_changes: Option<usize>) -> &mut App
+        where
+            K: Eq + Hash + Clone + Send + Sync + 'static,
+            V: Clone + Send + Sync + 'static;
+
+
+    /// Setup the app to restore the rigid bodies edited via [`UndoPhysics`](crate::prelude::UndoPhysics).
+    ///
+    /// The bodies are restored in [`PreUpdate`], right after they are undone or redone, so Rapier picks up
+    /// the restored transforms and velocities in the same frame.
+    #[cfg(feature = "rapier")]
+    fn add_undo_physics(&mut self) -> &mut App;
+
+
+    /// Setup the app to restore the rigid bodies edited via [`UndoXpbdPhysics`](crate::prelude::UndoXpbdPhysics),
+    /// right after they are undone or redone in [`PreUpdate`].
+    #[cfg(feature = "xpbd")]
+    fn add_undo_xpbd_physics(&mut self) -> &mut App;
+
+
+    /// Setup the app to let scripts take part in the history via [`UndoScriptApi`](crate::prelude::UndoScriptApi),
+    /// bound to Lua with the feature `lua` and to Rhai with the feature `rhai`.
+    ///
+    /// The entries registered by scripts are sent back as [`UndoScriptEvent`](crate::prelude::UndoScriptEvent) when undone or redone.
+    #[cfg(any(feature = "lua", feature = "rhai"))]
+    fn add_undo_scripting(&mut self) -> &mut App;
+
+
+    /// Setup the app to restore the tile component `T` of tilemaps painted via [`UndoTilemap`](crate::prelude::UndoTilemap).
+    ///
+    /// The tiles of an entry are restored in bulk, right after it is undone or redone.
+    #[cfg(feature = "tilemap")]
+    fn add_undo_tilemap<T: Component<Mutability = Mutable> + Clone>(&mut self) -> &mut App;
+
+
+    /// Setup the app to undo text edits made via [`UndoText`](crate::prelude::UndoText).
+    ///
+    /// The app applies [`UndoTextEvent`](crate::prelude::UndoTextEvent) to its own text fields.
+    fn add_undo_text(&mut self) -> &mut App;
+
+
+    /// Drops the entries of `T` whose undo would have no effect, such as an entity already despawned.
+    ///
+    /// The hook is called for each entry in the frames where entries of `T` are registered or any slot is undone, redone or dropped,
+    /// and a slot is dropped only if all of its entries are of `T` and judged as no-op.
+    fn compact_undo_noops<T: UndoPayload>(&mut self, is_noop: impl Fn(&T, &World) -> bool + Send + Sync + 'static) -> &mut App;
+
+
+    /// Stores the entries of `T` as deltas against the previous snapshot of the same target, see [`UndoDelta`](crate::prelude::UndoDelta).
+    ///
+    /// Every `keyframe_interval`th snapshot of a target is kept in full, so undoing never applies more deltas than that.
+    /// The snapshots are rebuilt right before their entries are dispatched, so the handlers receive them as usual.
+    /// Entries of `T` are not seen by [`AppUndoEx::compact_undo_noops`] while compressed.
+    fn compress_undo_deltas<T: UndoDelta>(&mut self, keyframe_interval: usize) -> &mut App;
+
+
+    /// Folds each newly registered entry of `T` into the previous entry of its channel when the payloads accept it, see [`MergeUndo`](crate::prelude::MergeUndo).
+    ///
+    /// Only single-entry slots are merged, and never linked, atomic or sticky ones.
+    /// Entries of `T` are not merged while compressed by [`AppUndoEx::compress_undo_deltas`].
+    fn merge_undo_entries<T: MergeUndo>(&mut self) -> &mut App;
+
+
+    /// Drops each newly registered entry of `T` equal to the previous entry of its channel for the same entities,
+    /// such as when a change-detection loop registers the same payload again.
+    ///
+    /// Only single-entry slots are dropped, and never linked, atomic or sticky ones.
+    fn dedup_undo_entries<T: UndoPayload + PartialEq>(&mut self) -> &mut App;
+
+
+    /// Same as [`AppUndoEx::dedup_undo_entries`], but the payloads are duplicates if the hook returns the same hash for both.
+    fn dedup_undo_entries_by_hash<T: UndoPayload>(&mut self, hash: impl Fn(&T) -> u64 + Send + Sync + 'static) -> &mut App;
+
+
+    /// Encodes the entries of `T` registered longer ago than `after` via [`UndoVersioned`](crate::prelude::UndoVersioned), off the main thread.
+    ///
+    /// The entries are decoded only when they are undone, discarded ones are dropped without decoding.
+    /// This requires [`Time`], so it does nothing without [`TimePlugin`](bevy::time::TimePlugin).
+    fn compact_cold_undo_entries<T: UndoVersioned>(&mut self, after: Duration) -> &mut App;
+
+
+    /// Same as [`AppUndoEx::compact_cold_undo_entries`], but the encoded entries are also compressed.
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    fn compact_cold_undo_entries_compressed<T: UndoVersioned>(&mut self, after: Duration, compression: UndoCompression) -> &mut App;
+
+
+    /// Replaces the storage of the registered entries of `T`, which are kept in memory by default.
+    ///
+    /// The entries returned by [`UndoStorage::restored`] are registered in the history,
+    /// so the entries persisted by a run which crashed can be undone again.
+    ///
+    /// This can be called before or after [`AppUndoEx::add_undo_event`].
+    fn set_undo_storage<T: UndoPayload>(&mut self, storage: impl UndoStorage<T>) -> &mut App;
+
+
+    /// Includes the payloads of `T` as RON in [`UndoHistoryExport`](crate::prelude::UndoHistoryExport).
+    ///
+    /// Payloads kept compressed or encoded by other extensions are not exported.
+    #[cfg(feature = "serde")]
+    fn export_undo_payloads<T: UndoPayload + serde::Serialize>(&mut self) -> &mut App;
+
+
+    /// Replays the payloads of `T` in [`UndoHistoryExport::replay`](crate::prelude::UndoHistoryExport::replay).
+    #[cfg(feature = "serde")]
+    fn import_undo_payloads<T: UndoPayload + serde::de::DeserializeOwned>(&mut self) -> &mut App;
+
+
+    /// Configures the capacity and eviction of the channel.
+    ///
+    /// Channels without configuration keep an unlimited count of entries.
+    fn configure_undo_channel(&mut self, channel: impl Into<UndoChannel>, config: UndoStackConfig) -> &mut App;
+
+
+    /// Sets the capacity and eviction of the entries of `E`, independent of the other types.
+    ///
+    /// The capacity counts the slots containing entries of `E` across all channels,
+    /// and is enforced in the frame the entries are registered, sending [`UndoEvicted`] for the dropped entries.
+    /// A slot which also contains entries of other types is dropped as a whole.
+    fn configure_undo_type<E: UndoPayload>(&mut self, config: UndoStackConfig) -> &mut App;
+
+
+    /// Drops undo and redo requests arriving within the duration after the last ones processed.
+    ///
+    /// The requests sent in the same frame, such as by [`UndoRequester::undo_count`](crate::prelude::UndoRequester::undo_count),
+    /// are processed or dropped together.
+    /// This requires [`Time`], so it does nothing without [`TimePlugin`](bevy::time::TimePlugin).
+    /// The count of dropped requests is available via [`UndoRequester::suppressed_requests`](crate::prelude::UndoRequester::suppressed_requests).
+    fn configure_undo_cooldown(&mut self, duration: Duration) -> &mut App;
+
+
+    /// Limits the count of undo and redo requests resolved per frame.
+    ///
+    /// By default all requests sent in a frame are resolved in the next frame in the order sent.
+    /// With a limit, the excess requests are kept in order and resolved in subsequent frames.
+    /// The count of waiting requests is available via [`UndoRequester::deferred_requests`](crate::prelude::UndoRequester::deferred_requests).
+    fn configure_undo_requests_per_frame(&mut self, per_frame: usize) -> &mut App;
+
+
+    /// Limits the undo and redo requests resolved per frame to the ones whose handlers fit in the budget,
+    /// protecting the frame rate on heavy undos.
+    ///
+    /// The handlers of all types are timed in each frame requests are resolved, and the excess requests
+    /// are kept in order for subsequent frames like with [`AppUndoEx::configure_undo_requests_per_frame`].
+    /// At least one request is resolved per frame, and only one until the handlers have been timed once.
+    fn configure_undo_dispatch_budget(&mut self, budget: Duration) -> &mut App;
+
+
+    /// Keeps the events undone and redone readable for at least the count of frames after the frame they are dispatched in,
+    /// so readers running less often than every frame, such as in [`FixedUpdate`](bevy::app::FixedUpdate) or behind run conditions,
+    /// still read each of them once.
+    ///
+    /// The events are dispatched in [`PreUpdate`], and the buffers of the types added via [`AppUndoEx::add_undo_event`] are
+    /// swapped in [`First`] once every window instead of every frame, so readers miss none as long as they run
+    /// at least once within the window, and the events are cleared within twice the window. Readers running in [`First`] or in [`PreUpdate`]
+    /// before the events are dispatched read them in the next frame.
+    ///
+    /// The window is a single frame by default. Types whose events are also added via [`App::add_message`] beforehand
+    /// keep the buffers updated by Bevy every frame.
+    fn configure_undo_delivery_window(&mut self, frames: u32) -> &mut App;
+
+
+    /// Sends [`AutosaveSuggested`](crate::prelude::AutosaveSuggested) according to the undoable actions registered.
+    ///
+    /// Calling this again replaces the config.
+    fn configure_undo_autosave(&mut self, config: UndoAutosaveConfig) -> &mut App;
+
+
+    /// Reports suspicious usage of the API, such as committing without reservations
+    /// or registering entries while undone events are being handled.
+    ///
+    /// Calling this again replaces the strictness.
+    fn configure_undo_strict(&mut self, strictness: UndoStrictness) -> &mut App;
+
+
+    /// Makes all entries registered in the same frame share one slot, so they are undone together
+    /// without reserving them, matching the "one action per input" pattern of most gameplay.
+    ///
+    /// This applies to the channels without their own strategy set via [`AppUndoEx::configure_undo_grouping`].
+    /// Calling this again replaces the setting.
+    fn configure_undo_frame_grouping(&mut self, enabled: bool) -> &mut App;
+
+
+    /// Selects how the entries registered to the channel are grouped into slots.
+    ///
+    /// Calling this again replaces the strategy of the channel.
+    fn configure_undo_grouping(&mut self, channel: impl Into<UndoChannel>, strategy: impl UndoGroupingStrategy) -> &mut App;
+
+
+    /// Selects how the registrations are bucketed into undoable steps, switching the feel of undo
+    /// without touching the registration sites.
+    ///
+    /// This applies to the channels without their own strategy set via [`AppUndoEx::configure_undo_grouping`].
+    /// Calling this again replaces the granularity, as does [`AppUndoEx::configure_undo_frame_grouping`].
+    fn configure_undo_granularity(&mut self, granularity: UndoGranularity) -> &mut App;
+
+
+    /// Orders the restoration of the entries sharing one slot by the priorities of their types, the higher first,
+    /// such as respawning an entity before restoring its components.
+    ///
+    /// This orders the dispatch and the built-in restoring systems in [`PreUpdate`],
+    /// and the handlers added via [`AppUndoEx::add_undo_handler`] and [`AppUndoEx::add_undo_handler_fallible`] in [`Update`].
+    /// The priority of a type should be configured once, since the orderings accumulate.
+    fn configure_undo_priority<T: UndoPayload>(&mut self, priority: i32) -> &mut App;
+
+
+    /// Calls the hook with each entry pushed to the history, such as to log or play a sound.
+    fn add_undo_hook_on_push(&mut self, hook: impl Fn(&UndoHookContext) + Send + Sync + 'static) -> &mut App;
+
+
+    /// Calls the hook with each entry undone, once before it is dispatched and once after its handlers have run,
+    /// see [`UndoHookPhase`](crate::prelude::UndoHookPhase).
+    fn add_undo_hook_on_undo(&mut self, hook: impl Fn(&UndoHookContext) + Send + Sync + 'static) -> &mut App;
+
+
+    /// Calls the hook with each entry redone, once before it is dispatched and once after its handlers have run,
+    /// see [`UndoHookPhase`](crate::prelude::UndoHookPhase).
+    fn add_undo_hook_on_redo(&mut self, hook: impl Fn(&UndoHookContext) + Send + Sync + 'static) -> &mut App;
+
+
+    /// Calls the veto with each entry about to be undone, such as to keep the entries of locked layers.
+    ///
+    /// If it returns [`UndoVerdict::Deny`](crate::prelude::UndoVerdict::Deny) for any entry reached by a request,
+    /// the request is dropped, leaving the entries in place, and [`UndoDenied`](crate::prelude::UndoDenied) is sent.
+    fn add_undo_veto(&mut self, veto: impl Fn(&UndoHookContext) -> UndoVerdict + Send + Sync + 'static) -> &mut App;
+
+
+    /// Calls the authorizer with each entry about to be undone or redone together with the requester,
+    /// such as for the host of a networked session to keep clients from undoing the actions of other users.
+    ///
+    /// Requests made on behalf of clients via [`UndoRequester::undo_channel_as`](crate::prelude::UndoRequester::undo_channel_as)
+    /// carry their site, local ones carry none.
+    /// If it returns [`UndoVerdict::Deny`](crate::prelude::UndoVerdict::Deny) for any entry reached by a request,
+    /// the request is dropped, leaving the entries in place, and [`UndoDenied`](crate::prelude::UndoDenied) is sent.
+    fn add_undo_authorizer(&mut self, authorizer: impl Fn(&UndoAuthorization) -> UndoVerdict + Send + Sync + 'static) -> &mut App;
+
+
+    /// Removes the entries invalidated by [`WeakEntityRef`](crate::prelude::WeakEntityRef) from the history
+    /// instead of keeping them, which is the default.
+    fn prune_invalid_undo_entries(&mut self, prune: bool) -> &mut App;
+
+
+    /// Enables collaborative editing as the client of the site.
+    ///
+    /// Entries registered from then on are stamped with a logical clock, see [`UndoMeta::stamp`](crate::prelude::UndoMeta::stamp),
+    /// and the entries of other clients can be merged via [`UndoCollab`](crate::prelude::UndoCollab).
+    fn enable_undo_collab(&mut self, site: UndoSiteId) -> &mut App;
+
+
+    /// Rewrites the local entries of `T` with the transform each time an entry of `O` stamped later
+    /// is merged from another client, so undoing them does not apply stale payloads.
+    ///
+    /// Both the undo-event and the redo-event of the entries are transformed.
+    fn add_undo_transform<T: UndoPayload, O: UndoPayload>(&mut self, transform: impl Fn(&mut T, &O) + Send + Sync + 'static) -> &mut App;
+
+
+    /// Passes the anonymized usage of the history to the callback at most once per interval,
+    /// such as to feed analytics pipelines with which tools are undone the most.
+    ///
+    /// The usage covers the period since the previous report, and is reported only if anything has been undone or redone.
+    /// All callbacks share the interval set last.
+    fn add_undo_telemetry(&mut self, interval: Duration, callback: impl Fn(&UndoUsage) + Send + Sync + 'static) -> &mut App;
+
+
+    /// Makes a game turn the unit of undo, such as for roguelikes and puzzle games:
+    /// the entries registered until [`UndoTurns::end_turn`](crate::prelude::UndoTurns::end_turn) share one slot,
+    /// so each undo request rewinds exactly one turn.
+    ///
+    /// This applies to the channels without their own strategy set via [`AppUndoEx::configure_undo_grouping`].
+    fn enable_undo_turns(&mut self) -> &mut App;
+
+
+    /// Runs the fixer over the entries of `T`, including the ones waiting for redo, each time [`UndoHotReloaded`](crate::prelude::UndoHotReloaded) is sent,
+    /// so that payloads referring to reloaded assets or scripts can be rewritten in place.
+    ///
+    /// The fixer returns false to invalidate the entry, whose slot is then dropped from the history.
+    /// Fixers of the same type run in the order added, until one of them returns false.
+    fn on_undo_hot_reload<T: UndoPayload>(&mut self, fixer: impl Fn(&mut T, &World) -> bool + Send + Sync + 'static) -> &mut App;
+
+
+    /// Appends a line to the log file for each entry registered, undone or redone,
+    /// such as for traceability of editor actions on shared assets.
+    ///
+    /// Each line holds the timestamp in milliseconds since the Unix epoch, the operation, the slot, the type name,
+    /// the tag of the entry and the user of the config, separated by tabs.
+    /// Failing to write the file is logged as a warning.
+    #[cfg(feature = "audit")]
+    fn enable_undo_audit(&mut self, config: UndoAuditConfig) -> &mut App;
+
+
+    /// Calls the callback with each slot once recorded, keeping the returned thumbnail with its entries,
+    /// such as to build a visual timeline of the history.
+    ///
+    /// The callback captures the thumbnail itself, such as by copying a render target into a small image.
+    /// The thumbnails are exposed via [`UndoEntryInfo::thumbnail`](crate::prelude::UndoEntryInfo::thumbnail),
+    /// and dropped along with their entries.
+    #[cfg(feature = "thumbnails")]
+    fn set_undo_thumbnail_capture(
+        &mut self,
+        capture: impl Fn(&mut World, usize) -> Option<Handle<Image>> + Send + Sync + 'static,
+    ) -> &mut App;
 }
 
 
 impl AppUndoEx for App {
-    fn add_undo_event<E: Event + Clone>(&mut self) -> &mut App {
-        self.add_event::<E>();
-        self.add_event::<UndoEvent<E>>();
-        self.add_event::<UndoReserveEvent<E>>();
-        self.init_resource::<UndoRegisteredArea<E>>();
-        self.init_resource::<UndoRegisteredArea<UndoReserveEvent<E>>>();
-        self.init_resource::<UndoReservedArea<E>>();
-        self.init_resource::<ReserveCounter>();
-        self.add_systems(Update, (
-               reserve_event_system::<E>,
-            register_all_reserved_events_system::<E>,
-            push_undo_event_system::<E>,
-            request_undo_event_system::<E>,
-            request_undo_event_system::<E>,
-            request_undo_event_system::<UndoReserveEvent<E>>,
-
-        ).chain());
+    fn add_undo_event<E: UndoPayload>(&mut self) -> &mut App {
+        if !self.world().contains_resource::<Messages<E>>() {
+            self
+                .init_resource::<Messages<E>>()
+                .add_systems(First, update_undo_events_system::<E>);
+        }
+        self.init_resource::<UndoDeliveryWindow>();
+        self.add_message::<UndoEvent<E>>();
+        self.add_message::<UndoEvicted<E>>();
+        self.add_message::<DryRun<E>>();
+        self.add_message::<Preview<E>>();
+        self.world_mut().get_resource_or_insert_with(UndoAreas::default).init::<E>();
+        self.world_mut().get_resource_or_insert_with(UndoTypeRegistry::default).register::<E>();
+        self.configure_sets(Update, UndoHandlerSet::of::<E>().in_set(UndoAllHandlersSet));
+        #[cfg(feature = "reserve")]
+        self.add_systems(PreUpdate, register_all_reserved_events_system::<E>
+                .in_set(UndoSystemSet::Record)
+                .run_if(on_message::<CommitReservationsEvent>));
+        // The systems of the type only run in frames with something to process, so types added defensively cost next to nothing.
+        self.add_systems(PreUpdate, (
+            push_undo_event_system::<E>
+                .in_set(UndoSystemSet::Record)
+                .run_if(on_message::<UndoEvent<E>>)
+                .run_if(event_condition_holds::<E>),
+            amend_latest_system::<E>
+                .in_set(UndoSystemSet::Record)
+                .after(push_undo_event_system::<E>)
+                .run_if(has_amendments::<E>),
+            replace_latest_system::<E>
+                .after(UndoSystemSet::Record)
+                .before(UndoSystemSet::Evict)
+                .run_if(has_replacement::<E>),
+            drop_replaced_entries_system::<E>
+                .in_set(UndoSystemSet::Evict)
+                .before(evict_over_capacity_system)
+                .run_if(has_replaced_entries),
+            fix_hot_reloaded_entries_system::<E>
+                .in_set(UndoSystemSet::Evict)
+                .run_if(has_hot_reload_work::<E>),
+            dispatch_undo_event_system::<E>
+                .in_set(UndoSystemSet::Dispatch)
+                .in_set(UndoHandlerSet::of::<E>())
+                .run_if(on_message::<DispatchUndoEvent>),
+            detect_unhandled_system::<E>
+                .in_set(UndoSystemSet::Dispatch)
+                .after(dispatch_undo_event_system::<E>)
+                .run_if(on_message::<E>)
+        ));
+        #[cfg(feature = "debug_invariants")]
+        self.add_systems(PreUpdate, check_type_invariants_system::<E>.after(UndoSystemSet::Dispatch));
         self
     }
-}
 
 
-fn register_all_reserved_events_system<E: Event + Clone>(
-    mut er: EventReader<CommitReservationsEvent>,
-    mut reserved_area: ResMut<UndoReservedArea<E>>,
-    mut registered_reserve_event_area: ResMut<UndoRegisteredArea<UndoReserveEvent<E>>>,
-) {
-    while let Some(CommitReservationsEvent(counter)) = er.iter().next() {
-        reserved_area.0.sort_by(|e1, e2| e2.reserve_no.partial_cmp(&e1.reserve_no).unwrap());
+    fn add_undo_event_with_condition<E: UndoPayload, M>(&mut self, condition: impl SystemCondition<M>) -> &mut App {
+        self
+            .add_undo_event::<E>()
+            .init_resource::<UndoEventCondition<E>>()
+            .add_systems(PreUpdate, condition
+                .pipe(update_event_condition_system::<E>)
+                .before(UndoSystemSet::Record))
+    }
 
-        while let Some(event) = reserved_area.pop_front() {
-            registered_reserve_event_area.push(UndoEvent {
-                inner: event.clone(),
-                no: **counter + event.reserve_no,
-            });
+
+    fn suspend_undo_in_states<S: States>(&mut self, states: impl IntoIterator<Item = S>) -> &mut App {
+        if let Some(mut suspended) = self.world_mut().get_resource_mut::<UndoSuspendedStates<S>>() {
+            suspended.0.extend(states);
+            return self;
         }
+        self
+            .insert_resource(UndoSuspendedStates(states.into_iter().collect()))
+            .add_systems(PreUpdate, update_suspension_system::<S>.before(UndoSystemSet::Record))
     }
-}
 
 
-fn push_undo_event_system<E: Event + Clone>(
-    mut er: EventReader<UndoEvent<E>>,
-    mut registered_area: ResMut<UndoRegisteredArea<E>>,
-) {
-    for e in er.iter() {
-        registered_area.push(e.clone());
+    fn enable_undo_windows(&mut self) -> &mut App {
+        self.add_systems(PreUpdate, track_focused_window_system.before(UndoSystemSet::Commit))
+    }
+
+
+    fn enable_undo_scenes(&mut self) -> &mut App {
+        self.add_systems(PreUpdate, track_loaded_scenes_system.before(UndoSystemSet::Commit))
+    }
+
+
+    fn add_undo_macros<E: UndoPayload>(&mut self) -> &mut App {
+        self
+            .init_resource::<UndoMacroLibrary>()
+            .add_systems(PreUpdate, record_macro_steps_system::<E>.in_set(UndoSystemSet::Record))
+    }
+
+
+    fn add_undo_handler<E: UndoPayload, M>(&mut self, handler: impl IntoScheduleConfigs<ScheduleSystem, M>) -> &mut App {
+        self.mark_undo_handled::<E>();
+        self.add_systems(Update, handler.in_set(UndoHandlerSet::of::<E>()));
+        self
+    }
+
+
+    fn add_undo_handler_fallible<E: UndoPayload, M>(
+        &mut self,
+        policy: UndoFailurePolicy,
+        handler: impl IntoSystem<SystemIn<E>, Result<(), UndoError>, M>,
+    ) -> &mut App {
+        let mut handler = IntoSystem::into_system(handler);
+        handler.initialize(self.world_mut());
+
+        self.mark_undo_handled::<E>();
+        self.insert_resource(UndoFallibleHandler::<E>::new(Box::new(handler), policy));
+        self.add_systems(Update, run_fallible_handler_system::<E>.in_set(UndoHandlerSet::of::<E>()));
+        self
+    }
+
+
+    fn mark_undo_handled<E: UndoPayload>(&mut self) -> &mut App {
+        self
+            .world_mut()
+            .get_resource_or_insert_with(UndoHandlers::default)
+            .handled
+            .insert(std::any::type_name::<E>());
+        self
+    }
+
+
+    fn warn_unhandled_undo(&mut self) -> &mut App {
+        self
+            .world_mut()
+            .get_resource_or_insert_with(UndoHandlers::default)
+            .warn = true;
+        self
+    }
+
+
+    fn add_undo_event_mapped<In, Stored, M>(&mut self, converter: impl IntoSystem<SystemIn<In>, Stored, M>) -> &mut App
+        where
+            In: UndoPayload,
+            Stored: UndoPayload
+    {
+        let mut converter = IntoSystem::into_system(converter);
+        converter.initialize(self.world_mut());
+
+        self.add_undo_event::<Stored>();
+        self.add_message::<UndoEvent<In>>();
+        self.insert_resource(UndoEventMapper::<In, Stored>(Box::new(converter)));
+        self.add_systems(PreUpdate, map_undo_event_system::<In, Stored>
+            .in_set(UndoSystemSet::Record)
+            .before(push_undo_event_system::<Stored>),
+        );
+        self
+    }
+
+
+    fn retain_undo_handles<E: UndoPayload + Reflect>(&mut self) -> &mut App {
+        self.init_resource::<UndoRetainedHandles<E>>();
+        self.add_systems(PreUpdate, (
+            retain_handles_system::<E>.in_set(UndoSystemSet::Record),
+            release_handles_system::<E>
+                .in_set(UndoSystemSet::Dispatch)
+                .after(dispatch_undo_event_system::<E>)
+        ));
+        self
+    }
+
+
+    fn add_undo_asset<A: Asset + Clone>(&mut self) -> &mut App {
+        self.add_undo_event::<UndoAssetEvent<A>>();
+        self.mark_undo_handled::<UndoAssetEvent<A>>();
+        self.add_systems(PreUpdate, restore_asset_system::<A>
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoAssetEvent<A>>())
+            .after(dispatch_undo_event_system::<UndoAssetEvent<A>>),
+        );
+        self
+    }
+
+
+    fn add_undo_component<C: Component<Mutability = Mutable> + Clone>(&mut self) -> &mut App {
+        self.add_undo_event::<UndoComponentEvent<C>>();
+        self.mark_undo_handled::<UndoComponentEvent<C>>();
+        self.init_resource::<UndoDragStarts<C>>();
+        self.add_systems(PreUpdate, restore_component_system::<C>
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoComponentEvent<C>>())
+            .after(dispatch_undo_event_system::<UndoComponentEvent<C>>),
+        );
+        self
+    }
+
+
+    fn track_component_lifecycle_undo<C: Component + Clone>(&mut self) -> &mut App {
+        self.add_undo_event::<UndoLifecycleEvent<C>>();
+        self.mark_undo_handled::<UndoLifecycleEvent<C>>();
+        self.init_resource::<UndoComponentShadow<C>>();
+        self.add_systems(PreUpdate, apply_lifecycle_events_system::<C>
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoLifecycleEvent<C>>())
+            .after(dispatch_undo_event_system::<UndoLifecycleEvent<C>>),
+        );
+        self.add_systems(Last, track_component_lifecycle_system::<C>);
+        self
+    }
+
+
+    fn track_state_undo<S: FreelyMutableState>(&mut self) -> &mut App {
+        self.add_undo_event::<UndoTransitionEvent<S>>();
+        self.mark_undo_handled::<UndoTransitionEvent<S>>();
+        self.init_resource::<UndoTrackedState<S>>();
+        self.add_systems(PreUpdate, apply_transition_events_system::<S>
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoTransitionEvent<S>>())
+            .after(dispatch_undo_event_system::<UndoTransitionEvent<S>>),
+        );
+        self.add_systems(Last, track_state_transition_system::<S>);
+        self
+    }
+
+
+    fn animate_undo_component<C: Component<Mutability = Mutable> + Clone>(
+        &mut self,
+        duration: Duration,
+        interpolate: impl Fn(f32, &C, &C) -> C + Send + Sync + 'static,
+    ) -> &mut App {
+        let configured = self.world().contains_resource::<UndoTweenConfig<C>>();
+        self.insert_resource(UndoTweenConfig::<C> {
+            duration,
+            interpolate: Box::new(interpolate),
+        });
+        if configured {
+            return self;
+        }
+        if !self.world().contains_resource::<UndoDragStarts<C>>() {
+            self.add_undo_component::<C>();
+        }
+        self
+            .add_message::<UndoTweenStarted<C>>()
+            .add_message::<UndoTweenFinished<C>>()
+            .add_systems(PreUpdate, start_tweens_system::<C>
+                .in_set(UndoSystemSet::Dispatch)
+                .in_set(UndoHandlerSet::of::<UndoComponentEvent<C>>())
+                .after(dispatch_undo_event_system::<UndoComponentEvent<C>>)
+                .before(restore_component_system::<C>))
+            .add_systems(Update, advance_tweens_system::<C>);
+        self
+    }
+
+
+    #[inline]
+    fn animate_undo_transforms(&mut self, duration: Duration) -> &mut App {
+        self.animate_undo_component::<Transform>(duration, interpolate_transform)
+    }
+
+
+    fn add_undo_bulk<S: Clone + Send + Sync + 'static>(&mut self, restore: impl Fn(&mut EntityWorldMut, &S) + Send + Sync + 'static) -> &mut App {
+        self.add_undo_event::<UndoBulkEvent<S>>();
+        self.mark_undo_handled::<UndoBulkEvent<S>>();
+        self.insert_resource(UndoBulkRestore::<S>(Box::new(restore)));
+        self.init_resource::<UndoBulkStarts<S>>();
+        self.add_systems(PreUpdate, restore_bulk_system::<S>
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoBulkEvent<S>>())
+            .after(dispatch_undo_event_system::<UndoBulkEvent<S>>),
+        );
+        self
+    }
+
+
+    #[inline]
+    fn add_undo_clipboard(&mut self) -> &mut App {
+        self.init_resource::<UndoClipboardContents>();
+        self.add_undo_spawns()
+    }
+
+
+    fn add_undo_spawns(&mut self) -> &mut App {
+        if self.world().contains_resource::<Messages<UndoSpawnEvent>>() {
+            return self;
+        }
+        self.add_undo_event::<UndoSpawnEvent>();
+        self.mark_undo_handled::<UndoSpawnEvent>();
+        self.add_systems(PreUpdate, apply_spawn_events_system
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoSpawnEvent>())
+            .after(dispatch_undo_event_system::<UndoSpawnEvent>),
+        );
+        self
+    }
+
+
+    fn add_undo_selection<M: Component + Default>(&mut self) -> &mut App {
+        self.add_undo_event::<UndoSelectionEvent<M>>();
+        self.mark_undo_handled::<UndoSelectionEvent<M>>();
+        self.add_systems(PreUpdate, restore_selection_system::<M>
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoSelectionEvent<M>>())
+            .after(dispatch_undo_event_system::<UndoSelectionEvent<M>>),
+        );
+        self
+    }
+
+
+    fn add_undo_stroke<K, V>(&mut self, max_changes: Option<usize>) -> &mut App
+        where
+            K: Eq + Hash + Clone + Send + Sync + 'static,
+            V: Clone + Send + Sync + 'static
+    {
+        self.add_undo_event::<UndoStrokeEvent<K, V>>();
+        self.insert_resource(UndoStrokeBuffer::<K, V>::new(max_changes));
+        self
+    }
+
+
+    #[cfg(feature = "rapier")]
+    fn add_undo_physics(&mut self) -> &mut App {
+        self.add_undo_event::<UndoPhysicsEvent>();
+        self.mark_undo_handled::<UndoPhysicsEvent>();
+        self.add_systems(PreUpdate, restore_physics_system
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoPhysicsEvent>())
+            .after(dispatch_undo_event_system::<UndoPhysicsEvent>),
+        );
+        self
+    }
+
+
+    #[cfg(feature = "xpbd")]
+    fn add_undo_xpbd_physics(&mut self) -> &mut App {
+        self.add_undo_event::<UndoXpbdEvent>();
+        self.mark_undo_handled::<UndoXpbdEvent>();
+        self.add_systems(PreUpdate, restore_xpbd_system
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoXpbdEvent>())
+            .after(dispatch_undo_event_system::<UndoXpbdEvent>),
+        );
+        self
+    }
+
+
+    #[cfg(any(feature = "lua", feature = "rhai"))]
+    fn add_undo_scripting(&mut self) -> &mut App {
+        self.add_undo_event::<UndoScriptEvent>();
+        self.init_resource::<UndoScriptApi>();
+        self.add_systems(PreUpdate, apply_script_commands_system.before(UndoSystemSet::Commit));
+        self
+    }
+
+
+    #[cfg(feature = "tilemap")]
+    fn add_undo_tilemap<T: Component<Mutability = Mutable> + Clone>(&mut self) -> &mut App {
+        self.add_undo_stroke::<UndoTileKey, T>(None);
+        self.mark_undo_handled::<UndoStrokeEvent<UndoTileKey, T>>();
+        self.add_systems(PreUpdate, restore_tiles_system::<T>
+            .in_set(UndoSystemSet::Dispatch)
+            .in_set(UndoHandlerSet::of::<UndoStrokeEvent<UndoTileKey, T>>())
+            .after(dispatch_undo_event_system::<UndoStrokeEvent<UndoTileKey, T>>),
+        );
+        self
+    }
+
+
+    fn add_undo_text(&mut self) -> &mut App {
+        self.add_undo_event::<UndoTextEvent>();
+        self.init_resource::<UndoTextPending>();
+        self.add_systems(PreUpdate, commit_text_edits_system.in_set(UndoSystemSet::Commit));
+        self
+    }
+
+
+    fn compact_undo_noops<E: UndoPayload>(&mut self, is_noop: impl Fn(&E, &World) -> bool + Send + Sync + 'static) -> &mut App {
+        self.insert_resource(UndoNoopHook::<E>(Box::new(is_noop)));
+        self.add_systems(PreUpdate, compact_noops_system::<E>
+            .in_set(UndoSystemSet::Evict)
+            .run_if(on_message::<UndoEvent<E>>.or_else(on_message::<DispatchUndoEvent>)));
+        self
+    }
+
+
+    fn compress_undo_deltas<E: UndoDelta>(&mut self, keyframe_interval: usize) -> &mut App {
+        self.insert_resource(UndoDeltaArea::<E>::new(keyframe_interval));
+        self.add_systems(PreUpdate, (
+            compress_deltas_system::<E>
+                .in_set(UndoSystemSet::Record)
+                .after(push_undo_event_system::<E>),
+            expand_deltas_system::<E>
+                .in_set(UndoSystemSet::Dispatch)
+                .before(dispatch_undo_event_system::<E>)
+        ));
+        self
+    }
+
+
+    fn merge_undo_entries<E: MergeUndo>(&mut self) -> &mut App {
+        self.add_systems(PreUpdate, merge_undo_entries_system::<E>
+            .after(UndoSystemSet::Record)
+            .before(UndoSystemSet::Evict));
+        self
+    }
+
+
+    fn dedup_undo_entries<E: UndoPayload + PartialEq>(&mut self) -> &mut App {
+        self.insert_resource(UndoDuplicateHook::<E>(Box::new(|previous, newer| previous == newer)));
+        self.add_systems(PreUpdate, dedup_undo_entries_system::<E>
+            .after(UndoSystemSet::Record)
+            .before(UndoSystemSet::Evict));
+        self
+    }
+
+
+    fn dedup_undo_entries_by_hash<E: UndoPayload>(&mut self, hash: impl Fn(&E) -> u64 + Send + Sync + 'static) -> &mut App {
+        self.insert_resource(UndoDuplicateHook::<E>(Box::new(move |previous, newer| hash(previous) == hash(newer))));
+        self.add_systems(PreUpdate, dedup_undo_entries_system::<E>
+            .after(UndoSystemSet::Record)
+            .before(UndoSystemSet::Evict));
+        self
+    }
+
+
+    fn compact_cold_undo_entries<E: UndoVersioned>(&mut self, after: Duration) -> &mut App {
+        self.insert_resource(UndoColdArea::<E>::new(after));
+        self.add_systems(PreUpdate, (
+            compact_cold_entries_system::<E>.in_set(UndoSystemSet::Evict),
+            rehydrate_cold_entries_system::<E>
+                .in_set(UndoSystemSet::Dispatch)
+                .before(dispatch_undo_event_system::<E>)
+        ));
+        self
+    }
+
+
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    fn compact_cold_undo_entries_compressed<E: UndoVersioned>(&mut self, after: Duration, compression: UndoCompression) -> &mut App {
+        self.compact_cold_undo_entries::<E>(after);
+        self.world_mut().resource_mut::<UndoColdArea<E>>().codec.compression = Some(compression);
+        self
+    }
+
+
+    fn set_undo_storage<E: UndoPayload>(&mut self, storage: impl UndoStorage<E>) -> &mut App {
+        let mut areas = self.world_mut().get_resource_or_insert_with(UndoAreas::default);
+        areas.init::<E>();
+        let restored = storage.restored();
+        *areas.registered_mut::<E>() = UndoRegisteredArea(Box::new(storage));
+        let mut history = self.world_mut().get_resource_or_insert_with(UndoHistory::default);
+        for (no, meta, redoable) in restored.iter() {
+            let meta = history.copy_meta(meta);
+            history.push::<E>(*no, meta, *redoable, Duration::ZERO);
+        }
+        if let Some(last) = restored.iter().map(|(no, ..)| *no).max() {
+            let mut counter = self.world_mut().get_resource_or_insert_with(UndoCounter::default);
+            if **counter < last {
+                counter.set(last);
+            }
+        }
+        self
+    }
+
+
+    #[cfg(feature = "serde")]
+    fn export_undo_payloads<E: UndoPayload + serde::Serialize>(&mut self) -> &mut App {
+        self.init_resource::<UndoPayloadExporters>();
+        self.world_mut()
+            .resource_mut::<UndoPayloadExporters>()
+            .0
+            .insert(std::any::type_name::<E>(), export_payloads::<E>);
+        self
+    }
+
+
+    #[cfg(feature = "serde")]
+    fn import_undo_payloads<E: UndoPayload + serde::de::DeserializeOwned>(&mut self) -> &mut App {
+        self.init_resource::<UndoPayloadImporters>();
+        self.world_mut()
+            .resource_mut::<UndoPayloadImporters>()
+            .0
+            .insert(std::any::type_name::<E>(), import_payload::<E>);
+        self
+    }
+
+
+    fn configure_undo_channel(&mut self, channel: impl Into<UndoChannel>, config: UndoStackConfig) -> &mut App {
+        self
+            .world_mut()
+            .get_resource_or_insert_with(UndoStackConfigs::default)
+            .0
+            .insert(channel.into(), config);
+        self
+    }
+
+
+    fn configure_undo_type<E: UndoPayload>(&mut self, config: UndoStackConfig) -> &mut App {
+        self
+            .world_mut()
+            .get_resource_or_insert_with(UndoTypeConfigs::default)
+            .0
+            .insert(TypeId::of::<E>(), (std::any::type_name::<E>(), config));
+        self
+    }
+
+
+    fn configure_undo_cooldown(&mut self, duration: Duration) -> &mut App {
+        self
+            .world_mut()
+            .get_resource_or_insert_with(UndoCooldown::default)
+            .duration = Some(duration);
+        self
+    }
+
+
+    fn configure_undo_requests_per_frame(&mut self, per_frame: usize) -> &mut App {
+        self
+            .world_mut()
+            .get_resource_or_insert_with(UndoRequestQueue::default)
+            .per_frame = Some(per_frame.max(1));
+        self
+    }
+
+
+    fn configure_undo_dispatch_budget(&mut self, budget: Duration) -> &mut App {
+        let mut queue = self.world_mut().get_resource_or_insert_with(UndoRequestQueue::default);
+        if queue.budget.replace(budget).is_none() {
+            self.add_systems(Update, (
+                start_handler_timer_system.before(UndoAllHandlersSet),
+                stop_handler_timer_system.after(UndoAllHandlersSet)
+            ));
+        }
+        self
+    }
+
+
+    fn configure_undo_delivery_window(&mut self, frames: u32) -> &mut App {
+        self.insert_resource(UndoDeliveryWindow(frames.max(1)))
+    }
+
+
+    fn configure_undo_autosave(&mut self, config: UndoAutosaveConfig) -> &mut App {
+        if let Some(mut autosave) = self.world_mut().get_resource_mut::<UndoAutosave>() {
+            autosave.config = config;
+            return self;
+        }
+
+        self.add_message::<AutosaveSuggested>();
+        self.insert_resource(UndoAutosave::new(config));
+        self.add_systems(PreUpdate, suggest_autosave_system.in_set(UndoSystemSet::Evict));
+        self
+    }
+
+
+    fn configure_undo_strict(&mut self, strictness: UndoStrictness) -> &mut App {
+        if let Some(mut strict) = self.world_mut().get_resource_mut::<UndoStrictMode>() {
+            strict.strictness = strictness;
+            return self;
+        }
+
+        self.insert_resource(UndoStrictMode::new(strictness));
+        #[cfg(feature = "reserve")]
+        self.add_systems(PreUpdate, (
+            check_empty_commit_system.in_set(UndoSystemSet::Commit),
+            check_request_with_reservations_system.in_set(UndoSystemSet::Resolve)
+        ));
+        self.add_systems(PreUpdate, store_dispatched_counters_system.after(UndoSystemSet::Dispatch));
+        self.add_systems(Last, check_register_during_dispatch_system);
+        self
+    }
+
+
+    fn configure_undo_frame_grouping(&mut self, enabled: bool) -> &mut App {
+        init_undo_groupings(self).default = enabled.then(|| Box::new(UndoFrameGrouping) as Box<dyn UndoGroupingStrategy>);
+        self
+    }
+
+
+    fn configure_undo_grouping(&mut self, channel: impl Into<UndoChannel>, strategy: impl UndoGroupingStrategy) -> &mut App {
+        init_undo_groupings(self).channels.insert(channel.into(), Box::new(strategy));
+        self
+    }
+
+
+    fn configure_undo_granularity(&mut self, granularity: UndoGranularity) -> &mut App {
+        if granularity == UndoGranularity::Turn {
+            return self.enable_undo_turns();
+        }
+        init_undo_groupings(self).default = Some(granularity.strategy());
+        self
+    }
+
+
+    fn configure_undo_priority<E: UndoPayload>(&mut self, priority: i32) -> &mut App {
+        configure_priority::<E>(self, priority);
+        self
+    }
+
+
+    fn add_undo_hook_on_push(&mut self, hook: impl Fn(&UndoHookContext) + Send + Sync + 'static) -> &mut App {
+        init_undo_hooks(self).on_push.push(Box::new(hook));
+        self
+    }
+
+
+    fn add_undo_hook_on_undo(&mut self, hook: impl Fn(&UndoHookContext) + Send + Sync + 'static) -> &mut App {
+        init_undo_hooks(self).on_undo.push(Box::new(hook));
+        self
+    }
+
+
+    fn add_undo_hook_on_redo(&mut self, hook: impl Fn(&UndoHookContext) + Send + Sync + 'static) -> &mut App {
+        init_undo_hooks(self).on_redo.push(Box::new(hook));
+        self
+    }
+
+
+    fn add_undo_veto(&mut self, veto: impl Fn(&UndoHookContext) -> UndoVerdict + Send + Sync + 'static) -> &mut App {
+        init_undo_hooks(self).vetoes.push(Box::new(veto));
+        self
+    }
+
+
+    fn add_undo_authorizer(&mut self, authorizer: impl Fn(&UndoAuthorization) -> UndoVerdict + Send + Sync + 'static) -> &mut App {
+        init_undo_hooks(self).authorizers.push(Box::new(authorizer));
+        self
+    }
+
+
+    fn prune_invalid_undo_entries(&mut self, prune: bool) -> &mut App {
+        self
+            .world_mut()
+            .get_resource_or_insert_with(UndoWeakRefs::default)
+            .prune = prune;
+        self
+    }
+
+
+    fn enable_undo_collab(&mut self, site: UndoSiteId) -> &mut App {
+        if !self.world().contains_resource::<UndoCollabClock>() {
+            self.add_systems(PreUpdate, (
+                merge_remote_entries_system.in_set(UndoSystemSet::Commit),
+                stamp_local_entries_system.after(UndoSystemSet::Record).before(UndoSystemSet::Evict)
+            ));
+        }
+        self.insert_resource(UndoCollabClock::new(site))
+    }
+
+
+    fn add_undo_transform<E: UndoPayload, O: UndoPayload>(&mut self, transform: impl Fn(&mut E, &O) + Send + Sync + 'static) -> &mut App {
+        self.insert_resource(UndoTransform::<E, O>(Box::new(transform)));
+        self.add_systems(PreUpdate, transform_local_entries_system::<E, O>
+            .after(UndoSystemSet::Record)
+            .before(UndoSystemSet::Evict))
+    }
+
+
+    fn add_undo_telemetry(&mut self, interval: Duration, callback: impl Fn(&UndoUsage) + Send + Sync + 'static) -> &mut App {
+        if !self.world().contains_resource::<UndoTelemetry>() {
+            self.init_resource::<UndoTelemetry>();
+            self.add_systems(PostUpdate, report_undo_telemetry_system);
+        }
+        let mut telemetry = self.world_mut().resource_mut::<UndoTelemetry>();
+        telemetry.interval = interval;
+        telemetry.callbacks.push(Box::new(callback));
+        self
+    }
+
+
+    fn enable_undo_turns(&mut self) -> &mut App {
+        let clock = self.world_mut().get_resource_or_insert_with(UndoTurnClock::default).clone();
+        let mut groupings = init_undo_groupings(self);
+        groupings.default = Some(Box::new(UndoTurnGrouping));
+        groupings.turns = Some(clock);
+        self
+    }
+
+
+    fn on_undo_hot_reload<E: UndoPayload>(&mut self, fixer: impl Fn(&mut E, &World) -> bool + Send + Sync + 'static) -> &mut App {
+        let mut areas = self.world_mut().get_resource_or_insert_with(UndoAreas::default);
+        areas.init::<E>();
+        lock(&areas.areas::<E>().fixers).fixers.push(Box::new(fixer));
+        self
+    }
+
+
+    #[cfg(feature = "audit")]
+    fn enable_undo_audit(&mut self, config: UndoAuditConfig) -> &mut App {
+        let log = UndoAuditLog::new(config);
+        let mut hooks = init_undo_hooks(self);
+        let hooks = &mut *hooks;
+        let push_log = log.clone();
+        hooks.on_push.push(Box::new(move |cx| push_log.append("register", cx)));
+        for (operation, hooks) in [("undo", &mut hooks.on_undo), ("redo", &mut hooks.on_redo)] {
+            let log = log.clone();
+            hooks.push(Box::new(move |cx| {
+                if cx.phase == UndoHookPhase::AfterApply {
+                    log.append(operation, cx);
+                }
+            }));
+        }
+        self
+    }
+
+
+    #[cfg(feature = "thumbnails")]
+    fn set_undo_thumbnail_capture(
+        &mut self,
+        capture: impl Fn(&mut World, usize) -> Option<Handle<Image>> + Send + Sync + 'static,
+    ) -> &mut App {
+        if !self.world().contains_resource::<UndoThumbnailCapture>() {
+            self.add_systems(PreUpdate, capture_thumbnails_system
+                .after(UndoSystemSet::Evict)
+                .before(UndoSystemSet::Resolve)
+                .run_if(resource_changed::<UndoHistory>));
+        }
+        self.insert_resource(UndoThumbnailCapture::new(Box::new(capture)))
     }
 }
 
 
-fn request_undo_event_system<E: Event + Clone>(
-    mut er: EventReader<RequestUndoEvent>,
-    mut ew: EventWriter<E>,
-    mut decrement_writer: EventWriter<DecrementCounterEvent>,
-    mut registered_area: ResMut<UndoRegisteredArea<E>>,
+#[cfg(feature = "reserve")]
+fn register_all_reserved_events_system<E: UndoPayload>(
+    mut er: MessageReader<CommitReservationsEvent>,
+    mut areas: ResMut<UndoAreas>,
+    mut history: ResMut<UndoHistory>,
+    time: Option<Res<Time>>,
+    hooks: Option<Res<UndoHooks>>,
+    recording: UndoRecording<E>,
 ) {
-    for RequestUndoEvent(counter) in er.iter() {
-        while let Some(undo) = registered_area.pop_if_has_latest(counter) {
-            ew.send(undo);
-            decrement_writer.send(DecrementCounterEvent);
+    // Like the direct registrations, the reservations committed while suspended are dropped.
+    let areas = areas.areas_mut::<E>();
+    let mut reserved_area = lock(&areas.reservations);
+    if recording.is_suspended() {
+        reserved_area.0.clear();
+        er.clear();
+        return;
+    }
+    let now = time.map(|time| time.elapsed()).unwrap_or_default();
+    let registered_reserve_event_area = &mut areas.reserved;
+    for CommitReservationsEvent(no) in er.read() {
+        reserved_area.0.sort_by(|e1, e2| e2.reserve_no.partial_cmp(&e1.reserve_no).unwrap());
+
+        while let Some(mut event) = reserved_area.pop_front() {
+            let meta = std::mem::take(&mut event.meta);
+            if let Some(hooks) = hooks.as_ref() {
+                hooks.pushed(*no, std::any::type_name::<E>(), &meta);
+            }
+            history.push::<E>(*no, meta, false, now);
+            registered_reserve_event_area.push(UndoEntry {
+                inner: event,
+                redo: None,
+                no: *no,
+            });
         }
     }
 }
 
 
-fn reserve_event_system<E: Event + Clone>(
-    mut er: EventReader<UndoReserveEvent<E>>,
-    mut ew: EventWriter<E>,
-    mut undo_event_writer: UndoRequester,
+fn push_undo_event_system<E: UndoPayload>(
+    mut er: MessageReader<UndoEvent<E>>,
+    mut areas: ResMut<UndoAreas>,
+    mut history: ResMut<UndoHistory>,
+    time: Option<Res<Time>>,
+    hooks: Option<Res<UndoHooks>>,
+    recording: UndoRecording<E>,
 ) {
-    if er.is_empty() {
+    if recording.is_suspended() {
+        er.clear();
         return;
     }
-
-    for event in er.iter() {
-        ew.send(event.inner.clone());
-        if 1 < event.reserve_no {
-            undo_event_writer.undo();
+    let now = time.map(|time| time.elapsed()).unwrap_or_default();
+    let registered_area = areas.registered_mut::<E>();
+    for e in er.read() {
+        let meta = history.copy_meta(&e.meta);
+        if let Some(hooks) = hooks.as_ref() {
+            hooks.pushed(e.no, std::any::type_name::<E>(), &meta);
+        }
+        registered_area.push_with_meta(UndoEntry {
+            inner: e.inner.duplicate(),
+            redo: e.redo.as_ref().map(UndoPayload::duplicate),
+            no: e.no,
+        }, &meta);
+        history.push::<E>(e.no, meta, e.redo.is_some(), now);
+        #[cfg(feature = "serde")]
+        if let Some(registered_on) = e.registered_on {
+            history.set_registered_on(e.no, registered_on);
         }
     }
 }
 
 
+fn dispatch_undo_event_system<E: UndoPayload>(
+    mut er: MessageReader<DispatchUndoEvent>,
+    mut ew: MessageWriter<E>,
+    mut evicted: MessageWriter<UndoEvicted<E>>,
+    mut dry_run: MessageWriter<DryRun<E>>,
+    mut preview: MessageWriter<Preview<E>>,
+    mut areas: ResMut<UndoAreas>,
+    condition: Option<Res<UndoEventCondition<E>>>,
+) {
+    let UndoTypeAreas {
+        registered: registered_area,
+        redo: redo_area,
+        #[cfg(feature = "reserve")]
+        reserved: registered_reserve_event_area,
+        ..
+    } = areas.areas_mut::<E>();
+    // The events are written at once, so large groups do not pay the overhead of sending each of them.
+    let mut events = Vec::new();
+    let mut evictions = Vec::new();
+    for dispatch in er.read() {
+        match *dispatch {
+            DispatchUndoEvent::Undo(no) => {
+                while let Some(entry) = registered_area.pop_entry(no) {
+                    if entry.redo.is_some() {
+                        events.push(entry.inner.duplicate());
+                        redo_area.push(entry);
+                    } else {
+                        events.push(entry.inner);
+                    }
+                }
+                #[cfg(feature = "reserve")]
+                while let Some(reserved) = registered_reserve_event_area.pop_slot(no) {
+                    events.push(reserved.inner);
+                }
+            }
+            DispatchUndoEvent::Discard(no) => {
+                while registered_area.pop_slot(no).is_some() {}
+                #[cfg(feature = "reserve")]
+                while registered_reserve_event_area.pop_slot(no).is_some() {}
+            }
+            DispatchUndoEvent::Evict(no) => {
+                while let Some(entry) = registered_area.pop_entry(no) {
+                    evictions.push(UndoEvicted { payload: entry.inner, redo: entry.redo });
+                }
+                #[cfg(feature = "reserve")]
+                while let Some(reserved) = registered_reserve_event_area.pop_slot(no) {
+                    evictions.push(UndoEvicted { payload: reserved.inner, redo: None });
+                }
+                while let Some(entry) = redo_area.pop_entry(no) {
+                    evictions.push(UndoEvicted { payload: entry.inner, redo: entry.redo });
+                }
+            }
+            DispatchUndoEvent::Redo(no) => {
+                while let Some(entry) = redo_area.pop_entry(no) {
+                    if let Some(redo) = entry.redo.as_ref() {
+                        events.push(redo.duplicate());
+                    }
+                    registered_area.push(entry);
+                }
+            }
+            DispatchUndoEvent::DiscardRedo(no) => {
+                while redo_area.pop_entry(no).is_some() {}
+            }
+            DispatchUndoEvent::DryRun(no) => {
+                dry_run.write_batch(registered_area.slot_events(no).into_iter().map(DryRun));
+                #[cfg(feature = "reserve")]
+                dry_run.write_batch(registered_reserve_event_area.slot_events(no).into_iter().map(|reserved| DryRun(reserved.inner)));
+            }
+            DispatchUndoEvent::PreviewRedo(no) => {
+                preview.write_batch(redo_area
+                    .0
+                    .iter()
+                    .filter(|entry| entry.no == no)
+                    .filter_map(|entry| entry.redo.as_ref().map(|redo| Preview(redo.duplicate()))));
+            }
+        }
+    }
+    // The entries still move between the areas while the condition does not hold, so they stay in step with the history.
+    if event_condition_holds(condition) {
+        ew.write_batch(events);
+    }
+    evicted.write_batch(evictions);
+}
+
@@ -0,0 +1,316 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::ecs::message::MessageCursor;
+use bevy::ecs::system::BoxedSystem;
+use bevy::prelude::{In, Message, Messages, Mut, Resource, World};
+
+use crate::channel::UndoChannel;
+use crate::counter::UndoCounter;
+use crate::document::UndoDocumentState;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEvent;
+
+/// The error returned by a fallible undo handler, such as "file locked".
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoError(pub String);
+
+
+impl UndoError {
+    #[inline(always)]
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+
+impl Display for UndoError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+
+impl std::error::Error for UndoError {}
+
+
+impl From<&str> for UndoError {
+    #[inline(always)]
+    fn from(message: &str) -> Self {
+        Self::new(message)
+    }
+}
+
+
+impl From<String> for UndoError {
+    #[inline(always)]
+    fn from(message: String) -> Self {
+        Self(message)
+    }
+}
+
+
+/// What to do with the event whose handler returned an error.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum UndoFailurePolicy {
+    /// The event is dropped.
+    #[default]
+    Drop,
+
+    /// The event is registered again as a new entry with default [`UndoMeta`], so it can be undone again later.
+    Repush,
+
+    /// The handler is run again with the event in the next frame, before the newly sent events,
+    /// up to the given count of times, after which the event is dropped.
+    Retry(u32),
+
+    /// The handler is run again with the event in the next frame, after the newly sent events, until it succeeds.
+    RequeueAtBack,
+}
+
+
+/// Sent when a fallible undo handler returned an error.
+#[derive(Message, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoFailed {
+    /// The type name of the undo event.
+    pub type_name: &'static str,
+
+    pub error: UndoError,
+
+    /// The count of times the handler has failed for the event, including this one.
+    pub attempts: u32,
+}
+
+
+/// The total count of failed undo events.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoFailureStats {
+    pub failed: usize,
+}
+
+
+pub(crate) type FallibleHandler<E> = BoxedSystem<In<E>, Result<(), UndoError>>;
+
+
+/// Holds the fallible handler of `E`, its own cursor on the events and the events waiting to be retried.
+#[derive(Resource)]
+pub(crate) struct UndoFallibleHandler<E: UndoPayload> {
+    handler: FallibleHandler<E>,
+    policy: UndoFailurePolicy,
+    reader: MessageCursor<E>,
+    retries: Vec<(E, u32)>,
+    requeued: Vec<(E, u32)>,
+}
+
+
+impl<E: UndoPayload> UndoFallibleHandler<E> {
+    #[inline]
+    pub fn new(handler: FallibleHandler<E>, policy: UndoFailurePolicy) -> Self {
+        Self {
+            handler,
+            policy,
+            reader: MessageCursor::default(),
+            retries: Vec::new(),
+            requeued: Vec::new(),
+        }
+    }
+}
+
+
+pub(crate) fn run_fallible_handler_system<E: UndoPayload>(world: &mut World) {
+    world.resource_scope(|world, mut handler: Mut<UndoFallibleHandler<E>>| {
+        let handler = &mut *handler;
+        let mut events = std::mem::take(&mut handler.retries);
+        events.extend(handler
+            .reader
+            .read(world.resource::<Messages<E>>())
+            .map(|event| (event.duplicate(), 0)));
+        events.append(&mut handler.requeued);
+
+        for (event, attempts) in events {
+            // Only keep a copy of the event if the policy may run it again, otherwise it is moved into the handler.
+            let kept = (handler.policy != UndoFailurePolicy::Drop).then(|| event.duplicate());
+            let Ok(Err(error)) = handler.handler.run_without_applying_deferred(event, world) else {
+                continue;
+            };
+            let attempts = attempts + 1;
+            world.resource_mut::<UndoFailureStats>().failed += 1;
+            world.write_message(UndoFailed {
+                type_name: std::any::type_name::<E>(),
+                error,
+                attempts,
+            });
+            let Some(event) = kept else {
+                continue;
+            };
+            match handler.policy {
+                UndoFailurePolicy::Drop => {}
+                UndoFailurePolicy::Repush => repush(world, event),
+                UndoFailurePolicy::Retry(times) if attempts <= times => handler.retries.push((event, attempts)),
+                UndoFailurePolicy::Retry(_) => {}
+                UndoFailurePolicy::RequeueAtBack => handler.requeued.push((event, attempts)),
+            }
+        }
+        handler.handler.apply_deferred(world);
+    });
+}
+
+
+fn repush<E: UndoPayload>(world: &mut World, event: E) {
+    let mut counter = world.resource_mut::<UndoCounter>();
+    counter.increment();
+    let no = **counter;
+    let channel = world.resource::<UndoDocumentState>().route(UndoChannel::DEFAULT);
+    world.write_message(UndoEvent {
+        inner: event,
+        redo: None,
+        no,
+        meta: UndoMeta::default().with_channel(channel),
+        #[cfg(feature = "serde")]
+        registered_on: None,
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bevy::app::{App, Startup};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{In, Message, Messages, Res, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoError, UndoFailed, UndoFailurePolicy, UndoRequester, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Save(&'static str);
+
+    #[derive(Resource, Default)]
+    struct Saved(Vec<&'static str>);
+
+
+    #[test]
+    fn report_and_repush_failures() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.init_resource::<Saved>();
+        app.add_undo_event::<Save>();
+        app.add_undo_handler_fallible::<Save, _>(UndoFailurePolicy::Repush, |In(save): In<Save>, mut saved: ResMut<Saved>| {
+            if save.0 == "locked" {
+                return Err(UndoError::new("file locked"));
+            }
+            saved.0.push(save.0);
+            Ok(())
+        });
+        app.add_systems(Startup, |mut s: UndoScheduler<Save>| s.register_all([Save("a"), Save("locked")]));
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let failed: Vec<UndoFailed> = app.world_mut().resource_mut::<Messages<UndoFailed>>().drain().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].error.to_string(), "file locked");
+
+        app.update();
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().failures(), 1);
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().len(), 2);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Saved>().0, vec!["a"]);
+    }
+
+
+    #[derive(Resource, Default)]
+    struct Loaded(bool);
+
+
+    fn retry_app(policy: UndoFailurePolicy) -> App {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.init_resource::<Saved>();
+        app.init_resource::<Loaded>();
+        app.add_undo_event::<Save>();
+        app.add_undo_handler_fallible::<Save, _>(policy, |In(save): In<Save>, mut saved: ResMut<Saved>, loaded: Res<Loaded>| {
+            if save.0 == "asset" && !loaded.0 {
+                return Err(UndoError::new("asset still loading"));
+            }
+            saved.0.push(save.0);
+            Ok(())
+        });
+        app.add_systems(Startup, |mut s: UndoScheduler<Save>| s.register_all([Save("a"), Save("asset")]));
+        app.update();
+        app
+    }
+
+
+    #[test]
+    fn retry_limited_times() {
+        let mut app = retry_app(UndoFailurePolicy::Retry(2));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.update();
+        app.world_mut().resource_mut::<Loaded>().0 = true;
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Saved>().0, vec!["asset", "a"]);
+
+        let mut app = retry_app(UndoFailurePolicy::Retry(1));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.update();
+        let attempts: Vec<u32> = app.world_mut().resource_mut::<Messages<UndoFailed>>().drain().map(|failed| failed.attempts).collect();
+        assert_eq!(attempts, vec![1, 2]);
+        app.world_mut().resource_mut::<Loaded>().0 = true;
+        app.update();
+        assert!(app.world().resource::<Saved>().0.is_empty());
+    }
+
+
+    #[test]
+    fn requeue_after_new_events() {
+        let mut app = retry_app(UndoFailurePolicy::RequeueAtBack);
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().resource_mut::<Loaded>().0 = true;
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Saved>().0, vec!["a", "asset"]);
+    }
+
+
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+
+    #[derive(Message)]
+    struct Large;
+
+
+    impl Clone for Large {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, Ordering::SeqCst);
+            Self
+        }
+    }
+
+
+    #[test]
+    fn move_event_into_handler_when_dropped_on_failure() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Large>();
+        app.add_undo_handler_fallible::<Large, _>(UndoFailurePolicy::Drop, |In(_): In<Large>| Ok(()));
+        app.add_systems(Startup, |mut s: UndoScheduler<Large>| s.register(Large));
+        app.update();
+
+        let registered = CLONES.load(Ordering::SeqCst);
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(CLONES.load(Ordering::SeqCst) - registered, 1);
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::Entity;
+
+use crate::channel::UndoChannel;
+use crate::meta::UndoMeta;
+
+/// Built-in policies for [`UndoRequester::gc_policy`](crate::prelude::UndoRequester::gc_policy),
+/// dropping the entries whose targets are gone forever.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum UndoGcPolicy {
+    /// Entries referring to any of the entities via [`UndoMeta::entities`], [`UndoMeta::weak_refs`] or [`UndoMeta::checked_entities`].
+    Entities(Vec<Entity>),
+
+    /// Entries carrying any of the tags, such as the ids of assets deleted from disk.
+    Tags(Vec<String>),
+
+    /// Entries of the channels, such as the documents deleted for good.
+    Channels(Vec<UndoChannel>),
+}
+
+
+impl UndoGcPolicy {
+    /// Returns true if the entry is dropped by the policy.
+    pub fn collects(&self, meta: &UndoMeta) -> bool {
+        match self {
+            Self::Entities(entities) => entities.iter().any(|entity| meta.entities.contains(entity)
+                || meta.weak_refs.iter().any(|weak| weak.0 == *entity)
+                || meta.checked_entities.contains(entity)),
+            Self::Tags(tags) => tags.iter().any(|tag| meta.has_tag(tag)),
+            Self::Channels(channels) => channels.contains(&meta.channel),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoGcPolicy, UndoMeta, UndoRequester, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Paint(i32);
+
+
+    #[test]
+    fn collect_entries_of_gone_targets() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Paint>();
+
+        let gone = app.world_mut().spawn_empty().id();
+        let mut state = SystemState::<UndoScheduler<Paint>>::new(app.world_mut());
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.register(Paint(1));
+        scheduler.register_with_meta(Paint(2), UndoMeta::default().with_weak_ref(gone));
+        scheduler.register_with_meta(Paint(3), UndoMeta::tagged("asset:7"));
+        scheduler.push(Paint(4), Some(Paint(5)), UndoMeta::tagged("asset:7"));
+        state.apply(app.world_mut());
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().resource_mut::<Messages<Paint>>().clear();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        let mut requester = state.get_mut(app.world_mut()).unwrap();
+        requester.gc_policy(UndoGcPolicy::Entities(vec![gone]));
+        requester.gc(|meta| meta.has_tag("asset:7"));
+        state.apply(app.world_mut());
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().len(), 1);
+        assert!(!state.get_mut(app.world_mut()).unwrap().can_redo());
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<Paint> = app.world_mut().resource_mut::<Messages<Paint>>().drain().collect();
+        assert_eq!(undone, vec![Paint(1)]);
+    }
+}
@@ -0,0 +1,107 @@
+use bevy::app::{App, Plugin, PostUpdate};
+use bevy::prelude::{Color, Entity, GlobalTransform, Gizmos, IntoScheduleConfigs, Isometry3d, Query, Res, Resource};
+use bevy::transform::TransformSystems;
+
+use crate::channel::UndoChannel;
+use crate::document::UndoDocumentState;
+use crate::history::UndoHistory;
+
+/// Draws gizmos on the entities the next undo of the channel would affect.
+///
+/// The entities are taken from [`UndoMeta::entities`](crate::prelude::UndoMeta::entities), so entries without them draw nothing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UndoGizmosPlugin {
+    pub channel: UndoChannel,
+    pub color: Color,
+    pub radius: f32,
+}
+
+
+impl Default for UndoGizmosPlugin {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            channel: UndoChannel::DEFAULT,
+            color: Color::srgb(1., 1., 0.),
+            radius: 16.,
+        }
+    }
+}
+
+
+impl Plugin for UndoGizmosPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(UndoGizmosConfig(*self))
+            .add_systems(PostUpdate, draw_undo_targets_system.after(TransformSystems::Propagate));
+    }
+}
+
+
+#[derive(Resource)]
+struct UndoGizmosConfig(UndoGizmosPlugin);
+
+
+fn draw_undo_targets_system(
+    mut gizmos: Gizmos,
+    config: Res<UndoGizmosConfig>,
+    documents: Res<UndoDocumentState>,
+    history: Res<UndoHistory>,
+    transforms: Query<&GlobalTransform>,
+) {
+    for entity in undo_targets(&history, &documents, config.0.channel) {
+        if let Ok(transform) = transforms.get(entity) {
+            gizmos.sphere(Isometry3d::from_translation(transform.translation()), config.0.radius, config.0.color);
+        }
+    }
+}
+
+
+/// Returns the entities the next undo of the channel would affect, the default channel standing for the active document.
+fn undo_targets(history: &UndoHistory, documents: &UndoDocumentState, channel: UndoChannel) -> Vec<Entity> {
+    history
+        .latest_no_in(documents.route(channel))
+        .map(|no| history.entities_of_slot(no))
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Message;
+
+    use crate::document::UndoDocumentState;
+    use crate::gizmos::undo_targets;
+    use crate::history::UndoHistory;
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoDocuments, UndoMeta, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Move;
+
+
+    #[test]
+    fn draw_targets_of_active_document() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        let entity = app.world_mut().spawn_empty().id();
+
+        let mut documents = SystemState::<UndoDocuments>::new(app.world_mut());
+        let mut document = documents.get_mut(app.world_mut()).unwrap();
+        document.create(1);
+        document.set_active(1);
+        documents.apply(app.world_mut());
+        let mut scheduler = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register_with_meta(Move, UndoMeta::for_entity(entity));
+        scheduler.apply(app.world_mut());
+        app.update();
+
+        let history = app.world().resource::<UndoHistory>();
+        let documents = app.world().resource::<UndoDocumentState>();
+        assert_eq!(undo_targets(history, documents, UndoChannel::DEFAULT), vec![entity]);
+        assert_eq!(undo_targets(history, documents, UndoChannel(1)), vec![entity]);
+    }
+}
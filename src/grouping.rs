@@ -0,0 +1,327 @@
+use std::time::Duration;
+
+use bevy::app::App;
+use bevy::prelude::{IntoScheduleConfigs, Mut, PreUpdate, Res, ResMut, Resource, Time};
+use bevy::platform::collections::HashMap;
+
+use crate::channel::UndoChannel;
+use crate::counter::UndoCounter;
+use crate::turn::{UndoTurnClock, UndoTurnGrouping};
+use crate::UndoSystemSet;
+
+/// Decides whether an entry joins the slot of the previous entry of its channel, so both are undone together.
+///
+/// Strategies are selected per channel via [`AppUndoEx::configure_undo_grouping`](crate::prelude::AppUndoEx::configure_undo_grouping).
+/// An entry only joins the previous slot while no other entry has been registered in between.
+pub trait UndoGroupingStrategy: Send + Sync + 'static {
+    fn joins(&self, previous: &UndoGroupingContext, now: &UndoGroupingContext) -> bool;
+}
+
+
+/// When an entry is registered, passed to [`UndoGroupingStrategy::joins`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoGroupingContext {
+    /// The count of frames since the grouping was configured.
+    pub frame: u64,
+
+    /// [`Time::elapsed`] at the start of the frame, `None` without [`TimePlugin`](bevy::time::TimePlugin).
+    pub elapsed: Option<Duration>,
+
+    /// The gesture in progress, see [`UndoScheduler::begin_gesture`](crate::prelude::UndoScheduler::begin_gesture).
+    pub gesture: Option<u64>,
+
+    /// The count of turns ended, see [`UndoTurns::end_turn`](crate::prelude::UndoTurns::end_turn).
+    pub turn: u64,
+}
+
+
+/// Every entry gets its own slot, grouping only via [`UndoScheduler::register_all_grouped`](crate::prelude::UndoScheduler::register_all_grouped) or reservations.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoManualGrouping;
+
+
+impl UndoGroupingStrategy for UndoManualGrouping {
+    #[inline(always)]
+    fn joins(&self, _: &UndoGroupingContext, _: &UndoGroupingContext) -> bool {
+        false
+    }
+}
+
+
+/// The entries registered in the same frame share one slot.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoFrameGrouping;
+
+
+impl UndoGroupingStrategy for UndoFrameGrouping {
+    #[inline(always)]
+    fn joins(&self, previous: &UndoGroupingContext, now: &UndoGroupingContext) -> bool {
+        previous.frame == now.frame
+    }
+}
+
+
+/// The entries registered within the window after the previous one share one slot, 300 ms by default.
+///
+/// This requires [`Time`], so it falls back to [`UndoFrameGrouping`] without [`TimePlugin`](bevy::time::TimePlugin).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoTimeWindowGrouping {
+    pub window: Duration,
+}
+
+
+impl Default for UndoTimeWindowGrouping {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(300),
+        }
+    }
+}
+
+
+impl UndoGroupingStrategy for UndoTimeWindowGrouping {
+    fn joins(&self, previous: &UndoGroupingContext, now: &UndoGroupingContext) -> bool {
+        match (previous.elapsed, now.elapsed) {
+            (Some(previous), Some(now)) => now.saturating_sub(previous) <= self.window,
+            _ => UndoFrameGrouping.joins(previous, now)
+        }
+    }
+}
+
+
+/// The entries registered during the same gesture share one slot,
+/// the gesture being delimited by [`UndoScheduler::begin_gesture`](crate::prelude::UndoScheduler::begin_gesture)
+/// and [`UndoScheduler::end_gesture`](crate::prelude::UndoScheduler::end_gesture), typically on pointer press and release.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoGestureGrouping;
+
+
+impl UndoGroupingStrategy for UndoGestureGrouping {
+    #[inline(always)]
+    fn joins(&self, previous: &UndoGroupingContext, now: &UndoGroupingContext) -> bool {
+        previous.gesture.is_some() && previous.gesture == now.gesture
+    }
+}
+
+
+/// How registrations are bucketed into undoable steps across the app,
+/// selected via [`AppUndoEx::configure_undo_granularity`](crate::prelude::AppUndoEx::configure_undo_granularity).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UndoGranularity {
+    /// Each registration is its own step, see [`UndoManualGrouping`].
+    #[default]
+    Action,
+
+    /// The registrations of a frame are one step, see [`UndoFrameGrouping`].
+    Frame,
+
+    /// The registrations within the window after the previous one are one step, see [`UndoTimeWindowGrouping`].
+    TimeWindow(Duration),
+
+    /// The registrations of a gesture are one step, see [`UndoGestureGrouping`].
+    Gesture,
+
+    /// The registrations of a game turn are one step, see [`UndoTurnGrouping`](crate::prelude::UndoTurnGrouping).
+    Turn,
+}
+
+
+impl UndoGranularity {
+    /// Returns the strategy implementing the granularity.
+    pub(crate) fn strategy(self) -> Box<dyn UndoGroupingStrategy> {
+        match self {
+            Self::Action => Box::new(UndoManualGrouping),
+            Self::Frame => Box::new(UndoFrameGrouping),
+            Self::TimeWindow(window) => Box::new(UndoTimeWindowGrouping { window }),
+            Self::Gesture => Box::new(UndoGestureGrouping),
+            Self::Turn => Box::new(UndoTurnGrouping),
+        }
+    }
+}
+
+
+#[derive(Resource, Default)]
+pub(crate) struct UndoGroupings {
+    /// The strategy of the channels without their own one.
+    pub default: Option<Box<dyn UndoGroupingStrategy>>,
+    pub channels: HashMap<UndoChannel, Box<dyn UndoGroupingStrategy>>,
+    pub turns: Option<UndoTurnClock>,
+    context: UndoGroupingContext,
+    last_gesture: u64,
+
+    /// The slot and the context of the previous entry per channel.
+    previous: HashMap<UndoChannel, (usize, UndoGroupingContext)>,
+}
+
+
+impl UndoGroupings {
+    /// Returns the slot of the entry registered now, allocating a new one unless it joins the previous slot.
+    pub fn next_slot(&mut self, channel: UndoChannel, counter: &mut UndoCounter) -> usize {
+        if let Some(turns) = self.turns.as_ref() {
+            self.context.turn = turns.turn();
+        }
+        let strategy = self.channels.get(&channel).or(self.default.as_ref());
+        let joined = strategy.and_then(|strategy| {
+            let (no, previous) = self.previous.get(&channel)?;
+            (*no == **counter && strategy.joins(previous, &self.context)).then_some(*no)
+        });
+        let no = joined.unwrap_or_else(|| {
+            counter.increment();
+            **counter
+        });
+        self.previous.insert(channel, (no, self.context));
+        no
+    }
+
+
+    #[inline]
+    pub fn begin_gesture(&mut self) {
+        self.last_gesture += 1;
+        self.context.gesture = Some(self.last_gesture);
+    }
+
+
+    #[inline(always)]
+    pub fn end_gesture(&mut self) {
+        self.context.gesture = None;
+    }
+}
+
+
+/// Returns the groupings, inserting them with the system advancing their frame if not configured yet.
+pub(crate) fn init_undo_groupings(app: &mut App) -> Mut<'_, UndoGroupings> {
+    if !app.world().contains_resource::<UndoGroupings>() {
+        app.init_resource::<UndoGroupings>();
+        app.add_systems(PreUpdate, advance_grouping_frame_system.in_set(UndoSystemSet::Commit));
+    }
+    app.world_mut().resource_mut::<UndoGroupings>()
+}
+
+
+fn advance_grouping_frame_system(
+    mut groupings: ResMut<UndoGroupings>,
+    time: Option<Res<Time>>,
+) {
+    groupings.context.frame += 1;
+    groupings.context.elapsed = time.map(|time| time.elapsed());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::{Local, Message, Time, Update};
+
+    use crate::prelude::{AppUndoEx, UndoGestureGrouping, UndoGranularity, UndoScheduler, UndoTimeWindowGrouping};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[test]
+    fn group_entries_of_same_frame() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.configure_undo_frame_grouping(true);
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Move>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.register(Move(1));
+                    scheduler.register(Move(2));
+                } else if *frame == 2 {
+                    scheduler.register_all([Move(3), Move(4)]);
+                }
+            });
+        });
+        harness.frames(2);
+        harness.undo();
+        harness.expect([Move(4), Move(3)]);
+        harness.undo();
+        harness.expect([Move(2), Move(1)]);
+    }
+
+
+    #[test]
+    fn group_entries_within_time_window() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.init_resource::<Time>();
+            app.configure_undo_grouping(0, UndoTimeWindowGrouping::default());
+        });
+        harness.frames(1);
+        harness.register(Move(1));
+        harness.app().world_mut().resource_mut::<Time>().advance_to(Duration::from_millis(200));
+        harness.frames(1);
+        harness.register(Move(2));
+        harness.app().world_mut().resource_mut::<Time>().advance_to(Duration::from_millis(800));
+        harness.frames(1);
+        harness.register(Move(3));
+
+        harness.undo();
+        harness.expect([Move(3)]);
+        harness.undo();
+        harness.expect([Move(2), Move(1)]);
+    }
+
+
+    #[test]
+    fn group_entries_of_gesture() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.configure_undo_grouping(0, UndoGestureGrouping);
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Move>, mut frame: Local<i32>| {
+                *frame += 1;
+                match *frame {
+                    1 => scheduler.begin_gesture(),
+                    2 | 3 => scheduler.register(Move(*frame)),
+                    4 => {
+                        scheduler.end_gesture();
+                        scheduler.register(Move(*frame));
+                    }
+                    _ => {}
+                }
+            });
+        });
+        harness.frames(4);
+        harness.undo();
+        harness.expect([Move(4)]);
+        harness.undo();
+        harness.expect([Move(3), Move(2)]);
+    }
+
+
+    fn register_twice_per_frame(granularity: UndoGranularity) -> UndoTestHarness<Move> {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.configure_undo_granularity(granularity);
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Move>, mut frame: Local<i32>| {
+                *frame += 1;
+                if *frame <= 2 {
+                    scheduler.register(Move(*frame * 2 - 1));
+                    scheduler.register(Move(*frame * 2));
+                }
+            });
+        });
+        harness.frames(2);
+        harness
+    }
+
+
+    #[test]
+    fn switch_granularity_without_touching_registrations() {
+        let mut harness = register_twice_per_frame(UndoGranularity::Action);
+        harness.undo();
+        harness.expect([Move(4)]);
+        harness.undo();
+        harness.expect([Move(3)]);
+
+        let mut harness = register_twice_per_frame(UndoGranularity::Frame);
+        harness.undo();
+        harness.expect([Move(4), Move(3)]);
+        harness.undo();
+        harness.expect([Move(2), Move(1)]);
+    }
+}
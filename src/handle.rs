@@ -0,0 +1,173 @@
+use std::marker::PhantomData;
+
+use bevy::asset::{AssetServer, ReflectHandle, UntypedHandle};
+use bevy::prelude::{AppTypeRegistry, MessageReader, Reflect, Res, ResMut, Resource};
+use bevy::reflect::{PartialReflect, ReflectRef, TypeRegistry};
+use bevy::platform::collections::HashMap;
+
+use crate::{DispatchUndoEvent, UndoAreas};
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEvent;
+
+/// Strong handles found in the stored payloads of `E`, keyed by slot number.
+#[derive(Resource)]
+pub(crate) struct UndoRetainedHandles<E: UndoPayload> {
+    slots: HashMap<usize, Vec<UntypedHandle>>,
+    released: Vec<UntypedHandle>,
+    _marker: PhantomData<E>,
+}
+
+
+impl<E: UndoPayload> Default for UndoRetainedHandles<E> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            slots: HashMap::default(),
+            released: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+
+pub(crate) fn retain_handles_system<E: UndoPayload + Reflect>(
+    mut er: MessageReader<UndoEvent<E>>,
+    mut retained: ResMut<UndoRetainedHandles<E>>,
+    asset_server: Res<AssetServer>,
+    type_registry: Res<AppTypeRegistry>,
+) {
+    let type_registry = type_registry.read();
+    for event in er.read() {
+        let mut handles = Vec::new();
+        collect_handles(&event.inner, &type_registry, &asset_server, &mut handles);
+        if let Some(redo) = event.redo.as_ref() {
+            collect_handles(redo, &type_registry, &asset_server, &mut handles);
+        }
+        if !handles.is_empty() {
+            retained.slots.entry(event.no).or_default().extend(handles);
+        }
+    }
+}
+
+
+/// Releases the handles of the slots which no longer exist in the registered area nor the redo area.
+///
+/// The handles are dropped one frame later, so that the systems reading the undone events can take them over.
+pub(crate) fn release_handles_system<E: UndoPayload + Reflect>(
+    mut er: MessageReader<DispatchUndoEvent>,
+    mut retained: ResMut<UndoRetainedHandles<E>>,
+    areas: Res<UndoAreas>,
+) {
+    retained.released.clear();
+    if er.read().count() == 0 {
+        return;
+    }
+
+    let areas = areas.areas::<E>();
+    let retained = &mut *retained;
+    let released = &mut retained.released;
+    retained.slots.retain(|no, handles| {
+        let alive = areas.registered.contains_slot(*no) || areas.redo.0.iter().any(|entry| entry.no == *no);
+        if !alive {
+            released.append(handles);
+        }
+        alive
+    });
+}
+
+
+fn collect_handles(
+    value: &dyn PartialReflect,
+    type_registry: &TypeRegistry,
+    asset_server: &AssetServer,
+    handles: &mut Vec<UntypedHandle>,
+) {
+    if let Some(value) = value.try_as_reflect() {
+        if let Some(reflect_handle) = type_registry.get_type_data::<ReflectHandle>(value.as_any().type_id()) {
+            if let Some(handle) = reflect_handle.downcast_handle_untyped(value.as_any()) {
+                handles.push(asset_server.get_id_handle_untyped(handle.id()).unwrap_or(handle));
+            }
+            return;
+        }
+    }
+
+    match value.reflect_ref() {
+        ReflectRef::Struct(s) => s
+            .iter_fields()
+            .for_each(|(_, field)| collect_handles(field, type_registry, asset_server, handles)),
+        ReflectRef::TupleStruct(s) => s
+            .iter_fields()
+            .for_each(|field| collect_handles(field, type_registry, asset_server, handles)),
+        ReflectRef::Tuple(t) => t
+            .iter_fields()
+            .for_each(|field| collect_handles(field, type_registry, asset_server, handles)),
+        ReflectRef::List(l) => l
+            .iter()
+            .for_each(|item| collect_handles(item, type_registry, asset_server, handles)),
+        ReflectRef::Array(a) => a
+            .iter()
+            .for_each(|item| collect_handles(item, type_registry, asset_server, handles)),
+        ReflectRef::Map(m) => m
+            .iter()
+            .for_each(|(key, value)| {
+                collect_handles(key, type_registry, asset_server, handles);
+                collect_handles(value, type_registry, asset_server, handles);
+            }),
+        ReflectRef::Enum(e) => e
+            .iter_fields()
+            .for_each(|field| collect_handles(field.value(), type_registry, asset_server, handles)),
+        ReflectRef::Set(s) => s
+            .iter()
+            .for_each(|item| collect_handles(item, type_registry, asset_server, handles)),
+        _ => {}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::asset::{Asset, AssetApp, AssetPlugin, Assets, Handle};
+    use bevy::prelude::{Message, Reflect, TaskPoolPlugin};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Asset, Reflect, Default)]
+    struct Blob;
+
+    #[derive(Message, Clone, Reflect)]
+    struct RestoreBlob {
+        handles: Vec<Handle<Blob>>,
+    }
+
+
+    #[test]
+    fn keep_assets_while_entry_lives() {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default(), UndoPlugin));
+        app.init_asset::<Blob>();
+        app.register_asset_reflect::<Blob>();
+        app.add_undo_event::<RestoreBlob>();
+        app.retain_undo_handles::<RestoreBlob>();
+
+        let handle = app.world_mut().resource_mut::<Assets<Blob>>().add(Blob);
+        let mut registered = Some(handle.clone());
+        app.add_systems(Startup, move |mut s: UndoScheduler<RestoreBlob>| {
+            s.register(RestoreBlob { handles: registered.take().into_iter().collect() });
+        });
+        app.update();
+        drop(handle);
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<Assets<Blob>>().len(), 1);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<Assets<Blob>>().len(), 0);
+    }
+}
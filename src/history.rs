@@ -0,0 +1,407 @@
+use std::any::TypeId;
+use std::time::Duration;
+#[cfg(feature = "serde")]
+use std::time::SystemTime;
+
+#[cfg(feature = "thumbnails")]
+use bevy::prelude::{Handle, Image};
+use bevy::prelude::{Entity, Resource};
+
+use crate::channel::UndoChannel;
+use crate::collab::UndoStamp;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::pool::{UndoMetaPool, UndoPoolStats};
+
+#[derive(Debug, Clone)]
+pub(crate) struct UndoHistoryEntry {
+    pub no: usize,
+    pub meta: UndoMeta,
+    pub redoable: bool,
+
+    /// The elapsed time of the app when registered.
+    pub registered_at: Duration,
+
+    /// The wall-clock time when registered, so the histories of different sessions can be interleaved.
+    #[cfg(feature = "serde")]
+    pub registered_on: SystemTime,
+
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+
+    /// Captured via [`AppUndoEx::set_undo_thumbnail_capture`](crate::prelude::AppUndoEx::set_undo_thumbnail_capture).
+    #[cfg(feature = "thumbnails")]
+    pub thumbnail: Option<Handle<Image>>,
+}
+
+
+/// Type-erased index of all registered entries.
+///
+/// The entries are kept sorted by slot number, since new slots always take the greatest number and redone slots are inserted in place.
+///
+/// The payloads themselves live in the registered area of their type in `UndoAreas`,
+/// this only keeps which slot numbers exist and their metadata so that requests can be resolved
+/// without knowing the event types.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoHistory {
+    entries: Vec<UndoHistoryEntry>,
+    redo: Vec<UndoHistoryEntry>,
+    discarded_redo: Vec<usize>,
+    registered_slots: usize,
+    last_pushed_no: Option<usize>,
+    pool: UndoMetaPool,
+}
+
+
+impl UndoHistory {
+    /// Pushes a newly registered entry, which invalidates the redo history of its channel.
+    ///
+    /// Committed reservations may be recorded before entries registered with a smaller slot number in the same frame,
+    /// so the entry is inserted after the ones with a slot number up to its own.
+    #[inline]
+    pub fn push<E: UndoPayload>(&mut self, no: usize, meta: UndoMeta, redoable: bool, registered_at: Duration) {
+        self.clear_redo_in(meta.channel);
+        if self.last_pushed_no != Some(no) {
+            self.registered_slots += 1;
+            self.last_pushed_no = Some(no);
+        }
+        let index = self.entries.partition_point(|entry| entry.no <= no);
+        self.entries.insert(index, UndoHistoryEntry {
+            no,
+            meta,
+            redoable,
+            registered_at,
+            #[cfg(feature = "serde")]
+            registered_on: SystemTime::now(),
+            type_id: TypeId::of::<E>(),
+            type_name: std::any::type_name::<E>(),
+            #[cfg(feature = "thumbnails")]
+            thumbnail: None,
+        });
+    }
+
+
+    /// Copies the metadata of a newly registered entry into buffers recycled from dropped entries.
+    #[inline(always)]
+    pub fn copy_meta(&mut self, meta: &UndoMeta) -> UndoMeta {
+        self.pool.copy(meta)
+    }
+
+
+    #[inline(always)]
+    pub fn pool_stats(&self) -> UndoPoolStats {
+        self.pool.stats
+    }
+
+
+    /// Returns the entries ordered from the oldest slot.
+    #[inline(always)]
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &UndoHistoryEntry> {
+        self.entries.iter()
+    }
+
+
+    /// Returns the entries waiting for redo, ordered from the most recently undone.
+    #[inline(always)]
+    pub fn redo_entries(&self) -> impl DoubleEndedIterator<Item = &UndoHistoryEntry> {
+        self.redo.iter().rev()
+    }
+
+
+    /// Returns the total count of slots registered so far, entries registered together being counted once.
+    #[inline(always)]
+    pub fn registered_slots(&self) -> usize {
+        self.registered_slots
+    }
+
+
+    /// Takes the slot numbers dropped from the redo history since the last call.
+    #[inline]
+    pub fn take_discarded_redo(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.discarded_redo)
+    }
+
+
+    /// Sets the thumbnail of the entries of the slot which can be undone.
+    #[cfg(feature = "thumbnails")]
+    pub fn set_thumbnail(&mut self, no: usize, thumbnail: Handle<Image>) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.no == no) {
+            entry.thumbnail = Some(thumbnail.clone());
+        }
+    }
+
+
+    /// Sets the wall-clock time of the entry of the slot pushed last, such as one replayed from an export.
+    #[cfg(feature = "serde")]
+    pub fn set_registered_on(&mut self, no: usize, registered_on: SystemTime) {
+        if let Some(entry) = self.entries.iter_mut().rev().find(|entry| entry.no == no) {
+            entry.registered_on = registered_on;
+        }
+    }
+
+
+    /// Removes the slot from the history, and moves it to the redo history if all of its entries are redoable.
+    ///
+    /// Otherwise the redo history is cleared since it can no longer be replayed in order.
+    pub fn undo_slot(&mut self, no: usize) {
+        let (slot, entries) = self.entries.drain(..).partition(|entry| entry.no == no);
+        self.entries = entries;
+        let slot: Vec<UndoHistoryEntry> = slot;
+        if slot.iter().all(|entry| entry.redoable) {
+            self.redo.extend(slot);
+        } else {
+            for entry in slot {
+                self.clear_redo_in(entry.meta.channel);
+                self.pool.recycle(entry.meta);
+            }
+        }
+    }
+
+
+    /// Moves the slot from the redo history back to the history.
+    pub fn redo_slot(&mut self, no: usize) {
+        let (slot, redo) = self.redo.drain(..).partition(|entry| entry.no == no);
+        self.redo = redo;
+        let slot: Vec<UndoHistoryEntry> = slot;
+        let at = self.entries.partition_point(|entry| entry.no < no);
+        self.entries.splice(at..at, slot);
+    }
+
+
+    /// Returns the slot and the slots linked with it via [`UndoMeta::link`], following the links of the linked slots too.
+    ///
+    /// The slots are searched in the redo history if `redo` is true, and ordered in which they should be processed:
+    /// from the latest for undo, and from the oldest for redo.
+    pub fn linked_slots(&self, no: usize, redo: bool) -> Vec<usize> {
+        let entries = if redo { &self.redo } else { &self.entries };
+        let mut slots = vec![no];
+        let mut links = Vec::new();
+        let mut i = 0;
+        while let Some(&no) = slots.get(i) {
+            i += 1;
+            for link in entries.iter().filter(|entry| entry.no == no).filter_map(|entry| entry.meta.link) {
+                if links.contains(&link) {
+                    continue;
+                }
+                links.push(link);
+                for entry in entries.iter().filter(|entry| entry.meta.link == Some(link)) {
+                    if !slots.contains(&entry.no) {
+                        slots.push(entry.no);
+                    }
+                }
+            }
+        }
+        if redo {
+            slots.sort_unstable();
+        } else {
+            slots.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        slots
+    }
+
+
+    /// Returns the most recently undone slot of the channel which can be redone.
+    #[inline]
+    pub fn latest_redo_in(&self, channel: UndoChannel) -> Option<usize> {
+        self.redo
+            .iter()
+            .rev()
+            .find(|entry| entry.meta.channel == channel)
+            .map(|entry| entry.no)
+    }
+
+
+    fn clear_redo_in(&mut self, channel: UndoChannel) {
+        for no in remove_where(&mut self.redo, &mut self.pool, |entry| entry.meta.channel == channel) {
+            if !self.discarded_redo.contains(&no) {
+                self.discarded_redo.push(no);
+            }
+        }
+    }
+
+
+    /// Returns the greatest slot number in use, including the slots waiting for redo.
+    #[inline]
+    pub fn max_no(&self) -> Option<usize> {
+        self.entries
+            .iter()
+            .chain(self.redo.iter())
+            .map(|entry| entry.no)
+            .max()
+    }
+
+
+    #[inline]
+    pub fn latest_no_in(&self, channel: UndoChannel) -> Option<usize> {
+        self.latest_matching(|meta| meta.channel == channel)
+    }
+
+
+    #[inline]
+    pub fn latest_matching(&self, predicate: impl Fn(&UndoMeta) -> bool) -> Option<usize> {
+        self.entries
+            .iter()
+            .filter(|entry| predicate(&entry.meta))
+            .map(|entry| entry.no)
+            .max()
+    }
+
+
+    #[inline]
+    pub fn slot_entries(&self, no: usize) -> impl Iterator<Item = &UndoHistoryEntry> {
+        self.entries.iter().filter(move |entry| entry.no == no)
+    }
+
+
+    /// Returns true if an entry of the slot is [`UndoMeta::sticky`].
+    #[inline]
+    pub fn is_sticky(&self, no: usize) -> bool {
+        self.slot_entries(no).any(|entry| entry.meta.sticky)
+    }
+
+
+    /// Returns the count of entries belonging to the slot.
+    #[inline]
+    pub fn slot_len(&self, no: usize) -> usize {
+        self.entries.iter().filter(|entry| entry.no == no).count()
+    }
+
+
+    /// Returns the count of entries belonging to the slot waiting for redo.
+    #[inline]
+    pub fn redo_slot_len(&self, no: usize) -> usize {
+        self.redo.iter().filter(|entry| entry.no == no).count()
+    }
+
+
+    /// Returns the distinct entities affected by the entries of the slot.
+    pub fn entities_of_slot(&self, no: usize) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = self.entries
+            .iter()
+            .filter(|entry| entry.no == no)
+            .flat_map(|entry| entry.meta.entities.iter().copied())
+            .collect();
+        entities.sort_unstable();
+        entities.dedup();
+        entities
+    }
+
+
+    /// Returns the distinct slot numbers which contain at least one matching entry.
+    pub fn slots_matching(&self, predicate: impl Fn(&UndoMeta) -> bool) -> Vec<usize> {
+        let mut slots: Vec<usize> = self.entries
+            .iter()
+            .filter(|entry| predicate(&entry.meta))
+            .map(|entry| entry.no)
+            .collect();
+        slots.sort_unstable();
+        slots.dedup();
+        slots
+    }
+
+
+    /// Returns the distinct slot numbers which contain at least one entry whose payload type is named so.
+    pub fn slots_of_type(&self, type_id: TypeId) -> Vec<usize> {
+        let mut slots: Vec<usize> = self.entries
+            .iter()
+            .filter(|entry| entry.type_id == type_id)
+            .map(|entry| entry.no)
+            .collect();
+        slots.dedup();
+        slots
+    }
+
+
+    #[inline]
+    pub fn count_matching(&self, predicate: impl Fn(&UndoMeta) -> bool) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| predicate(&entry.meta))
+            .count()
+    }
+
+
+    /// Removes all entries of the channel including the ones waiting for redo, and returns their distinct slot numbers.
+    pub fn remove_channel(&mut self, channel: UndoChannel) -> Vec<usize> {
+        let mut slots = remove_where(&mut self.entries, &mut self.pool, |entry| entry.meta.channel == channel);
+        for no in remove_where(&mut self.redo, &mut self.pool, |entry| entry.meta.channel == channel) {
+            if !slots.contains(&no) {
+                slots.push(no);
+            }
+        }
+        self.entries.shrink_to_fit();
+        self.redo.shrink_to_fit();
+        slots
+    }
+
+
+    /// Removes the entries waiting for redo which match, and returns their distinct slot numbers.
+    ///
+    /// Sticky entries are kept.
+    pub fn remove_redo_matching(&mut self, predicate: impl Fn(&UndoMeta) -> bool) -> Vec<usize> {
+        remove_where(&mut self.redo, &mut self.pool, |entry| !entry.meta.sticky && predicate(&entry.meta))
+    }
+
+
+    /// Removes all entries which belong to the slot.
+    #[inline]
+    pub fn remove_slot(&mut self, no: usize) {
+        remove_where(&mut self.entries, &mut self.pool, |entry| entry.no == no);
+    }
+
+
+    /// Removes all entries waiting for redo which belong to the slot.
+    #[inline]
+    pub fn remove_redo_slot(&mut self, no: usize) {
+        remove_where(&mut self.redo, &mut self.pool, |entry| entry.no == no);
+    }
+
+
+    /// Makes the most recent entry refer to a payload of the type, which is no longer redoable.
+    ///
+    /// Returns its slot and the type it referred to before.
+    pub fn replace_latest(&mut self, type_name: &'static str) -> Option<(usize, &'static str)> {
+        let entry = self.entries.last_mut()?;
+        let previous = std::mem::replace(&mut entry.type_name, type_name);
+        entry.redoable = false;
+        Some((entry.no, previous))
+    }
+
+
+    /// Removes all entries, returning the slots of the history and of the redo history.
+    #[cfg(feature = "serde")]
+    pub fn drain_slots(&mut self) -> (Vec<usize>, Vec<usize>) {
+        let registered = remove_where(&mut self.entries, &mut self.pool, |_| true);
+        let redo = remove_where(&mut self.redo, &mut self.pool, |_| true);
+        (registered, redo)
+    }
+
+
+    /// Sets the stamp of the entries without one, the function taking the slot number.
+    pub fn stamp_unstamped(&mut self, mut stamp: impl FnMut(usize) -> UndoStamp) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.meta.stamp.is_none()) {
+            entry.meta.stamp = Some(stamp(entry.no));
+        }
+    }
+}
+
+
+/// Removes the matching entries, returning their metadata to the pool, and returns their distinct slot numbers.
+fn remove_where(
+    entries: &mut Vec<UndoHistoryEntry>,
+    pool: &mut UndoMetaPool,
+    mut predicate: impl FnMut(&UndoHistoryEntry) -> bool,
+) -> Vec<usize> {
+    let mut slots = Vec::new();
+    entries.retain_mut(|entry| {
+        if !predicate(entry) {
+            return true;
+        }
+        if !slots.contains(&entry.no) {
+            slots.push(entry.no);
+        }
+        pool.recycle(std::mem::take(&mut entry.meta));
+        false
+    });
+    slots
+}
@@ -0,0 +1,451 @@
+use bevy::app::App;
+use bevy::ecs::entity::Entities;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Message, MessageWriter, Mut, PostUpdate, Res, ResMut, Resource};
+
+use crate::DispatchUndoEvent;
+use crate::collab::UndoSiteId;
+use crate::confirm::UndoConfirmation;
+use crate::history::{UndoHistory, UndoHistoryEntry};
+use crate::meta::UndoMeta;
+use crate::policy::{UndoPolicy, UndoRefused};
+use crate::request::RequestUndoEvent;
+use crate::selective::{UndoSelectiveRefused, UndoSelectiveReverted};
+use crate::telemetry::UndoTelemetry;
+use crate::weak::{reused_entity, UndoEntrySkipped, UndoWeakRefs};
+
+/// A callback installed via [`AppUndoEx::add_undo_hook_on_push`](crate::prelude::AppUndoEx::add_undo_hook_on_push)
+/// and its siblings.
+pub type UndoHook = Box<dyn Fn(&UndoHookContext) + Send + Sync + 'static>;
+
+
+/// A callback installed via [`AppUndoEx::add_undo_veto`](crate::prelude::AppUndoEx::add_undo_veto),
+/// deciding whether an entry may be undone.
+pub type UndoVeto = Box<dyn Fn(&UndoHookContext) -> UndoVerdict + Send + Sync + 'static>;
+
+
+/// A callback installed via [`AppUndoEx::add_undo_authorizer`](crate::prelude::AppUndoEx::add_undo_authorizer),
+/// deciding whether the requester may undo or redo an entry.
+pub type UndoAuthorizer = Box<dyn Fn(&UndoAuthorization) -> UndoVerdict + Send + Sync + 'static>;
+
+
+/// Returned by an [`UndoVeto`] or an [`UndoAuthorizer`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum UndoVerdict {
+    Allow,
+
+    /// Leaves the entry in the history and sends [`UndoDenied`] with the reason.
+    Deny(String),
+}
+
+
+/// Sent when a request is dropped since an [`UndoVeto`] or an [`UndoAuthorizer`] denied one of its entries,
+/// such as an entry affecting a locked layer.
+#[derive(Message, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoDenied {
+    /// The slot of the denied entry.
+    pub no: usize,
+    pub type_name: &'static str,
+    pub reason: String,
+}
+
+
+/// When a hook is called.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UndoHookPhase {
+    /// The entry has been pushed to the history.
+    Pushed,
+
+    /// The entry is about to be undone or redone, its handlers have not run yet.
+    BeforeDispatch,
+
+    /// The handlers of the entry have run in [`Update`](bevy::prelude::Update).
+    AfterApply,
+}
+
+
+/// Passed to the hooks, describing the entry they are called for.
+#[derive(Debug, Copy, Clone)]
+pub struct UndoHookContext<'a> {
+    pub phase: UndoHookPhase,
+    pub no: usize,
+    pub type_name: &'static str,
+    pub meta: &'a UndoMeta,
+}
+
+
+/// Passed to the authorizers, describing a request and an entry it undoes or redoes.
+#[derive(Debug, Copy, Clone)]
+pub struct UndoAuthorization<'a> {
+    /// The client on whose behalf the request is made, `None` for local requests.
+    ///
+    /// See [`UndoRequester::undo_channel_as`](crate::prelude::UndoRequester::undo_channel_as).
+    pub requester: Option<UndoSiteId>,
+    pub redo: bool,
+
+    /// The entry, whose [`UndoMeta::stamp`] tells the client which registered it in collaborative sessions.
+    pub entry: UndoHookContext<'a>,
+}
+
+
+#[derive(Resource, Default)]
+pub(crate) struct UndoHooks {
+    pub on_push: Vec<UndoHook>,
+    pub on_undo: Vec<UndoHook>,
+    pub on_redo: Vec<UndoHook>,
+    pub vetoes: Vec<UndoVeto>,
+    pub authorizers: Vec<UndoAuthorizer>,
+
+    /// The entries dispatched this frame and whether they are redone, awaiting the hooks run after they are applied.
+    dispatched: Vec<(bool, UndoHistoryEntry)>,
+}
+
+
+impl UndoHooks {
+    pub fn pushed(&self, no: usize, type_name: &'static str, meta: &UndoMeta) {
+        let cx = UndoHookContext {
+            phase: UndoHookPhase::Pushed,
+            no,
+            type_name,
+            meta,
+        };
+        for hook in &self.on_push {
+            hook(&cx);
+        }
+    }
+
+
+    /// Returns the first denial of the entries by the vetoes.
+    fn veto<'a>(&self, mut entries: impl Iterator<Item = &'a UndoHistoryEntry>) -> Option<UndoDenied> {
+        entries.find_map(|entry| {
+            let cx = UndoHookContext {
+                phase: UndoHookPhase::BeforeDispatch,
+                no: entry.no,
+                type_name: entry.type_name,
+                meta: &entry.meta,
+            };
+            self.vetoes.iter().find_map(|veto| match veto(&cx) {
+                UndoVerdict::Allow => None,
+                UndoVerdict::Deny(reason) => Some(UndoDenied {
+                    no: entry.no,
+                    type_name: entry.type_name,
+                    reason,
+                }),
+            })
+        })
+    }
+
+
+    /// Returns the first denial of the entries requested by the requester.
+    fn authorize<'a>(
+        &self,
+        requester: Option<UndoSiteId>,
+        redo: bool,
+        mut entries: impl Iterator<Item = &'a UndoHistoryEntry>,
+    ) -> Option<UndoDenied> {
+        entries.find_map(|entry| {
+            let authorization = UndoAuthorization {
+                requester,
+                redo,
+                entry: UndoHookContext {
+                    phase: UndoHookPhase::BeforeDispatch,
+                    no: entry.no,
+                    type_name: entry.type_name,
+                    meta: &entry.meta,
+                },
+            };
+            self.authorizers.iter().find_map(|authorizer| match authorizer(&authorization) {
+                UndoVerdict::Allow => None,
+                UndoVerdict::Deny(reason) => Some(UndoDenied {
+                    no: entry.no,
+                    type_name: entry.type_name,
+                    reason,
+                }),
+            })
+        })
+    }
+
+
+    fn dispatching<'a>(&mut self, redo: bool, entries: impl Iterator<Item = &'a UndoHistoryEntry>) {
+        for entry in entries {
+            self.call(redo, UndoHookPhase::BeforeDispatch, entry);
+            self.dispatched.push((redo, entry.clone()));
+        }
+    }
+
+
+    fn call(&self, redo: bool, phase: UndoHookPhase, entry: &UndoHistoryEntry) {
+        let hooks = if redo { &self.on_redo } else { &self.on_undo };
+        let cx = UndoHookContext {
+            phase,
+            no: entry.no,
+            type_name: entry.type_name,
+            meta: &entry.meta,
+        };
+        for hook in hooks {
+            hook(&cx);
+        }
+    }
+}
+
+
+/// Sends [`DispatchUndoEvent`], calling the hooks of the entries undone or redone,
+/// records the usage reported via telemetry,
+/// and holds back the undos denied by the vetoes, awaiting confirmation, refused by the [`UndoPolicy`]
+/// or invalidated by [`WeakEntityRef`](crate::prelude::WeakEntityRef).
+#[derive(SystemParam)]
+pub(crate) struct UndoDispatcher<'w> {
+    ew: MessageWriter<'w, DispatchUndoEvent>,
+    denied: MessageWriter<'w, UndoDenied>,
+    hooks: Option<ResMut<'w, UndoHooks>>,
+    confirmation: UndoConfirmation<'w>,
+    weak_refs: Res<'w, UndoWeakRefs>,
+    entities: &'w Entities,
+    skipped: MessageWriter<'w, UndoEntrySkipped>,
+    telemetry: Option<ResMut<'w, UndoTelemetry>>,
+    policy: ResMut<'w, UndoPolicy>,
+    refused: MessageWriter<'w, UndoRefused>,
+    selective_reverted: MessageWriter<'w, UndoSelectiveReverted>,
+    selective_refused: MessageWriter<'w, UndoSelectiveRefused>,
+}
+
+
+impl<'w> UndoDispatcher<'w> {
+    #[inline(always)]
+    pub fn send(&mut self, dispatch: DispatchUndoEvent) {
+        self.ew.write(dispatch);
+    }
+
+
+    /// Returns true after sending [`UndoDenied`] if a veto denies undoing an entry of the slots.
+    pub fn denies(&mut self, history: &UndoHistory, slots: &[usize]) -> bool {
+        if let Some(no) = self.weak_refs.invalid_among(slots) {
+            self.denied.write(UndoDenied {
+                no,
+                type_name: history.slot_entries(no).next().map(|entry| entry.type_name).unwrap_or_default(),
+                reason: "an entity the entry depends on has been despawned".to_string(),
+            });
+            return true;
+        }
+        let Some(hooks) = self.hooks.as_ref() else {
+            return false;
+        };
+        match hooks.veto(history.entries().rev().filter(|entry| slots.contains(&entry.no))) {
+            Some(denied) => {
+                self.denied.write(denied);
+                true
+            }
+            None => false
+        }
+    }
+
+
+    /// Returns true after sending [`UndoDenied`] if an authorizer denies the requester undoing or redoing an entry of the slots.
+    pub fn unauthorized(&mut self, history: &UndoHistory, requester: Option<UndoSiteId>, redo: bool, slots: &[usize]) -> bool {
+        let Some(hooks) = self.hooks.as_ref() else {
+            return false;
+        };
+        let denied = if redo {
+            hooks.authorize(requester, redo, history.redo_entries().filter(|entry| slots.contains(&entry.no)))
+        } else {
+            hooks.authorize(requester, redo, history.entries().rev().filter(|entry| slots.contains(&entry.no)))
+        };
+        match denied {
+            Some(denied) => {
+                self.denied.write(denied);
+                true
+            }
+            None => false
+        }
+    }
+
+
+    /// Returns true after sending [`UndoRefused`] if the policy refuses undoing the slots, otherwise charges the undo.
+    pub fn refuses(&mut self, history: &UndoHistory, slots: &[usize]) -> bool {
+        match self.policy.charge(history, slots) {
+            Ok(()) => false,
+            Err(reason) => {
+                self.refused.write(UndoRefused { reason });
+                true
+            }
+        }
+    }
+
+
+    /// Sends [`UndoSelectiveRefused`] for the slot requested via [`UndoRequester::undo_entry`](crate::prelude::UndoRequester::undo_entry).
+    #[inline(always)]
+    pub fn refuse_selective(&mut self, requested: usize, dependents: Vec<usize>) {
+        self.selective_refused.write(UndoSelectiveRefused { requested, dependents });
+    }
+
+
+    /// Sends [`UndoSelectiveReverted`] for the slot requested via [`UndoRequester::undo_entry`](crate::prelude::UndoRequester::undo_entry).
+    #[inline(always)]
+    pub fn selectively_reverted(&mut self, requested: usize, reverted: Vec<(usize, String)>) {
+        self.selective_reverted.write(UndoSelectiveReverted { requested, reverted });
+    }
+
+
+    /// Returns true if undoing the slots waits for a confirmation, see [`UndoNeedsConfirmation`](crate::prelude::UndoNeedsConfirmation).
+    #[inline(always)]
+    pub fn awaits_confirmation(&mut self, history: &UndoHistory, request: &RequestUndoEvent, slots: &[usize]) -> bool {
+        self.confirmation.awaits(history, request, slots)
+    }
+
+
+    /// Returns true after sending [`UndoEntrySkipped`] if an entity id checked by the slot has been reused,
+    /// in which case the slot is to be discarded instead of undone.
+    pub fn skips(&mut self, history: &UndoHistory, no: usize) -> bool {
+        match reused_entity(self.entities, history, no) {
+            Some(entity) => {
+                self.skipped.write(UndoEntrySkipped { no, entity });
+                true
+            }
+            None => false
+        }
+    }
+
+
+    /// Dispatches the undo of the slot, to be called before the slot is moved off the history.
+    pub fn undo(&mut self, history: &UndoHistory, no: usize) {
+        if let Some(hooks) = self.hooks.as_mut() {
+            hooks.dispatching(false, history.slot_entries(no));
+        }
+        if let Some(telemetry) = self.telemetry.as_mut() {
+            telemetry.dispatched(false, history.slot_entries(no));
+        }
+        self.ew.write(DispatchUndoEvent::Undo(no));
+    }
+
+
+    /// Dispatches the redo of the slot, to be called before the slot is moved back to the history.
+    pub fn redo(&mut self, history: &UndoHistory, no: usize) {
+        if let Some(hooks) = self.hooks.as_mut() {
+            hooks.dispatching(true, history.redo_entries().filter(|entry| entry.no == no));
+        }
+        if let Some(telemetry) = self.telemetry.as_mut() {
+            telemetry.dispatched(true, history.redo_entries().filter(|entry| entry.no == no));
+        }
+        self.ew.write(DispatchUndoEvent::Redo(no));
+    }
+}
+
+
+/// Returns the hooks, inserting them with the system calling the hooks after application if not installed yet.
+pub(crate) fn init_undo_hooks(app: &mut App) -> Mut<'_, UndoHooks> {
+    if !app.world().contains_resource::<UndoHooks>() {
+        app.init_resource::<UndoHooks>();
+        app.add_systems(PostUpdate, run_applied_hooks_system);
+    }
+    app.world_mut().resource_mut::<UndoHooks>()
+}
+
+
+pub(crate) fn run_applied_hooks_system(mut hooks: ResMut<UndoHooks>) {
+    if hooks.dispatched.is_empty() {
+        return;
+    }
+    let dispatched = std::mem::take(&mut hooks.dispatched);
+    for (redo, entry) in &dispatched {
+        hooks.call(*redo, UndoHookPhase::AfterApply, entry);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoDenied, UndoHookPhase, UndoSiteId, UndoVerdict};
+    use crate::request::RequestUndoEvent;
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Edit(i32);
+
+
+    #[test]
+    fn call_hooks_at_each_phase() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut harness = UndoTestHarness::<Edit>::new();
+        let (push, undo) = (calls.clone(), calls.clone());
+        harness.setup(move |app| {
+            app.add_undo_hook_on_push(move |cx| push.lock().unwrap().push(("push", cx.phase, cx.no)));
+            app.add_undo_hook_on_undo(move |cx| undo.lock().unwrap().push(("undo", cx.phase, cx.no)));
+        });
+        harness.register(Edit(1));
+        harness.register(Edit(2));
+        harness.undo();
+        harness.expect([Edit(2)]);
+
+        assert_eq!(*calls.lock().unwrap(), vec![
+            ("push", UndoHookPhase::Pushed, 1),
+            ("push", UndoHookPhase::Pushed, 2),
+            ("undo", UndoHookPhase::BeforeDispatch, 2),
+            ("undo", UndoHookPhase::AfterApply, 2),
+        ]);
+    }
+
+
+    #[test]
+    fn keep_entry_denied_by_veto() {
+        let locked = Arc::new(AtomicBool::new(true));
+        let mut harness = UndoTestHarness::<Edit>::new();
+        let veto = locked.clone();
+        harness.setup(move |app| {
+            app.add_undo_veto(move |cx| if cx.no == 2 && veto.load(Ordering::Relaxed) {
+                UndoVerdict::Deny("the layer is locked".to_string())
+            } else {
+                UndoVerdict::Allow
+            });
+        });
+        harness.register(Edit(1));
+        harness.register(Edit(2));
+        harness.undo();
+        harness.expect([]);
+        let denied: Vec<UndoDenied> = harness.app().world_mut().resource_mut::<Messages<UndoDenied>>().drain().collect();
+        assert_eq!(denied, vec![UndoDenied {
+            no: 2,
+            type_name: std::any::type_name::<Edit>(),
+            reason: "the layer is locked".to_string(),
+        }]);
+
+        locked.store(false, Ordering::Relaxed);
+        harness.undo();
+        harness.expect([Edit(2)]);
+    }
+
+
+    #[test]
+    fn drop_requests_denied_by_authorizer() {
+        let mut harness = UndoTestHarness::<Edit>::new();
+        harness.setup(|app| {
+            app.add_undo_authorizer(|authorization| match authorization.requester {
+                Some(UndoSiteId(2)) => UndoVerdict::Deny(format!("the slot {} belongs to another user", authorization.entry.no)),
+                _ => UndoVerdict::Allow
+            });
+        });
+        harness.register(Edit(1));
+        harness.register(Edit(2));
+
+        harness.app().world_mut().write_message(RequestUndoEvent::LatestAs(UndoChannel::DEFAULT, UndoSiteId(2)));
+        harness.frames(1);
+        harness.expect([]);
+        let denied: Vec<String> = harness
+            .app()
+            .world_mut()
+            .resource_mut::<Messages<UndoDenied>>()
+            .drain()
+            .map(|denied| denied.reason)
+            .collect();
+        assert_eq!(denied, vec!["the slot 2 belongs to another user".to_string()]);
+
+        harness.app().world_mut().write_message(RequestUndoEvent::LatestAs(UndoChannel::DEFAULT, UndoSiteId(1)));
+        harness.frames(1);
+        harness.undo();
+        harness.expect([Edit(2), Edit(1)]);
+    }
+}
@@ -0,0 +1,203 @@
+use bevy::ecs::message::MessageCursor;
+use bevy::ecs::system::SystemParam;
+use std::marker::PhantomData;
+
+use bevy::prelude::{Local, Message, Messages, Mut, Res, World};
+
+use crate::{DispatchUndoEvent, lock, UndoAreas};
+use crate::counter::UndoCounter;
+use crate::history::UndoHistory;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEntry;
+
+/// Sent by the app once assets or scripts have been hot-reloaded,
+/// running the fixers added via [`AppUndoEx::on_undo_hot_reload`](crate::prelude::AppUndoEx::on_undo_hot_reload) at the start of the next frame.
+#[derive(Message, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoHotReloaded;
+
+
+type UndoFixer<E> = Box<dyn Fn(&mut E, &World) -> bool + Send + Sync + 'static>;
+
+
+type UndoInvalidation<E> = Box<dyn Fn(&E) -> bool + Send + Sync + 'static>;
+
+
+pub(crate) struct UndoHotReloadFixers<E: UndoPayload> {
+    pub fixers: Vec<UndoFixer<E>>,
+    invalidations: Vec<UndoInvalidation<E>>,
+}
+
+
+impl<E: UndoPayload> Default for UndoHotReloadFixers<E> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            fixers: Vec::new(),
+            invalidations: Vec::new(),
+        }
+    }
+}
+
+
+/// Drops the entries of `E` whose payloads refer to stale data, such as after a hot-reload.
+#[derive(SystemParam)]
+pub struct UndoInvalidator<'w, E: UndoPayload> {
+    areas: Res<'w, UndoAreas>,
+    marker: PhantomData<E>,
+}
+
+
+impl<'w, E: UndoPayload> UndoInvalidator<'w, E> {
+    /// Drops every entry of `E` the predicate returns true for, including the ones waiting for redo,
+    /// at the start of the next frame.
+    ///
+    /// The whole slot of a dropped entry is dropped, so entries registered together never get undone partially.
+    #[inline]
+    pub fn invalidate_where(&mut self, predicate: impl Fn(&E) -> bool + Send + Sync + 'static) {
+        lock(&self.areas.areas::<E>().fixers).invalidations.push(Box::new(predicate));
+    }
+}
+
+
+#[inline]
+pub(crate) fn has_hot_reload_work<E: UndoPayload>(areas: Res<UndoAreas>) -> bool {
+    areas.get::<E>().is_some_and(|areas| {
+        let fixers = lock(&areas.fixers);
+        !fixers.fixers.is_empty() || !fixers.invalidations.is_empty()
+    })
+}
+
+
+/// Runs the fixers of `E` over its entries after a hot-reload, and the pending invalidations,
+/// dropping the slots of the entries rejected by either.
+pub(crate) fn fix_hot_reloaded_entries_system<E: UndoPayload>(
+    world: &mut World,
+    mut reader: Local<MessageCursor<UndoHotReloaded>>,
+) {
+    let reloaded = reader.read(world.resource::<Messages<UndoHotReloaded>>()).count() > 0;
+    let slots = world.resource_scope(|world, mut areas: Mut<UndoAreas>| {
+        let areas = areas.areas_mut::<E>();
+        let mut fixers = lock(&areas.fixers);
+        let invalidations = std::mem::take(&mut fixers.invalidations);
+        if !reloaded && invalidations.is_empty() {
+            return None;
+        }
+        let fixers: &[UndoFixer<E>] = if reloaded { &fixers.fixers } else { &[] };
+        let keeps = |event: &mut E| {
+            fixers.iter().all(|fixer| fixer(event, world))
+                && !invalidations.iter().any(|invalidation| invalidation(event))
+        };
+        let mut invalid = Vec::new();
+        let entries = areas.registered.0.drain();
+        for mut entry in entries {
+            if !keeps_entry(&mut entry, &keeps) {
+                invalid.push(entry.no);
+            }
+            areas.registered.push(entry);
+        }
+        let mut invalid_redo = Vec::new();
+        for entry in areas.redo.0.iter_mut() {
+            if !keeps_entry(entry, &keeps) {
+                invalid_redo.push(entry.no);
+            }
+        }
+        Some((invalid, invalid_redo))
+    });
+    if let Some((invalid, invalid_redo)) = slots {
+        drop_slots(world, invalid, invalid_redo);
+    }
+}
+
+
+fn keeps_entry<E: UndoPayload>(entry: &mut UndoEntry<E>, keeps: &impl Fn(&mut E) -> bool) -> bool {
+    keeps(&mut entry.inner) && entry.redo.as_mut().is_none_or(keeps)
+}
+
+
+fn drop_slots(world: &mut World, mut invalid: Vec<usize>, mut invalid_redo: Vec<usize>) {
+    if invalid.is_empty() && invalid_redo.is_empty() {
+        return;
+    }
+    invalid.sort_unstable();
+    invalid.dedup();
+    invalid_redo.sort_unstable();
+    invalid_redo.dedup();
+
+    let mut history = world.resource_mut::<UndoHistory>();
+    for no in invalid.iter() {
+        history.remove_slot(*no);
+    }
+    for no in invalid_redo.iter() {
+        history.remove_redo_slot(*no);
+    }
+    let max_no = history.max_no().unwrap_or_default();
+    world.resource_mut::<UndoCounter>().set(max_no);
+    world.write_message_batch(invalid.into_iter().map(DispatchUndoEvent::Discard));
+    world.write_message_batch(invalid_redo.into_iter().map(DispatchUndoEvent::DiscardRedo));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoHotReloaded, UndoInvalidator, UndoMeta, UndoScheduler};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Paint {
+        texture: u32,
+    }
+
+
+    /// The textures remapped by the last reload.
+    #[derive(Resource)]
+    struct Remapped(Vec<(u32, Option<u32>)>);
+
+
+    fn register(harness: &mut UndoTestHarness<Paint>, texture: u32) {
+        let mut state = SystemState::<UndoScheduler<Paint>>::new(harness.app().world_mut());
+        let paint = Paint { texture };
+        state.get_mut(harness.app().world_mut()).unwrap().push(paint.clone(), Some(paint), UndoMeta::default());
+        state.apply(harness.app().world_mut());
+        harness.frames(1);
+    }
+
+
+    #[test]
+    fn rewrite_and_invalidate_on_hot_reload() {
+        let mut harness = UndoTestHarness::<Paint>::new();
+        harness.setup(|app| {
+            app.insert_resource(Remapped(vec![(1, Some(10)), (2, None)]));
+            app.on_undo_hot_reload::<Paint>(|paint, world| {
+                match world.resource::<Remapped>().0.iter().find(|(from, _)| *from == paint.texture) {
+                    Some((_, Some(to))) => paint.texture = *to,
+                    Some((_, None)) => return false,
+                    None => {}
+                }
+                true
+            });
+        });
+        for texture in [1, 2, 3, 4] {
+            register(&mut harness, texture);
+        }
+        harness.undo();
+        harness.expect([Paint { texture: 4 }]);
+
+        harness.app().world_mut().write_message(UndoHotReloaded);
+        harness.frames(1);
+        let mut state = SystemState::<UndoInvalidator<Paint>>::new(harness.app().world_mut());
+        state.get_mut(harness.app().world_mut()).unwrap().invalidate_where(|paint| paint.texture == 4);
+        harness.frames(1);
+
+        harness.redo();
+        harness.expect([]);
+        harness.undo();
+        harness.expect([Paint { texture: 3 }]);
+        harness.undo();
+        harness.expect([Paint { texture: 10 }]);
+        harness.undo();
+        harness.expect([]);
+    }
+}
@@ -0,0 +1,128 @@
+use bevy::prelude::Res;
+
+#[cfg(feature = "reserve")]
+use crate::lock;
+use crate::UndoAreas;
+use crate::counter::UndoCounter;
+use crate::history::UndoHistory;
+use crate::payload::UndoPayload;
+#[cfg(feature = "reserve")]
+use crate::reserve::ReserveCounter;
+
+/// Panics with the violations and a dump of the history.
+#[track_caller]
+fn report_violations(violations: Vec<String>, history: &UndoHistory) {
+    if violations.is_empty() {
+        return;
+    }
+    panic!("undo invariants violated:\n  {}\n{history:#?}", violations.join("\n  "));
+}
+
+
+/// Validates the type-erased history at the end of the undo pipeline.
+pub(crate) fn check_history_invariants_system(
+    counter: Res<UndoCounter>,
+    history: Res<UndoHistory>,
+) {
+    let mut violations = Vec::new();
+    if let Some(max_no) = history.max_no().filter(|no| **counter < *no) {
+        violations.push(format!("the counter {} is behind the slot {max_no}", **counter));
+    }
+
+    let mut previous: Option<usize> = None;
+    for entry in history.entries() {
+        if previous.is_some_and(|previous| entry.no < previous) {
+            violations.push(format!("the slot {} is registered after the slot {}", entry.no, previous.unwrap_or_default()));
+        }
+        previous = Some(entry.no);
+    }
+
+    let mut redo_slots: Vec<usize> = Vec::new();
+    for entry in history.redo_entries() {
+        if history.slot_len(entry.no) != 0 {
+            violations.push(format!("the slot {} is both registered and waiting for redo", entry.no));
+        }
+        if redo_slots.last() != Some(&entry.no) && redo_slots.contains(&entry.no) {
+            violations.push(format!("the slot {} is split in the redo history", entry.no));
+        }
+        redo_slots.push(entry.no);
+    }
+    report_violations(violations, &history);
+}
+
+
+/// Validates that the stored entries of `E` agree with the history at the end of the undo pipeline.
+///
+/// Entries moved out of the areas by other extensions, such as delta compression, are not seen here.
+pub(crate) fn check_type_invariants_system<E: UndoPayload>(
+    areas: Res<UndoAreas>,
+    history: Res<UndoHistory>,
+    #[cfg(feature = "reserve")]
+    reserve_counter: Res<ReserveCounter>,
+) {
+    let areas = areas.areas::<E>();
+    let type_name = std::any::type_name::<E>();
+    let mut violations = Vec::new();
+    let mut registered_slots = Vec::new();
+    areas.registered.0.for_each(&mut |entry| registered_slots.push(entry.no));
+    #[cfg(feature = "reserve")]
+    areas.reserved.0.for_each(&mut |entry| registered_slots.push(entry.no));
+    for no in registered_slots {
+        if !history.slot_entries(no).any(|entry| entry.type_name == type_name) {
+            violations.push(format!("an entry of {type_name} is stored in the slot {no} missing from the history"));
+        }
+    }
+
+    for entry in areas.redo.0.iter() {
+        if !history.redo_entries().any(|redo| redo.no == entry.no && redo.type_name == type_name) {
+            violations.push(format!("an entry of {type_name} waits for redo in the slot {} missing from the redo history", entry.no));
+        }
+    }
+
+    #[cfg(feature = "reserve")]
+    let reserved = lock(&areas.reservations).0.len();
+    #[cfg(feature = "reserve")]
+    if **reserve_counter < reserved {
+        violations.push(format!(
+            "{reserved} entries of {type_name} are reserved while the reserve counter is {}",
+            **reserve_counter
+        ));
+    }
+    report_violations(violations, &history);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Message;
+
+    use crate::counter::UndoCounter;
+    use crate::testing::UndoTestHarness;
+    use crate::undo_test;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[test]
+    fn pass_on_coherent_history() {
+        undo_test!(Move;
+            register Move(1);
+            register_grouped [Move(2), Move(3)];
+            undo;
+            undo;
+            redo;
+            expect [Move(3), Move(2), Move(1)];
+        );
+    }
+
+
+    #[test]
+    #[should_panic(expected = "the counter 0 is behind the slot 1")]
+    fn panic_on_counter_behind_history() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.register(Move(1));
+        harness.app().world_mut().resource_mut::<UndoCounter>().set(0);
+        harness.frames(1);
+    }
+}
@@ -1,23 +1,250 @@
+use std::any::{Any, TypeId};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
 use bevy::app::{App, Plugin};
-use bevy::prelude::{Event, IntoSystemConfigs, EventReader, EventWriter, PreUpdate, ResMut, Resource};
+use bevy::prelude::{IntoScheduleConfigs, Message, MessageWriter, PostUpdate, PreUpdate, Res, ResMut, resource_equals, Resource, SystemSet, Time};
+use bevy::platform::collections::HashMap;
+
+use crate::amend::{UndoAmendments, UndoReplacedEntries, UndoReplacement};
+use crate::atomic::{track_atomic_groups_system, UndoAtomicBlocked, UndoAtomicCoordinator, UndoAtomicGroups};
+use crate::batch::{UndoBatchFinished, UndoBatchProgress, UndoBatchStarted, UndoBatchWriter};
+use crate::channel::{UndoCapacities, UndoCapacityScope, UndoFullScopes, UndoHistoryFull, UndoStackConfigs, UndoTypeConfigs};
 
+use crate::cooldown::UndoCooldown;
+use crate::confirm::{CancelUndoEvent, ConfirmUndoEvent, resolve_confirmations_system, UndoConfirmations, UndoNeedsConfirmation};
 use crate::counter::UndoCounter;
+use crate::deferred::{register_deferred_events_system, UndoDeferredRegistrations};
+use crate::document::{UndoDocumentState, UndoOpenDocuments};
+use crate::failure::{UndoFailed, UndoFailureStats};
+use crate::history::UndoHistory;
+use crate::hooks::{UndoDenied, UndoDispatcher};
+use crate::hot_reload::{UndoHotReloaded, UndoHotReloadFixers};
+use crate::meta::UndoMeta;
+use crate::pacing::{UndoRequestPacing, UndoRequestQueue};
+use crate::partial::{send_partial_outcomes_system, UndoOutcomeReports, UndoPartial};
+use crate::payload::UndoPayload;
+use crate::placeholder::{index_stable_ids_system, UndoStableIndex};
+use crate::policy::{UndoPolicy, UndoRefused};
+use crate::registry::UndoTypeRegistry;
 use crate::request::RequestUndoEvent;
-use crate::reserve::{RequestCommitReservationsEvent, RequestCommitReservationsFromSchedulerEvent, ReserveCounter};
-use crate::undo_event::UndoEvent;
+use crate::selective::{selective_slots, UndoSelectiveRefused, UndoSelectiveReverted};
+use crate::stage::{has_pending_undo_operations, PostUndo, PreUndo, run_post_undo_schedule_system, run_pre_undo_schedule_system};
+use crate::state::{update_undo_state_system, UndoState, UndoStateChanged};
+use crate::suspend::UndoSuspension;
+#[cfg(feature = "reserve")]
+use crate::reserve::{CommitReservationsEvent, RequestCommitReservationsEvent, RequestCommitReservationsFromSchedulerEvent, reserve_reset_system, ReserveCounter, UndoReservedArea, UndoReserveEvent};
+use crate::storage::{UndoMemoryStorage, UndoStorage};
+use crate::undo_event::UndoEntry;
+use crate::unhandled::{UndoHandlers, UndoUnhandled};
+use crate::weak::{invalidate_weak_refs_system, UndoEntryInvalidated, UndoEntrySkipped, UndoWeakRefs};
 
+mod amend;
+mod asset;
+mod atomic;
+#[cfg(feature = "audit")]
+mod audit;
+mod autosave;
+mod batch;
+mod bulk;
+mod channel;
+mod clipboard;
+mod cold;
+mod collab;
+#[cfg(feature = "compat")]
+pub mod compat;
+mod compaction;
+mod condition;
+mod confirm;
+mod context;
+mod component;
+mod cooldown;
 mod counter;
+mod dedup;
+mod deferred;
+mod delivery;
+mod delta;
+mod document;
+mod drag;
+mod dry_run;
+mod entity_snapshot;
+#[cfg(feature = "egui")]
+mod editor;
+mod erased;
+#[cfg(feature = "serde")]
+mod export;
 mod extension;
+mod failure;
+mod gc;
+#[cfg(feature = "debug_gizmos")]
+mod gizmos;
+mod grouping;
+mod handle;
+mod history;
+mod hooks;
+mod hot_reload;
+mod lifecycle;
+mod link;
+#[cfg(feature = "debug_invariants")]
+mod invariants;
+mod mapped;
+mod merge;
+mod meta;
+#[cfg(feature = "dev_overlay")]
+mod overlay;
+mod pacing;
+mod partial;
+mod payload;
+#[cfg(feature = "rapier")]
+mod physics;
+mod placeholder;
+mod policy;
+mod pool;
+mod priority;
+mod raw;
+mod recorder;
+mod registry;
 mod request;
+mod scene;
+mod scope;
+#[cfg(any(feature = "lua", feature = "rhai"))]
+mod scripting;
+mod search;
+mod selective;
+mod selection;
+mod snapshot;
+mod spawn;
+mod stage;
+mod state;
+mod storage;
+mod strict;
+mod suspend;
+#[cfg(feature = "stress")]
+mod stress;
+mod stroke;
+mod telemetry;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+mod text;
+#[cfg(feature = "thumbnails")]
+mod thumbnail;
+#[cfg(feature = "time_travel")]
+mod time_travel;
+#[cfg(feature = "tilemap")]
+mod tilemap;
+#[cfg(feature = "toast")]
+mod toast;
+mod transform;
+mod transition;
+mod turn;
+mod tween;
 mod undo_event;
+mod unhandled;
+#[cfg(feature = "reserve")]
 mod reserve;
+mod version;
+mod view;
+mod weak;
+mod window;
+mod world;
+#[cfg(feature = "xpbd")]
+mod xpbd;
 
 pub mod prelude {
+    pub use crate::asset::{UndoAssetEvent, UndoAssets};
+    pub use crate::atomic::{UndoAtomicBlocked, UndoAtomicGroup};
+    #[cfg(feature = "audit")]
+    pub use crate::audit::UndoAuditConfig;
+    pub use crate::autosave::{AutosaveSuggested, UndoAutosaveConfig};
+    pub use crate::batch::{UndoBatchFinished, UndoBatchProgress, UndoBatchStarted};
+    pub use crate::bulk::{UndoBulkEdit, UndoBulkEvent};
+    pub use crate::channel::{UndoCapacityScope, UndoChannel, UndoEvicted, UndoEviction, UndoHistoryFull, UndoStackConfig};
+    pub use crate::clipboard::UndoClipboard;
+    pub use crate::confirm::{CancelUndoEvent, ConfirmUndoEvent, UndoNeedsConfirmation};
+    pub use crate::context::UndoContext;
+    pub use crate::collab::{UndoCollab, UndoRemoteEntry, UndoSiteId, UndoStamp};
+    pub use crate::component::UndoComponentEvent;
+    pub use crate::deferred::UndoDeferredId;
+    pub use crate::delta::UndoDelta;
+    pub use crate::document::UndoDocuments;
+    pub use crate::drag::UndoDrag;
+    pub use crate::dry_run::{DryRun, Preview};
+    #[cfg(feature = "egui")]
+    pub use crate::editor::{undo_history_ui, UndoEditorPlugin, UndoHighlighted};
+    pub use crate::entity_snapshot::UndoEntitySnapshot;
+    pub use crate::erased::{UndoAnyEvent, UndoAnyScheduler};
+    #[cfg(feature = "serde")]
+    pub use crate::export::{UndoExportEntry, UndoHistoryExport, UndoSnapshot};
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    pub use crate::export::UndoCompression;
     pub use crate::extension::AppUndoEx;
+    pub use crate::failure::{UndoError, UndoFailed, UndoFailurePolicy};
+    pub use crate::gc::UndoGcPolicy;
+    #[cfg(feature = "debug_gizmos")]
+    pub use crate::gizmos::UndoGizmosPlugin;
+    pub use crate::grouping::{UndoFrameGrouping, UndoGestureGrouping, UndoGranularity, UndoGroupingContext, UndoGroupingStrategy, UndoManualGrouping, UndoTimeWindowGrouping};
+    pub use crate::hooks::{UndoAuthorization, UndoAuthorizer, UndoDenied, UndoHook, UndoHookContext, UndoHookPhase, UndoVerdict, UndoVeto};
+    pub use crate::hot_reload::{UndoHotReloaded, UndoInvalidator};
+    pub use crate::lifecycle::UndoLifecycleEvent;
+    pub use crate::link::UndoLink;
+    pub use crate::merge::MergeUndo;
+    pub use crate::meta::{UndoMeta, UndoUserData};
+    #[cfg(feature = "dev_overlay")]
+    pub use crate::overlay::UndoDevOverlayPlugin;
+    pub use crate::partial::{UndoOutcomes, UndoPartial};
+    pub use crate::payload::UndoPayload;
+    #[cfg(feature = "rapier")]
+    pub use crate::physics::{UndoPhysics, UndoPhysicsEvent, UndoPhysicsSnapshot};
+    pub use crate::placeholder::{UndoEntities, UndoEntityRef, UndoStableId};
+    pub use crate::policy::{UndoCost, UndoPolicy, UndoRefusal, UndoRefused};
+    pub use crate::pool::UndoPoolStats;
+    pub use crate::raw::UndoRawAccess;
+    pub use crate::recorder::UndoMacros;
+    pub use crate::registry::{UndoTypeInfo, UndoTypes};
     pub use crate::request::{UndoRequester};
-    pub use crate::undo_event::{UndoReserveCommitter, UndoScheduler};
+    pub use crate::scope::UndoScope;
+    #[cfg(any(feature = "lua", feature = "rhai"))]
+    pub use crate::scripting::{UndoScriptApi, UndoScriptEvent};
+    pub use crate::search::UndoSearchMatch;
+    pub use crate::selective::{UndoSelectiveMode, UndoSelectiveRefused, UndoSelectiveReverted};
+    pub use crate::selection::{UndoSelection, UndoSelectionEvent};
+    pub use crate::snapshot::{UndoHistoryDiff, UndoHistorySnapshot, UndoSnapshotEntry};
+    pub use crate::spawn::{CommandsUndoEx, UndoSpawnEvent, UndoSpawns};
+    pub use crate::stage::{PostUndo, PreUndo};
+    pub use crate::state::{undo_in_progress, UndoState, UndoStateChanged};
+    pub use crate::storage::{UndoMemoryStorage, UndoStorage};
+    pub use crate::storage::inline::UndoInlineStorage;
+    pub use crate::strict::UndoStrictness;
+    pub use crate::suspend::UndoLock;
+    #[cfg(feature = "sqlite")]
+    pub use crate::storage::sqlite::UndoSqliteStorage;
+    #[cfg(feature = "stress")]
+    pub use crate::stress::{UndoStressConfig, UndoStressEvent, UndoStressPlugin, UndoStressStats};
+    pub use crate::stroke::{UndoStroke, UndoStrokeEvent};
+    pub use crate::telemetry::UndoUsage;
+    #[cfg(any(test, feature = "testing"))]
+    pub use crate::testing::{AppUndoTestEx, UndoTestHarness};
+    pub use crate::text::{UndoText, UndoTextEvent, UndoTextState};
+    #[cfg(feature = "time_travel")]
+    pub use crate::time_travel::{UndoTimeline, UndoTimelineFrame, UndoTimeTravelPlugin};
+    #[cfg(feature = "tilemap")]
+    pub use crate::tilemap::UndoTilemap;
+    #[cfg(feature = "toast")]
+    pub use crate::toast::UndoToastPlugin;
+    pub use crate::transition::UndoTransitionEvent;
+    pub use crate::turn::{UndoTurnGrouping, UndoTurns};
+    pub use crate::tween::{UndoTween, UndoTweenFinished, UndoTweenStarted};
+    pub use crate::undo_event::{UndoEntry, UndoScheduler};
+    #[cfg(feature = "reserve")]
+    pub use crate::undo_event::UndoReserveCommitter;
     #[cfg(feature = "callback_event")]
-    pub use crate::undo_event::callback::UndoCallbackEvent;
+    pub use crate::undo_event::callback::{UndoCallbackEvent, UndoCallbackSkipped};
+    pub use crate::unhandled::UndoUnhandled;
+    pub use crate::version::{UndoVersioned, UndoVersionedBytes};
+    pub use crate::view::{UndoEntryInfo, UndoView};
+    pub use crate::weak::{UndoEntryInvalidated, UndoEntrySkipped, WeakEntityRef};
+    pub use crate::world::WorldUndoEx;
+    #[cfg(feature = "xpbd")]
+    pub use crate::xpbd::{UndoXpbdEvent, UndoXpbdPhysics, UndoXpbdSnapshot};
     pub use crate::UndoPlugin;
 }
 
@@ -30,106 +257,511 @@ pub struct UndoPlugin;
 impl Plugin for UndoPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_event::<RequestUndoEvent>()
-            .add_event::<CommitReservationsEvent>()
-            .add_event::<DecrementCounterEvent>()
-            .add_event::<RequestCommitReservationsFromSchedulerEvent>()
-            .add_event::<RequestCommitReservationsEvent>()
+            .add_message::<RequestUndoEvent>()
+            .add_message::<DispatchUndoEvent>()
+            .add_message::<UndoBatchStarted>()
+            .add_message::<UndoBatchProgress>()
+            .add_message::<UndoBatchFinished>()
+            .add_message::<UndoUnhandled>()
+            .add_message::<UndoFailed>()
+            .add_message::<UndoStateChanged>()
+            .add_message::<UndoAtomicBlocked>()
+            .add_message::<UndoHistoryFull>()
+            .add_message::<UndoDenied>()
+            .add_message::<UndoNeedsConfirmation>()
+            .add_message::<ConfirmUndoEvent>()
+            .add_message::<CancelUndoEvent>()
+            .add_message::<UndoEntryInvalidated>()
+            .add_message::<UndoEntrySkipped>()
+            .add_message::<UndoPartial>()
+            .add_message::<UndoRefused>()
+            .add_message::<UndoHotReloaded>()
+            .add_message::<UndoSelectiveReverted>()
+            .add_message::<UndoSelectiveRefused>()
+            .init_resource::<UndoAreas>()
+            .init_resource::<UndoTypeRegistry>()
             .init_resource::<UndoCounter>()
+            .init_resource::<UndoHistory>()
+            .init_resource::<UndoStackConfigs>()
+            .init_resource::<UndoTypeConfigs>()
+            .init_resource::<UndoFullScopes>()
+            .init_resource::<UndoCooldown>()
+            .init_resource::<UndoRequestQueue>()
+            .init_resource::<UndoDocumentState>()
+            .init_resource::<UndoOpenDocuments>()
+            .init_resource::<UndoHandlers>()
+            .init_resource::<UndoFailureStats>()
+            .init_resource::<UndoState>()
+            .init_resource::<UndoAtomicGroups>()
+            .init_resource::<UndoConfirmations>()
+            .init_resource::<UndoReplacedEntries>()
+            .init_resource::<UndoDeferredRegistrations>()
+            .init_resource::<UndoStableIndex>()
+            .init_resource::<UndoWeakRefs>()
+            .init_resource::<UndoOutcomeReports>()
+            .init_resource::<UndoPolicy>()
+            .init_resource::<UndoSuspension>()
+            .init_schedule(PreUndo)
+            .init_schedule(PostUndo)
+            .configure_sets(PreUpdate, (
+                UndoSystemSet::Commit,
+                UndoSystemSet::Record,
+                UndoSystemSet::Evict,
+                UndoSystemSet::Resolve,
+                UndoSystemSet::Dispatch
+            ).chain())
             .add_systems(PreUpdate, (
-                decrement_counter,
-                reserve_reset_system
-            ).chain());
+                run_pre_undo_schedule_system.run_if(has_pending_undo_operations).before(UndoSystemSet::Commit),
+                register_deferred_events_system.in_set(UndoSystemSet::Commit),
+                index_stable_ids_system.in_set(UndoSystemSet::Commit),
+                track_atomic_groups_system.after(UndoSystemSet::Record).before(UndoSystemSet::Evict),
+                invalidate_weak_refs_system.in_set(UndoSystemSet::Evict).before(evict_over_capacity_system),
+                evict_over_capacity_system.in_set(UndoSystemSet::Evict),
+                resolve_confirmations_system.in_set(UndoSystemSet::Resolve).before(resolve_undo_requests_system),
+                resolve_undo_requests_system.in_set(UndoSystemSet::Resolve),
+                update_undo_state_system.after(UndoSystemSet::Dispatch)
+            ))
+            .add_systems(PostUpdate, (
+                send_partial_outcomes_system,
+                run_post_undo_schedule_system.run_if(resource_equals(UndoState::Dispatching))
+            ));
+
+        #[cfg(feature = "reserve")]
+        app
+            .add_message::<CommitReservationsEvent>()
+            .add_message::<RequestCommitReservationsFromSchedulerEvent>()
+            .add_message::<RequestCommitReservationsEvent>()
+            .init_resource::<ReserveCounter>()
+            .add_systems(PreUpdate, reserve_reset_system.in_set(UndoSystemSet::Commit));
+
+        #[cfg(feature = "debug_invariants")]
+        app.add_systems(PreUpdate, crate::invariants::check_history_invariants_system.after(UndoSystemSet::Dispatch));
 
         #[cfg(feature = "callback_event")]
         app.add_plugins(crate::undo_event::callback::UndoCallbackEventPlugin);
+
+        #[cfg(feature = "compat")]
+        app.add_plugins(crate::compat::UndoCompatPlugin);
     }
 }
 
 
-#[derive(Resource)]
-struct UndoRegisteredArea<T: Event + Clone>(Vec<UndoEvent<T>>);
+/// The stages an undo-operation goes through in [`PreUpdate`].
+///
+/// Messages requested in a frame are resolved in the next frame,
+/// so undone events can be read by systems in [`Update`](bevy::prelude::Update) above all.
+#[derive(SystemSet, Debug, Hash, Eq, PartialEq, Copy, Clone)]
+pub(crate) enum UndoSystemSet {
+    Commit,
+    Record,
+    Evict,
+    Resolve,
+    Dispatch,
+}
+
+
+/// The areas of all undo-event types in a single resource, so adding many types does not fill the world with resources.
+#[derive(Resource, Default)]
+struct UndoAreas(HashMap<TypeId, Box<dyn Any + Send + Sync>>);
+
+
+impl UndoAreas {
+    /// Adds the areas of `E` unless they already exist.
+    #[inline]
+    pub fn init<E: UndoPayload>(&mut self) {
+        self.0
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::<UndoTypeAreas<E>>::default());
+    }
+
+
+    #[inline]
+    pub fn get<E: UndoPayload>(&self) -> Option<&UndoTypeAreas<E>> {
+        self.0
+            .get(&TypeId::of::<E>())
+            .and_then(|areas| areas.downcast_ref())
+    }
+
+
+    /// Returns the areas of `E`, panicking if `E` has not been added via [`AppUndoEx::add_undo_event`](crate::prelude::AppUndoEx::add_undo_event).
+    #[inline]
+    pub fn areas<E: UndoPayload>(&self) -> &UndoTypeAreas<E> {
+        self.get().unwrap_or_else(|| panic!("{} has not been added as an undo-event", std::any::type_name::<E>()))
+    }
+
+
+    #[inline]
+    pub fn areas_mut<E: UndoPayload>(&mut self) -> &mut UndoTypeAreas<E> {
+        self.0
+            .get_mut(&TypeId::of::<E>())
+            .and_then(|areas| areas.downcast_mut())
+            .unwrap_or_else(|| panic!("{} has not been added as an undo-event", std::any::type_name::<E>()))
+    }
 
 
-impl<T: Event + Clone> Default for UndoRegisteredArea<T> {
+    #[inline(always)]
+    pub fn registered<E: UndoPayload>(&self) -> &UndoRegisteredArea<E> {
+        &self.areas::<E>().registered
+    }
+
+
+    #[inline(always)]
+    pub fn registered_mut<E: UndoPayload>(&mut self) -> &mut UndoRegisteredArea<E> {
+        &mut self.areas_mut::<E>().registered
+    }
+}
+
+
+/// The entries of an undo-event type, both registered and waiting for redo,
+/// together with the requests left by the schedulers for the pipeline.
+///
+/// The requests are locked, so the schedulers of all types take the areas as a shared resource.
+struct UndoTypeAreas<E: UndoPayload> {
+    registered: UndoRegisteredArea<E>,
+    redo: UndoRedoArea<E>,
+    #[cfg(feature = "reserve")]
+    reserved: UndoRegisteredArea<UndoReserveEvent<E>>,
+    amendments: Mutex<UndoAmendments<E>>,
+    replacement: Mutex<UndoReplacement<E>>,
+    fixers: Mutex<UndoHotReloadFixers<E>>,
+    #[cfg(feature = "reserve")]
+    reservations: Mutex<UndoReservedArea<E>>,
+}
+
+
+impl<E: UndoPayload> Default for UndoTypeAreas<E> {
     #[inline(always)]
     fn default() -> Self {
-        Self(vec![])
+        Self {
+            registered: UndoRegisteredArea::default(),
+            redo: UndoRedoArea::default(),
+            #[cfg(feature = "reserve")]
+            reserved: UndoRegisteredArea::default(),
+            amendments: Mutex::default(),
+            replacement: Mutex::default(),
+            fixers: Mutex::default(),
+            #[cfg(feature = "reserve")]
+            reservations: Mutex::default(),
+        }
     }
 }
 
 
-impl<E: Event + Clone> UndoRegisteredArea<E> {
+/// Locks the requests of a type, recovering them if a panic poisoned the lock, as they are only ever pushed or taken.
+#[inline]
+fn lock<T>(requests: &Mutex<T>) -> MutexGuard<'_, T> {
+    requests.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+
+struct UndoRegisteredArea<T: UndoPayload>(Box<dyn UndoStorage<T>>);
+
+
+impl<T: UndoPayload> Default for UndoRegisteredArea<T> {
     #[inline(always)]
-    pub fn push(&mut self, e: UndoEvent<E>) {
+    fn default() -> Self {
+        Self(Box::<UndoMemoryStorage<T>>::default())
+    }
+}
+
+
+impl<E: UndoPayload> UndoRegisteredArea<E> {
+    #[inline(always)]
+    pub fn push(&mut self, e: UndoEntry<E>) {
         self.0.push(e);
     }
 
 
     #[inline(always)]
-    pub fn pop(&mut self) -> Option<E> {
-        self.0.pop().map(|e| e.inner)
+    pub fn push_with_meta(&mut self, e: UndoEntry<E>, meta: &UndoMeta) {
+        self.0.push_with_meta(e, meta);
     }
 
 
+    /// Pops the most recent event belonging to the slot.
     #[inline(always)]
-    pub fn pop_if_has_latest(&mut self, counter: &UndoCounter) -> Option<E> {
-        let index = self.0.iter().position(|undo| {
-            **counter <= undo.no
-        })?;
+    pub fn pop_slot(&mut self, no: usize) -> Option<E> {
+        self.pop_entry(no).map(|entry| entry.inner)
+    }
 
-        Some(self.0.remove(index).inner)
+
+    #[inline(always)]
+    pub fn pop_entry(&mut self, no: usize) -> Option<UndoEntry<E>> {
+        self.0.pop(no)
+    }
+
+
+    /// Duplicates the events belonging to the slot in the order they would be popped.
+    pub fn slot_events(&self, no: usize) -> Vec<E> {
+        let mut events = Vec::new();
+        self.0.for_each(&mut |entry| {
+            if entry.no == no {
+                events.push(entry.inner.duplicate());
+            }
+        });
+        events.reverse();
+        events
+    }
+
+
+    #[inline]
+    pub fn contains_slot(&self, no: usize) -> bool {
+        let mut contains = false;
+        self.0.for_each(&mut |entry| contains |= entry.no == no);
+        contains
+    }
+
+
+    #[cfg(test)]
+    pub fn events(&self) -> Vec<E> {
+        let mut events = Vec::new();
+        self.0.for_each(&mut |entry| events.push(entry.inner.duplicate()));
+        events
+    }
+
+}
+
+
+/// Keeps the undone entries which have a redo-event.
+struct UndoRedoArea<T: UndoPayload>(Vec<UndoEntry<T>>);
+
+
+impl<T: UndoPayload> Default for UndoRedoArea<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(vec![])
     }
 }
 
 
-#[derive(Event)]
-pub(crate) struct DecrementCounterEvent;
+impl<E: UndoPayload> UndoRedoArea<E> {
+    #[inline(always)]
+    pub fn push(&mut self, e: UndoEntry<E>) {
+        self.0.push(e);
+    }
+
+
+    /// Pops the oldest entry belonging to the slot, so entries are redone in the order they were registered.
+    #[inline(always)]
+    pub fn pop_entry(&mut self, no: usize) -> Option<UndoEntry<E>> {
+        let index = self.0.iter().position(|redo| redo.no == no)?;
+
+        Some(self.0.remove(index))
+    }
+}
+
+
+/// Notifies each event type of what to do with its entries belonging to the slot.
+#[derive(Message, Debug, Copy, Clone)]
+pub(crate) enum DispatchUndoEvent {
+    /// The entries are sent.
+    Undo(usize),
+
+    /// The entries are dropped without being sent.
+    Discard(usize),
 
+    /// The entries, including the ones waiting for redo, are dropped due to the capacity of the channel
+    /// or the document being closed, and sent as [`UndoEvicted`](crate::prelude::UndoEvicted).
+    Evict(usize),
 
-fn decrement_counter(
-    mut er: EventReader<DecrementCounterEvent>,
+    /// The redo-events of the entries are sent.
+    Redo(usize),
+
+    /// The entries waiting for redo are dropped.
+    DiscardRedo(usize),
+
+    /// The entries are sent wrapped in [`DryRun`](crate::prelude::DryRun), while kept in the history.
+    DryRun(usize),
+
+    /// The redo-events of the entries are sent wrapped in [`Preview`](crate::prelude::Preview), while kept waiting for redo.
+    PreviewRedo(usize),
+}
+
+
+fn resolve_undo_requests_system(
+    mut dispatcher: UndoDispatcher,
+    mut history: ResMut<UndoHistory>,
     mut counter: ResMut<UndoCounter>,
+    mut batch: UndoBatchWriter,
+    mut pacing: UndoRequestPacing,
+    documents: Res<UndoDocumentState>,
+    mut atomic: UndoAtomicCoordinator,
 ) {
-    for _ in er.iter() {
-        counter.decrement();
+    for no in history.take_discarded_redo() {
+        dispatcher.send(DispatchUndoEvent::DiscardRedo(no));
     }
-}
 
+    for request in pacing.take() {
+        let (slots, redo): (Vec<usize>, bool) = match &request {
+            RequestUndoEvent::Redo(channel) | RequestUndoEvent::RedoAs(channel, _) => {
+                let slots = history.latest_redo_in(documents.route(*channel)).map(|no| history.linked_slots(no, true));
+                (slots.unwrap_or_default(), true)
+            }
+            RequestUndoEvent::Latest(channel) | RequestUndoEvent::LatestAs(channel, _) => {
+                let slots = history.latest_no_in(documents.route(*channel)).map(|no| history.linked_slots(no, false));
+                (slots.unwrap_or_default(), false)
+            }
+            #[cfg(feature = "serde")]
+            RequestUndoEvent::Restore(channel) => {
+                let slots = history.latest_no_in(documents.route(*channel)).map(|no| history.linked_slots(no, false));
+                (slots.unwrap_or_default(), false)
+            }
+            RequestUndoEvent::Matching(predicate) => {
+                let slots = history.latest_matching(|meta| predicate(meta)).map(|no| history.linked_slots(no, false));
+                (slots.unwrap_or_default(), false)
+            }
+            RequestUndoEvent::Selective(no, mode) => match selective_slots(&history, *no, *mode) {
+                Ok(slots) => (slots, false),
+                Err(dependents) => {
+                    dispatcher.refuse_selective(*no, dependents);
+                    continue;
+                }
+            },
+            RequestUndoEvent::Collect(predicate) => {
+                for no in history.slots_matching(|meta| predicate(meta)) {
+                    if history.is_sticky(no) {
+                        continue;
+                    }
+                    history.remove_slot(no);
+                    dispatcher.send(DispatchUndoEvent::Discard(no));
+                }
+                for no in history.remove_redo_matching(|meta| predicate(meta)) {
+                    dispatcher.send(DispatchUndoEvent::DiscardRedo(no));
+                }
+                counter.set(history.max_no().unwrap_or_default());
+                continue;
+            }
+            RequestUndoEvent::DryRun(channel) => {
+                if let Some(no) = history.latest_no_in(documents.route(*channel)) {
+                    dispatcher.send(DispatchUndoEvent::DryRun(no));
+                }
+                continue;
+            }
+            RequestUndoEvent::PreviewRedo(channel) => {
+                if let Some(no) = history.latest_redo_in(documents.route(*channel)) {
+                    dispatcher.send(DispatchUndoEvent::PreviewRedo(no));
+                }
+                continue;
+            }
+            RequestUndoEvent::CloseChannel(channel) => {
+                for no in history.remove_channel(*channel) {
+                    dispatcher.send(DispatchUndoEvent::Evict(no));
+                }
+                counter.set(history.max_no().unwrap_or_default());
+                continue;
+            }
+        };
+        let Some(slots) = atomic.resolve(&history, slots, redo) else {
+            continue;
+        };
+        if !request.is_restore() && dispatcher.unauthorized(&history, request.requester(), redo, &slots) {
+            continue;
+        }
+        if !redo && !request.is_restore() && (dispatcher.denies(&history, &slots)
+            || dispatcher.awaits_confirmation(&history, &request, &slots)
+            || dispatcher.refuses(&history, &slots)) {
+            continue;
+        }
+
+        let total = slots
+            .iter()
+            .map(|no| if redo { history.redo_slot_len(*no) } else { history.slot_len(*no) })
+            .sum();
+        let mut completed = 0;
+        let mut reverted = Vec::new();
+        batch.start(total);
+        for no in slots {
+            if redo {
+                completed += history.redo_slot_len(no);
+                dispatcher.redo(&history, no);
+                history.redo_slot(no);
+            } else {
+                completed += history.slot_len(no);
+                if dispatcher.skips(&history, no) {
+                    history.remove_slot(no);
+                    dispatcher.send(DispatchUndoEvent::Discard(no));
+                } else {
+                    if let Some(entry) = history.slot_entries(no).next() {
+                        reverted.push((no, entry.meta.tag.clone()));
+                    }
+                    dispatcher.undo(&history, no);
+                    history.undo_slot(no);
+                }
+                for no in history.take_discarded_redo() {
+                    dispatcher.send(DispatchUndoEvent::DiscardRedo(no));
+                }
+            }
+            counter.set(history.max_no().unwrap_or_default());
+            batch.progress(completed, total);
+        }
+        batch.finish(total);
+        if let RequestUndoEvent::Selective(requested, _) = request {
+            dispatcher.selectively_reverted(requested, reverted);
+        }
+    }
+}
 
-#[derive(Event)]
-pub(crate) struct CommitReservationsEvent(pub UndoCounter);
 
-fn reserve_reset_system(
-    mut er: EventReader<RequestCommitReservationsEvent>,
-    mut er2: EventReader<RequestCommitReservationsFromSchedulerEvent>,
-    mut ew: EventWriter<CommitReservationsEvent>,
+fn evict_over_capacity_system(
+    mut ew: MessageWriter<DispatchUndoEvent>,
+    mut history: ResMut<UndoHistory>,
     mut counter: ResMut<UndoCounter>,
-    mut reserve_counter: ResMut<ReserveCounter>,
+    mut capacities: UndoCapacities,
+    time: Option<Res<Time>>,
 ) {
-    if er.iter().next().is_some() || er2.iter().next().is_some() {
-        ew.send(CommitReservationsEvent(*counter));
-        *counter += *reserve_counter;
-        reserve_counter.reset();
+    let now = time.map(|time| time.elapsed());
+    for (scope, config) in capacities.scopes() {
+        let mut slots = match scope {
+            UndoCapacityScope::Channel(channel) => history.slots_matching(|meta| meta.channel == channel),
+            UndoCapacityScope::Type(type_id) => history.slots_of_type(type_id),
+        };
+        slots.retain(|no| !history.is_sticky(*no));
+        let total = slots.len();
+        let (evicted, over_capacity) = config.evicted_slots(&history, slots, now);
+        capacities.notify(scope, &config, over_capacity, total - evicted.len());
+        if evicted.is_empty() {
+            continue;
+        }
+        for no in evicted {
+            history.remove_slot(no);
+            ew.write(DispatchUndoEvent::Evict(no));
+        }
+        counter.set(history.max_no().unwrap_or_default());
     }
 }
 
 
+
 #[cfg(test)]
 mod tests {
+    use std::any::TypeId;
+    use std::time::Duration;
+
     use bevy::app::{App, Startup, Update};
-    use bevy::input::Input;
-    use bevy::prelude::{Commands, Component, Event, EventReader, KeyCode, Res};
+    use bevy::ecs::system::SystemState;
+    use bevy::input::ButtonInput;
+    use bevy::prelude::{Commands, Component, IntoScheduleConfigs, KeyCode, Local, Message, MessageReader, Messages, MinimalPlugins, on_message, Res, ResMut, Time};
+    use crate::batch::{UndoBatchFinished, UndoBatchProgress, UndoBatchStarted};
     use crate::counter::UndoCounter;
     use crate::extension::AppUndoEx;
     use crate::prelude::UndoRequester;
-    use crate::reserve::{ReserveCounter, UndoReservedArea, UndoReserveEvent};
+    #[cfg(feature = "reserve")]
+    use crate::reserve::ReserveCounter;
+    use crate::channel::{UndoCapacityScope, UndoChannel, UndoEvicted, UndoEviction, UndoHistoryFull, UndoStackConfig};
+    use crate::meta::UndoMeta;
+    use crate::pacing::{stop_handler_timer_system, UndoRequestQueue};
+    use crate::request::RequestUndoEvent;
     use crate::undo_event::UndoScheduler;
-    use crate::{UndoPlugin, UndoRegisteredArea};
+    #[cfg(feature = "reserve")]
+    use crate::lock;
+    use crate::{UndoAreas, UndoPlugin};
 
-    #[derive(Event, Clone, Default)]
+    #[derive(Message, Clone, Default)]
     struct UndoEvent;
 
+    #[derive(Message, Clone)]
+    struct TaggedEvent(usize);
+
     #[derive(Component)]
     struct OnUndo;
 
@@ -142,11 +774,11 @@ mod tests {
         });
         app.update();
 
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::R);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyR);
         app.update();
         app.update();
 
-        assert_eq!(app.world.query::<&OnUndo>().iter(&app.world).len(), 1);
+        assert_eq!(app.world_mut().query::<&OnUndo>().iter(app.world()).len(), 1);
     }
 
 
@@ -160,20 +792,21 @@ mod tests {
 
         app.update();
 
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::R);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyR);
         app.update();
 
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::R);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyR);
         app.update();
 
-        assert_eq!(app.world.query::<&OnUndo>().iter(&app.world).len(), 1);
+        assert_eq!(app.world_mut().query::<&OnUndo>().iter(app.world()).len(), 1);
         app.update();
 
-        assert_eq!(app.world.query::<&OnUndo>().iter(&app.world).len(), 2);
+        assert_eq!(app.world_mut().query::<&OnUndo>().iter(app.world()).len(), 2);
     }
 
 
     #[test]
+    #[cfg(feature = "reserve")]
     fn reserve_init_3times() {
         let mut app = new_app();
         app.add_systems(Startup, |mut s: UndoScheduler<UndoEvent>| {
@@ -183,71 +816,710 @@ mod tests {
             s.register_all_reserved();
         });
 
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::R);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyR);
         app.update();
         app.update();
 
-        assert_eq!(app.world.query::<&OnUndo>().iter(&app.world).len(), 3);
+        assert_eq!(app.world_mut().query::<&OnUndo>().iter(app.world()).len(), 3);
     }
 
 
     #[test]
+    #[cfg(feature = "reserve")]
     fn reserve_at_intervals() {
         let mut app = new_app();
-        app.add_systems(Update, |mut s: UndoScheduler<UndoEvent>, key: Res<Input<KeyCode>>| {
-            if key.just_pressed(KeyCode::A) {
+        app.add_systems(Update, |mut s: UndoScheduler<UndoEvent>, key: Res<ButtonInput<KeyCode>>| {
+            if key.just_pressed(KeyCode::KeyA) {
                 s.reserve_default();
-            } else if key.just_pressed(KeyCode::B) {
+            } else if key.just_pressed(KeyCode::KeyB) {
                 s.register_all_reserved();
             }
         });
 
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::A);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyA);
         app.update();
-        assert_eq!(app.world.resource_mut::<UndoReservedArea<UndoEvent>>().0.len(), 1);
+        assert_eq!(lock(&app.world().resource::<UndoAreas>().areas::<UndoEvent>().reservations).0.len(), 1);
 
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::A);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyA);
         app.update();
-        assert_eq!(app.world.resource_mut::<UndoReservedArea<UndoEvent>>().0.len(), 2);
+        assert_eq!(lock(&app.world().resource::<UndoAreas>().areas::<UndoEvent>().reservations).0.len(), 2);
 
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::A);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyA);
         app.update();
-           assert_eq!(app.world.resource_mut::<UndoReservedArea<UndoEvent>>().0.len(), 3);
+           assert_eq!(lock(&app.world().resource::<UndoAreas>().areas::<UndoEvent>().reservations).0.len(), 3);
 
-        app.world.resource_mut::<Input<KeyCode>>().reset(KeyCode::A);
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::B);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().reset(KeyCode::KeyA);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyB);
         app.update();
-        app.world.resource_mut::<Input<KeyCode>>().reset(KeyCode::B);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().reset(KeyCode::KeyB);
         app.update();
 
-        assert_eq!(app.world.resource_mut::<UndoReservedArea<UndoEvent>>().0.len(), 0);
-        assert_eq!(app.world.resource_mut::<UndoRegisteredArea<UndoReserveEvent<UndoEvent>>>().0.len(), 3);
+        assert_eq!(lock(&app.world().resource::<UndoAreas>().areas::<UndoEvent>().reservations).0.len(), 0);
+        assert_eq!(app.world().resource::<UndoAreas>().areas::<UndoEvent>().reserved.0.len(), 3);
 
-        app.world.resource_mut::<Input<KeyCode>>().reset(KeyCode::B);
-        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::R);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().reset(KeyCode::KeyB);
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyR);
         app.update();
 
         app.update();
 
-        assert_eq!(**app.world.resource_mut::<UndoCounter>(), 0);
-        assert_eq!(**app.world.resource_mut::<ReserveCounter>(), 0);
-        assert_eq!(app.world.resource_mut::<UndoRegisteredArea<UndoEvent>>().0.len(), 0);
-        assert_eq!(app.world.resource_mut::<UndoReservedArea<UndoEvent>>().0.len(), 0);
-        assert_eq!(app.world.query::<&OnUndo>().iter(&app.world).len(), 3);
+        assert_eq!(**app.world_mut().resource_mut::<UndoCounter>(), 0);
+        assert_eq!(**app.world_mut().resource_mut::<ReserveCounter>(), 0);
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<UndoEvent>().0.len(), 0);
+        assert_eq!(lock(&app.world().resource::<UndoAreas>().areas::<UndoEvent>().reservations).0.len(), 0);
+        assert_eq!(app.world_mut().query::<&OnUndo>().iter(app.world()).len(), 3);
+    }
+
+
+    #[test]
+    #[cfg(feature = "reserve")]
+    fn count_pending_reservations() {
+        let mut app = new_app();
+        let mut state = SystemState::<UndoScheduler<UndoEvent>>::new(app.world_mut());
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.reserve_default();
+        scheduler.reserve_default();
+        assert_eq!(scheduler.pending_reservations(), 2);
+        scheduler.register_all_reserved();
+        assert_eq!(scheduler.pending_reservations(), 2);
+        state.apply(app.world_mut());
+
+        app.update();
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().pending_reservations(), 0);
     }
 
 
-    fn undo(mut req: UndoRequester, key: Res<Input<KeyCode>>) {
-        if key.just_pressed(KeyCode::R) {
+    #[test]
+    fn undo_last_matching_skips_newer_entries() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_meta(TaggedEvent(1), UndoMeta::tagged("paint"));
+            s.register_with_meta(TaggedEvent(2), UndoMeta::tagged("move"));
+        });
+        app.update();
+
+        app.add_systems(Update, |mut req: UndoRequester, mut done: Local<bool>| {
+            if !*done {
+                req.undo_last_matching(|meta| meta.tag == "paint");
+                *done = true;
+            }
+        });
+        app.update();
+        app.update();
+
+        let events = app.world().resource::<Messages<TaggedEvent>>();
+        let undone: Vec<usize> = events.iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(undone, vec![1]);
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 1);
+    }
+
+
+    #[test]
+    fn undo_entity_skips_entries_of_other_entities() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        let e1 = app.world_mut().spawn_empty().id();
+        let e2 = app.world_mut().spawn_empty().id();
+        app.add_systems(Startup, move |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_meta(TaggedEvent(1), UndoMeta::for_entity(e1));
+            s.register_with_meta(TaggedEvent(2), UndoMeta::for_entity(e2));
+            s.register_with_meta(TaggedEvent(3), UndoMeta::for_entity(e2).with_entity(e1));
+        });
+        app.update();
+
+        app.add_systems(Update, move |mut req: UndoRequester, mut done: Local<bool>| {
+            if !*done {
+                req.undo_entity(e1);
+                req.undo_entity(e1);
+                *done = true;
+            }
+        });
+        app.update();
+        app.update();
+
+        let events = app.world().resource::<Messages<TaggedEvent>>();
+        let undone: Vec<usize> = events.iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(undone, vec![3, 1]);
+    }
+
+
+    #[test]
+    fn next_undo_targets() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        let e1 = app.world_mut().spawn_empty().id();
+        let e2 = app.world_mut().spawn_empty().id();
+        app.add_systems(Startup, move |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_meta(TaggedEvent(1), UndoMeta::for_entity(e1));
+            s.register_with_meta(TaggedEvent(2), UndoMeta::for_entity(e2).with_entity(e1));
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        let requester = state.get_mut(app.world_mut()).unwrap();
+        let mut targets = vec![e1, e2];
+        targets.sort_unstable();
+        assert_eq!(requester.next_undo_targets(UndoChannel::DEFAULT), targets);
+        assert!(requester.next_undo_targets(1).is_empty());
+    }
+
+
+    #[test]
+    fn can_undo_and_len() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register(TaggedEvent(1));
+            s.register_all_grouped((2..=3).map(TaggedEvent));
+            s.register_to(1, TaggedEvent(4));
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        let requester = state.get_mut(app.world_mut()).unwrap();
+        assert!(requester.can_undo());
+        assert!(!requester.can_redo());
+        assert_eq!(requester.len(), 2);
+        assert_eq!(requester.len_channel(1), 1);
+        assert!(!requester.can_undo_channel(2));
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let requester = state.get_mut(app.world_mut()).unwrap();
+        assert!(requester.is_empty());
+        assert_eq!(requester.len(), 0);
+        assert!(requester.can_undo_channel(1));
+    }
+
+
+    #[test]
+    fn resolve_all_requests_of_frame() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_all((1..=4).map(TaggedEvent));
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().undo_count(3);
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 1);
+    }
+
+
+    #[test]
+    fn defer_requests_over_limit() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_requests_per_frame(1);
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_all((1..=4).map(TaggedEvent));
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().undo_count(2);
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 3);
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().deferred_requests(), 1);
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 2);
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().deferred_requests(), 0);
+    }
+
+
+    #[test]
+    fn defer_requests_over_budget() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_dispatch_budget(Duration::from_millis(25));
+        // The handlers are reported to take 10ms per request, so two requests fit in the budget.
+        app.add_systems(Update, (|mut queue: ResMut<UndoRequestQueue>| queue.set_cost(Duration::from_millis(10)))
+            .after(stop_handler_timer_system)
+            .run_if(on_message::<TaggedEvent>));
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_all((1..=6).map(TaggedEvent));
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().undo_count(5);
+        app.update();
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().deferred_requests(), 4);
+        app.update();
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().deferred_requests(), 2);
+        app.update();
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 1);
+    }
+
+
+    #[test]
+    fn drop_requests_within_cooldown() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_cooldown(Duration::from_millis(100));
+        app.init_resource::<Time>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_all((1..=3).map(TaggedEvent));
+        });
+        app.update();
+
+        app.world_mut().write_message_batch([RequestUndoEvent::Latest(UndoChannel::DEFAULT), RequestUndoEvent::Latest(UndoChannel::DEFAULT)]);
+        app.update();
+        app.world_mut().resource_mut::<Time>().advance_to(Duration::from_millis(50));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        // Both undos of the first frame went through, the one within the cooldown was dropped.
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 1);
+
+        app.world_mut().resource_mut::<Time>().advance_to(Duration::from_millis(200));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 0);
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().suppressed_requests(), 1);
+    }
+
+
+    #[test]
+    fn resolve_deferred_requests_within_cooldown() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_cooldown(Duration::from_millis(100));
+        app.configure_undo_requests_per_frame(1);
+        app.init_resource::<Time>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_all((1..=3).map(TaggedEvent));
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().undo_count(3);
+        state.apply(app.world_mut());
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 0);
+        assert_eq!(state.get_mut(app.world_mut()).unwrap().suppressed_requests(), 0);
+    }
+
+
+    #[test]
+    fn scope_by_tags() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_meta(TaggedEvent(1), UndoMeta::tagged("tool:brush").with_tag("layer:background"));
+            s.register_with_meta(TaggedEvent(2), UndoMeta::default().with_tag("layer:foreground"));
+            s.register_with_meta(TaggedEvent(3), UndoMeta::default().with_tag("layer:background"));
+            s.register_with_meta(TaggedEvent(4), UndoMeta::default().with_tag("layer:foreground"));
+        });
+        app.update();
+
+        app.add_systems(Update, |mut req: UndoRequester, mut frame: Local<usize>| {
+            match *frame {
+                0 => {
+                    assert_eq!(req.count_tag("layer:background"), 2);
+                    assert_eq!(req.count_tag("tool:brush"), 1);
+                    req.clear_tag("layer:foreground");
+                }
+                1 => {
+                    assert_eq!(req.count_tag("layer:foreground"), 0);
+                    req.undo_tag("layer:background");
+                }
+                _ => {}
+            }
+            *frame += 1;
+        });
+        app.update();
+        app.update();
+        app.update();
+
+        let events = app.world().resource::<Messages<TaggedEvent>>();
+        let undone: Vec<usize> = events.iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(undone, vec![3]);
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 1);
+    }
+
+
+    #[test]
+    fn clear_entries_waiting_for_redo() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_redo_and_meta(TaggedEvent(1), TaggedEvent(10), UndoMeta::tagged("layer:background"));
+            s.register_with_redo_and_meta(TaggedEvent(2), TaggedEvent(20), UndoMeta::tagged("layer:foreground"));
+            s.register_with_redo_and_meta(TaggedEvent(3), TaggedEvent(30), UndoMeta::tagged("layer:background"));
+        });
+        app.update();
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().undo_count(2);
+        state.apply(app.world_mut());
+        app.update();
+
+        state.get_mut(app.world_mut()).unwrap().clear_tag("layer:background");
+        state.apply(app.world_mut());
+        app.update();
+        assert_eq!(app.world().resource::<UndoAreas>().areas::<TaggedEvent>().redo.0.len(), 1);
+        assert!(state.get_mut(app.world_mut()).unwrap().can_redo());
+
+        state.get_mut(app.world_mut()).unwrap().clear();
+        state.apply(app.world_mut());
+        app.update();
+        assert!(!state.get_mut(app.world_mut()).unwrap().can_redo());
+        assert_eq!(app.world().resource::<UndoAreas>().areas::<TaggedEvent>().redo.0.len(), 0);
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 0);
+    }
+
+
+    #[test]
+    fn keep_sticky_entries() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_channel(UndoChannel::DEFAULT, UndoStackConfig::with_capacity(1));
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_meta(TaggedEvent(1), UndoMeta::tagged("project created").sticky());
+            s.register(TaggedEvent(2));
+            s.register(TaggedEvent(3));
+        });
+        app.update();
+        let remaining = |app: &App| -> Vec<usize> {
+            app.world().resource::<UndoAreas>().registered::<TaggedEvent>().events().iter().map(|e| e.0).collect()
+        };
+        assert_eq!(remaining(&app), vec![1, 3]);
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().clear();
+        state.apply(app.world_mut());
+        app.update();
+        assert_eq!(remaining(&app), vec![1]);
+    }
+
+
+    #[test]
+    fn channels_are_isolated() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register(TaggedEvent(1));
+            s.register_to(7, TaggedEvent(2));
+            s.register_to(8, TaggedEvent(3));
+        });
+        app.update();
+
+        app.add_systems(Update, |mut req: UndoRequester, mut done: Local<bool>| {
+            if !*done {
+                req.undo_channel(7);
+                req.undo();
+                req.undo_channel(7);
+                *done = true;
+            }
+        });
+        app.update();
+        app.update();
+
+        let events = app.world().resource::<Messages<TaggedEvent>>();
+        let undone: Vec<usize> = events.iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(undone, vec![2, 1]);
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<TaggedEvent>().0.len(), 1);
+    }
+
+
+    #[test]
+    fn evict_over_capacity() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_channel(UndoChannel::DEFAULT, UndoStackConfig::with_capacity(2));
+        app.configure_undo_channel(1, UndoStackConfig::with_capacity(1).eviction(UndoEviction::RejectNewest));
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register(TaggedEvent(1));
+            s.register(TaggedEvent(2));
+            s.register(TaggedEvent(3));
+            s.register_to(1, TaggedEvent(4));
+            s.register_to(1, TaggedEvent(5));
+        });
+        app.update();
+
+        let remaining: Vec<usize> = app.world().resource::<UndoAreas>().registered::<TaggedEvent>()
+            .events()
+            .iter()
+            .map(|e| e.0)
+            .collect();
+        assert_eq!(remaining, vec![2, 3, 4]);
+
+        let events = app.world().resource::<Messages<UndoEvicted<TaggedEvent>>>();
+        let mut evicted: Vec<usize> = events.iter_current_update_messages().map(|e| e.payload.0).collect();
+        evicted.sort_unstable();
+        assert_eq!(evicted, vec![1, 5]);
+    }
+
+
+    #[test]
+    fn notify_history_full_once_per_crossing() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_channel(UndoChannel::DEFAULT, UndoStackConfig::with_capacity(1));
+        let mut scheduler = SystemState::<UndoScheduler<TaggedEvent>>::new(app.world_mut());
+        let mut register = |app: &mut App, n: usize| {
+            scheduler.get_mut(app.world_mut()).unwrap().register(TaggedEvent(n));
+            scheduler.apply(app.world_mut());
+            app.update();
+            app.world_mut().resource_mut::<Messages<UndoHistoryFull>>().drain().count()
+        };
+        assert_eq!(register(&mut app, 1), 0);
+        assert_eq!(register(&mut app, 2), 1);
+        assert_eq!(register(&mut app, 3), 0);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.update();
+        assert_eq!(register(&mut app, 4), 0);
+        assert_eq!(register(&mut app, 5), 1);
+    }
+
+
+    #[test]
+    fn evict_over_type_capacity() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_type::<TaggedEvent>(UndoStackConfig::with_capacity(2));
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register(TaggedEvent(1));
+            s.register_to(1, TaggedEvent(2));
+            s.register(TaggedEvent(3));
+        });
+        app.add_systems(Startup, |mut s: UndoScheduler<UndoEvent>| {
+            s.register_all([UndoEvent, UndoEvent, UndoEvent]);
+        });
+        app.update();
+
+        let remaining: Vec<usize> = app.world().resource::<UndoAreas>().registered::<TaggedEvent>()
+            .events()
+            .iter()
+            .map(|e| e.0)
+            .collect();
+        assert_eq!(remaining, vec![2, 3]);
+        assert_eq!(app.world().resource::<UndoAreas>().registered::<UndoEvent>().0.len(), 3);
+
+        let events = app.world().resource::<Messages<UndoEvicted<TaggedEvent>>>();
+        let evicted: Vec<usize> = events.iter_current_update_messages().map(|e| e.payload.0).collect();
+        assert_eq!(evicted, vec![1]);
+
+        let full: Vec<UndoHistoryFull> = app.world_mut().resource_mut::<Messages<UndoHistoryFull>>().drain().collect();
+        assert_eq!(full, vec![UndoHistoryFull {
+            scope: UndoCapacityScope::Type(TypeId::of::<TaggedEvent>()),
+            capacity: 2,
+            type_name: Some(std::any::type_name::<TaggedEvent>()),
+        }]);
+    }
+
+
+    fn resource_count(app: &App) -> usize {
+        app.world().iter_resources().count()
+    }
+
+
+    #[test]
+    fn keep_areas_of_all_types_in_one_resource() {
+        let mut app = new_app();
+        let types = app.world().resource::<UndoAreas>().0.len();
+        let resources = resource_count(&app);
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register(TaggedEvent(1));
+            s.amend_latest(|event| event.0 = 2);
+        });
+        app.update();
+
+        // Apart from the areas, only the event queues of the type are added.
+        assert_eq!(resource_count(&app), resources + 5);
+        let areas = app.world().resource::<UndoAreas>();
+        assert_eq!(areas.0.len(), types + 1);
+        assert_eq!(areas.registered::<TaggedEvent>().events().iter().map(|e| e.0).collect::<Vec<_>>(), vec![2]);
+    }
+
+
+    #[test]
+    fn prune_by_importance_and_tag() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_channel(1, UndoStackConfig::with_capacity(2).eviction(UndoEviction::LowestImportance));
+        app.configure_undo_channel(2, UndoStackConfig::with_capacity(2).eviction(UndoEviction::LeastRecentTag));
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_meta(TaggedEvent(1), UndoMeta::default().with_channel(1).with_importance(5));
+            s.register_with_meta(TaggedEvent(2), UndoMeta::default().with_channel(1));
+            s.register_with_meta(TaggedEvent(3), UndoMeta::default().with_channel(1).with_importance(1));
+
+            s.register_with_meta(TaggedEvent(4), UndoMeta::tagged("move").with_channel(2));
+            s.register_with_meta(TaggedEvent(5), UndoMeta::tagged("paint").with_channel(2));
+            s.register_with_meta(TaggedEvent(6), UndoMeta::tagged("move").with_channel(2));
+        });
+        app.update();
+
+        let mut remaining: Vec<usize> = app.world().resource::<UndoAreas>().registered::<TaggedEvent>()
+            .events()
+            .iter()
+            .map(|e| e.0)
+            .collect();
+        remaining.sort_unstable();
+        assert_eq!(remaining, vec![1, 3, 4, 6]);
+    }
+
+
+    #[test]
+    fn expire_by_age() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.configure_undo_channel(UndoChannel::DEFAULT, UndoStackConfig::default().max_age(Duration::from_secs(5)));
+        app.init_resource::<Time>();
+        SystemState::<UndoScheduler<TaggedEvent>>::new(app.world_mut())
+            .get_mut(app.world_mut()).unwrap()
+            .register(TaggedEvent(1));
+        app.update();
+
+        app.world_mut().resource_mut::<Time>().advance_to(Duration::from_secs(10));
+        SystemState::<UndoScheduler<TaggedEvent>>::new(app.world_mut())
+            .get_mut(app.world_mut()).unwrap()
+            .register(TaggedEvent(2));
+        app.update();
+
+        let remaining: Vec<usize> = app.world().resource::<UndoAreas>().registered::<TaggedEvent>()
+            .events()
+            .iter()
+            .map(|e| e.0)
+            .collect();
+        assert_eq!(remaining, vec![2]);
+    }
+
+
+    #[test]
+    fn register_all() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_all((1..=2).map(TaggedEvent));
+            s.register_all_grouped((3..=5).map(TaggedEvent));
+        });
+        app.update();
+        assert_eq!(**app.world().resource::<UndoCounter>(), 3);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let events = app.world().resource::<Messages<TaggedEvent>>();
+        let undone: Vec<usize> = events.iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(undone, vec![5, 4, 3]);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let events = app.world().resource::<Messages<TaggedEvent>>();
+        let undone: Vec<usize> = events.iter_current_update_messages().map(|e| e.0).collect();
+        assert_eq!(undone, vec![2]);
+    }
+
+
+    #[test]
+    fn redo_most_recently_undone_first() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.push(TaggedEvent(1), Some(TaggedEvent(11)), UndoMeta::default());
+            s.push(TaggedEvent(2), Some(TaggedEvent(12)), UndoMeta::default());
+        });
+        app.update();
+
+        for request in [RequestUndoEvent::Latest(UndoChannel::DEFAULT), RequestUndoEvent::Latest(UndoChannel::DEFAULT)] {
+            app.world_mut().write_message(request);
+            app.update();
+        }
+        let mut redone = Vec::new();
+        for _ in 0..2 {
+            app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+            app.update();
+            let events = app.world().resource::<Messages<TaggedEvent>>();
+            redone.extend(events.iter_current_update_messages().map(|e| e.0));
+        }
+        assert_eq!(redone, vec![11, 12]);
+    }
+
+
+    #[test]
+    fn redo_with_prebuilt_events() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register_with_redo(TaggedEvent(1), TaggedEvent(11));
+        });
+        app.update();
+
+        let mut sent = Vec::new();
+        for request in [RequestUndoEvent::Latest(UndoChannel::DEFAULT), RequestUndoEvent::Redo(UndoChannel::DEFAULT), RequestUndoEvent::Latest(UndoChannel::DEFAULT)] {
+            app.world_mut().write_message(request);
+            app.update();
+            let events = app.world().resource::<Messages<TaggedEvent>>();
+            sent.extend(events.iter_current_update_messages().map(|e| e.0));
+        }
+        assert_eq!(sent, vec![1, 11, 1]);
+    }
+
+
+    #[test]
+    fn batch_events_for_groups() {
+        let mut app = new_app();
+        app.add_undo_event::<TaggedEvent>();
+        app.add_systems(Startup, |mut s: UndoScheduler<TaggedEvent>| {
+            s.register(TaggedEvent(1));
+            s.register_all_grouped((2..=4).map(TaggedEvent));
+        });
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let started = app.world().resource::<Messages<UndoBatchStarted>>();
+        assert_eq!(started.iter_current_update_messages().next(), Some(&UndoBatchStarted { total: 3 }));
+        let progress = app.world().resource::<Messages<UndoBatchProgress>>();
+        assert_eq!(progress.iter_current_update_messages().next(), Some(&UndoBatchProgress { completed: 3, total: 3 }));
+        let finished = app.world().resource::<Messages<UndoBatchFinished>>();
+        assert_eq!(finished.iter_current_update_messages().next(), Some(&UndoBatchFinished { total: 3 }));
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let started = app.world().resource::<Messages<UndoBatchStarted>>();
+        assert_eq!(started.iter_current_update_messages().count(), 0);
+    }
+
+
+
+    #[test]
+    fn undo_with_minimal_plugins() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, UndoPlugin));
+        app.add_undo_event::<UndoEvent>();
+        app.add_undo_handler::<UndoEvent, _>(read_undo);
+        app.add_systems(Startup, |mut s: UndoScheduler<UndoEvent>| s.register(UndoEvent));
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world_mut().query::<&OnUndo>().iter(app.world()).count(), 1);
+    }
+
+    fn undo(mut req: UndoRequester, key: Res<ButtonInput<KeyCode>>) {
+        if key.just_pressed(KeyCode::KeyR) {
             req.undo();
         }
     }
 
     fn read_undo(
         mut commands: Commands,
-        mut er: EventReader<UndoEvent>,
+        mut er: MessageReader<UndoEvent>,
     ) {
-        for _ in er.iter() {
+        for _ in er.read() {
             commands.spawn(OnUndo);
         }
     }
@@ -255,7 +1527,7 @@ mod tests {
     fn new_app() -> App {
         let mut app = App::new();
         app.add_plugins(UndoPlugin);
-        app.init_resource::<Input<KeyCode>>();
+        app.init_resource::<ButtonInput<KeyCode>>();
         app.add_undo_event::<UndoEvent>();
         app.add_systems(Update, read_undo);
         app.add_systems(Update, undo);
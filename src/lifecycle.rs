@@ -0,0 +1,147 @@
+use bevy::ecs::entity::Entities;
+use bevy::prelude::{Changed, Commands, Component, DetectChanges, Entity, Message, MessageReader, Query, Ref, RemovedComponents, ResMut, Resource};
+use bevy::platform::collections::{HashMap, HashSet};
+
+use crate::meta::UndoMeta;
+use crate::undo_event::UndoScheduler;
+
+/// Inserts the component into the entity, or removes it if `value` is `None`, when undone or redone.
+///
+/// Registered automatically for the component types set up via
+/// [`AppUndoEx::track_component_lifecycle_undo`](crate::prelude::AppUndoEx::track_component_lifecycle_undo).
+#[derive(Message, Debug, Clone)]
+pub struct UndoLifecycleEvent<C: Component + Clone> {
+    pub entity: Entity,
+    pub value: Option<C>,
+}
+
+
+/// The last known values of the tracked components, to be restored once removed,
+/// and the entities whose component has just been inserted or removed by an undo or redo.
+#[derive(Resource)]
+pub(crate) struct UndoComponentShadow<C: Component + Clone> {
+    values: HashMap<Entity, C>,
+    restored: HashSet<Entity>,
+}
+
+
+impl<C: Component + Clone> Default for UndoComponentShadow<C> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            values: HashMap::default(),
+            restored: HashSet::default(),
+        }
+    }
+}
+
+
+/// Registers the insertions and removals of `C` made in the frame, except the ones made by undoing or redoing them.
+pub(crate) fn track_component_lifecycle_system<C: Component + Clone>(
+    changed: Query<(Entity, Ref<C>), Changed<C>>,
+    mut removed: RemovedComponents<C>,
+    mut shadow: ResMut<UndoComponentShadow<C>>,
+    entities: &Entities,
+    mut scheduler: UndoScheduler<UndoLifecycleEvent<C>>,
+) {
+    for entity in removed.read() {
+        let value = shadow.values.remove(&entity);
+        // Despawned entities are left to the spawn tracking, they cannot get the component back.
+        if shadow.restored.remove(&entity) || !entities.contains(entity) {
+            continue;
+        }
+        if let Some(value) = value {
+            scheduler.register_with_redo_and_meta(
+                UndoLifecycleEvent { entity, value: Some(value) },
+                UndoLifecycleEvent { entity, value: None },
+                UndoMeta::for_entity(entity),
+            );
+        }
+    }
+    for (entity, component) in changed.iter() {
+        shadow.values.insert(entity, C::clone(&component));
+        if !component.is_added() || shadow.restored.remove(&entity) {
+            continue;
+        }
+        scheduler.register_with_redo_and_meta(
+            UndoLifecycleEvent { entity, value: None },
+            UndoLifecycleEvent { entity, value: Some(C::clone(&component)) },
+            UndoMeta::for_entity(entity),
+        );
+    }
+    shadow.restored.clear();
+}
+
+
+pub(crate) fn apply_lifecycle_events_system<C: Component + Clone>(
+    mut er: MessageReader<UndoLifecycleEvent<C>>,
+    mut commands: Commands,
+    mut shadow: ResMut<UndoComponentShadow<C>>,
+) {
+    for event in er.read() {
+        let Ok(mut entity) = commands.get_entity(event.entity) else {
+            continue;
+        };
+        match event.value.clone() {
+            Some(value) => entity.insert(value),
+            None => entity.remove::<C>()
+        };
+        shadow.restored.insert(event.entity);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Component, Entity};
+
+    use crate::prelude::{AppUndoEx, UndoRequester};
+    use crate::UndoPlugin;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Light(u32);
+
+
+    fn request(app: &mut App, f: impl FnOnce(&mut UndoRequester)) {
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        f(&mut state.get_mut(app.world_mut()).unwrap());
+        app.update();
+        app.update();
+    }
+
+
+    fn light(app: &App, entity: Entity) -> Option<&Light> {
+        app.world().get::<Light>(entity)
+    }
+
+
+    #[test]
+    fn undo_insertions_and_removals() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.track_component_lifecycle_undo::<Light>();
+        let lamp = app.world_mut().spawn_empty().id();
+        app.update();
+
+        app.world_mut().entity_mut(lamp).insert(Light(3));
+        app.update();
+        app.world_mut().get_mut::<Light>(lamp).unwrap().0 = 5;
+        app.update();
+        app.world_mut().entity_mut(lamp).remove::<Light>();
+        app.update();
+        app.update();
+
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(light(&app, lamp), Some(&Light(5)));
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(light(&app, lamp), None);
+        request(&mut app, |requester| requester.redo());
+        assert_eq!(light(&app, lamp), Some(&Light(3)));
+        request(&mut app, |requester| requester.redo());
+        assert_eq!(light(&app, lamp), None);
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(light(&app, lamp), Some(&Light(5)));
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Links entries across channels, so undoing or redoing one of them also undoes or redoes the others.
+///
+/// Entries are linked by registering them with the same link via [`UndoMeta::with_link`](crate::prelude::UndoMeta::with_link),
+/// such as a scene edit and a node-graph edit produced by one gesture.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+#[repr(transparent)]
+pub struct UndoLink(pub u64);
+
+
+impl UndoLink {
+    /// Creates a link distinct from the other links created by this.
+    #[inline]
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+
+impl Default for UndoLink {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoLink, UndoMeta, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Edit(&'static str);
+
+
+    fn drain(app: &mut App) -> Vec<Edit> {
+        app.world_mut().resource_mut::<Messages<Edit>>().drain().collect()
+    }
+
+
+    #[test]
+    fn undo_and_redo_linked_partners() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        let mut state = SystemState::<UndoScheduler<Edit>>::new(app.world_mut());
+        let link = UndoLink::new();
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.push(Edit("scene"), Some(Edit("scene")), UndoMeta::default().with_link(link));
+        scheduler.push(Edit("graph"), Some(Edit("graph")), UndoMeta::default().with_channel(1).with_link(link));
+        scheduler.push(Edit("other"), Some(Edit("other")), UndoMeta::default().with_channel(1));
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(drain(&mut app), vec![Edit("graph"), Edit("scene")]);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel(1)));
+        app.update();
+        assert_eq!(drain(&mut app), vec![Edit("other")]);
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel(1)));
+        app.update();
+        assert_eq!(drain(&mut app), vec![Edit("other")]);
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel(1)));
+        app.update();
+        assert_eq!(drain(&mut app), vec![Edit("scene"), Edit("graph")]);
+    }
+}
@@ -0,0 +1,85 @@
+use bevy::ecs::system::BoxedSystem;
+use bevy::prelude::{In, Messages, Mut, Resource, World};
+
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEvent;
+
+/// Holds the system which converts the registered `I` into the stored `S`.
+#[derive(Resource)]
+pub(crate) struct UndoEventMapper<I: UndoPayload, S: UndoPayload>(pub BoxedSystem<In<I>, S>);
+
+
+/// Converts all events registered as `I` into `S` keeping their slot numbers and metadata.
+///
+/// Messages the converter could not run for, such as when its parameters are missing, are dropped.
+pub(crate) fn map_undo_event_system<I: UndoPayload, S: UndoPayload>(world: &mut World) {
+    let events: Vec<UndoEvent<I>> = world
+        .resource_mut::<Messages<UndoEvent<I>>>()
+        .drain()
+        .collect();
+    if events.is_empty() {
+        return;
+    }
+
+    world.resource_scope(|world, mut mapper: Mut<UndoEventMapper<I, S>>| {
+        let mapped: Vec<UndoEvent<S>> = events
+            .into_iter()
+            .filter_map(|event| {
+                let inner = mapper.0.run_without_applying_deferred(event.inner, world).ok()?;
+                let redo = event.redo.and_then(|redo| mapper.0.run_without_applying_deferred(redo, world).ok());
+                Some(UndoEvent {
+                    inner,
+                    redo,
+                    no: event.no,
+                    meta: event.meta,
+                    #[cfg(feature = "serde")]
+                    registered_on: event.registered_on,
+                })
+            })
+            .collect();
+        mapper.0.apply_deferred(world);
+        world.write_message_batch(mapped);
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{In, Message, Messages, Res, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Moved;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct RestorePosition(f32);
+
+    #[derive(Resource)]
+    struct Position(f32);
+
+
+    #[test]
+    fn store_converted_event() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.insert_resource(Position(3.));
+        app.add_undo_event_mapped::<Moved, RestorePosition, _>(|_: In<Moved>, position: Res<Position>| {
+            RestorePosition(position.0)
+        });
+        app.add_systems(Startup, |mut s: UndoScheduler<Moved>| {
+            s.register(Moved);
+        });
+        app.update();
+        app.world_mut().resource_mut::<Position>().0 = 5.;
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+
+        let events = app.world().resource::<Messages<RestorePosition>>();
+        assert_eq!(events.iter_current_update_messages().collect::<Vec<_>>(), vec![&RestorePosition(3.)]);
+    }
+}
@@ -0,0 +1,133 @@
+use bevy::prelude::{MessageReader, ResMut};
+
+use crate::UndoAreas;
+use crate::counter::UndoCounter;
+use crate::history::{UndoHistory, UndoHistoryEntry};
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEvent;
+
+/// Payloads which can fold a newly registered entry into the previous one of the same channel,
+/// such as two consecutive moves of the same entity.
+///
+/// Enabled per type via [`AppUndoEx::merge_undo_entries`](crate::prelude::AppUndoEx::merge_undo_entries).
+pub trait MergeUndo: UndoPayload {
+    /// Folds the newer payload into this one, returning false if they are not compatible.
+    ///
+    /// This payload is the one sent when undone, so a payload restoring an absolute state is usually kept as is,
+    /// while a relative one such as an offset is accumulated.
+    fn merge(&mut self, newer: Self) -> bool;
+}
+
+
+/// Returns the slot of the previous entry of the channel, if both are single plain entries of the type.
+pub(crate) fn mergeable_slot(history: &UndoHistory, no: usize, type_name: &str) -> Option<usize> {
+    let is_plain = |entry: &UndoHistoryEntry| {
+        entry.type_name == type_name
+            && history.slot_len(entry.no) == 1
+            && entry.meta.link.is_none()
+            && entry.meta.atomic_group.is_none()
+            && !entry.meta.sticky
+    };
+    let newer = history.slot_entries(no).next().filter(|entry| is_plain(entry))?;
+    history
+        .entries()
+        .rev()
+        .find(|entry| entry.no < no && entry.meta.channel == newer.meta.channel)
+        .filter(|entry| is_plain(entry) && entry.redoable == newer.redoable)
+        .map(|entry| entry.no)
+}
+
+
+/// Folds the entries of `E` registered in this frame into their previous entries, as long as the payloads accept it.
+pub(crate) fn merge_undo_entries_system<E: MergeUndo>(
+    mut er: MessageReader<UndoEvent<E>>,
+    mut areas: ResMut<UndoAreas>,
+    mut history: ResMut<UndoHistory>,
+    mut counter: ResMut<UndoCounter>,
+) {
+    let registered_area = areas.registered_mut::<E>();
+    let mut registered: Vec<usize> = er.read().map(|e| e.no).collect();
+    if registered.is_empty() {
+        return;
+    }
+    registered.sort_unstable();
+    registered.dedup();
+
+    let type_name = std::any::type_name::<E>();
+    let mut merged = false;
+    for newer in registered {
+        let Some(previous) = mergeable_slot(&history, newer, type_name) else {
+            continue;
+        };
+        let Some(mut newer_entry) = registered_area.pop_entry(newer) else {
+            continue;
+        };
+        let Some(mut previous_entry) = registered_area.pop_entry(previous) else {
+            registered_area.push(newer_entry);
+            continue;
+        };
+        if !previous_entry.inner.merge(newer_entry.inner.duplicate()) {
+            registered_area.push(previous_entry);
+            registered_area.push(newer_entry);
+            continue;
+        }
+
+        if newer_entry.redo.is_some() {
+            previous_entry.redo = newer_entry.redo.take();
+        }
+        registered_area.push(previous_entry);
+        history.remove_slot(newer);
+        merged = true;
+    }
+    if merged {
+        counter.set(history.max_no().unwrap_or_default());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Local, Message, Update};
+
+    use crate::prelude::{AppUndoEx, MergeUndo, UndoScheduler};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move {
+        id: u32,
+        to: i32,
+    }
+
+
+    impl MergeUndo for Move {
+        fn merge(&mut self, newer: Self) -> bool {
+            self.id == newer.id
+        }
+    }
+
+
+    #[test]
+    fn merge_consecutive_moves_of_same_target() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.merge_undo_entries::<Move>();
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Move>, mut frame: Local<usize>| {
+                *frame += 1;
+                if *frame == 1 {
+                    scheduler.register(Move { id: 1, to: 0 });
+                    scheduler.register(Move { id: 1, to: 1 });
+                } else if *frame == 2 {
+                    scheduler.register(Move { id: 1, to: 2 });
+                    scheduler.register(Move { id: 2, to: 0 });
+                }
+            });
+        });
+        harness.frames(3);
+        harness.undo();
+        harness.expect([Move { id: 2, to: 0 }]);
+        harness.undo();
+        harness.expect([Move { id: 1, to: 0 }]);
+        harness.undo();
+        harness.expect([]);
+    }
+}
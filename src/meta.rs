@@ -0,0 +1,243 @@
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use bevy::prelude::Entity;
+
+use crate::atomic::UndoAtomicGroup;
+use crate::channel::UndoChannel;
+use crate::collab::UndoStamp;
+use crate::link::UndoLink;
+use crate::weak::WeakEntityRef;
+
+/// Metadata attached to an entry when it is registered.
+///
+/// It is kept in the history alongside the entry and can be used to select which entry to undo,
+/// see [`UndoRequester::undo_last_matching`](crate::prelude::UndoRequester::undo_last_matching).
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct UndoMeta {
+    /// Free-form tag, typically the name of the tool which registered the entry.
+    pub tag: String,
+
+    /// Additional tags such as `"layer:background"`, used for scoping the history.
+    ///
+    /// Enum tags can be used by converting them into a string.
+    pub tags: Vec<String>,
+
+    /// Entities affected by the entry.
+    pub entities: Vec<Entity>,
+
+    /// The channel the entry belongs to.
+    pub channel: UndoChannel,
+
+    /// Entries with lower importance are evicted first by [`UndoEviction::LowestImportance`](crate::prelude::UndoEviction::LowestImportance).
+    pub importance: i32,
+
+    /// Entries sharing the link are undone and redone together, across channels.
+    pub link: Option<UndoLink>,
+
+    /// Entries sharing the group are undone and redone all at once or not at all, across channels.
+    pub atomic_group: Option<UndoAtomicGroup>,
+
+    /// Sticky entries are neither evicted nor dropped by [`UndoRequester::clear`](crate::prelude::UndoRequester::clear),
+    /// keeping a floor such as "project created" in the history.
+    pub sticky: bool,
+
+    /// Undoing the entry waits for [`ConfirmUndoEvent`](crate::prelude::ConfirmUndoEvent),
+    /// see [`UndoNeedsConfirmation`](crate::prelude::UndoNeedsConfirmation).
+    pub requires_confirmation: bool,
+
+    /// Entities the entry depends on, invalidating it once despawned, see [`WeakEntityRef`].
+    pub weak_refs: Vec<WeakEntityRef>,
+
+    /// Entities whose ids are checked just before the entry is undone,
+    /// dropping it with [`UndoEntrySkipped`](crate::prelude::UndoEntrySkipped) if they were reused by other entities.
+    pub checked_entities: Vec<Entity>,
+
+    /// The logical clock and site of the entry, set once collaborative editing is enabled
+    /// via [`AppUndoEx::enable_undo_collab`](crate::prelude::AppUndoEx::enable_undo_collab).
+    pub stamp: Option<UndoStamp>,
+
+    /// Data of the app associated with the entry, such as a thumbnail or the context of the tool,
+    /// retrieved via [`UndoMeta::user_data`].
+    pub user_data: Option<UndoUserData>,
+}
+
+
+impl UndoMeta {
+    /// Creates the metadata with the given tag.
+    #[inline(always)]
+    pub fn tagged(tag: impl Into<String>) -> Self {
+        Self {
+            tag: tag.into(),
+            ..Self::default()
+        }
+    }
+
+
+    /// Adds a tag to the entry.
+    #[inline(always)]
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+
+    /// Returns true if either [`UndoMeta::tag`] or one of [`UndoMeta::tags`] equals the tag.
+    #[inline]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tag == tag || self.tags.iter().any(|t| t == tag)
+    }
+
+
+    /// Creates the metadata targeting the given entity.
+    #[inline(always)]
+    pub fn for_entity(entity: Entity) -> Self {
+        Self {
+            entities: vec![entity],
+            ..Self::default()
+        }
+    }
+
+
+    /// Adds an entity affected by the entry.
+    #[inline(always)]
+    pub fn with_entity(mut self, entity: Entity) -> Self {
+        self.entities.push(entity);
+        self
+    }
+
+
+    /// Adds an entity the entry depends on, see [`UndoMeta::weak_refs`].
+    #[inline(always)]
+    pub fn with_weak_ref(mut self, entity: impl Into<WeakEntityRef>) -> Self {
+        self.weak_refs.push(entity.into());
+        self
+    }
+
+
+    /// Adds an entity whose id is checked before the entry is undone, see [`UndoMeta::checked_entities`].
+    #[inline(always)]
+    pub fn with_checked_entity(mut self, entity: Entity) -> Self {
+        self.checked_entities.push(entity);
+        self
+    }
+
+
+    /// Moves the entry to the channel.
+    #[inline(always)]
+    pub fn with_channel(mut self, channel: impl Into<UndoChannel>) -> Self {
+        self.channel = channel.into();
+        self
+    }
+
+
+    #[inline(always)]
+    pub fn with_importance(mut self, importance: i32) -> Self {
+        self.importance = importance;
+        self
+    }
+
+
+    /// Links the entry with the other entries registered with the same link.
+    #[inline(always)]
+    pub fn with_link(mut self, link: UndoLink) -> Self {
+        self.link = Some(link);
+        self
+    }
+
+
+    /// Adds the entry to the atomic group, see [`UndoAtomicGroup`].
+    #[inline(always)]
+    pub fn in_atomic_group(mut self, group: UndoAtomicGroup) -> Self {
+        self.atomic_group = Some(group);
+        self
+    }
+
+
+    /// Makes the entry sticky, see [`UndoMeta::sticky`].
+    #[inline(always)]
+    pub fn sticky(mut self) -> Self {
+        self.sticky = true;
+        self
+    }
+
+
+    /// Makes undoing the entry wait for a confirmation, see [`UndoMeta::requires_confirmation`].
+    #[inline(always)]
+    pub fn requiring_confirmation(mut self) -> Self {
+        self.requires_confirmation = true;
+        self
+    }
+
+
+    /// Associates the data with the entry, see [`UndoMeta::user_data`].
+    #[inline(always)]
+    pub fn with_user_data(mut self, data: impl Any + Send + Sync) -> Self {
+        self.user_data = Some(UndoUserData::new(data));
+        self
+    }
+
+
+    /// Returns the data associated with the entry if it is of type `T`.
+    #[inline]
+    pub fn user_data<T: Any>(&self) -> Option<&T> {
+        self.user_data.as_ref().and_then(UndoUserData::downcast_ref)
+    }
+
+
+    /// Returns true if the entry affects the entity.
+    #[inline(always)]
+    pub fn affects(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+}
+
+
+/// Arbitrary data attached to an entry via [`UndoMeta::with_user_data`].
+///
+/// It is shared rather than copied along with the metadata, and compares equal only to itself.
+/// It is not exported with the history.
+#[derive(Clone)]
+pub struct UndoUserData(Arc<dyn Any + Send + Sync>);
+
+
+impl UndoUserData {
+    #[inline(always)]
+    pub fn new(data: impl Any + Send + Sync) -> Self {
+        Self(Arc::new(data))
+    }
+
+
+    #[inline(always)]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+
+impl Debug for UndoUserData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UndoUserData(..)")
+    }
+}
+
+
+impl PartialEq for UndoUserData {
+    #[inline(always)]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+
+impl Eq for UndoUserData {}
+
+
+impl Hash for UndoUserData {
+    #[inline(always)]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).cast::<()>().hash(state);
+    }
+}
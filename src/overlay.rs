@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use bevy::app::{App, Plugin, PostUpdate, PreUpdate};
+use bevy::prelude::{ButtonInput, Color, Commands, Component, DetectChanges, Display, FontSize, IntoScheduleConfigs, KeyCode, Local, MessageReader, Node, Query, Res, ResMut, Resource, Startup, Text, TextColor, TextFont, Val, With};
+
+use crate::{DispatchUndoEvent, UndoSystemSet};
+use crate::counter::UndoCounter;
+use crate::history::UndoHistory;
+use crate::pacing::UndoRequestQueue;
+#[cfg(feature = "reserve")]
+use crate::reserve::ReserveCounter;
+
+/// Shows a text panel with the stack depths per channel, the counters, and the time the last dispatch took.
+///
+/// The panel is toggled with `toggle_key`, this requires `bevy_ui` to be rendered.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UndoDevOverlayPlugin {
+    pub toggle_key: KeyCode,
+    pub visible: bool,
+    pub font_size: f32,
+    pub color: Color,
+}
+
+
+impl Default for UndoDevOverlayPlugin {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            toggle_key: KeyCode::F9,
+            visible: true,
+            font_size: 16.,
+            color: Color::WHITE,
+        }
+    }
+}
+
+
+impl Plugin for UndoDevOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(UndoDevOverlayConfig(*self))
+            .init_resource::<UndoDispatchLatency>()
+            .add_systems(Startup, spawn_overlay_system)
+            .add_systems(PreUpdate, (
+                start_dispatch_timer_system.before(UndoSystemSet::Resolve),
+                stop_dispatch_timer_system.after(UndoSystemSet::Dispatch)
+            ))
+            .add_systems(PostUpdate, (
+                toggle_overlay_system,
+                update_overlay_system
+            ));
+    }
+}
+
+
+#[derive(Resource)]
+struct UndoDevOverlayConfig(UndoDevOverlayPlugin);
+
+
+/// The time from the start of the resolution to the end of the dispatch, in the last frame anything was dispatched.
+#[derive(Resource, Default)]
+struct UndoDispatchLatency {
+    started: Option<Instant>,
+    last: Option<Duration>,
+}
+
+
+#[derive(Component)]
+struct UndoDevOverlay;
+
+
+fn spawn_overlay_system(
+    mut commands: Commands,
+    config: Res<UndoDevOverlayConfig>,
+) {
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: FontSize::Px(config.0.font_size),
+            ..Default::default()
+        },
+        TextColor(config.0.color),
+        Node {
+            top: Val::Px(8.),
+            left: Val::Px(8.),
+            display: display(config.0.visible),
+            ..Default::default()
+        },
+        UndoDevOverlay,
+    ));
+}
+
+
+fn start_dispatch_timer_system(mut latency: ResMut<UndoDispatchLatency>) {
+    latency.started = Some(Instant::now());
+}
+
+
+fn stop_dispatch_timer_system(
+    mut er: MessageReader<DispatchUndoEvent>,
+    mut latency: ResMut<UndoDispatchLatency>,
+) {
+    let started = latency.started.take();
+    if er.read().count() == 0 {
+        return;
+    }
+    if let Some(started) = started {
+        latency.last = Some(started.elapsed());
+    }
+}
+
+
+fn toggle_overlay_system(
+    mut overlay: Query<&mut Node, With<UndoDevOverlay>>,
+    mut visible: Local<Option<bool>>,
+    config: Res<UndoDevOverlayConfig>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+) {
+    let visible = visible.get_or_insert(config.0.visible);
+    if !keys.is_some_and(|keys| keys.just_pressed(config.0.toggle_key)) {
+        return;
+    }
+    *visible = !*visible;
+    for mut node in overlay.iter_mut() {
+        node.display = display(*visible);
+    }
+}
+
+
+fn update_overlay_system(
+    mut overlay: Query<&mut Text, With<UndoDevOverlay>>,
+    history: Res<UndoHistory>,
+    counter: Res<UndoCounter>,
+    queue: Res<UndoRequestQueue>,
+    latency: Res<UndoDispatchLatency>,
+    #[cfg(feature = "reserve")]
+    reserve_counter: Res<ReserveCounter>,
+) {
+    #[cfg(feature = "reserve")]
+    let (reservations, reservations_changed) = (Some(**reserve_counter), reserve_counter.is_changed());
+    #[cfg(not(feature = "reserve"))]
+    let (reservations, reservations_changed) = (None, false);
+    if !(history.is_changed() || counter.is_changed() || reservations_changed || latency.is_changed()) {
+        return;
+    }
+    let text = overlay_text(&history, **counter, reservations, queue.len(), latency.last);
+    for mut overlay in overlay.iter_mut() {
+        overlay.0.clone_from(&text);
+    }
+}
+
+
+fn overlay_text(
+    history: &UndoHistory,
+    counter: usize,
+    reservations: Option<usize>,
+    deferred: usize,
+    latency: Option<Duration>,
+) -> String {
+    // (undo, redo) slot counts per channel.
+    let mut depths: BTreeMap<u64, (usize, usize)> = BTreeMap::new();
+    let mut previous = None;
+    for entry in history.entries() {
+        if previous.replace(entry.no) != Some(entry.no) {
+            depths.entry(entry.meta.channel.0).or_default().0 += 1;
+        }
+    }
+    let mut previous = None;
+    for entry in history.redo_entries() {
+        if previous.replace(entry.no) != Some(entry.no) {
+            depths.entry(entry.meta.channel.0).or_default().1 += 1;
+        }
+    }
+
+    let mut text = String::from("undo\n");
+    for (channel, (undo, redo)) in depths.iter() {
+        let _ = writeln!(text, "  channel {channel}: {undo} undo / {redo} redo");
+    }
+    let _ = writeln!(text, "  counter: {counter}");
+    if let Some(reservations) = reservations {
+        let _ = writeln!(text, "  reservations: {reservations}");
+    }
+    let _ = writeln!(text, "  deferred requests: {deferred}");
+    match latency {
+        Some(latency) => {
+            let _ = write!(text, "  last dispatch: {:.3} ms", latency.as_secs_f64() * 1000.);
+        }
+        None => {
+            let _ = write!(text, "  last dispatch: -");
+        }
+    }
+    text
+}
+
+
+#[inline(always)]
+fn display(visible: bool) -> Display {
+    if visible {
+        Display::Flex
+    } else {
+        Display::None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{ButtonInput, Display, KeyCode, Message, Node, Text, With};
+
+    use crate::overlay::{UndoDevOverlay, UndoDispatchLatency};
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoDevOverlayPlugin, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Move;
+
+
+    #[test]
+    fn show_depths_and_toggle() {
+        let mut app = App::new();
+        app.add_plugins((UndoPlugin, UndoDevOverlayPlugin::default()));
+        app.add_undo_event::<Move>();
+        app.init_resource::<ButtonInput<KeyCode>>();
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register_all([Move, Move]);
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+
+        let text = app.world_mut().query_filtered::<&Text, With<UndoDevOverlay>>().single(app.world()).unwrap().0.clone();
+        assert!(text.contains("channel 0: 1 undo / 0 redo"), "{text}");
+        assert!(text.contains("counter: 1"), "{text}");
+        assert!(app.world().resource::<UndoDispatchLatency>().last.is_some());
+
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::F9);
+        app.update();
+        let node = app.world_mut().query_filtered::<&Node, With<UndoDevOverlay>>().single(app.world()).unwrap();
+        assert_eq!(node.display, Display::None);
+    }
+}
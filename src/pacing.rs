@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{MessageReader, Res, ResMut, Resource, Time};
+
+use crate::cooldown::UndoCooldown;
+use crate::request::RequestUndoEvent;
+use crate::suspend::UndoSuspension;
+
+/// Requests waiting to be resolved in a later frame.
+///
+/// By default all requests of a frame are resolved in the order sent,
+/// the limit is configured via [`AppUndoEx::configure_undo_requests_per_frame`](crate::prelude::AppUndoEx::configure_undo_requests_per_frame)
+/// or [`AppUndoEx::configure_undo_dispatch_budget`](crate::prelude::AppUndoEx::configure_undo_dispatch_budget).
+#[derive(Resource, Default)]
+pub(crate) struct UndoRequestQueue {
+    pub per_frame: Option<usize>,
+    pub budget: Option<Duration>,
+    deferred: VecDeque<RequestUndoEvent>,
+
+    /// The time the handlers took per request resolved, in the last frame anything was resolved.
+    cost: Option<Duration>,
+    resolved: usize,
+    started: Option<Instant>,
+}
+
+
+impl UndoRequestQueue {
+    /// Returns the requests to resolve in this frame, deferring the rest to later frames.
+    pub fn take(&mut self, requests: impl IntoIterator<Item = RequestUndoEvent>) -> Vec<RequestUndoEvent> {
+        self.deferred.extend(requests);
+        let mut len = self.per_frame.map_or(self.deferred.len(), |per_frame| per_frame.min(self.deferred.len()));
+        if let Some(affordable) = self.affordable() {
+            len = len.min(affordable);
+        }
+        self.resolved = len;
+        self.deferred.drain(..len).collect()
+    }
+
+
+    /// Returns the count of requests whose handlers fit in the budget, at least one.
+    ///
+    /// Until the handlers have been measured, a single request is resolved.
+    fn affordable(&self) -> Option<usize> {
+        let budget = self.budget?;
+        let affordable = match self.cost {
+            Some(cost) if !cost.is_zero() => (budget.as_nanos() / cost.as_nanos()) as usize,
+            Some(_) => usize::MAX,
+            None => 1
+        };
+        Some(affordable.max(1))
+    }
+
+
+    /// Puts the requests back in front of the waiting ones, keeping their order.
+    pub fn hold(&mut self, requests: Vec<RequestUndoEvent>) {
+        for request in requests.into_iter().rev() {
+            self.deferred.push_front(request);
+        }
+    }
+
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.deferred.len()
+    }
+
+
+    /// Sets the time the handlers took per request resolved, in place of the measured one.
+    #[cfg(test)]
+    pub fn set_cost(&mut self, cost: Duration) {
+        self.cost = Some(cost);
+    }
+}
+
+
+/// Selects the requests resolved in this frame, applying the per-frame limit, the cooldown and the lock.
+#[derive(SystemParam)]
+pub(crate) struct UndoRequestPacing<'w, 's> {
+    requests: MessageReader<'w, 's, RequestUndoEvent>,
+    queue: ResMut<'w, UndoRequestQueue>,
+    cooldown: ResMut<'w, UndoCooldown>,
+    suspension: Res<'w, UndoSuspension>,
+    time: Option<Res<'w, Time>>,
+}
+
+
+impl<'w, 's> UndoRequestPacing<'w, 's> {
+    /// Returns the requests to resolve in this frame.
+    ///
+    /// The cooldown applies to the operations sent in this frame as a whole, before they are queued,
+    /// so the ones deferred by the per-frame limit or the lock are not dropped later.
+    /// While locked, the undo and redo operations wait in the queue until unlocked,
+    /// while the other requests, such as to discard entries or close a channel, go through.
+    pub fn take(&mut self) -> Vec<RequestUndoEvent> {
+        let now = self.time.as_ref().map(|time| time.elapsed());
+        let sent: Vec<RequestUndoEvent> = self.requests.read().cloned().collect();
+        let operations = sent.iter().filter(|request| request.is_operation()).count();
+        let admitted = operations == 0 || self.cooldown.admit(now, operations);
+        let requests = self.queue.take(sent.into_iter().filter(|request| admitted || !request.is_operation()));
+        if !self.suspension.is_locked() {
+            return requests;
+        }
+        let (held, passed) = requests.into_iter().partition(RequestUndoEvent::is_operation);
+        self.queue.hold(held);
+        passed
+    }
+}
+
+
+/// Starts measuring the handlers in the frames requests have been resolved.
+pub(crate) fn start_handler_timer_system(mut queue: ResMut<UndoRequestQueue>) {
+    if 0 < queue.resolved {
+        queue.started = Some(Instant::now());
+    }
+}
+
+
+/// Stops measuring the handlers, keeping the time they took per request resolved.
+pub(crate) fn stop_handler_timer_system(mut queue: ResMut<UndoRequestQueue>) {
+    if let Some(started) = queue.started.take() {
+        queue.cost = Some(started.elapsed() / queue.resolved as u32);
+    }
+}
@@ -0,0 +1,139 @@
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Entity, Message, MessageWriter, ResMut, Resource};
+
+use crate::payload::UndoPayload;
+
+/// Sent after handlers ran when some of the targets reported via [`UndoOutcomes`] failed,
+/// so UIs can tell users "restored 18 of 20 objects".
+#[derive(Message, Debug, Clone, Eq, PartialEq)]
+pub struct UndoPartial {
+    /// The type name of the undo-event whose handler reported the outcomes.
+    pub type_name: &'static str,
+    pub succeeded: Vec<Entity>,
+    pub failed: Vec<Entity>,
+}
+
+
+impl UndoPartial {
+    /// Returns the number of targets restored.
+    #[inline(always)]
+    pub fn restored(&self) -> usize {
+        self.succeeded.len()
+    }
+
+
+    /// Returns the number of targets reported.
+    #[inline(always)]
+    pub fn total(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+}
+
+
+/// The outcomes reported this frame for each undo-event type.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoOutcomeReports(Vec<UndoPartial>);
+
+
+impl UndoOutcomeReports {
+    pub(crate) fn report(&mut self, type_name: &'static str) -> &mut UndoPartial {
+        let index = match self.0.iter().position(|report| report.type_name == type_name) {
+            Some(index) => index,
+            None => {
+                self.0.push(UndoPartial {
+                    type_name,
+                    succeeded: Vec::new(),
+                    failed: Vec::new(),
+                });
+                self.0.len() - 1
+            }
+        };
+        &mut self.0[index]
+    }
+}
+
+
+/// Reports which targets the handler of `E` restored, for entries touching many entities such as bulk deletes.
+#[derive(SystemParam)]
+pub struct UndoOutcomes<'w, E: UndoPayload> {
+    reports: ResMut<'w, UndoOutcomeReports>,
+    marker: PhantomData<E>,
+}
+
+
+impl<'w, E: UndoPayload> UndoOutcomes<'w, E> {
+    #[inline]
+    pub fn succeeded(&mut self, entity: Entity) {
+        self.reports.report(std::any::type_name::<E>()).succeeded.push(entity);
+    }
+
+
+    #[inline]
+    pub fn failed(&mut self, entity: Entity) {
+        self.reports.report(std::any::type_name::<E>()).failed.push(entity);
+    }
+}
+
+
+/// Sends [`UndoPartial`] for each type whose handler reported failed targets this frame.
+pub(crate) fn send_partial_outcomes_system(
+    mut reports: ResMut<UndoOutcomeReports>,
+    mut ew: MessageWriter<UndoPartial>,
+) {
+    if reports.0.is_empty() {
+        return;
+    }
+    ew.write_batch(reports
+        .0
+        .drain(..)
+        .filter(|report| !report.failed.is_empty()));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Entity, Message, MessageReader, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoOutcomes, UndoPartial, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Restore(Vec<Entity>);
+
+
+    #[test]
+    fn report_partially_restored_targets() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Restore>();
+        app.add_undo_handler::<Restore, _>(|mut er: MessageReader<Restore>, mut outcomes: UndoOutcomes<Restore>| {
+            for Restore(targets) in er.read() {
+                for (i, target) in targets.iter().enumerate() {
+                    if i % 3 == 2 {
+                        outcomes.failed(*target);
+                    } else {
+                        outcomes.succeeded(*target);
+                    }
+                }
+            }
+        });
+
+        let targets: Vec<Entity> = (0..3).map(|_| app.world_mut().spawn_empty().id()).collect();
+        let mut state = SystemState::<UndoScheduler<Restore>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(Restore(targets.clone()));
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let partial: Vec<UndoPartial> = app.world_mut().resource_mut::<Messages<UndoPartial>>().drain().collect();
+        assert_eq!(partial.len(), 1);
+        assert_eq!((partial[0].restored(), partial[0].total()), (2, 3));
+        assert_eq!(partial[0].failed, vec![targets[2]]);
+    }
+}
@@ -0,0 +1,61 @@
+use bevy::prelude::Message;
+
+/// Messages which can be stored in the history.
+///
+/// It is implemented for all events implementing [`Clone`].
+/// For payloads which can't derive it, such as FFI handles or big buffers,
+/// implement this trait manually to define how the payload is duplicated.
+pub trait UndoPayload: Message {
+    /// Duplicates the payload, called when an event is moved into the history or sent while keeping it for redo.
+    fn duplicate(&self) -> Self;
+}
+
+
+impl<E: Message + Clone> UndoPayload for E {
+    #[inline(always)]
+    fn duplicate(&self) -> Self {
+        self.clone()
+    }
+}
+
+
+#[cfg(all(test, feature = "reserve"))]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoPayload, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Debug, PartialEq)]
+    struct Buffer(Vec<u8>);
+
+
+    impl UndoPayload for Buffer {
+        fn duplicate(&self) -> Self {
+            Buffer(self.0.clone())
+        }
+    }
+
+
+    #[test]
+    fn undo_non_clone_payload() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Buffer>();
+        app.add_systems(Startup, |mut s: UndoScheduler<Buffer>| {
+            s.register(Buffer(vec![1, 2]));
+            s.reserve(Buffer(vec![3]));
+            s.register_all_reserved();
+        });
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+
+        let events = app.world().resource::<Messages<Buffer>>();
+        assert_eq!(events.iter_current_update_messages().collect::<Vec<_>>(), vec![&Buffer(vec![3]), &Buffer(vec![1, 2])]);
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Entity, Message, MessageReader, Query, Transform};
+use bevy_rapier3d::prelude::{ExternalForce, ExternalImpulse, Sleeping, Velocity};
+
+use crate::meta::UndoMeta;
+use crate::undo_event::UndoScheduler;
+
+type UndoPhysicsComponents<'a> = (
+    &'a mut Transform,
+    Option<&'a mut Velocity>,
+    Option<&'a mut ExternalImpulse>,
+    Option<&'a mut ExternalForce>,
+    Option<&'a mut Sleeping>,
+);
+
+
+/// The transform of a rigid body together with the Rapier components driving its motion.
+///
+/// Components the body does not have are left out, and are not inserted when restored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoPhysicsSnapshot {
+    pub entity: Entity,
+    pub transform: Transform,
+    pub velocity: Option<Velocity>,
+    pub impulse: Option<ExternalImpulse>,
+    pub force: Option<ExternalForce>,
+    pub sleeping: Option<Sleeping>,
+}
+
+
+/// Restores the rigid body to the snapshot when undone or redone.
+///
+/// Registered via [`UndoPhysics::register`], and applied automatically once set up via
+/// [`AppUndoEx::add_undo_physics`](crate::prelude::AppUndoEx::add_undo_physics).
+#[derive(Message, Debug, Clone)]
+pub struct UndoPhysicsEvent(pub UndoPhysicsSnapshot);
+
+
+/// Captures rigid bodies before they are edited, such as moved by a gizmo, and registers the edits,
+/// so undoing them restores the velocities, pending impulses and forces and the sleeping state along with the transforms,
+/// instead of leaving the bodies moving as before.
+#[derive(SystemParam)]
+pub struct UndoPhysics<'w, 's> {
+    bodies: Query<'w, 's, UndoPhysicsComponents<'static>>,
+    scheduler: UndoScheduler<'w, UndoPhysicsEvent>,
+}
+
+
+impl<'w, 's> UndoPhysics<'w, 's> {
+    /// Returns the current state of the body, or `None` if the entity has no [`Transform`].
+    pub fn capture(&self, entity: Entity) -> Option<UndoPhysicsSnapshot> {
+        let (transform, velocity, impulse, force, sleeping) = self.bodies.get(entity).ok()?;
+        Some(UndoPhysicsSnapshot {
+            entity,
+            transform: *transform,
+            velocity: velocity.copied(),
+            impulse: impulse.copied(),
+            force: force.copied(),
+            sleeping: sleeping.copied(),
+        })
+    }
+
+
+    /// Registers the edit of the body captured before it, which is undone by restoring the snapshot
+    /// and redone by restoring the state of the body now.
+    #[inline]
+    pub fn register(&mut self, before: UndoPhysicsSnapshot) {
+        self.register_with_meta(before, UndoMeta::default());
+    }
+
+
+    /// Like [`UndoPhysics::register`], together with its [`UndoMeta`], to which the entity is added.
+    pub fn register_with_meta(&mut self, before: UndoPhysicsSnapshot, meta: UndoMeta) {
+        let Some(after) = self.capture(before.entity) else {
+            return;
+        };
+        let meta = if meta.entities.contains(&before.entity) {
+            meta
+        } else {
+            meta.with_entity(before.entity)
+        };
+        self.scheduler.register_with_redo_and_meta(UndoPhysicsEvent(before), UndoPhysicsEvent(after), meta);
+    }
+}
+
+
+pub(crate) fn restore_physics_system(
+    mut er: MessageReader<UndoPhysicsEvent>,
+    mut bodies: Query<UndoPhysicsComponents>,
+) {
+    for UndoPhysicsEvent(snapshot) in er.read() {
+        let Ok((mut transform, velocity, impulse, force, sleeping)) = bodies.get_mut(snapshot.entity) else {
+            continue;
+        };
+        *transform = snapshot.transform;
+        if let (Some(mut velocity), Some(value)) = (velocity, snapshot.velocity) {
+            *velocity = value;
+        }
+        if let (Some(mut impulse), Some(value)) = (impulse, snapshot.impulse) {
+            *impulse = value;
+        }
+        if let (Some(mut force), Some(value)) = (force, snapshot.force) {
+            *force = value;
+        }
+        if let (Some(mut sleeping), Some(value)) = (sleeping, snapshot.sleeping) {
+            *sleeping = value;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Transform, Vec3};
+    use bevy_rapier3d::prelude::{Sleeping, Velocity};
+
+    use crate::prelude::{AppUndoEx, UndoPhysics, UndoRequester};
+    use crate::UndoPlugin;
+
+    fn request(app: &mut App, f: impl FnOnce(&mut UndoRequester)) {
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        f(&mut state.get_mut(app.world_mut()).unwrap());
+        app.update();
+    }
+
+
+    #[test]
+    fn restore_velocity_along_with_transform() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_physics();
+        let crate_body = app.world_mut().spawn((Transform::default(), Velocity::zero(), Sleeping::default())).id();
+        let mut physics = SystemState::<UndoPhysics>::new(app.world_mut());
+
+        let before = physics.get_mut(app.world_mut()).unwrap().capture(crate_body).unwrap();
+        let moved = Transform::from_xyz(4., 0., 0.);
+        *app.world_mut().get_mut::<Transform>(crate_body).unwrap() = moved;
+        *app.world_mut().get_mut::<Velocity>(crate_body).unwrap() = Velocity::linear(Vec3::X * 8.);
+        physics.get_mut(app.world_mut()).unwrap().register(before);
+        app.update();
+        // The body keeps flying after the edit until undone.
+        app.world_mut().get_mut::<Velocity>(crate_body).unwrap().linear = Vec3::Y * 30.;
+        app.world_mut().get_mut::<Sleeping>(crate_body).unwrap().sleeping = true;
+
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(*app.world().get::<Transform>(crate_body).unwrap(), Transform::default());
+        assert_eq!(*app.world().get::<Velocity>(crate_body).unwrap(), Velocity::zero());
+        assert!(!app.world().get::<Sleeping>(crate_body).unwrap().sleeping);
+
+        request(&mut app, |requester| requester.redo());
+        assert_eq!(*app.world().get::<Transform>(crate_body).unwrap(), moved);
+        assert_eq!(app.world().get::<Velocity>(crate_body).unwrap().linear, Vec3::X * 8.);
+    }
+}
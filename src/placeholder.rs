@@ -0,0 +1,131 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Changed, Component, Entity, Query, RemovedComponents, Res, ResMut, Resource};
+use bevy::platform::collections::HashMap;
+
+/// A label identifying an entity across respawns and scene reloads,
+/// to be referenced by entries via [`UndoEntityRef::Stable`].
+#[derive(Component, Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+#[repr(transparent)]
+pub struct UndoStableId(pub u64);
+
+
+/// An entity referenced by an entry, resolved via [`UndoEntities::resolve`] when the entry is undone or redone.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum UndoEntityRef {
+    /// The entity itself, which is no longer valid once despawned.
+    Entity(Entity),
+
+    /// Whichever entity currently carries the [`UndoStableId`].
+    Stable(UndoStableId),
+}
+
+
+impl From<Entity> for UndoEntityRef {
+    #[inline(always)]
+    fn from(entity: Entity) -> Self {
+        Self::Entity(entity)
+    }
+}
+
+
+impl From<UndoStableId> for UndoEntityRef {
+    #[inline(always)]
+    fn from(id: UndoStableId) -> Self {
+        Self::Stable(id)
+    }
+}
+
+
+/// The entities carrying each [`UndoStableId`], updated at the start of each frame.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoStableIndex {
+    entities: HashMap<UndoStableId, Entity>,
+    ids: HashMap<Entity, UndoStableId>,
+}
+
+
+/// Resolves the entities referenced by entries, typically in undo handlers.
+#[derive(SystemParam)]
+pub struct UndoEntities<'w> {
+    index: Res<'w, UndoStableIndex>,
+}
+
+
+impl<'w> UndoEntities<'w> {
+    /// Returns the entity currently referenced, `None` if no entity carries its [`UndoStableId`].
+    #[inline]
+    pub fn resolve(&self, entity: impl Into<UndoEntityRef>) -> Option<Entity> {
+        match entity.into() {
+            UndoEntityRef::Entity(entity) => Some(entity),
+            UndoEntityRef::Stable(id) => self.index.entities.get(&id).copied(),
+        }
+    }
+}
+
+
+pub(crate) fn index_stable_ids_system(
+    mut index: ResMut<UndoStableIndex>,
+    changed: Query<(Entity, &UndoStableId), Changed<UndoStableId>>,
+    mut removed: RemovedComponents<UndoStableId>,
+) {
+    for entity in removed.read() {
+        if let Some(id) = index.ids.remove(&entity) {
+            if index.entities.get(&id) == Some(&entity) {
+                index.entities.remove(&id);
+            }
+        }
+    }
+    for (entity, id) in changed.iter() {
+        if let Some(previous) = index.ids.insert(entity, *id) {
+            if index.entities.get(&previous) == Some(&entity) {
+                index.entities.remove(&previous);
+            }
+        }
+        index.entities.insert(*id, entity);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Entity, Message, MessageReader, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoEntities, UndoEntityRef, UndoScheduler, UndoStableId};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Recolor(UndoEntityRef);
+
+
+    #[derive(Resource, Default)]
+    struct Recolored(Vec<Option<Entity>>);
+
+
+    #[test]
+    fn resolve_respawned_entity() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.init_resource::<Recolored>();
+        app.add_undo_event::<Recolor>();
+        app.add_undo_handler::<Recolor, _>(|mut er: MessageReader<Recolor>, entities: UndoEntities, mut recolored: ResMut<Recolored>| {
+            for Recolor(entity) in er.read() {
+                recolored.0.push(entities.resolve(*entity));
+            }
+        });
+
+        let original = app.world_mut().spawn(UndoStableId(7)).id();
+        let mut state = SystemState::<UndoScheduler<Recolor>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(Recolor(UndoStableId(7).into()));
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().despawn(original);
+        let respawned = app.world_mut().spawn(UndoStableId(7)).id();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Recolored>().0, vec![Some(respawned)]);
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::{Message, Resource};
+
+use crate::history::UndoHistory;
+use crate::meta::UndoMeta;
+
+/// Computes the cost of undoing a step from the metadata of its entries, see [`UndoPolicy::with_cost`].
+pub type UndoCost = Box<dyn Fn(&[&UndoMeta]) -> u32 + Send + Sync + 'static>;
+
+
+/// Limits the undos allowed, typically replaced on each level of a puzzle game.
+///
+/// Undo requests exceeding the policy are dropped with [`UndoRefused`]. Redo requests are not limited.
+#[derive(Resource, Default)]
+pub struct UndoPolicy {
+    /// Refuses all undos, such as on levels played without undo.
+    pub disabled: bool,
+
+    /// The count of undos allowed, `None` for no limit.
+    pub max_undos: Option<usize>,
+
+    cost: Option<(u32, UndoCost)>,
+    pub(crate) used: usize,
+}
+
+
+impl UndoPolicy {
+    /// Returns the policy refusing all undos.
+    #[inline]
+    pub fn disabled() -> Self {
+        Self {
+            disabled: true,
+            ..Self::default()
+        }
+    }
+
+
+    #[inline]
+    pub fn with_max_undos(mut self, max_undos: usize) -> Self {
+        self.max_undos = Some(max_undos);
+        self
+    }
+
+
+    /// Charges each undo the cost computed by the callback, refusing the undos costing more than the budget left.
+    #[inline]
+    pub fn with_cost(mut self, budget: u32, cost: impl Fn(&[&UndoMeta]) -> u32 + Send + Sync + 'static) -> Self {
+        self.cost = Some((budget, Box::new(cost)));
+        self
+    }
+
+
+    /// Returns the count of undos allowed so far.
+    #[inline(always)]
+    pub fn undos_used(&self) -> usize {
+        self.used
+    }
+
+
+    /// Returns the count of undos left, `None` if not limited.
+    #[inline]
+    pub fn undos_left(&self) -> Option<usize> {
+        self.max_undos.map(|max| max.saturating_sub(self.used))
+    }
+
+
+    /// Returns the budget left for [`UndoPolicy::with_cost`], `None` if undos cost nothing.
+    #[inline]
+    pub fn budget_left(&self) -> Option<u32> {
+        self.cost.as_ref().map(|(budget, _)| *budget)
+    }
+
+
+    /// Charges the undo of the slots, or returns why it is refused.
+    pub(crate) fn charge(&mut self, history: &UndoHistory, slots: &[usize]) -> Result<(), UndoRefusal> {
+        if self.disabled {
+            return Err(UndoRefusal::Disabled);
+        }
+        if let Some(max) = self.max_undos.filter(|max| *max <= self.used) {
+            return Err(UndoRefusal::LimitReached { max });
+        }
+        if let Some((budget, cost)) = self.cost.as_mut() {
+            let metas: Vec<&UndoMeta> = slots
+                .iter()
+                .flat_map(|no| history.slot_entries(*no))
+                .map(|entry| &entry.meta)
+                .collect();
+            let cost = cost(&metas);
+            if *budget < cost {
+                return Err(UndoRefusal::InsufficientBudget { cost, budget: *budget });
+            }
+            *budget -= cost;
+        }
+        self.used += 1;
+        Ok(())
+    }
+}
+
+
+/// Why an undo has been refused by the [`UndoPolicy`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UndoRefusal {
+    Disabled,
+
+    /// All the undos allowed have been used.
+    LimitReached {
+        max: usize,
+    },
+
+    /// The undo costs more than the budget left.
+    InsufficientBudget {
+        cost: u32,
+        budget: u32,
+    },
+}
+
+
+/// Sent when an undo request is dropped by the [`UndoPolicy`].
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoRefused {
+    pub reason: UndoRefusal,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{UndoPolicy, UndoRefusal, UndoRefused};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    fn refused(harness: &mut UndoTestHarness<Move>) -> Vec<UndoRefusal> {
+        harness.app().world_mut().resource_mut::<Messages<UndoRefused>>().drain().map(|refused| refused.reason).collect()
+    }
+
+
+    #[test]
+    fn refuse_undos_over_limit() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.insert_resource(UndoPolicy::default().with_max_undos(1));
+        });
+        harness.register(Move(1));
+        harness.register(Move(2));
+        harness.undo();
+        harness.expect([Move(2)]);
+        harness.undo();
+        harness.expect([]);
+        assert_eq!(refused(&mut harness), vec![UndoRefusal::LimitReached { max: 1 }]);
+
+        harness.app().insert_resource(UndoPolicy::default());
+        harness.undo();
+        harness.expect([Move(1)]);
+    }
+
+
+    #[test]
+    fn refuse_undos_when_disabled() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.insert_resource(UndoPolicy::disabled());
+        });
+        harness.register(Move(1));
+        harness.undo();
+        harness.expect([]);
+        assert_eq!(refused(&mut harness), vec![UndoRefusal::Disabled]);
+    }
+
+
+    #[test]
+    fn charge_undos_against_budget() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.insert_resource(UndoPolicy::default().with_cost(3, |metas| 2 * metas.len() as u32));
+        });
+        harness.register(Move(1));
+        harness.register(Move(2));
+        harness.undo();
+        harness.expect([Move(2)]);
+        assert_eq!(harness.app().world().resource::<UndoPolicy>().budget_left(), Some(1));
+        harness.undo();
+        harness.expect([]);
+        assert_eq!(refused(&mut harness), vec![UndoRefusal::InsufficientBudget { cost: 2, budget: 1 }]);
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::Entity;
+
+use crate::meta::UndoMeta;
+
+/// The count of buffers each kind of pooled buffer is capped at.
+const POOL_CAPACITY: usize = 256;
+
+
+/// How the buffers of the metadata kept in the history are recycled, see [`UndoRequester::pool_stats`](crate::prelude::UndoRequester::pool_stats).
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct UndoPoolStats {
+    /// The count of entries whose metadata has been returned to the pool when dropped from the history.
+    pub recycled: usize,
+
+    /// The count of buffers taken from the pool instead of being allocated.
+    pub reused: usize,
+
+    /// The count of buffers allocated since the pool was empty.
+    pub allocated: usize,
+}
+
+
+/// Recycles the strings and entity lists of the metadata of dropped entries for the metadata of newly registered ones.
+#[derive(Debug, Default)]
+pub(crate) struct UndoMetaPool {
+    strings: Vec<String>,
+    tags: Vec<Vec<String>>,
+    entities: Vec<Vec<Entity>>,
+    pub stats: UndoPoolStats,
+}
+
+
+impl UndoMetaPool {
+    /// Copies the metadata into buffers taken from the pool.
+    pub fn copy(&mut self, meta: &UndoMeta) -> UndoMeta {
+        let tag = self.string(&meta.tag);
+        let mut tags = Self::take(&mut self.tags, meta.tags.len(), &mut self.stats);
+        for t in &meta.tags {
+            let t = self.string(t);
+            tags.push(t);
+        }
+        let mut entities = Self::take(&mut self.entities, meta.entities.len(), &mut self.stats);
+        entities.extend_from_slice(&meta.entities);
+        let mut checked_entities = Self::take(&mut self.entities, meta.checked_entities.len(), &mut self.stats);
+        checked_entities.extend_from_slice(&meta.checked_entities);
+        UndoMeta {
+            tag,
+            tags,
+            entities,
+            channel: meta.channel,
+            importance: meta.importance,
+            link: meta.link,
+            atomic_group: meta.atomic_group,
+            sticky: meta.sticky,
+            requires_confirmation: meta.requires_confirmation,
+            weak_refs: meta.weak_refs.clone(),
+            checked_entities,
+            stamp: meta.stamp,
+            user_data: meta.user_data.clone(),
+        }
+    }
+
+
+    /// Returns the buffers of the metadata to the pool.
+    pub fn recycle(&mut self, meta: UndoMeta) {
+        self.stats.recycled += 1;
+        let UndoMeta { tag, mut tags, entities, checked_entities, .. } = meta;
+        for t in tags.drain(..).chain(Some(tag)) {
+            Self::put(&mut self.strings, t, String::clear);
+        }
+        Self::put(&mut self.tags, tags, Vec::clear);
+        Self::put(&mut self.entities, entities, Vec::clear);
+        Self::put(&mut self.entities, checked_entities, Vec::clear);
+    }
+
+
+    fn string(&mut self, s: &str) -> String {
+        if s.is_empty() {
+            return String::new();
+        }
+        let mut buffer = Self::take(&mut self.strings, s.len(), &mut self.stats);
+        buffer.push_str(s);
+        buffer
+    }
+
+
+    /// Takes a buffer from the pool if anything is to be written into it, as empty ones do not allocate.
+    fn take<T: Default>(pool: &mut Vec<T>, len: usize, stats: &mut UndoPoolStats) -> T {
+        if len == 0 {
+            return T::default();
+        }
+        match pool.pop() {
+            Some(buffer) => {
+                stats.reused += 1;
+                buffer
+            }
+            None => {
+                stats.allocated += 1;
+                T::default()
+            }
+        }
+    }
+
+
+    fn put<T>(pool: &mut Vec<T>, mut buffer: T, clear: fn(&mut T)) {
+        if POOL_CAPACITY <= pool.len() {
+            return;
+        }
+        clear(&mut buffer);
+        pool.push(buffer);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Message;
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoMeta, UndoRequester, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Paint;
+
+
+    #[test]
+    fn reuse_metadata_of_undone_entries() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Paint>();
+
+        let register = |app: &mut App| {
+            let mut state = SystemState::<UndoScheduler<Paint>>::new(app.world_mut());
+            state.get_mut(app.world_mut()).unwrap().register_with_meta(Paint, UndoMeta::tagged("brush").with_tag("layer:1"));
+            state.apply(app.world_mut());
+            app.update();
+        };
+        register(&mut app);
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        register(&mut app);
+
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        let stats = state.get_mut(app.world_mut()).unwrap().pool_stats();
+        assert_eq!(stats.recycled, 1);
+        assert_eq!(stats.allocated, 3);
+        assert_eq!(stats.reused, 3);
+    }
+}
@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+
+use bevy::app::{App, PreUpdate, Update};
+use bevy::prelude::{IntoScheduleConfigs, Resource, SystemSet};
+use bevy::platform::collections::HashMap;
+
+use crate::payload::UndoPayload;
+
+/// Contains the systems dispatching and handling the events of one type, keyed by its type name.
+#[derive(SystemSet, Debug, Hash, Eq, PartialEq, Copy, Clone)]
+pub(crate) struct UndoHandlerSet(&'static str);
+
+
+impl UndoHandlerSet {
+    #[inline(always)]
+    pub fn of<E: UndoPayload>() -> Self {
+        Self(std::any::type_name::<E>())
+    }
+}
+
+
+/// Contains the handler sets of all types in [`Update`](bevy::prelude::Update).
+#[derive(SystemSet, Debug, Hash, Eq, PartialEq, Copy, Clone)]
+pub(crate) struct UndoAllHandlersSet;
+
+
+/// The priorities configured via [`AppUndoEx::configure_undo_priority`](crate::prelude::AppUndoEx::configure_undo_priority).
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoPriorities(HashMap<&'static str, i32>);
+
+
+impl UndoPriorities {
+    #[inline(always)]
+    pub fn get(&self, type_name: &str) -> Option<i32> {
+        self.0.get(type_name).copied()
+    }
+}
+
+
+/// Orders the handler set of `E` against the sets of the other types with a configured priority, the higher first.
+pub(crate) fn configure_priority<E: UndoPayload>(app: &mut App, priority: i32) {
+    let type_name = std::any::type_name::<E>();
+    let mut priorities = app.world_mut().get_resource_or_insert_with(UndoPriorities::default);
+    let others: Vec<(&'static str, i32)> = priorities.0
+        .iter()
+        .filter(|(other, _)| **other != type_name)
+        .map(|(other, priority)| (*other, *priority))
+        .collect();
+    priorities.0.insert(type_name, priority);
+
+    for (other, other_priority) in others {
+        let (first, then) = match priority.cmp(&other_priority) {
+            Ordering::Greater => (type_name, other),
+            Ordering::Less => (other, type_name),
+            Ordering::Equal => continue
+        };
+        app.configure_sets(PreUpdate, UndoHandlerSet(first).before(UndoHandlerSet(then)));
+        app.configure_sets(Update, UndoHandlerSet(first).before(UndoHandlerSet(then)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, MessageReader, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Respawn;
+
+
+    #[derive(Message, Clone)]
+    struct RestoreComponents;
+
+
+    #[derive(Resource, Default)]
+    struct Applied(Vec<&'static str>);
+
+
+    #[test]
+    fn handle_higher_priority_first() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.init_resource::<Applied>();
+        app.add_undo_event::<RestoreComponents>();
+        app.add_undo_event::<Respawn>();
+        app.configure_undo_frame_grouping(true);
+        app.configure_undo_priority::<RestoreComponents>(0);
+        app.configure_undo_priority::<Respawn>(10);
+        app.add_undo_handler::<RestoreComponents, _>(|mut er: MessageReader<RestoreComponents>, mut applied: ResMut<Applied>| {
+            applied.0.extend(er.read().map(|_| "components"));
+        });
+        app.add_undo_handler::<Respawn, _>(|mut er: MessageReader<Respawn>, mut applied: ResMut<Applied>| {
+            applied.0.extend(er.read().map(|_| "respawn"));
+        });
+
+        let mut components = SystemState::<UndoScheduler<RestoreComponents>>::new(app.world_mut());
+        let mut respawn = SystemState::<UndoScheduler<Respawn>>::new(app.world_mut());
+        components.get_mut(app.world_mut()).unwrap().register(RestoreComponents);
+        respawn.get_mut(app.world_mut()).unwrap().register(Respawn);
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Applied>().0, vec!["respawn", "components"]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::ResMut;
+
+use crate::UndoAreas;
+use crate::counter::UndoCounter;
+use crate::history::UndoHistory;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::storage::UndoStorage;
+use crate::undo_event::UndoEntry;
+
+/// Direct read and write access to the typed stacks and the slot counter, for advanced integrations
+/// such as custom history UIs or merging histories from outside the app.
+///
+/// This bypasses the whole pipeline: no hooks, vetoes, policies, capacities or events are involved,
+/// and only [`UndoRawAccess::push_entry`] keeps the type-erased history behind [`UndoView`](crate::prelude::UndoView)
+/// in sync. Entries removed from the stacks leave slots the history still lists, which undo nothing once requested,
+/// while the `debug_invariants` feature reports entries pushed to the stacks alone.
+///
+/// Prefer [`UndoScheduler`](crate::prelude::UndoScheduler) and [`UndoRequester`](crate::prelude::UndoRequester)
+/// whenever they suffice.
+#[derive(SystemParam)]
+pub struct UndoRawAccess<'w> {
+    areas: ResMut<'w, UndoAreas>,
+    history: ResMut<'w, UndoHistory>,
+    counter: ResMut<'w, UndoCounter>,
+}
+
+
+impl<'w> UndoRawAccess<'w> {
+    /// Returns the slot number allocated last, the next entry taking a greater one.
+    #[inline(always)]
+    pub fn counter(&self) -> usize {
+        **self.counter
+    }
+
+
+    /// Sets the slot number allocated last.
+    ///
+    /// Lowering it below the slots already registered makes new entries share their slots.
+    #[inline(always)]
+    pub fn set_counter(&mut self, no: usize) {
+        self.counter.set(no);
+    }
+
+
+    /// Returns the stack of the entries of `E` which can be undone.
+    ///
+    /// Panics if `E` has not been added via [`AppUndoEx::add_undo_event`](crate::prelude::AppUndoEx::add_undo_event).
+    #[inline]
+    pub fn stack<E: UndoPayload>(&self) -> &dyn UndoStorage<E> {
+        &*self.areas.registered::<E>().0
+    }
+
+
+    /// Returns the stack of the entries of `E` which can be undone, whose payloads can be rewritten in place
+    /// by draining and pushing them back.
+    #[inline]
+    pub fn stack_mut<E: UndoPayload>(&mut self) -> &mut dyn UndoStorage<E> {
+        &mut *self.areas.registered_mut::<E>().0
+    }
+
+
+    /// Returns the undone entries of `E` waiting for redo, from the oldest undone.
+    #[inline]
+    pub fn redo_stack<E: UndoPayload>(&self) -> &[UndoEntry<E>] {
+        &self.areas.areas::<E>().redo.0
+    }
+
+
+    #[inline]
+    pub fn redo_stack_mut<E: UndoPayload>(&mut self) -> &mut Vec<UndoEntry<E>> {
+        &mut self.areas.areas_mut::<E>().redo.0
+    }
+
+
+    /// Pushes an entry both to the stack of `E` and to the history, as if registered without a [`Time`](bevy::prelude::Time),
+    /// which invalidates the redo history of its channel like a regular registration.
+    ///
+    /// The counter is raised to the slot of the entry, so later registrations do not join it.
+    pub fn push_entry<E: UndoPayload>(&mut self, entry: UndoEntry<E>, meta: UndoMeta) {
+        let meta = self.history.copy_meta(&meta);
+        let no = entry.no;
+        let redoable = entry.redo.is_some();
+        self.areas.registered_mut::<E>().push_with_meta(entry, &meta);
+        self.history.push::<E>(no, meta, redoable, Duration::ZERO);
+        if self.counter() < no {
+            self.counter.set(no);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Message;
+
+    use crate::prelude::{UndoEntry, UndoMeta, UndoRawAccess};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    fn with_raw_access(harness: &mut UndoTestHarness<Move>, f: impl FnOnce(&mut UndoRawAccess)) {
+        let world = harness.app().world_mut();
+        let mut state = SystemState::<UndoRawAccess>::new(world);
+        f(&mut state.get_mut(world).unwrap());
+    }
+
+
+    #[test]
+    fn rewrite_and_merge_entries() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.register(Move(1));
+        harness.frames(2);
+
+        with_raw_access(&mut harness, |raw| {
+            let stack = raw.stack_mut::<Move>();
+            for mut entry in stack.drain() {
+                entry.inner.0 *= 10;
+                stack.push(entry);
+            }
+            let no = raw.counter() + 1;
+            raw.push_entry(UndoEntry {
+                inner: Move(2),
+                redo: Some(Move(-2)),
+                no,
+            }, UndoMeta::default());
+            assert_eq!(raw.counter(), no);
+            assert_eq!(raw.stack::<Move>().len(), 2);
+        });
+
+        harness.undo();
+        harness.expect([Move(2)]);
+        with_raw_access(&mut harness, |raw| assert_eq!(raw.redo_stack::<Move>().len(), 1));
+        harness.redo();
+        harness.expect([Move(-2)]);
+        harness.undo();
+        harness.undo();
+        harness.expect([Move(2), Move(10)]);
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Commands, MessageReader, ResMut, Resource, World};
+use bevy::platform::collections::HashMap;
+
+use crate::erased::UndoAnyScheduler;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::suspend::UndoRecording;
+use crate::undo_event::UndoEvent;
+
+/// Applies a recorded action and registers it again in the slot.
+type UndoMacroStep = Arc<dyn Fn(&mut World, usize, &UndoMeta) + Send + Sync + 'static>;
+
+
+/// The recorded macros, and the one being recorded.
+#[derive(Resource, Default)]
+pub(crate) struct UndoMacroLibrary {
+    recording: Option<(String, Vec<UndoMacroStep>)>,
+    macros: HashMap<String, Vec<UndoMacroStep>>,
+}
+
+
+/// Records the actions registered in the meantime as a named macro, and replays macros as new entries.
+///
+/// Only the entries of the types set up via [`AppUndoEx::add_undo_macros`](crate::prelude::AppUndoEx::add_undo_macros)
+/// which have a redo-event are recorded, since the redo-event is what applies the action again.
+#[derive(SystemParam)]
+pub struct UndoMacros<'w, 's> {
+    library: ResMut<'w, UndoMacroLibrary>,
+    commands: Commands<'w, 's>,
+    scheduler: UndoAnyScheduler<'w, 's>,
+}
+
+
+impl<'w, 's> UndoMacros<'w, 's> {
+    /// Starts recording a macro under the name, discarding the recording in progress if any.
+    #[inline]
+    pub fn start_recording(&mut self, name: impl Into<String>) {
+        self.library.recording = Some((name.into(), Vec::new()));
+    }
+
+
+    /// Stops recording, keeping the macro unless it is empty, and returns its count of actions.
+    ///
+    /// The actions registered in this frame are not recorded yet, so it is to be called in a later frame.
+    pub fn stop_recording(&mut self) -> usize {
+        let Some((name, steps)) = self.library.recording.take() else {
+            return 0;
+        };
+        let len = steps.len();
+        if 0 < len {
+            self.library.macros.insert(name, steps);
+        }
+        len
+    }
+
+
+    #[inline(always)]
+    pub fn is_recording(&self) -> bool {
+        self.library.recording.is_some()
+    }
+
+
+    /// Returns the names of the recorded macros, in no particular order.
+    #[inline]
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.library.macros.keys().map(String::as_str)
+    }
+
+
+    /// Removes the macro, returning true if it existed.
+    #[inline]
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.library.macros.remove(name).is_some()
+    }
+
+
+    /// Applies the actions of the macro again by sending their redo-events, and registers them as a single entry
+    /// tagged with the name, so the whole replay is undone at once.
+    ///
+    /// Returns false if there is no such macro.
+    pub fn replay(&mut self, name: &str) -> bool {
+        let Some(steps) = self.library.macros.get(name).cloned() else {
+            return false;
+        };
+        let meta = UndoMeta::tagged(name);
+        let meta = self.scheduler.routed(meta);
+        let no = self.scheduler.next_slot(meta.channel);
+        self.commands.queue(move |world: &mut World| {
+            for step in steps {
+                step(world, no, &meta);
+            }
+        });
+        true
+    }
+}
+
+
+/// Records the entries of `E` registered while a macro is being recorded.
+pub(crate) fn record_macro_steps_system<E: UndoPayload>(
+    mut er: MessageReader<UndoEvent<E>>,
+    mut library: ResMut<UndoMacroLibrary>,
+    recording: UndoRecording<E>,
+) {
+    let Some((_, steps)) = library.recording.as_mut().filter(|_| !recording.is_suspended()) else {
+        er.clear();
+        return;
+    };
+    for e in er.read() {
+        let Some(redo) = e.redo.as_ref() else {
+            continue;
+        };
+        let (inner, redo) = (e.inner.duplicate(), redo.duplicate());
+        steps.push(Arc::new(move |world: &mut World, no: usize, meta: &UndoMeta| {
+            world.write_message(redo.duplicate());
+            world.write_message(UndoEvent {
+                inner: inner.duplicate(),
+                redo: Some(redo.duplicate()),
+                no,
+                meta: meta.clone(),
+                #[cfg(feature = "serde")]
+                registered_on: None,
+            });
+        }));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoMacros, UndoMeta, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    /// Moves by the offset.
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Nudge(i32);
+
+
+    fn sent(app: &mut App) -> Vec<Nudge> {
+        app.world_mut().resource_mut::<Messages<Nudge>>().drain().collect()
+    }
+
+
+    #[test]
+    fn replay_recorded_actions_as_one_entry() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Nudge>();
+        app.add_undo_macros::<Nudge>();
+        let mut macros = SystemState::<UndoMacros>::new(app.world_mut());
+        let mut scheduler = SystemState::<UndoScheduler<Nudge>>::new(app.world_mut());
+
+        macros.get_mut(app.world_mut()).unwrap().start_recording("nudge twice");
+        scheduler.get_mut(app.world_mut()).unwrap().push(Nudge(-1), Some(Nudge(1)), UndoMeta::default());
+        app.update();
+        scheduler.get_mut(app.world_mut()).unwrap().register(Nudge(5));
+        scheduler.get_mut(app.world_mut()).unwrap().push(Nudge(-2), Some(Nudge(2)), UndoMeta::default());
+        app.update();
+        assert_eq!(macros.get_mut(app.world_mut()).unwrap().stop_recording(), 2);
+        assert_eq!(macros.get_mut(app.world_mut()).unwrap().names().collect::<Vec<_>>(), vec!["nudge twice"]);
+        sent(&mut app);
+
+        assert!(macros.get_mut(app.world_mut()).unwrap().replay("nudge twice"));
+        macros.apply(app.world_mut());
+        assert_eq!(sent(&mut app), vec![Nudge(1), Nudge(2)]);
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(sent(&mut app), vec![Nudge(-2), Nudge(-1)]);
+        assert!(!macros.get_mut(app.world_mut()).unwrap().replay("missing"));
+    }
+}
@@ -0,0 +1,176 @@
+use std::any::TypeId;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Res, Resource};
+
+use crate::UndoAreas;
+use crate::channel::{UndoStackConfig, UndoTypeConfigs};
+use crate::payload::UndoPayload;
+use crate::priority::UndoPriorities;
+use crate::unhandled::UndoHandlers;
+
+/// The types added via [`AppUndoEx::add_undo_event`](crate::prelude::AppUndoEx::add_undo_event), in the order added.
+#[derive(Resource, Default)]
+pub(crate) struct UndoTypeRegistry(Vec<UndoTypeRegistration>);
+
+
+struct UndoTypeRegistration {
+    name: &'static str,
+    type_id: TypeId,
+
+    /// Counts the entries of the type which can be undone and redone.
+    depths: fn(&UndoAreas) -> (usize, usize),
+}
+
+
+impl UndoTypeRegistry {
+    /// Adds `E` unless it is already listed.
+    pub fn register<E: UndoPayload>(&mut self) {
+        if self.0.iter().any(|registration| registration.type_id == TypeId::of::<E>()) {
+            return;
+        }
+        self.0.push(UndoTypeRegistration {
+            name: std::any::type_name::<E>(),
+            type_id: TypeId::of::<E>(),
+            depths: |areas| {
+                let areas = areas.areas::<E>();
+                (areas.registered.0.len(), areas.redo.0.len())
+            },
+        });
+    }
+}
+
+
+/// Describes a type added via [`AppUndoEx::add_undo_event`](crate::prelude::AppUndoEx::add_undo_event).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UndoTypeInfo<'a> {
+    /// The full path of the type, as returned by [`std::any::type_name`].
+    pub name: &'static str,
+    pub type_id: TypeId,
+
+    /// The capacity set via [`AppUndoEx::configure_undo_type`](crate::prelude::AppUndoEx::configure_undo_type).
+    pub config: Option<&'a UndoStackConfig>,
+
+    /// The priority set via [`AppUndoEx::configure_undo_priority`](crate::prelude::AppUndoEx::configure_undo_priority).
+    pub priority: Option<i32>,
+
+    /// Whether a handler has been added via [`AppUndoEx::add_undo_handler`](crate::prelude::AppUndoEx::add_undo_handler)
+    /// or its siblings, or the type has been marked as handled.
+    pub handled: bool,
+
+    /// The count of entries which can be undone, across all channels.
+    pub depth: usize,
+
+    /// The count of entries which can be redone, across all channels.
+    pub redo_depth: usize,
+}
+
+
+/// Lists the undo-event types added to the app together with their settings and current depths,
+/// for editor tooling and debug consoles to display and validate the configuration at runtime.
+#[derive(SystemParam)]
+pub struct UndoTypes<'w> {
+    registry: Res<'w, UndoTypeRegistry>,
+    areas: Res<'w, UndoAreas>,
+    configs: Option<Res<'w, UndoTypeConfigs>>,
+    priorities: Option<Res<'w, UndoPriorities>>,
+    handlers: Option<Res<'w, UndoHandlers>>,
+}
+
+
+impl<'w> UndoTypes<'w> {
+    /// Iterates the types in the order added.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = UndoTypeInfo<'_>> {
+        self.registry.0.iter().map(|registration| self.info(registration))
+    }
+
+
+    #[inline]
+    pub fn get<E: UndoPayload>(&self) -> Option<UndoTypeInfo<'_>> {
+        self.get_by_id(TypeId::of::<E>())
+    }
+
+
+    #[inline]
+    pub fn get_by_id(&self, type_id: TypeId) -> Option<UndoTypeInfo<'_>> {
+        self.registry.0
+            .iter()
+            .find(|registration| registration.type_id == type_id)
+            .map(|registration| self.info(registration))
+    }
+
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.registry.0.len()
+    }
+
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.registry.0.is_empty()
+    }
+
+
+    fn info(&self, registration: &UndoTypeRegistration) -> UndoTypeInfo<'_> {
+        let (depth, redo_depth) = (registration.depths)(&self.areas);
+        UndoTypeInfo {
+            name: registration.name,
+            type_id: registration.type_id,
+            config: self.configs.as_ref().and_then(|configs| configs.0.get(&registration.type_id)).map(|(_, config)| config),
+            priority: self.priorities.as_ref().and_then(|priorities| priorities.get(registration.name)),
+            handled: self.handlers.as_ref().is_some_and(|handlers| handlers.handled.contains(registration.name)),
+            depth,
+            redo_depth,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, MessageReader};
+
+    use crate::prelude::{AppUndoEx, UndoStackConfig, UndoTypes};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Paint;
+
+
+    #[test]
+    fn list_types_with_settings_and_depths() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.add_undo_event::<Paint>();
+            app.configure_undo_type::<Paint>(UndoStackConfig::with_capacity(8));
+            app.configure_undo_priority::<Paint>(2);
+            app.add_undo_handler::<Paint, _>(|mut er: MessageReader<Paint>| er.clear());
+        });
+        harness.register(Move(1));
+        harness.register(Move(2));
+        harness.undo();
+
+        let mut state = SystemState::<UndoTypes>::new(harness.app().world_mut());
+        let types = state.get(harness.app().world()).unwrap();
+        // The plugin adds its own types depending on the features.
+        let names: Vec<&str> = types.iter().map(|info| info.name).filter(|name| name.contains("tests")).collect();
+        assert_eq!(names, vec![std::any::type_name::<Move>(), std::any::type_name::<Paint>()]);
+
+        let moves = types.get::<Move>().unwrap();
+        assert_eq!((moves.type_id, moves.depth, moves.redo_depth), (TypeId::of::<Move>(), 1, 0));
+        assert_eq!((moves.config, moves.priority), (None, None));
+        let paint = types.get::<Paint>().unwrap();
+        assert_eq!(paint.config.and_then(|config| config.capacity), Some(8));
+        assert_eq!((paint.priority, paint.handled, paint.depth), (Some(2), true, 0));
+        assert!(types.get_by_id(TypeId::of::<u8>()).is_none());
+    }
+}
@@ -1,15 +1,82 @@
+use std::sync::Arc;
+
+use bevy::asset::UntypedAssetId;
 use bevy::ecs::system::SystemParam;
-use bevy::prelude::{Event, EventWriter, Res};
-use crate::counter::UndoCounter;
+use bevy::prelude::{Entity, Message, MessageWriter, Res};
+
+use crate::channel::UndoChannel;
+use crate::collab::UndoSiteId;
+use crate::cooldown::UndoCooldown;
+use crate::document::UndoDocumentState;
+use crate::failure::UndoFailureStats;
+use crate::gc::UndoGcPolicy;
+use crate::history::UndoHistory;
+use crate::meta::UndoMeta;
+use crate::pacing::UndoRequestQueue;
+use crate::pool::UndoPoolStats;
+use crate::selective::UndoSelectiveMode;
+
+#[derive(Message, Clone)]
+pub(crate) enum RequestUndoEvent {
+    Latest(UndoChannel),
+    Redo(UndoChannel),
 
-#[derive(Event, Default, PartialEq, Debug, Copy, Clone, )]
-pub(crate) struct RequestUndoEvent(pub UndoCounter);
+    /// Undoes the latest entry of the channel on behalf of another client, see [`UndoRequester::undo_channel_as`].
+    LatestAs(UndoChannel, UndoSiteId),
+    RedoAs(UndoChannel, UndoSiteId),
+    Matching(Arc<dyn Fn(&UndoMeta) -> bool + Send + Sync + 'static>),
+
+    /// Undoes the slot wherever it is in the history, see [`UndoRequester::undo_entry`].
+    Selective(usize, UndoSelectiveMode),
+    Collect(Arc<dyn Fn(&UndoMeta) -> bool + Send + Sync + 'static>),
+    CloseChannel(UndoChannel),
+    DryRun(UndoChannel),
+    PreviewRedo(UndoChannel),
+
+    /// Undoes the latest entry of the channel while replaying a saved history,
+    /// bypassing the cooldown, the vetoes, the confirmations and the policy.
+    #[cfg(feature = "serde")]
+    Restore(UndoChannel),
+}
+
+
+impl RequestUndoEvent {
+    /// Returns true if the request undoes or redoes an entry, as opposed to dropping entries.
+    #[inline(always)]
+    pub fn is_operation(&self) -> bool {
+        matches!(self, Self::Latest(_) | Self::Redo(_) | Self::LatestAs(..) | Self::RedoAs(..) | Self::Matching(_) | Self::Selective(..))
+    }
+
+
+    /// Returns the client on whose behalf the request is made, `None` for local requests.
+    #[inline(always)]
+    pub fn requester(&self) -> Option<UndoSiteId> {
+        match self {
+            Self::LatestAs(_, site) | Self::RedoAs(_, site) => Some(*site),
+            _ => None
+        }
+    }
+
+
+    /// Returns true if the request replays a saved history, see [`UndoSnapshot::restore`](crate::prelude::UndoSnapshot::restore).
+    #[inline(always)]
+    pub fn is_restore(&self) -> bool {
+        #[cfg(feature = "serde")]
+        return matches!(self, Self::Restore(_));
+        #[cfg(not(feature = "serde"))]
+        false
+    }
+}
 
 
 #[derive(SystemParam)]
 pub struct UndoRequester<'w> {
-    ew: EventWriter<'w, RequestUndoEvent>,
-    counter: Res<'w, UndoCounter>
+    ew: MessageWriter<'w, RequestUndoEvent>,
+    history: Res<'w, UndoHistory>,
+    cooldown: Res<'w, UndoCooldown>,
+    queue: Res<'w, UndoRequestQueue>,
+    documents: Res<'w, UndoDocumentState>,
+    failures: Res<'w, UndoFailureStats>,
 }
 
 
@@ -18,6 +85,319 @@ impl<'w> UndoRequester<'w> {
     /// This will send　the most recent event registered via [`UndoScheduler`](crate::undo_event::UndoScheduler).
     #[inline(always)]
     pub fn undo(&mut self) {
-        self.ew.send(RequestUndoEvent(*self.counter));
+        self.undo_channel(UndoChannel::DEFAULT);
+    }
+
+
+    /// request undo-operation for the most recent entry registered to the channel.
+    ///
+    /// See [`UndoScheduler::register_to`](crate::undo_event::UndoScheduler::register_to).
+    #[inline(always)]
+    pub fn undo_channel(&mut self, channel: impl Into<UndoChannel>) {
+        self.ew.write(RequestUndoEvent::Latest(channel.into()));
+    }
+
+
+    /// request undo-operation for the most recent entry registered in the window,
+    /// see [`AppUndoEx::enable_undo_windows`](crate::prelude::AppUndoEx::enable_undo_windows).
+    #[inline(always)]
+    pub fn undo_for_window(&mut self, window: Entity) {
+        self.undo_channel(UndoChannel::for_window(window));
+    }
+
+
+    /// request undo-operation for the most recent entry registered while the scene was loaded,
+    /// even if it is unloaded now, see [`AppUndoEx::enable_undo_scenes`](crate::prelude::AppUndoEx::enable_undo_scenes).
+    #[inline(always)]
+    pub fn undo_for_scene(&mut self, scene: impl Into<UntypedAssetId>) {
+        self.undo_channel(UndoChannel::for_scene(scene));
+    }
+
+
+    /// request `n` undo-operations, each undoing the next most recent entry.
+    ///
+    /// This is the same as calling [`UndoRequester::undo`] `n` times,
+    /// all of them are resolved in the next frame unless limited via [`AppUndoEx::configure_undo_requests_per_frame`](crate::prelude::AppUndoEx::configure_undo_requests_per_frame).
+    #[inline(always)]
+    pub fn undo_count(&mut self, n: usize) {
+        self.undo_channel_count(UndoChannel::DEFAULT, n);
+    }
+
+
+    #[inline]
+    pub fn undo_channel_count(&mut self, channel: impl Into<UndoChannel>, n: usize) {
+        let channel = channel.into();
+        self.ew.write_batch((0..n).map(|_| RequestUndoEvent::Latest(channel)));
+    }
+
+
+    /// request undo-operation for the most recent entry of the channel on behalf of another client,
+    /// such as a request received by the host of a networked session.
+    ///
+    /// The requester is passed to the authorizers added via [`AppUndoEx::add_undo_authorizer`](crate::prelude::AppUndoEx::add_undo_authorizer).
+    #[inline(always)]
+    pub fn undo_channel_as(&mut self, channel: impl Into<UndoChannel>, requester: UndoSiteId) {
+        self.ew.write(RequestUndoEvent::LatestAs(channel.into(), requester));
     }
-}
\ No newline at end of file
+
+
+    /// request redo-operation.
+    ///
+    /// This will send the redo-event of the most recently undone entry,
+    /// entries registered without a redo-event can't be redone.
+    #[inline(always)]
+    pub fn redo(&mut self) {
+        self.redo_channel(UndoChannel::DEFAULT);
+    }
+
+
+    /// request redo-operation for the most recently undone entry of the channel.
+    #[inline(always)]
+    pub fn redo_channel(&mut self, channel: impl Into<UndoChannel>) {
+        self.ew.write(RequestUndoEvent::Redo(channel.into()));
+    }
+
+
+    #[inline(always)]
+    pub fn redo_for_window(&mut self, window: Entity) {
+        self.redo_channel(UndoChannel::for_window(window));
+    }
+
+
+    /// request redo-operation for the most recent entry undone in the scene, see [`UndoRequester::undo_for_scene`].
+    #[inline(always)]
+    pub fn redo_for_scene(&mut self, scene: impl Into<UntypedAssetId>) {
+        self.redo_channel(UndoChannel::for_scene(scene));
+    }
+
+
+    /// request `n` redo-operations, see [`UndoRequester::undo_count`].
+    #[inline(always)]
+    pub fn redo_count(&mut self, n: usize) {
+        self.redo_channel_count(UndoChannel::DEFAULT, n);
+    }
+
+
+    #[inline]
+    pub fn redo_channel_count(&mut self, channel: impl Into<UndoChannel>, n: usize) {
+        let channel = channel.into();
+        self.ew.write_batch((0..n).map(|_| RequestUndoEvent::Redo(channel)));
+    }
+
+
+    /// request redo-operation for the most recently undone entry of the channel on behalf of another client,
+    /// see [`UndoRequester::undo_channel_as`].
+    #[inline(always)]
+    pub fn redo_channel_as(&mut self, channel: impl Into<UndoChannel>, requester: UndoSiteId) {
+        self.ew.write(RequestUndoEvent::RedoAs(channel.into(), requester));
+    }
+
+
+    /// request a dry-run of [`UndoRequester::undo`].
+    ///
+    /// The events the undo would send are sent wrapped in [`DryRun`](crate::prelude::DryRun) instead,
+    /// and the history, the counters and the cooldown are left untouched.
+    /// This is useful for tests and preview UIs.
+    #[inline(always)]
+    pub fn dry_run(&mut self) {
+        self.dry_run_channel(UndoChannel::DEFAULT);
+    }
+
+
+    #[inline(always)]
+    pub fn dry_run_channel(&mut self, channel: impl Into<UndoChannel>) {
+        self.ew.write(RequestUndoEvent::DryRun(channel.into()));
+    }
+
+
+    /// request a preview of [`UndoRequester::redo`].
+    ///
+    /// The redo-events the redo would send are sent wrapped in [`Preview`](crate::prelude::Preview) instead,
+    /// such as to render ghosts of the entities the redo would bring back, and the entries keep waiting for redo.
+    #[inline(always)]
+    pub fn preview_redo(&mut self) {
+        self.preview_redo_channel(UndoChannel::DEFAULT);
+    }
+
+
+    #[inline(always)]
+    pub fn preview_redo_channel(&mut self, channel: impl Into<UndoChannel>) {
+        self.ew.write(RequestUndoEvent::PreviewRedo(channel.into()));
+    }
+
+
+    /// request undo-operation for the most recent entry whose [`UndoMeta`] matches the predicate.
+    ///
+    /// Unlike [`UndoRequester::undo`], entries of all channels are candidates.
+    ///
+    /// Newer entries which do not match are skipped and stay in the history,
+    /// so tools can undo only their own actions.
+    #[inline(always)]
+    pub fn undo_last_matching(&mut self, predicate: impl Fn(&UndoMeta) -> bool + Send + Sync + 'static) {
+        self.ew.write(RequestUndoEvent::Matching(Arc::new(predicate)));
+    }
+
+
+    /// request undo-operation for the entry of the slot, wherever it is in the history, such as for non-linear history panels.
+    ///
+    /// The later entries depending on it are either undone first or make the request refused according to the mode,
+    /// see [`UndoSelectiveMode`]. [`UndoSelectiveReverted`](crate::prelude::UndoSelectiveReverted) lists the slots actually undone.
+    #[inline(always)]
+    pub fn undo_entry(&mut self, no: usize, mode: UndoSelectiveMode) {
+        self.ew.write(RequestUndoEvent::Selective(no, mode));
+    }
+
+
+    /// request undo-operation for the most recent entry affecting the entity.
+    ///
+    /// The entities are declared at registration via [`UndoMeta::entities`].
+    #[inline(always)]
+    pub fn undo_entity(&mut self, entity: Entity) {
+        self.undo_last_matching(move |meta| meta.affects(entity));
+    }
+
+
+    /// request undo-operation for the most recent entry carrying the tag.
+    ///
+    /// See [`UndoMeta::has_tag`].
+    #[inline(always)]
+    pub fn undo_tag(&mut self, tag: impl Into<String>) {
+        let tag = tag.into();
+        self.undo_last_matching(move |meta| meta.has_tag(&tag));
+    }
+
+
+    /// Returns true if there is anything to undo in the default channel.
+    #[inline(always)]
+    pub fn can_undo(&self) -> bool {
+        self.can_undo_channel(UndoChannel::DEFAULT)
+    }
+
+
+    #[inline]
+    pub fn can_undo_channel(&self, channel: impl Into<UndoChannel>) -> bool {
+        self.history.latest_no_in(self.documents.route(channel.into())).is_some()
+    }
+
+
+    /// Returns true if there is anything to redo in the default channel.
+    #[inline(always)]
+    pub fn can_redo(&self) -> bool {
+        self.can_redo_channel(UndoChannel::DEFAULT)
+    }
+
+
+    #[inline]
+    pub fn can_redo_channel(&self, channel: impl Into<UndoChannel>) -> bool {
+        self.history.latest_redo_in(self.documents.route(channel.into())).is_some()
+    }
+
+
+    /// Returns the count of undo-operations available in the default channel.
+    ///
+    /// Entries registered together, such as reserved ones, are counted once.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len_channel(UndoChannel::DEFAULT)
+    }
+
+
+    #[inline]
+    pub fn len_channel(&self, channel: impl Into<UndoChannel>) -> usize {
+        let channel = self.documents.route(channel.into());
+        self.history.slots_matching(|meta| meta.channel == channel).len()
+    }
+
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        !self.can_undo()
+    }
+
+
+    /// Returns the count of requests waiting for a later frame due to the per-frame limit or the dispatch budget.
+    #[inline(always)]
+    pub fn deferred_requests(&self) -> usize {
+        self.queue.len()
+    }
+
+
+    /// Returns the total count of undo events whose fallible handler returned an error.
+    ///
+    /// See [`AppUndoEx::add_undo_handler_fallible`](crate::prelude::AppUndoEx::add_undo_handler_fallible).
+    #[inline(always)]
+    pub fn failures(&self) -> usize {
+        self.failures.failed
+    }
+
+
+    /// Returns how the buffers of the metadata of dropped entries have been recycled so far.
+    #[inline(always)]
+    pub fn pool_stats(&self) -> UndoPoolStats {
+        self.history.pool_stats()
+    }
+
+
+    /// Returns the total count of requests dropped by the cooldown.
+    ///
+    /// See [`AppUndoEx::configure_undo_cooldown`](crate::prelude::AppUndoEx::configure_undo_cooldown).
+    #[inline(always)]
+    pub fn suppressed_requests(&self) -> usize {
+        self.cooldown.suppressed()
+    }
+
+
+    /// Returns the count of registered entries carrying the tag.
+    #[inline]
+    pub fn count_tag(&self, tag: &str) -> usize {
+        self.history.count_matching(|meta| meta.has_tag(tag))
+    }
+
+
+    /// Returns the entities the next undo of the channel would affect, as declared via [`UndoMeta::entities`].
+    ///
+    /// Like the undos themselves, the default channel stands for the active document.
+    #[inline]
+    pub fn next_undo_targets(&self, channel: impl Into<UndoChannel>) -> Vec<Entity> {
+        self.history
+            .latest_no_in(self.documents.route(channel.into()))
+            .map(|no| self.history.entities_of_slot(no))
+            .unwrap_or_default()
+    }
+
+
+    /// Drops every entry from the history without sending them, including the ones waiting for redo,
+    /// except the [sticky](UndoMeta::sticky) ones.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.gc(|_| true);
+    }
+
+
+    /// Drops every entry carrying the tag from the history without sending them, including the ones waiting for redo.
+    ///
+    /// Entries registered in the same slot, like reserved ones, are dropped together.
+    /// Sticky entries are kept.
+    #[inline(always)]
+    pub fn clear_tag(&mut self, tag: impl Into<String>) {
+        let tag = tag.into();
+        self.gc(move |meta| meta.has_tag(&tag));
+    }
+
+
+    /// Drops every entry the predicate returns true for, including the ones waiting for redo,
+    /// such as the entries referring to content removed for good in long sessions.
+    ///
+    /// Sticky entries are kept.
+    #[inline(always)]
+    pub fn gc(&mut self, predicate: impl Fn(&UndoMeta) -> bool + Send + Sync + 'static) {
+        self.ew.write(RequestUndoEvent::Collect(Arc::new(predicate)));
+    }
+
+
+    /// Same as [`UndoRequester::gc`], dropping the entries collected by the policy.
+    #[inline(always)]
+    pub fn gc_policy(&mut self, policy: UndoGcPolicy) {
+        self.gc(move |meta| policy.collects(meta));
+    }
+}
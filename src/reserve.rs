@@ -1,21 +1,37 @@
-use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::ops::Deref;
-use bevy::prelude::{Event, Resource};
+use bevy::prelude::{Message, MessageReader, MessageWriter, ResMut, Resource};
 
+use crate::counter::UndoCounter;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
 
-#[derive(Event, Clone)]
+
+#[derive(Message, Clone)]
 pub(crate) struct RequestCommitReservationsFromSchedulerEvent;
 
 
-#[derive(Event, Clone)]
+#[derive(Message, Clone)]
 pub(crate) struct RequestCommitReservationsEvent;
 
 
-#[derive(Event, Clone)]
-pub(crate) struct UndoReserveEvent<E: Event + Clone> {
+#[derive(Message)]
+pub(crate) struct UndoReserveEvent<E: UndoPayload> {
     pub inner: E,
     pub reserve_no: usize,
+    pub meta: UndoMeta,
+}
+
+
+impl<E: UndoPayload> Clone for UndoReserveEvent<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.duplicate(),
+            reserve_no: self.reserve_no,
+            meta: self.meta.clone(),
+        }
+    }
 }
 
 
@@ -48,11 +64,10 @@ impl ReserveCounter {
 }
 
 
-#[derive(Resource)]
-pub(crate) struct UndoReservedArea<E: Event + Clone>(pub(crate) Vec<UndoReserveEvent<E>>);
+pub(crate) struct UndoReservedArea<E: UndoPayload>(pub(crate) Vec<UndoReserveEvent<E>>);
 
 
-impl<E: Event + Clone> UndoReservedArea<E> {
+impl<E: UndoPayload> UndoReservedArea<E> {
     #[inline]
     pub fn push(&mut self, event: UndoReserveEvent<E>) {
         self.0.push(event);
@@ -71,7 +86,7 @@ impl<E: Event + Clone> UndoReservedArea<E> {
 }
 
 
-impl<E: Event + Clone> Default for UndoReservedArea<E> {
+impl<E: UndoPayload> Default for UndoReservedArea<E> {
     #[inline(always)]
     fn default() -> Self {
         Self(Vec::new())
@@ -79,5 +94,21 @@ impl<E: Event + Clone> Default for UndoReservedArea<E> {
 }
 
 
-
-
+/// Carries the slot number assigned to the reservations being committed.
+#[derive(Message)]
+pub(crate) struct CommitReservationsEvent(pub usize);
+
+pub(crate) fn reserve_reset_system(
+    mut er: MessageReader<RequestCommitReservationsEvent>,
+    mut er2: MessageReader<RequestCommitReservationsFromSchedulerEvent>,
+    mut ew: MessageWriter<CommitReservationsEvent>,
+    mut counter: ResMut<UndoCounter>,
+    mut reserve_counter: ResMut<ReserveCounter>,
+) {
+    let requested = er.read().count() + er2.read().count();
+    if 0 < requested && 0 < **reserve_counter {
+        counter.increment();
+        ew.write(CommitReservationsEvent(**counter));
+        reserve_counter.reset();
+    }
+}
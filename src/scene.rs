@@ -0,0 +1,111 @@
+use bevy::prelude::{Added, AnyOf, Entity, Local, Query, RemovedComponents};
+use bevy::world_serialization::{DynamicWorldRoot, WorldAssetRoot, WorldInstance};
+
+use crate::channel::UndoChannel;
+use crate::document::UndoDocuments;
+
+/// The root holding the handle of the scene a [`WorldInstance`] is spawned from.
+type UndoSceneRoot<'a> = AnyOf<(&'a WorldAssetRoot, &'a DynamicWorldRoot)>;
+
+
+/// Keeps a document per scene, activating the scene spawned last and keeping the documents of unloaded scenes.
+pub(crate) fn track_loaded_scenes_system(
+    spawned: Query<(Entity, UndoSceneRoot), Added<WorldInstance>>,
+    mut unloaded: RemovedComponents<WorldInstance>,
+    mut loaded: Local<Vec<(Entity, UndoChannel)>>,
+    mut documents: UndoDocuments,
+) {
+    for entity in unloaded.read() {
+        let Some(index) = loaded.iter().position(|(instance, _)| *instance == entity) else {
+            continue;
+        };
+        let (_, channel) = loaded.remove(index);
+        if documents.active() != Some(channel) {
+            continue;
+        }
+        match loaded.last() {
+            Some((_, previous)) => {
+                documents.set_active(*previous);
+            }
+            None => documents.deactivate()
+        }
+    }
+    for (entity, (scene, dynamic_scene)) in spawned.iter() {
+        let channel = match (scene, dynamic_scene) {
+            (Some(scene), _) => UndoChannel::for_scene(scene.0.id()),
+            (None, Some(scene)) => UndoChannel::for_scene(scene.0.id()),
+            (None, None) => continue
+        };
+        documents.create(channel);
+        documents.set_active(channel);
+        loaded.push((entity, channel));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, PreUpdate};
+    use bevy::asset::uuid::Uuid;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Handle, Message, Messages};
+    use bevy::world_serialization::{DynamicWorld, DynamicWorldRoot, world_instance_spawner, WorldInstanceSpawner};
+
+    use crate::prelude::{AppUndoEx, UndoRequester, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Edit(&'static str);
+
+
+    fn undone(app: &mut App) -> Vec<Edit> {
+        app.world_mut().resource_mut::<Messages<Edit>>().drain().collect()
+    }
+
+
+    #[test]
+    fn resume_history_of_reloaded_scene() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.init_resource::<WorldInstanceSpawner>();
+        app.add_systems(PreUpdate, world_instance_spawner);
+        app.add_undo_event::<Edit>();
+        app.enable_undo_scenes();
+        let (forest, cave) = (
+            Handle::<DynamicWorld>::from(Uuid::new_v4()),
+            Handle::<DynamicWorld>::from(Uuid::new_v4()),
+        );
+        let mut scheduler = SystemState::<UndoScheduler<Edit>>::new(app.world_mut());
+        let mut requester = SystemState::<UndoRequester>::new(app.world_mut());
+        let spawn = |app: &mut App, scene: &Handle<DynamicWorld>| app.world_mut().spawn(DynamicWorldRoot(scene.clone())).id();
+
+        let instance = spawn(&mut app, &forest);
+        app.update();
+        app.update();
+        scheduler.get_mut(app.world_mut()).unwrap().register_with_redo(Edit("plant tree"), Edit("replant"));
+        app.update();
+        app.world_mut().despawn(instance);
+        app.update();
+
+        spawn(&mut app, &cave);
+        app.update();
+        app.update();
+        scheduler.get_mut(app.world_mut()).unwrap().register(Edit("place torch"));
+        app.update();
+        requester.get_mut(app.world_mut()).unwrap().undo_for_scene(forest.id());
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("plant tree")]);
+        requester.get_mut(app.world_mut()).unwrap().redo_for_scene(forest.id());
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("replant")]);
+
+        spawn(&mut app, &forest);
+        app.update();
+        app.update();
+        requester.get_mut(app.world_mut()).unwrap().undo();
+        app.update();
+        requester.get_mut(app.world_mut()).unwrap().undo_for_scene(cave.id());
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("plant tree"), Edit("place torch")]);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoScheduler;
+
+/// Registers the event restoring the state captured when created once dropped,
+/// so every path leaving the scope, early returns included, is undoable.
+///
+/// Created via [`UndoScheduler::scope`] or [`undo_scope!`](crate::undo_scope).
+///
+/// ```ignore
+/// fn move_system(mut scheduler: UndoScheduler<Move>, mut transform: Query<&mut Transform>) {
+///     let mut transform = transform.single_mut();
+///     let scope = undo_scope!(scheduler, || Move(transform.translation));
+///     if !dragging {
+///         scope.commit_without_undo();
+///         return;
+///     }
+///     transform.translation += delta;
+/// }
+/// ```
+#[must_use = "the restoring event is registered as soon as the scope is dropped"]
+pub struct UndoScope<'a, 'w, E: UndoPayload> {
+    scheduler: &'a mut UndoScheduler<'w, E>,
+    snapshot: Option<E>,
+}
+
+
+impl<'a, 'w, E: UndoPayload> UndoScope<'a, 'w, E> {
+    /// Ends the scope without registering anything, such as when nothing changed.
+    #[inline]
+    pub fn commit_without_undo(mut self) {
+        self.snapshot = None;
+    }
+}
+
+
+impl<'a, 'w, E: UndoPayload> Drop for UndoScope<'a, 'w, E> {
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            self.scheduler.register(snapshot);
+        }
+    }
+}
+
+
+impl<'w, E: UndoPayload> UndoScheduler<'w, E> {
+    /// Captures the event restoring the current state, registered when the returned scope is dropped.
+    #[inline]
+    pub fn scope(&mut self, capture: impl FnOnce() -> E) -> UndoScope<'_, 'w, E> {
+        UndoScope {
+            snapshot: Some(capture()),
+            scheduler: self,
+        }
+    }
+}
+
+
+/// Creates an [`UndoScope`] from the scheduler and the function capturing the restoring event.
+#[macro_export]
+macro_rules! undo_scope {
+    ($scheduler:expr, $capture:expr $(,)?) => {
+        $scheduler.scope($capture)
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Local, Message, Update};
+
+    use crate::prelude::UndoScheduler;
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[test]
+    fn register_snapshot_when_scope_ends() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Move>, mut frame: Local<i32>, mut position: Local<i32>| {
+                *frame += 1;
+                if 3 < *frame {
+                    return;
+                }
+                let scope = undo_scope!(scheduler, || Move(*position));
+                if *frame == 2 {
+                    scope.commit_without_undo();
+                    return;
+                }
+                *position += 1;
+            });
+        });
+        harness.frames(3);
+        harness.undo();
+        harness.expect([Move(1)]);
+        harness.undo();
+        harness.expect([Move(0)]);
+        harness.undo();
+        harness.expect([]);
+    }
+}
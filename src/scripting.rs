@@ -0,0 +1,217 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::{Local, Message, Res, Resource};
+
+use crate::channel::UndoChannel;
+use crate::lock;
+use crate::meta::UndoMeta;
+use crate::request::UndoRequester;
+use crate::undo_event::UndoScheduler;
+
+#[cfg(feature = "lua")]
+mod lua;
+#[cfg(feature = "rhai")]
+mod rhai;
+
+/// An entry registered by a script, sent as an event when it is undone or redone.
+///
+/// The app forwards it to the script which registered it, such as by calling a function named after [`UndoScriptEvent::kind`].
+#[derive(Message, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoScriptEvent {
+    /// The kind of action chosen by the script, such as `"paint"`, also used as the tag of the entry.
+    pub kind: String,
+
+    /// The data needed to revert the action, encoded by the script such as in JSON.
+    pub data: String,
+}
+
+
+#[derive(Debug)]
+enum UndoScriptCommand {
+    Register {
+        event: UndoScriptEvent,
+        redo: Option<UndoScriptEvent>,
+    },
+    Undo(UndoChannel),
+    Redo(UndoChannel),
+    BeginGroup,
+    EndGroup,
+}
+
+
+/// The undo operations available to scripts, added via [`AppUndoEx::add_undo_scripting`](crate::prelude::AppUndoEx::add_undo_scripting).
+///
+/// The calls are queued and applied at the start of the next frame, together with the registrations of native systems.
+/// The handle is cheap to clone, so it can be captured by the functions bound to the script engines,
+/// see `UndoScriptApi::install_lua` and `UndoScriptApi::install_rhai` with the features `lua` and `rhai`.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct UndoScriptApi(Arc<Mutex<Vec<UndoScriptCommand>>>);
+
+
+impl UndoScriptApi {
+    /// Registers an entry sending an [`UndoScriptEvent`] of the kind and data when undone.
+    #[inline]
+    pub fn register(&self, kind: impl Into<String>, data: impl Into<String>) {
+        let event = UndoScriptEvent {
+            kind: kind.into(),
+            data: data.into(),
+        };
+        self.push(UndoScriptCommand::Register { event, redo: None });
+    }
+
+
+    /// Like [`UndoScriptApi::register`], along with the data sent when the entry is redone.
+    #[inline]
+    pub fn register_with_redo(&self, kind: impl Into<String>, data: impl Into<String>, redo: impl Into<String>) {
+        let kind = kind.into();
+        let redo = UndoScriptEvent {
+            kind: kind.clone(),
+            data: redo.into(),
+        };
+        let event = UndoScriptEvent {
+            kind,
+            data: data.into(),
+        };
+        self.push(UndoScriptCommand::Register { event, redo: Some(redo) });
+    }
+
+
+    /// Requests undoing the most recent entry of the channel, whether registered by a script or not.
+    #[inline]
+    pub fn undo(&self, channel: impl Into<UndoChannel>) {
+        self.push(UndoScriptCommand::Undo(channel.into()));
+    }
+
+
+    /// Requests redoing the most recently undone entry of the channel.
+    #[inline]
+    pub fn redo(&self, channel: impl Into<UndoChannel>) {
+        self.push(UndoScriptCommand::Redo(channel.into()));
+    }
+
+
+    /// Starts a group, the entries registered until [`UndoScriptApi::end_group`] sharing one slot so they are undone at once.
+    ///
+    /// Groups can be nested, only the outermost one delimiting the slot.
+    #[inline]
+    pub fn begin_group(&self) {
+        self.push(UndoScriptCommand::BeginGroup);
+    }
+
+
+    #[inline]
+    pub fn end_group(&self) {
+        self.push(UndoScriptCommand::EndGroup);
+    }
+
+
+    #[inline]
+    fn push(&self, command: UndoScriptCommand) {
+        lock(&self.0).push(command);
+    }
+}
+
+
+/// The group opened by the scripts, kept across frames.
+#[derive(Default)]
+pub(crate) struct UndoScriptGroup {
+    depth: usize,
+    no: Option<usize>,
+}
+
+
+pub(crate) fn apply_script_commands_system(
+    api: Res<UndoScriptApi>,
+    mut scheduler: UndoScheduler<UndoScriptEvent>,
+    mut requester: UndoRequester,
+    mut group: Local<UndoScriptGroup>,
+) {
+    let commands = std::mem::take(&mut *lock(&api.0));
+    for command in commands {
+        match command {
+            UndoScriptCommand::Register { event, redo } => {
+                let meta = scheduler.routed(UndoMeta::tagged(event.kind.clone()));
+                let no = match group.no {
+                    Some(no) => no,
+                    None => scheduler.next_slot(meta.channel),
+                };
+                if 0 < group.depth {
+                    group.no = Some(no);
+                }
+                scheduler.push_to_slot(no, event, redo, meta);
+            }
+            UndoScriptCommand::Undo(channel) => requester.undo_channel(channel),
+            UndoScriptCommand::Redo(channel) => requester.redo_channel(channel),
+            UndoScriptCommand::BeginGroup => group.depth += 1,
+            UndoScriptCommand::EndGroup => {
+                group.depth = group.depth.saturating_sub(1);
+                if group.depth == 0 {
+                    group.no = None;
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::prelude::Messages;
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoScriptApi, UndoScriptEvent};
+    use crate::UndoPlugin;
+
+    pub(super) fn new_app() -> (App, UndoScriptApi) {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_scripting();
+        let api = app.world().resource::<UndoScriptApi>().clone();
+        (app, api)
+    }
+
+
+    pub(super) fn drain_undone(app: &mut App) -> Vec<String> {
+        app.world_mut()
+            .resource_mut::<Messages<UndoScriptEvent>>()
+            .drain()
+            .map(|event| event.data)
+            .collect()
+    }
+
+
+    #[test]
+    fn undo_grouped_script_entries_at_once() {
+        let (mut app, api) = new_app();
+        api.register("paint", "1");
+        api.begin_group();
+        api.register("paint", "2");
+        api.begin_group();
+        api.register("paint", "3");
+        api.end_group();
+        api.end_group();
+        app.update();
+
+        api.undo(UndoChannel::DEFAULT);
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["3", "2"]);
+    }
+
+
+    #[test]
+    fn redo_script_entry() {
+        let (mut app, api) = new_app();
+        api.register_with_redo("paint", "erase", "paint again");
+        app.update();
+        api.undo(UndoChannel::DEFAULT);
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["erase"]);
+
+        api.redo(UndoChannel::DEFAULT);
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["paint again"]);
+    }
+}
@@ -0,0 +1,86 @@
+use mlua::Lua;
+
+use crate::channel::UndoChannel;
+use crate::scripting::UndoScriptApi;
+
+fn channel(channel: Option<u64>) -> UndoChannel {
+    channel.map_or(UndoChannel::DEFAULT, UndoChannel)
+}
+
+
+impl UndoScriptApi {
+    /// Binds the operations to the global table `undo` of the Lua state, as
+    /// `undo.register(kind, data[, redo])`, `undo.undo([channel])`, `undo.redo([channel])`,
+    /// `undo.begin_group()` and `undo.end_group()`.
+    pub fn install_lua(&self, lua: &Lua) -> mlua::Result<()> {
+        let table = lua.create_table()?;
+        let api = self.clone();
+        table.set("register", lua.create_function(move |_, (kind, data, redo): (String, String, Option<String>)| {
+            match redo {
+                Some(redo) => api.register_with_redo(kind, data, redo),
+                None => api.register(kind, data),
+            }
+            Ok(())
+        })?)?;
+        let api = self.clone();
+        table.set("undo", lua.create_function(move |_, id: Option<u64>| {
+            api.undo(channel(id));
+            Ok(())
+        })?)?;
+        let api = self.clone();
+        table.set("redo", lua.create_function(move |_, id: Option<u64>| {
+            api.redo(channel(id));
+            Ok(())
+        })?)?;
+        let api = self.clone();
+        table.set("begin_group", lua.create_function(move |_, ()| {
+            api.begin_group();
+            Ok(())
+        })?)?;
+        let api = self.clone();
+        table.set("end_group", lua.create_function(move |_, ()| {
+            api.end_group();
+            Ok(())
+        })?)?;
+        lua.globals().set("undo", table)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+
+    use crate::scripting::tests::{drain_undone, new_app};
+
+    #[test]
+    fn undo_from_lua() {
+        let (mut app, api) = new_app();
+        let lua = Lua::new();
+        api.install_lua(&lua).unwrap();
+        lua.load(r#"
+            undo.register("paint", "1")
+            undo.begin_group()
+            undo.register("paint", "2")
+            undo.register("paint", "3")
+            undo.end_group()
+            undo.register("paint", "4", "redo 4")
+        "#).exec().unwrap();
+        app.update();
+
+        lua.load("undo.undo()").exec().unwrap();
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["4"]);
+
+        lua.load("undo.redo(0)").exec().unwrap();
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["redo 4"]);
+
+        lua.load("undo.undo() undo.undo()").exec().unwrap();
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["4", "3", "2"]);
+    }
+}
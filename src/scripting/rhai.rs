@@ -0,0 +1,93 @@
+use rhai::{Engine, ImmutableString, INT, Module};
+
+use crate::channel::UndoChannel;
+use crate::scripting::UndoScriptApi;
+
+impl UndoScriptApi {
+    /// Binds the operations to the static module `undo` of the engine, as
+    /// `undo::register(kind, data[, redo])`, `undo::undo([channel])`, `undo::redo([channel])`,
+    /// `undo::begin_group()` and `undo::end_group()`.
+    pub fn install_rhai(&self, engine: &mut Engine) {
+        let mut module = Module::new();
+        let api = self.clone();
+        module.set_native_fn("register", move |kind: ImmutableString, data: ImmutableString| {
+            api.register(kind.as_str(), data.as_str());
+            Ok(())
+        });
+        let api = self.clone();
+        module.set_native_fn("register", move |kind: ImmutableString, data: ImmutableString, redo: ImmutableString| {
+            api.register_with_redo(kind.as_str(), data.as_str(), redo.as_str());
+            Ok(())
+        });
+        let api = self.clone();
+        module.set_native_fn("undo", move || {
+            api.undo(UndoChannel::DEFAULT);
+            Ok(())
+        });
+        let api = self.clone();
+        module.set_native_fn("undo", move |channel: INT| {
+            api.undo(UndoChannel(channel as u64));
+            Ok(())
+        });
+        let api = self.clone();
+        module.set_native_fn("redo", move || {
+            api.redo(UndoChannel::DEFAULT);
+            Ok(())
+        });
+        let api = self.clone();
+        module.set_native_fn("redo", move |channel: INT| {
+            api.redo(UndoChannel(channel as u64));
+            Ok(())
+        });
+        let api = self.clone();
+        module.set_native_fn("begin_group", move || {
+            api.begin_group();
+            Ok(())
+        });
+        let api = self.clone();
+        module.set_native_fn("end_group", move || {
+            api.end_group();
+            Ok(())
+        });
+        engine.register_static_module("undo", module.into());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rhai::Engine;
+
+    use crate::scripting::tests::{drain_undone, new_app};
+
+    #[test]
+    fn undo_from_rhai() {
+        let (mut app, api) = new_app();
+        let mut engine = Engine::new();
+        api.install_rhai(&mut engine);
+        engine.run(r#"
+            undo::register("paint", "1");
+            undo::begin_group();
+            undo::register("paint", "2");
+            undo::register("paint", "3");
+            undo::end_group();
+            undo::register("paint", "4", "redo 4");
+        "#).unwrap();
+        app.update();
+
+        engine.run("undo::undo();").unwrap();
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["4"]);
+
+        engine.run("undo::redo(0);").unwrap();
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["redo 4"]);
+
+        engine.run("undo::undo(); undo::undo();").unwrap();
+        app.update();
+        app.update();
+        assert_eq!(drain_undone(&mut app), vec!["4", "3", "2"]);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::view::UndoEntryInfo;
+
+/// An entry found via [`UndoView::find`](crate::prelude::UndoView::find).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UndoSearchMatch<'a> {
+    /// The position of the entry in [`UndoView::entries`](crate::prelude::UndoView::entries), from the oldest.
+    pub index: usize,
+
+    /// How closely the entry matches, higher being closer.
+    pub score: usize,
+
+    pub entry: UndoEntryInfo<'a>,
+}
+
+
+/// Scores the text against the query, ignoring case, `None` unless all characters of the query appear in order.
+///
+/// Consecutive characters and characters starting words score higher, and the whole query appearing as is scores highest,
+/// so "del cube" finds "Delete cube" before "Deselect all cubes".
+pub(crate) fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let mut score = 0;
+    let mut matched = 0;
+    let mut previous: Option<usize> = None;
+    for (i, c) in text.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if *c != query[matched] {
+            continue;
+        }
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == i) {
+            score += 2;
+        }
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(i);
+        matched += 1;
+    }
+    if matched < query.len() {
+        return None;
+    }
+    if text.windows(query.len()).any(|window| window == query.as_slice()) {
+        score += 4 * query.len();
+    }
+    Some(score)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Message;
+
+    use crate::prelude::{AppUndoEx, UndoMeta, UndoScheduler, UndoView};
+    use crate::search::fuzzy_score;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Edit;
+
+
+    #[test]
+    fn score_closer_matches_higher() {
+        assert_eq!(fuzzy_score("xyz", "Delete cube"), None);
+        assert_eq!(fuzzy_score("", "Delete cube"), Some(0));
+        assert!(fuzzy_score("del cube", "Deselect all cubes") < fuzzy_score("del cube", "Delete cube"));
+        assert!(fuzzy_score("rc", "Rock") < fuzzy_score("rc", "Rename cube"));
+    }
+
+
+    #[test]
+    fn find_entries_by_label_and_tags() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        app.add_systems(Startup, |mut scheduler: UndoScheduler<Edit>| {
+            scheduler.register_with_meta(Edit, UndoMeta::tagged("Rename boss room"));
+            scheduler.register_with_meta(Edit, UndoMeta::tagged("Delete cube"));
+            scheduler.register_with_meta(Edit, UndoMeta::tagged("Move light").with_tag("room:boss"));
+        });
+        app.update();
+
+        let mut state = SystemState::<UndoView>::new(app.world_mut());
+        let view = state.get(app.world()).unwrap();
+        let found: Vec<(usize, &str)> = view
+            .find("boss room")
+            .iter()
+            .map(|found| (found.index, found.entry.label()))
+            .collect();
+        assert_eq!(found, vec![(2, "Move light"), (0, "Rename boss room")]);
+        assert!(view.find("paint").is_empty());
+    }
+}
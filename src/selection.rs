@@ -0,0 +1,191 @@
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Commands, Component, Entity, Message, MessageReader, Query, With};
+
+use crate::meta::UndoMeta;
+use crate::undo_event::UndoScheduler;
+
+/// Sent when a selection change is undone or redone, carrying the entities which should be selected.
+#[derive(Message, Debug)]
+pub struct UndoSelectionEvent<M: Component> {
+    pub selected: Vec<Entity>,
+    _marker: PhantomData<M>,
+}
+
+
+impl<M: Component> UndoSelectionEvent<M> {
+    #[inline(always)]
+    fn new(selected: Vec<Entity>) -> Self {
+        Self {
+            selected,
+            _marker: PhantomData,
+        }
+    }
+}
+
+
+impl<M: Component> Clone for UndoSelectionEvent<M> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Self::new(self.selected.clone())
+    }
+}
+
+
+/// Changes the selection marked by the component `M` as undoable entries.
+///
+/// Registering the selection before a destructive action lets undo also restore what had been selected.
+/// The marker type must be set up via [`AppUndoEx::add_undo_selection`](crate::prelude::AppUndoEx::add_undo_selection).
+#[derive(SystemParam)]
+pub struct UndoSelection<'w, 's, M: Component + Default> {
+    commands: Commands<'w, 's>,
+    selected: Query<'w, 's, Entity, With<M>>,
+    scheduler: UndoScheduler<'w, UndoSelectionEvent<M>>,
+}
+
+
+impl<'w, 's, M: Component + Default> UndoSelection<'w, 's, M> {
+    /// Returns the currently selected entities.
+    #[inline]
+    pub fn selected(&self) -> Vec<Entity> {
+        let mut selected: Vec<Entity> = self.selected.iter().collect();
+        selected.sort_unstable();
+        selected
+    }
+
+
+    /// Replaces the selection, and registers an entry restoring the previous one.
+    ///
+    /// Nothing is registered if the selection is unchanged.
+    pub fn set(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        let mut next: Vec<Entity> = entities.into_iter().collect();
+        next.sort_unstable();
+        next.dedup();
+        let previous = self.selected();
+        if previous == next {
+            return;
+        }
+
+        for entity in previous.iter().filter(|entity| !next.contains(entity)) {
+            self.commands.entity(*entity).remove::<M>();
+        }
+        for entity in next.iter().filter(|entity| !previous.contains(entity)) {
+            self.commands.entity(*entity).insert(M::default());
+        }
+        let meta = UndoMeta {
+            entities: previous.iter().chain(next.iter()).copied().collect(),
+            ..UndoMeta::default()
+        };
+        self.scheduler.push(UndoSelectionEvent::new(previous), Some(UndoSelectionEvent::new(next)), meta);
+    }
+
+
+    /// Adds the entities to the selection.
+    #[inline]
+    pub fn select(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        let selected = self.selected();
+        self.set(selected.into_iter().chain(entities));
+    }
+
+
+    /// Removes the entities from the selection.
+    #[inline]
+    pub fn deselect(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        let entities: Vec<Entity> = entities.into_iter().collect();
+        let selected = self.selected();
+        self.set(selected.into_iter().filter(|entity| !entities.contains(entity)));
+    }
+
+
+    /// Clears the selection.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.set([]);
+    }
+}
+
+
+pub(crate) fn restore_selection_system<M: Component + Default>(
+    mut er: MessageReader<UndoSelectionEvent<M>>,
+    mut commands: Commands,
+    selected: Query<Entity, With<M>>,
+) {
+    let Some(event) = er.read().last() else {
+        return;
+    };
+
+    for entity in selected.iter().filter(|entity| !event.selected.contains(entity)) {
+        commands.entity(entity).remove::<M>();
+    }
+    for entity in event.selected.iter() {
+        if let Ok(mut entity) = commands.get_entity(*entity) {
+            entity.insert(M::default());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::prelude::{Component, Entity, Local, Res, Resource, With};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoSelection};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Component, Default)]
+    struct Selected;
+
+    #[derive(Resource)]
+    struct Entities(Vec<Entity>);
+
+
+    fn selected(app: &mut App) -> Vec<Entity> {
+        let mut selected: Vec<Entity> = app
+            .world_mut()
+            .query_filtered::<Entity, With<Selected>>()
+            .iter(app.world())
+            .collect();
+        selected.sort_unstable();
+        selected
+    }
+
+
+    #[test]
+    fn restore_selection() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_selection::<Selected>();
+        let a = app.world_mut().spawn(Selected).id();
+        let b = app.world_mut().spawn_empty().id();
+        let mut both = vec![a, b];
+        both.sort_unstable();
+        app.insert_resource(Entities(vec![a, b]));
+        app.add_systems(Update, |mut selection: UndoSelection<Selected>, entities: Res<Entities>, mut frame: Local<usize>| {
+            match *frame {
+                0 => selection.select([entities.0[1]]),
+                1 => selection.deselect([entities.0[0]]),
+                _ => {}
+            }
+            *frame += 1;
+        });
+        app.update();
+        app.update();
+        app.update();
+        assert_eq!(selected(&mut app), vec![b]);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(selected(&mut app), both);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(selected(&mut app), vec![a]);
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(selected(&mut app), both);
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::Message;
+
+use crate::history::UndoHistory;
+
+/// What to do when the entry to undo via [`UndoRequester::undo_entry`](crate::prelude::UndoRequester::undo_entry)
+/// has later entries depending on it.
+///
+/// A later entry depends on the entry if they share an entity listed in [`UndoMeta::entities`](crate::prelude::UndoMeta::entities),
+/// directly or through other dependents. Entries listing no entities are considered independent of all others.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UndoSelectiveMode {
+    /// Drops the request and sends [`UndoSelectiveRefused`].
+    #[default]
+    Refuse,
+
+    /// Undoes the dependents first, the latest first, then the entry.
+    Cascade,
+}
+
+
+/// Sent when an entry has been undone via [`UndoRequester::undo_entry`](crate::prelude::UndoRequester::undo_entry),
+/// describing what was actually reverted.
+#[derive(Message, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoSelectiveReverted {
+    /// The slot requested.
+    pub requested: usize,
+
+    /// The slots undone in the order dispatched with the tag of their first entry,
+    /// including the dependents and the linked slots undone along with the requested one.
+    pub reverted: Vec<(usize, String)>,
+}
+
+
+/// Sent when a request made via [`UndoRequester::undo_entry`](crate::prelude::UndoRequester::undo_entry)
+/// with [`UndoSelectiveMode::Refuse`] is dropped since later entries depend on the entry.
+#[derive(Message, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoSelectiveRefused {
+    pub requested: usize,
+
+    /// The dependent slots, the latest first.
+    pub dependents: Vec<usize>,
+}
+
+
+/// Returns the slots registered after the slot which depend on it, the latest first.
+pub(crate) fn dependent_slots(history: &UndoHistory, no: usize) -> Vec<usize> {
+    let mut entities = history.entities_of_slot(no);
+    let mut dependents = Vec::new();
+    let mut later: Vec<usize> = history.entries().map(|entry| entry.no).filter(|other| no < *other).collect();
+    later.dedup();
+    // Slots are visited from the oldest, so a dependent passes its entities on to the later ones.
+    for other in later {
+        let others = history.entities_of_slot(other);
+        if others.iter().any(|entity| entities.contains(entity)) {
+            entities.extend(others);
+            dependents.push(other);
+        }
+    }
+    dependents.reverse();
+    dependents
+}
+
+
+/// Returns the slots to undo for the slot in the order to undo them, `Err` with the dependents if refused,
+/// or empty if the slot is not in the history.
+pub(crate) fn selective_slots(history: &UndoHistory, no: usize, mode: UndoSelectiveMode) -> Result<Vec<usize>, Vec<usize>> {
+    if history.slot_len(no) == 0 {
+        return Ok(Vec::new());
+    }
+    let dependents = dependent_slots(history, no);
+    if mode == UndoSelectiveMode::Refuse && !dependents.is_empty() {
+        return Err(dependents);
+    }
+    let mut slots: Vec<usize> = Vec::new();
+    for no in dependents.into_iter().chain([no]) {
+        for linked in history.linked_slots(no, false) {
+            if !slots.contains(&linked) {
+                slots.push(linked);
+            }
+        }
+    }
+    Ok(slots)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Entity, Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoMeta, UndoRequester, UndoScheduler, UndoSelectiveMode, UndoSelectiveRefused, UndoSelectiveReverted};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Edit(&'static str);
+
+
+    fn drain<E: Message>(app: &mut App) -> Vec<E> {
+        app.world_mut().resource_mut::<Messages<E>>().drain().collect()
+    }
+
+
+    #[test]
+    fn refuse_or_cascade_over_dependents() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        let (a, b, c) = (Entity::from_raw_u32(1).unwrap(), Entity::from_raw_u32(2).unwrap(), Entity::from_raw_u32(3).unwrap());
+        let mut scheduler = SystemState::<UndoScheduler<Edit>>::new(app.world_mut());
+        for (edit, meta) in [
+            (Edit("create a"), UndoMeta::tagged("create a").with_entity(a)),
+            (Edit("paint c"), UndoMeta::tagged("paint c").with_entity(c)),
+            (Edit("attach b to a"), UndoMeta::tagged("attach b").with_entity(a).with_entity(b)),
+            (Edit("move b"), UndoMeta::tagged("move b").with_entity(b)),
+        ] {
+            scheduler.get_mut(app.world_mut()).unwrap().register_with_meta(edit, meta);
+            app.update();
+        }
+        let mut requester = SystemState::<UndoRequester>::new(app.world_mut());
+
+        requester.get_mut(app.world_mut()).unwrap().undo_entry(1, UndoSelectiveMode::Refuse);
+        app.update();
+        assert!(drain::<Edit>(&mut app).is_empty());
+        assert_eq!(drain::<UndoSelectiveRefused>(&mut app), vec![UndoSelectiveRefused {
+            requested: 1,
+            dependents: vec![4, 3],
+        }]);
+
+        requester.get_mut(app.world_mut()).unwrap().undo_entry(2, UndoSelectiveMode::Refuse);
+        app.update();
+        assert_eq!(drain::<Edit>(&mut app), vec![Edit("paint c")]);
+
+        requester.get_mut(app.world_mut()).unwrap().undo_entry(1, UndoSelectiveMode::Cascade);
+        app.update();
+        assert_eq!(drain::<Edit>(&mut app), vec![Edit("move b"), Edit("attach b to a"), Edit("create a")]);
+        let reverted = drain::<UndoSelectiveReverted>(&mut app);
+        assert_eq!(reverted.last().unwrap(), &UndoSelectiveReverted {
+            requested: 1,
+            reverted: vec![(4, "move b".to_string()), (3, "attach b".to_string()), (1, "create a".to_string())],
+        });
+    }
+}
@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use bevy::platform::collections::HashMap;
+
+use crate::meta::UndoMeta;
+use crate::view::UndoEntryInfo;
+
+/// Owned metadata of an entry, taken by [`UndoView::snapshot`](crate::prelude::UndoView::snapshot).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoSnapshotEntry {
+    pub group: usize,
+    pub type_name: &'static str,
+    pub registered_at: Duration,
+    pub redoable: bool,
+    pub meta: UndoMeta,
+}
+
+
+impl<'a> From<UndoEntryInfo<'a>> for UndoSnapshotEntry {
+    #[inline]
+    fn from(info: UndoEntryInfo<'a>) -> Self {
+        Self {
+            group: info.group,
+            type_name: info.type_name,
+            registered_at: info.registered_at,
+            redoable: info.redoable,
+            meta: info.meta.clone(),
+        }
+    }
+}
+
+
+/// The entries which could be undone at some point, ordered from the oldest.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct UndoHistorySnapshot {
+    pub entries: Vec<UndoSnapshotEntry>,
+}
+
+
+/// The difference between two snapshots, see [`UndoHistorySnapshot::diff`].
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct UndoHistoryDiff {
+    /// Entries only in the newer snapshot.
+    pub added: Vec<UndoSnapshotEntry>,
+
+    /// Entries only in the older snapshot.
+    pub removed: Vec<UndoSnapshotEntry>,
+
+    /// Entries in both snapshots whose order relative to the others changed.
+    pub reordered: Vec<UndoSnapshotEntry>,
+}
+
+
+impl UndoHistoryDiff {
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.reordered.is_empty()
+    }
+}
+
+
+impl UndoHistorySnapshot {
+    /// Reports how `newer` differs from this snapshot.
+    ///
+    /// Entries are identified by all of their fields.
+    /// The reordered entries are the fewest ones to move so that the common entries line up,
+    /// which takes time proportional to the product of the snapshot lengths.
+    pub fn diff(&self, newer: &UndoHistorySnapshot) -> UndoHistoryDiff {
+        let old_common = common(&self.entries, &newer.entries);
+        let new_common = common(&newer.entries, &self.entries);
+        let kept = longest_common_subsequence(&old_common, &new_common);
+        let new_common: Vec<UndoSnapshotEntry> = new_common.into_iter().cloned().collect();
+
+        UndoHistoryDiff {
+            added: difference(&newer.entries, &self.entries),
+            removed: difference(&self.entries, &newer.entries),
+            reordered: difference(&new_common, &kept),
+        }
+    }
+}
+
+
+fn counts(entries: &[UndoSnapshotEntry]) -> HashMap<&UndoSnapshotEntry, usize> {
+    let mut counts = HashMap::default();
+    for entry in entries {
+        *counts.entry(entry).or_insert(0) += 1;
+    }
+    counts
+}
+
+
+/// Returns the entries of `a` which are not in `b`, as a multiset.
+fn difference(a: &[UndoSnapshotEntry], b: &[UndoSnapshotEntry]) -> Vec<UndoSnapshotEntry> {
+    let mut remaining = counts(b);
+    a
+        .iter()
+        .filter(|entry| match remaining.get_mut(entry) {
+            Some(count) if 0 < *count => {
+                *count -= 1;
+                false
+            }
+            _ => true
+        })
+        .cloned()
+        .collect()
+}
+
+
+/// Returns the entries of `a` which are also in `b`, as a multiset.
+fn common<'a>(a: &'a [UndoSnapshotEntry], b: &[UndoSnapshotEntry]) -> Vec<&'a UndoSnapshotEntry> {
+    let mut remaining = counts(b);
+    a
+        .iter()
+        .filter(|entry| match remaining.get_mut(entry) {
+            Some(count) if 0 < *count => {
+                *count -= 1;
+                true
+            }
+            _ => false
+        })
+        .collect()
+}
+
+
+fn longest_common_subsequence(a: &[&UndoSnapshotEntry], b: &[&UndoSnapshotEntry]) -> Vec<UndoSnapshotEntry> {
+    let width = b.len() + 1;
+    let mut lengths = vec![0_u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut kept = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            kept.push(a[i].clone());
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    kept
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::{UndoHistorySnapshot, UndoMeta, UndoSnapshotEntry};
+
+    fn entry(group: usize) -> UndoSnapshotEntry {
+        UndoSnapshotEntry {
+            group,
+            type_name: "Move",
+            registered_at: Default::default(),
+            redoable: false,
+            meta: UndoMeta::default(),
+        }
+    }
+
+
+    fn snapshot(groups: &[usize]) -> UndoHistorySnapshot {
+        UndoHistorySnapshot {
+            entries: groups.iter().copied().map(entry).collect(),
+        }
+    }
+
+
+    #[test]
+    fn diff_snapshots() {
+        let diff = snapshot(&[1, 2, 3, 4]).diff(&snapshot(&[1, 3, 4, 2, 5]));
+        assert_eq!(diff.added, vec![entry(5)]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.reordered, vec![entry(2)]);
+
+        let diff = snapshot(&[1, 2]).diff(&snapshot(&[1]));
+        assert_eq!(diff.removed, vec![entry(2)]);
+        assert!(snapshot(&[1, 2]).diff(&snapshot(&[1, 2])).is_empty());
+    }
+}
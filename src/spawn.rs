@@ -0,0 +1,326 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::ecs::message::MessageCursor;
+use bevy::ecs::system::{EntityCommands, SystemParam};
+use bevy::prelude::{AppTypeRegistry, Bundle, ChildOf, Children, Commands, Entity, Local, Message, Messages, World};
+use bevy::platform::collections::HashMap;
+
+use crate::entity_snapshot::UndoEntitySnapshot;
+use crate::meta::UndoMeta;
+use crate::undo_event::{UndoEvent, UndoScheduler};
+use crate::world::WorldUndoEx;
+
+#[derive(Debug, Clone)]
+pub(crate) enum UndoSpawnedEntity {
+    Live(Entity),
+    Despawned(UndoEntitySnapshot),
+}
+
+
+pub(crate) type UndoSpawnedEntities = Arc<Mutex<Vec<UndoSpawnedEntity>>>;
+
+
+/// Spawns or despawns the entities registered via [`UndoSpawns`] or [`UndoClipboard`](crate::prelude::UndoClipboard).
+///
+/// The undo and redo of an entry share their entities, so redoing a despawn despawns the entities its undo respawned.
+#[derive(Message, Debug, Clone)]
+pub struct UndoSpawnEvent {
+    spawn: bool,
+    entities: UndoSpawnedEntities,
+}
+
+
+/// Registers the entities spawned by an instantiation, such as of a prefab or a scene,
+/// as a single entry which despawns them all and respawns them when redone.
+///
+/// Despawned entities are kept as reflected snapshots, see [`UndoEntitySnapshot`],
+/// and respawn with new ids under the same parents.
+/// It must be set up via [`AppUndoEx::add_undo_spawns`](crate::prelude::AppUndoEx::add_undo_spawns).
+#[derive(SystemParam)]
+pub struct UndoSpawns<'w, 's> {
+    commands: Commands<'w, 's>,
+    scheduler: UndoScheduler<'w, UndoSpawnEvent>,
+}
+
+
+impl<'w, 's> UndoSpawns<'w, 's> {
+    /// Registers the roots together with all of their descendants, once the [`Commands`] of the system are applied,
+    /// so they can be spawned by the same system.
+    pub fn register(&mut self, roots: impl IntoIterator<Item = Entity>) {
+        let roots: Vec<Entity> = roots.into_iter().collect();
+        let meta = UndoMeta {
+            entities: roots.clone(),
+            ..UndoMeta::default()
+        };
+        let meta = self.scheduler.routed(meta);
+        let no = self.scheduler.next_slot(meta.channel);
+        self.commands.queue(move |world: &mut World| {
+            let entities = with_descendants(world, &roots);
+            send(world, live(&entities), false, no, meta);
+        });
+    }
+}
+
+
+/// Spawns entities from [`Commands`] as undoable entries, despawning them when undone and respawning them when redone.
+///
+/// Despawned entities are kept as reflected snapshots like with [`UndoSpawns`], so only the components registered
+/// for reflection are respawned. It must be set up via [`AppUndoEx::add_undo_spawns`](crate::prelude::AppUndoEx::add_undo_spawns).
+pub trait CommandsUndoEx<'w, 's> {
+    /// Spawns the entity and registers an entry which despawns it along with its descendants,
+    /// including the children added via the returned [`EntityCommands`].
+    fn spawn_undoable<'a>(&'a mut self, bundle: impl Bundle) -> EntityCommands<'a>;
+
+
+    /// Like [`CommandsUndoEx::spawn_undoable`], registering the entry with the meta, to which the entity is added.
+    fn spawn_undoable_with_meta<'a>(&'a mut self, bundle: impl Bundle, meta: UndoMeta) -> EntityCommands<'a>;
+}
+
+
+impl<'w, 's> CommandsUndoEx<'w, 's> for Commands<'w, 's> {
+    #[inline]
+    fn spawn_undoable<'a>(&'a mut self, bundle: impl Bundle) -> EntityCommands<'a> {
+        self.spawn_undoable_with_meta(bundle, UndoMeta::default())
+    }
+
+
+    fn spawn_undoable_with_meta<'a>(&'a mut self, bundle: impl Bundle, meta: UndoMeta) -> EntityCommands<'a> {
+        let entity = self.spawn(bundle).id();
+        let meta = if meta.entities.contains(&entity) {
+            meta
+        } else {
+            meta.with_entity(entity)
+        };
+        self.queue(move |world: &mut World| {
+            let entities = live(&[entity]);
+            world.undo_scheduler(|scheduler: &mut UndoScheduler<UndoSpawnEvent>| scheduler.register_with_redo_and_meta(
+                UndoSpawnEvent { spawn: false, entities: entities.clone() },
+                UndoSpawnEvent { spawn: true, entities },
+                meta,
+            ));
+        });
+        self.entity(entity)
+    }
+}
+
+
+/// Returns the entities and their descendants, parents first.
+pub(crate) fn with_descendants(world: &World, roots: &[Entity]) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = roots
+        .iter()
+        .copied()
+        .filter(|root| world.get_entity(*root).is_ok())
+        .collect();
+    let mut i = 0;
+    while i < entities.len() {
+        if let Some(children) = world.get::<Children>(entities[i]) {
+            let children: Vec<Entity> = children
+                .iter()
+                .copied()
+                .filter(|child| !entities.contains(child))
+                .collect();
+            entities.extend(children);
+        }
+        i += 1;
+    }
+    entities
+}
+
+
+#[inline]
+pub(crate) fn live(entities: &[Entity]) -> UndoSpawnedEntities {
+    Arc::new(Mutex::new(entities.iter().copied().map(UndoSpawnedEntity::Live).collect()))
+}
+
+
+#[inline]
+pub(crate) fn despawned(snapshots: Vec<UndoEntitySnapshot>) -> UndoSpawnedEntities {
+    Arc::new(Mutex::new(snapshots.into_iter().map(UndoSpawnedEntity::Despawned).collect()))
+}
+
+
+/// Registers the entry which spawns the entities when undone if `undo_spawns`, and despawns them otherwise,
+/// redone by the opposite.
+pub(crate) fn send(world: &mut World, entities: UndoSpawnedEntities, undo_spawns: bool, no: usize, meta: UndoMeta) {
+    world.write_message(UndoEvent {
+        inner: UndoSpawnEvent {
+            spawn: undo_spawns,
+            entities: entities.clone(),
+        },
+        redo: Some(UndoSpawnEvent {
+            spawn: !undo_spawns,
+            entities,
+        }),
+        no,
+        meta,
+        #[cfg(feature = "serde")]
+        registered_on: None,
+    });
+}
+
+
+/// Spawns the despawned entities from their snapshots, or despawns the live ones after capturing them.
+///
+/// Respawned entities get their respawned parent, or their former one if it was not despawned with them.
+/// Live entities are despawned along with the descendants they got since registered, such as the children
+/// added after [`CommandsUndoEx::spawn_undoable`].
+/// Entities despawned by something else in the meantime are skipped.
+pub(crate) fn set_spawned(world: &mut World, entities: &Mutex<Vec<UndoSpawnedEntity>>, spawn: bool) {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let mut entities = entities.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if spawn {
+        let mut respawned = HashMap::new();
+        let mut parents = Vec::new();
+        for entity in entities.iter_mut() {
+            if let UndoSpawnedEntity::Despawned(snapshot) = &*entity {
+                let live = snapshot.spawn(world, &type_registry.read());
+                respawned.insert(snapshot.entity, live);
+                parents.extend(snapshot.parent.map(|parent| (live, parent)));
+                *entity = UndoSpawnedEntity::Live(live);
+            }
+        }
+        for (live, parent) in parents {
+            let parent = respawned.get(&parent).copied().unwrap_or(parent);
+            if world.get_entity(parent).is_ok() {
+                world.entity_mut(live).insert(ChildOf(parent));
+            }
+        }
+        return;
+    }
+
+    let roots: Vec<Entity> = entities
+        .iter()
+        .filter_map(|entity| match entity {
+            UndoSpawnedEntity::Live(live) => Some(*live),
+            UndoSpawnedEntity::Despawned(_) => None,
+        })
+        .collect();
+    for descendant in with_descendants(world, &roots) {
+        if !roots.contains(&descendant) {
+            entities.push(UndoSpawnedEntity::Live(descendant));
+        }
+    }
+    let snapshots: Vec<Option<UndoEntitySnapshot>> = entities
+        .iter()
+        .map(|entity| match entity {
+            UndoSpawnedEntity::Live(live) => UndoEntitySnapshot::capture(world, &type_registry, *live),
+            UndoSpawnedEntity::Despawned(_) => None,
+        })
+        .collect();
+    // Descendants come after their ancestors, so they are despawned first rather than along with them.
+    for (entity, snapshot) in entities.iter_mut().zip(snapshots).rev() {
+        let Some(snapshot) = snapshot else {
+            continue;
+        };
+        world.despawn(snapshot.entity);
+        *entity = UndoSpawnedEntity::Despawned(snapshot);
+    }
+}
+
+
+pub(crate) fn apply_spawn_events_system(
+    world: &mut World,
+    mut reader: Local<MessageCursor<UndoSpawnEvent>>,
+) {
+    let events: Vec<UndoSpawnEvent> = reader
+        .read(world.resource::<Messages<UndoSpawnEvent>>())
+        .cloned()
+        .collect();
+    for event in events {
+        set_spawned(world, &event.entities, event.spawn);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{ChildOf, Children, Commands, Component, Entity, Reflect, ReflectComponent, With, Without};
+
+    use crate::prelude::{AppUndoEx, CommandsUndoEx, UndoChannel, UndoSpawns};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Component, Reflect, Default, Debug, PartialEq)]
+    #[reflect(Component)]
+    struct Name(u32);
+
+
+    /// Returns the names of the children of each named root under the level.
+    fn tree(app: &mut App, level: Entity) -> Vec<(u32, Vec<u32>)> {
+        let mut roots = app.world_mut().query_filtered::<(&Name, &ChildOf, Option<&Children>), Without<Level>>();
+        let mut names = app.world_mut().query::<&Name>();
+        let roots: Vec<(u32, Vec<Entity>)> = roots
+            .iter(app.world())
+            .filter(|(_, child_of, _)| child_of.parent() == level)
+            .map(|(name, _, children)| (name.0, children.map(|children| children.to_vec()).unwrap_or_default()))
+            .collect();
+        roots
+            .into_iter()
+            .map(|(name, children)| (name, children.iter().map(|child| names.get(app.world(), *child).unwrap().0).collect()))
+            .collect()
+    }
+
+
+    #[derive(Component)]
+    struct Level;
+
+
+    #[test]
+    fn despawn_and_respawn_instantiated_hierarchy() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.register_type::<Name>();
+        app.add_undo_spawns();
+        let level = app.world_mut().spawn(Level).id();
+        let root = app.world_mut().spawn((Name(1), ChildOf(level))).id();
+        app.world_mut().spawn((Name(2), ChildOf(root)));
+        app.world_mut().spawn((Name(3), ChildOf(root)));
+
+        let mut state = SystemState::<UndoSpawns>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register([root]);
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world_mut().query::<&Name>().iter(app.world()).count(), 0);
+        assert!(app.world().get::<Children>(level).is_none());
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(tree(&mut app, level), vec![(1, vec![2, 3])]);
+        assert_eq!(app.world_mut().query_filtered::<(), With<ChildOf>>().iter(app.world()).count(), 3);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world_mut().query::<&Name>().iter(app.world()).count(), 0);
+    }
+
+
+    #[test]
+    fn undo_spawn_from_commands() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.register_type::<Name>();
+        app.add_undo_spawns();
+        let level = app.world_mut().spawn(Level).id();
+
+        let mut state = SystemState::<Commands>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().spawn_undoable(Name(1)).insert(ChildOf(level)).with_children(|parent| {
+            parent.spawn(Name(2));
+        });
+        state.apply(app.world_mut());
+        app.update();
+        assert_eq!(tree(&mut app, level), vec![(1, vec![2])]);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world_mut().query::<&Name>().iter(app.world()).count(), 0);
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(tree(&mut app, level), vec![(1, vec![2])]);
+    }
+}
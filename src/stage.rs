@@ -0,0 +1,99 @@
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::{MessageReader, Res, World};
+
+use crate::pacing::UndoRequestQueue;
+use crate::request::RequestUndoEvent;
+
+/// Runs once at the start of [`PreUpdate`](bevy::prelude::PreUpdate) on the frames where undo or redo requests
+/// are about to be resolved, such as to commit an in-progress text field so the undo operates on consistent state.
+///
+/// Entries registered here are recorded before the requests are resolved, so they are the first to be undone.
+/// Add systems via `app.add_systems(PreUndo, ...)`.
+#[derive(ScheduleLabel, Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct PreUndo;
+
+
+/// Runs once in [`PostUpdate`](bevy::prelude::PostUpdate) on the frames where entries have been undone or redone,
+/// after all of their handlers, such as to rebuild navmeshes, recompute bounds or refresh UIs.
+///
+/// Add systems via `app.add_systems(PostUndo, ...)`.
+#[derive(ScheduleLabel, Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct PostUndo;
+
+
+/// Returns true if an undo or redo has been requested since the last call, or requests are deferred from earlier frames.
+pub(crate) fn has_pending_undo_operations(
+    mut er: MessageReader<RequestUndoEvent>,
+    queue: Res<UndoRequestQueue>,
+) -> bool {
+    let requested = er.read().filter(|request| request.is_operation()).count();
+    0 < requested || 0 < queue.len()
+}
+
+
+pub(crate) fn run_pre_undo_schedule_system(world: &mut World) {
+    world.run_schedule(PreUndo);
+}
+
+
+pub(crate) fn run_post_undo_schedule_system(world: &mut World) {
+    world.run_schedule(PostUndo);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Local, Message, ResMut, Resource};
+
+    use crate::prelude::{PostUndo, PreUndo, UndoScheduler};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[derive(Resource, Default)]
+    struct Rebuilds(usize);
+
+
+    #[test]
+    fn run_once_per_undo_frame() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.init_resource::<Rebuilds>();
+            app.add_systems(PostUndo, |mut rebuilds: ResMut<Rebuilds>| rebuilds.0 += 1);
+        });
+        harness.register(Move(1));
+        harness.register(Move(1));
+        harness.frames(2);
+        assert_eq!(harness.app().world().resource::<Rebuilds>().0, 0);
+
+        harness.undo();
+        harness.frames(2);
+        assert_eq!(harness.app().world().resource::<Rebuilds>().0, 1);
+        harness.undo();
+        assert_eq!(harness.app().world().resource::<Rebuilds>().0, 2);
+    }
+
+
+    #[test]
+    fn flush_pending_edits_before_undo() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.add_systems(PreUndo, |mut scheduler: UndoScheduler<Move>, mut flushed: Local<bool>| {
+                if !*flushed {
+                    scheduler.register(Move(2));
+                    *flushed = true;
+                }
+            });
+        });
+        harness.register(Move(1));
+        harness.frames(2);
+        harness.expect([]);
+
+        harness.undo();
+        harness.expect([Move(2)]);
+        harness.undo();
+        harness.expect([Move(1)]);
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::{Message, MessageReader, MessageWriter, Res, ResMut, Resource};
+
+use crate::DispatchUndoEvent;
+use crate::pacing::UndoRequestQueue;
+
+/// Where the undo-operations are in the current frame, updated at the end of the pipeline in [`PreUpdate`](bevy::prelude::PreUpdate).
+///
+/// Systems which should not run while an undo is being handled, such as input or physics,
+/// can be gated with `run_if(not(undo_in_progress()))`.
+#[derive(Resource, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UndoState {
+    /// Nothing is undone or redone in this frame.
+    #[default]
+    Idle,
+
+    /// Requests are deferred to later frames by
+    /// [`AppUndoEx::configure_undo_requests_per_frame`](crate::prelude::AppUndoEx::configure_undo_requests_per_frame),
+    /// or held back by an [`UndoLock`](crate::prelude::UndoLock).
+    Deferred,
+
+    /// Entries are undone or redone in this frame, so their handlers run in [`Update`](bevy::prelude::Update).
+    Dispatching,
+}
+
+
+impl UndoState {
+    /// Returns true unless [`UndoState::Idle`], that is while requests accepted are being resolved,
+    /// whether dispatched in this frame or deferred to later ones.
+    #[inline(always)]
+    pub fn in_progress(self) -> bool {
+        self != UndoState::Idle
+    }
+}
+
+
+/// A run condition which holds while an undo or redo is in progress, see [`UndoState::in_progress`].
+///
+/// Gameplay, physics or autosave systems can pause themselves while the world is being rewound
+/// with `run_if(not(undo_in_progress()))`.
+pub fn undo_in_progress() -> impl FnMut(Option<Res<UndoState>>) -> bool + Clone {
+    |state: Option<Res<UndoState>>| state.is_some_and(|state| state.in_progress())
+}
+
+
+/// Sent when [`UndoState`] changes.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoStateChanged {
+    pub from: UndoState,
+    pub to: UndoState,
+}
+
+
+pub(crate) fn update_undo_state_system(
+    mut er: MessageReader<DispatchUndoEvent>,
+    mut ew: MessageWriter<UndoStateChanged>,
+    mut state: ResMut<UndoState>,
+    queue: Res<UndoRequestQueue>,
+) {
+    let dispatching = er
+        .read()
+        .any(|dispatch| matches!(dispatch, DispatchUndoEvent::Undo(_) | DispatchUndoEvent::Redo(_)));
+    let next = if dispatching {
+        UndoState::Dispatching
+    } else if 0 < queue.len() {
+        UndoState::Deferred
+    } else {
+        UndoState::Idle
+    };
+    if *state != next {
+        ew.write(UndoStateChanged { from: *state, to: next });
+        *state = next;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::prelude::{IntoScheduleConfigs, Message, Messages, not, ResMut, Resource};
+
+    use crate::prelude::{undo_in_progress, UndoState, UndoStateChanged};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move;
+
+
+    fn changes(app: &mut App) -> Vec<(UndoState, UndoState)> {
+        app.world_mut()
+            .resource_mut::<Messages<UndoStateChanged>>()
+            .drain()
+            .map(|changed| (changed.from, changed.to))
+            .collect()
+    }
+
+
+    #[test]
+    fn notify_transitions() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.register(Move);
+        harness.register(Move);
+        assert!(changes(harness.app()).is_empty());
+
+        harness.undo();
+        assert_eq!(*harness.app().world().resource::<UndoState>(), UndoState::Dispatching);
+        assert_eq!(changes(harness.app()), vec![(UndoState::Idle, UndoState::Dispatching)]);
+
+        harness.frames(1);
+        assert_eq!(changes(harness.app()), vec![(UndoState::Dispatching, UndoState::Idle)]);
+    }
+
+
+    #[derive(Resource, Default)]
+    struct Ticks(u32);
+
+
+    #[test]
+    fn pause_systems_while_in_progress() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.init_resource::<Ticks>();
+            app.add_systems(Update, (|mut ticks: ResMut<Ticks>| ticks.0 += 1).run_if(not(undo_in_progress())));
+        });
+        harness.register(Move);
+        let ticks = harness.app().world().resource::<Ticks>().0;
+
+        harness.undo();
+        assert_eq!(harness.app().world().resource::<Ticks>().0, ticks);
+        harness.frames(1);
+        assert_eq!(harness.app().world().resource::<Ticks>().0, ticks + 1);
+    }
+}
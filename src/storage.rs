@@ -0,0 +1,176 @@
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEntry;
+
+pub mod inline;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Where the registered entries of `E` are kept until they are undone or dropped.
+///
+/// The default is [`UndoMemoryStorage`], other backends such as disk-backed ones are set via
+/// [`AppUndoEx::set_undo_storage`](crate::prelude::AppUndoEx::set_undo_storage).
+/// The metadata of the entries is kept by the crate regardless of the storage,
+/// backends persisting it implement [`UndoStorage::push_with_meta`] and [`UndoStorage::restored`].
+pub trait UndoStorage<E: UndoPayload>: Send + Sync + 'static {
+    fn push(&mut self, entry: UndoEntry<E>);
+
+
+    /// Pushes a newly registered entry along with its metadata, defaulting to [`UndoStorage::push`].
+    #[inline]
+    fn push_with_meta(&mut self, entry: UndoEntry<E>, _meta: &UndoMeta) {
+        self.push(entry);
+    }
+
+
+    /// Returns the slot, metadata and whether redoable of the entries the storage held before it was set,
+    /// such as the ones persisted by a past run, which are then registered in the history.
+    #[inline]
+    fn restored(&self) -> Vec<(usize, UndoMeta, bool)> {
+        Vec::new()
+    }
+
+
+    /// Removes the most recently pushed entry belonging to the slot.
+    fn pop(&mut self, no: usize) -> Option<UndoEntry<E>>;
+
+
+    /// Calls `f` with each entry, from the oldest pushed.
+    fn for_each(&self, f: &mut dyn FnMut(&UndoEntry<E>));
+
+
+    /// Removes all entries, returning them from the oldest pushed.
+    fn drain(&mut self) -> Vec<UndoEntry<E>>;
+
+
+    fn len(&self) -> usize;
+
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+
+/// Keeps the entries in memory, the default [`UndoStorage`].
+pub struct UndoMemoryStorage<E: UndoPayload>(Vec<UndoEntry<E>>);
+
+
+impl<E: UndoPayload> Default for UndoMemoryStorage<E> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+
+impl<E: UndoPayload> UndoMemoryStorage<E> {
+    /// Creates the storage with room for the count of entries allocated upfront,
+    /// so bursts of registrations from high-frequency tools do not grow it on each push.
+    #[inline(always)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+}
+
+
+impl<E: UndoPayload> UndoStorage<E> for UndoMemoryStorage<E> {
+    #[inline(always)]
+    fn push(&mut self, entry: UndoEntry<E>) {
+        self.0.push(entry);
+    }
+
+
+    #[inline]
+    fn pop(&mut self, no: usize) -> Option<UndoEntry<E>> {
+        let index = self.0.iter().rposition(|entry| entry.no == no)?;
+
+        Some(self.0.remove(index))
+    }
+
+
+    #[inline]
+    fn for_each(&self, f: &mut dyn FnMut(&UndoEntry<E>)) {
+        self.0.iter().for_each(f);
+    }
+
+
+    #[inline(always)]
+    fn drain(&mut self) -> Vec<UndoEntry<E>> {
+        std::mem::take(&mut self.0)
+    }
+
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoEntry, UndoMemoryStorage, UndoScheduler, UndoStorage};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(usize);
+
+
+    /// Counts the entries ever pushed, delegating the rest to the memory storage.
+    #[derive(Default)]
+    struct CountingStorage {
+        pushed: Arc<Mutex<usize>>,
+        inner: UndoMemoryStorage<Move>,
+    }
+
+
+    impl UndoStorage<Move> for CountingStorage {
+        fn push(&mut self, entry: UndoEntry<Move>) {
+            *self.pushed.lock().unwrap() += 1;
+            self.inner.push(entry);
+        }
+
+        fn pop(&mut self, no: usize) -> Option<UndoEntry<Move>> {
+            self.inner.pop(no)
+        }
+
+        fn for_each(&self, f: &mut dyn FnMut(&UndoEntry<Move>)) {
+            self.inner.for_each(f);
+        }
+
+        fn drain(&mut self) -> Vec<UndoEntry<Move>> {
+            self.inner.drain()
+        }
+
+        fn len(&self) -> usize {
+            self.inner.len()
+        }
+    }
+
+
+    #[test]
+    fn undo_through_custom_storage() {
+        let storage = CountingStorage::default();
+        let pushed = storage.pushed.clone();
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.set_undo_storage::<Move>(storage);
+        app.add_undo_event::<Move>();
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>| s.register_all([Move(1), Move(2)]));
+        app.update();
+        assert_eq!(*pushed.lock().unwrap(), 2);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(2)]);
+    }
+}
@@ -0,0 +1,83 @@
+use smallvec::SmallVec;
+
+use crate::payload::UndoPayload;
+use crate::storage::UndoStorage;
+use crate::undo_event::UndoEntry;
+
+/// Keeps up to `N` entries inline without allocating, and moves them to the heap only beyond that,
+/// for high-frequency tools whose histories are usually short.
+pub struct UndoInlineStorage<E: UndoPayload, const N: usize = 16>(SmallVec<[UndoEntry<E>; N]>);
+
+
+impl<E: UndoPayload, const N: usize> Default for UndoInlineStorage<E, N> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(SmallVec::new())
+    }
+}
+
+
+impl<E: UndoPayload, const N: usize> UndoStorage<E> for UndoInlineStorage<E, N> {
+    #[inline(always)]
+    fn push(&mut self, entry: UndoEntry<E>) {
+        self.0.push(entry);
+    }
+
+
+    #[inline]
+    fn pop(&mut self, no: usize) -> Option<UndoEntry<E>> {
+        let index = self.0.iter().rposition(|entry| entry.no == no)?;
+
+        Some(self.0.remove(index))
+    }
+
+
+    #[inline]
+    fn for_each(&self, f: &mut dyn FnMut(&UndoEntry<E>)) {
+        self.0.iter().for_each(f);
+    }
+
+
+    #[inline(always)]
+    fn drain(&mut self) -> Vec<UndoEntry<E>> {
+        self.0.drain(..).collect()
+    }
+
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoInlineStorage, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Brush(u8);
+
+
+    #[test]
+    fn spill_beyond_inline_capacity() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.set_undo_storage::<Brush>(UndoInlineStorage::<Brush, 2>::default());
+        app.add_undo_event::<Brush>();
+        app.add_systems(Startup, |mut s: UndoScheduler<Brush>| s.register_all((0..4).map(Brush)));
+        app.update();
+
+        for _ in 0..4 {
+            app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        }
+        app.update();
+        let undone: Vec<Brush> = app.world_mut().resource_mut::<Messages<Brush>>().drain().collect();
+        assert_eq!(undone, vec![Brush(3), Brush(2), Brush(1), Brush(0)]);
+    }
+}
@@ -0,0 +1,537 @@
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::log::warn;
+use bevy::platform::collections::HashMap;
+use rusqlite::{Connection, params};
+
+use crate::channel::UndoChannel;
+use crate::meta::UndoMeta;
+use crate::storage::UndoStorage;
+use crate::undo_event::UndoEntry;
+use crate::version::{UndoVersioned, UndoVersionedBytes};
+
+/// Persists the entries of `E` as rows of a SQLite database, encoded via [`UndoVersioned`].
+///
+/// Each storage opened on a database starts a new session, and only sees the rows of its own session.
+/// The rows of past sessions are kept, so they survive crashes: [`UndoSqliteStorage::resume`] continues the latest session,
+/// and the entries it holds are registered in the history again once the storage is set.
+///
+/// Rows are kept in the `undo_entries` table with the columns
+/// `session`, `type_name`, `no`, `version`, `payload`, `redo_version` and `redo`,
+/// along with the metadata `channel`, `tag`, `tags`, `importance`, `sticky` and `registered_on` (milliseconds since the Unix epoch).
+/// Metadata referring to the running app, such as entities or links, is not persisted.
+///
+/// The rows are decoded once when the session is opened, the decoded entries being kept in memory alongside the database.
+/// Rows which fail to decode are kept in the database untouched.
+pub struct UndoSqliteStorage<E: UndoVersioned> {
+    connection: Mutex<Connection>,
+    session: i64,
+    entries: Vec<UndoSqliteEntry<E>>,
+    /// The metadata of the popped entries by slot, given back once they are pushed again such as on redo.
+    popped: HashMap<usize, Vec<UndoSqliteMeta>>,
+}
+
+
+/// A decoded entry along with its row.
+struct UndoSqliteEntry<E: UndoVersioned> {
+    id: i64,
+    entry: UndoEntry<E>,
+    meta: UndoSqliteMeta,
+}
+
+
+/// The metadata persisted along with an entry.
+#[derive(Debug, Default, Clone)]
+struct UndoSqliteMeta {
+    channel: u64,
+    tag: String,
+    tags: Vec<String>,
+    importance: i32,
+    sticky: bool,
+    registered_on: Option<i64>,
+}
+
+
+/// Separates the tags in the `tags` column.
+const TAG_SEPARATOR: char = '\u{1f}';
+
+
+impl UndoSqliteMeta {
+    fn new(meta: &UndoMeta) -> Self {
+        Self {
+            channel: meta.channel.0,
+            tag: meta.tag.clone(),
+            tags: meta.tags.clone(),
+            importance: meta.importance,
+            sticky: meta.sticky,
+            registered_on: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_millis() as i64),
+        }
+    }
+
+
+    fn to_meta(&self) -> UndoMeta {
+        UndoMeta {
+            channel: UndoChannel(self.channel),
+            tag: self.tag.clone(),
+            tags: self.tags.clone(),
+            importance: self.importance,
+            sticky: self.sticky,
+            ..UndoMeta::default()
+        }
+    }
+
+
+    fn joined_tags(&self) -> String {
+        self.tags.join(&TAG_SEPARATOR.to_string())
+    }
+}
+
+
+impl<E: UndoVersioned> UndoSqliteStorage<E> {
+    /// Opens or creates the database file, starting a new session.
+    #[inline]
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+
+    /// Opens a database which lives only as long as the storage.
+    #[inline]
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+
+    /// Opens or creates the database file, continuing the latest session holding entries of `E`,
+    /// such as the one of a run which crashed.
+    ///
+    /// A new session is started if there is none.
+    pub fn resume(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        create_tables(&connection)?;
+        let session: Option<i64> = connection.query_row(
+            "SELECT MAX(session) FROM undo_entries WHERE type_name = ?1",
+            [std::any::type_name::<E>()],
+            |row| row.get(0),
+        )?;
+        match session {
+            Some(session) => Self::with_session(connection, session),
+            None => Self::new(connection),
+        }
+    }
+
+
+    /// Starts a new session on the database.
+    ///
+    /// Sessions are allocated from the `undo_sessions` table, so storages opened concurrently never share one.
+    pub fn new(connection: Connection) -> rusqlite::Result<Self> {
+        create_tables(&connection)?;
+        connection.execute("INSERT INTO undo_sessions (started_on) VALUES (strftime('%s', 'now'))", [])?;
+        let session = connection.last_insert_rowid();
+        Self::with_session(connection, session)
+    }
+
+
+    /// Continues the session, loading the entries of `E` it holds.
+    pub fn with_session(connection: Connection, session: i64) -> rusqlite::Result<Self> {
+        create_tables(&connection)?;
+        let entries = load_entries(&connection, session)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            session,
+            entries,
+            popped: HashMap::new(),
+        })
+    }
+
+
+    /// Returns the session of this storage, which is greater than the ones started before on the database.
+    #[inline(always)]
+    pub fn session(&self) -> i64 {
+        self.session
+    }
+
+
+    /// Runs the function with the connection, for queries over past sessions.
+    pub fn with_connection<R>(&self, f: impl FnOnce(&Connection) -> R) -> R {
+        let connection = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&connection)
+    }
+
+
+    fn try_insert(&self, entry: &UndoEntry<E>, meta: &UndoSqliteMeta) -> rusqlite::Result<i64> {
+        let payload = UndoVersionedBytes::encode(&entry.inner);
+        let redo = entry.redo.as_ref().map(UndoVersionedBytes::encode);
+        self.with_connection(|connection| {
+            connection.execute(
+                "INSERT INTO undo_entries (session, type_name, no, version, payload, redo_version, redo,
+                    channel, tag, tags, importance, sticky, registered_on)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    self.session,
+                    std::any::type_name::<E>(),
+                    entry.no as i64,
+                    payload.version,
+                    payload.bytes,
+                    redo.as_ref().map(|redo| redo.version),
+                    redo.map(|redo| redo.bytes),
+                    meta.channel as i64,
+                    meta.tag,
+                    meta.joined_tags(),
+                    meta.importance,
+                    meta.sticky,
+                    meta.registered_on,
+                ],
+            )?;
+            Ok(connection.last_insert_rowid())
+        })
+    }
+
+
+    fn try_delete(&self, ids: &[i64]) -> rusqlite::Result<()> {
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare_cached("DELETE FROM undo_entries WHERE id = ?1")?;
+            for id in ids {
+                statement.execute([id])?;
+            }
+            Ok(())
+        })
+    }
+
+
+    fn insert(&mut self, entry: UndoEntry<E>, meta: UndoSqliteMeta) {
+        // The entry is kept even if the row could not be written, so the running app can still undo it.
+        let id = report(self.try_insert(&entry, &meta).map(Some)).unwrap_or(-1);
+        self.entries.push(UndoSqliteEntry { id, entry, meta });
+    }
+}
+
+
+fn create_tables(connection: &Connection) -> rusqlite::Result<()> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS undo_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_on INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS undo_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session INTEGER NOT NULL,
+            type_name TEXT NOT NULL,
+            no INTEGER NOT NULL,
+            version INTEGER NOT NULL,
+            payload BLOB NOT NULL,
+            redo_version INTEGER,
+            redo BLOB
+        );
+        CREATE INDEX IF NOT EXISTS undo_entries_slot ON undo_entries (session, type_name, no);",
+    )?;
+    // The metadata columns are added separately, so databases written before they existed are migrated.
+    let mut statement = connection.prepare("SELECT name FROM pragma_table_info('undo_entries')")?;
+    let columns = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (column, definition) in [
+        ("channel", "INTEGER NOT NULL DEFAULT 0"),
+        ("tag", "TEXT NOT NULL DEFAULT ''"),
+        ("tags", "TEXT NOT NULL DEFAULT ''"),
+        ("importance", "INTEGER NOT NULL DEFAULT 0"),
+        ("sticky", "INTEGER NOT NULL DEFAULT 0"),
+        ("registered_on", "INTEGER"),
+    ] {
+        if !columns.iter().any(|name| name == column) {
+            connection.execute(&format!("ALTER TABLE undo_entries ADD COLUMN {column} {definition}"), [])?;
+        }
+    }
+    Ok(())
+}
+
+
+fn load_entries<E: UndoVersioned>(connection: &Connection, session: i64) -> rusqlite::Result<Vec<UndoSqliteEntry<E>>> {
+    let mut statement = connection.prepare(
+        "SELECT id, no, version, payload, redo_version, redo, channel, tag, tags, importance, sticky, registered_on
+        FROM undo_entries WHERE session = ?1 AND type_name = ?2 ORDER BY id",
+    )?;
+    let rows = statement.query_map(params![session, std::any::type_name::<E>()], |row| {
+        let redo_version: Option<u32> = row.get(4)?;
+        let redo: Option<Vec<u8>> = row.get(5)?;
+        let tags: String = row.get(8)?;
+        Ok(UndoSqliteRow {
+            id: row.get(0)?,
+            no: row.get::<_, i64>(1)? as usize,
+            payload: UndoVersionedBytes {
+                version: row.get(2)?,
+                bytes: row.get(3)?,
+            },
+            redo: redo_version
+                .zip(redo)
+                .map(|(version, bytes)| UndoVersionedBytes { version, bytes }),
+            meta: UndoSqliteMeta {
+                channel: row.get::<_, i64>(6)? as u64,
+                tag: row.get(7)?,
+                tags: tags.split(TAG_SEPARATOR).filter(|tag| !tag.is_empty()).map(String::from).collect(),
+                importance: row.get(9)?,
+                sticky: row.get(10)?,
+                registered_on: row.get(11)?,
+            },
+        })
+    })?;
+    let mut entries = Vec::new();
+    for row in rows {
+        entries.extend(decode(row?));
+    }
+    Ok(entries)
+}
+
+
+/// A row read from the table, before its payloads are decoded.
+struct UndoSqliteRow {
+    id: i64,
+    no: usize,
+    payload: UndoVersionedBytes,
+    redo: Option<UndoVersionedBytes>,
+    meta: UndoSqliteMeta,
+}
+
+
+fn decode<E: UndoVersioned>(row: UndoSqliteRow) -> Option<UndoSqliteEntry<E>> {
+    let Some(inner) = row.payload.decode::<E>() else {
+        warn!("failed to decode a stored undo entry of {}, keeping its row {}", std::any::type_name::<E>(), row.id);
+        return None;
+    };
+    Some(UndoSqliteEntry {
+        id: row.id,
+        entry: UndoEntry {
+            inner,
+            redo: row.redo.and_then(|redo| redo.decode::<E>()),
+            no: row.no,
+        },
+        meta: row.meta,
+    })
+}
+
+
+fn report<T: Default>(result: rusqlite::Result<T>) -> T {
+    result.unwrap_or_else(|error| {
+        warn!("undo sqlite storage: {error}");
+        T::default()
+    })
+}
+
+
+impl<E: UndoVersioned> UndoStorage<E> for UndoSqliteStorage<E> {
+    #[inline]
+    fn push(&mut self, entry: UndoEntry<E>) {
+        let meta = self
+            .popped
+            .get_mut(&entry.no)
+            .and_then(Vec::pop)
+            .unwrap_or_default();
+        self.insert(entry, meta);
+    }
+
+
+    #[inline]
+    fn push_with_meta(&mut self, entry: UndoEntry<E>, meta: &UndoMeta) {
+        // A registration discards the redo history of its channel, so its popped entries are never pushed again.
+        for popped in self.popped.values_mut() {
+            popped.retain(|popped| popped.channel != meta.channel.0);
+        }
+        self.popped.retain(|_, popped| !popped.is_empty());
+        self.insert(entry, UndoSqliteMeta::new(meta));
+    }
+
+
+    fn restored(&self) -> Vec<(usize, UndoMeta, bool)> {
+        self.entries
+            .iter()
+            .map(|stored| (stored.entry.no, stored.meta.to_meta(), stored.entry.redo.is_some()))
+            .collect()
+    }
+
+
+    fn pop(&mut self, no: usize) -> Option<UndoEntry<E>> {
+        let index = self.entries.iter().rposition(|stored| stored.entry.no == no)?;
+        let stored = self.entries.remove(index);
+        report(self.try_delete(&[stored.id]));
+        let popped = self.popped.entry(no).or_default();
+        popped.push(stored.meta);
+        Some(stored.entry)
+    }
+
+
+    #[inline]
+    fn for_each(&self, f: &mut dyn FnMut(&UndoEntry<E>)) {
+        self.entries.iter().for_each(|stored| f(&stored.entry));
+    }
+
+
+    fn drain(&mut self) -> Vec<UndoEntry<E>> {
+        let ids: Vec<i64> = self.entries.iter().map(|stored| stored.id).collect();
+        report(self.try_delete(&ids));
+        self.popped.clear();
+        std::mem::take(&mut self.entries)
+            .into_iter()
+            .map(|stored| stored.entry)
+            .collect()
+    }
+
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Message, Messages};
+
+    use crate::history::UndoHistory;
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoEntry, UndoMeta, UndoScheduler, UndoSqliteStorage, UndoStorage, UndoVersioned};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(u8);
+
+
+    impl UndoVersioned for Move {
+        const VERSION: u32 = 1;
+
+        fn encode(&self) -> Vec<u8> {
+            vec![self.0]
+        }
+
+        fn decode(bytes: &[u8]) -> Option<Self> {
+            match bytes {
+                [x] => Some(Self(*x)),
+                _ => None
+            }
+        }
+    }
+
+
+    #[test]
+    fn undo_through_sqlite() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.set_undo_storage::<Move>(UndoSqliteStorage::<Move>::open_in_memory().unwrap());
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>| {
+            s.register(Move(1));
+            s.register_all_grouped([Move(2), Move(3)]);
+        });
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(3), Move(2)]);
+    }
+
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("bevy_undo2_sqlite_{name}_{}.db", std::process::id()))
+    }
+
+
+    #[test]
+    fn keep_rows_of_past_sessions() {
+        let path = temp_path("past");
+        let mut storage = UndoSqliteStorage::<Move>::open(&path).unwrap();
+        storage.push(UndoEntry { inner: Move(1), redo: Some(Move(2)), no: 1 });
+        drop(storage);
+
+        let storage = UndoSqliteStorage::<Move>::open(&path).unwrap();
+        assert!(storage.is_empty());
+        let past: i64 = storage.with_connection(|connection| {
+            connection
+                .query_row("SELECT COUNT(*) FROM undo_entries WHERE session < ?1", [storage.session()], |row| row.get(0))
+                .unwrap()
+        });
+        assert_eq!(past, 1);
+        drop(storage);
+        let _ = std::fs::remove_file(path);
+    }
+
+
+    #[test]
+    fn allocate_distinct_sessions_to_concurrent_storages() {
+        let path = temp_path("concurrent");
+        let first = UndoSqliteStorage::<Move>::open(&path).unwrap();
+        let second = UndoSqliteStorage::<Move>::open(&path).unwrap();
+        assert_ne!(first.session(), second.session());
+        drop((first, second));
+        let _ = std::fs::remove_file(path);
+    }
+
+
+    #[test]
+    fn resume_history_after_crash() {
+        let path = temp_path("resume");
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.set_undo_storage::<Move>(UndoSqliteStorage::<Move>::open(&path).unwrap());
+        app.add_systems(Startup, |mut s: UndoScheduler<Move>| {
+            s.register(Move(1));
+            s.register_with_meta(Move(2), UndoMeta::tagged("brush"));
+        });
+        app.update();
+        // The app is dropped without clearing anything, as if it crashed.
+        drop(app);
+
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.set_undo_storage::<Move>(UndoSqliteStorage::<Move>::resume(&path).unwrap());
+        let tags: Vec<String> = app.world().resource::<UndoHistory>().entries().map(|entry| entry.meta.tag.clone()).collect();
+        assert_eq!(tags, vec![String::new(), "brush".to_string()]);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<Move> = app.world_mut().resource_mut::<Messages<Move>>().drain().collect();
+        assert_eq!(undone, vec![Move(2), Move(1)]);
+        drop(app);
+        let _ = std::fs::remove_file(path);
+    }
+
+
+    #[test]
+    fn keep_rows_failing_to_decode() {
+        let path = temp_path("undecodable");
+        let mut storage = UndoSqliteStorage::<Move>::open(&path).unwrap();
+        storage.push(UndoEntry { inner: Move(1), redo: None, no: 1 });
+        storage.with_connection(|connection| {
+            connection
+                .execute(
+                    "INSERT INTO undo_entries (session, type_name, no, version, payload) VALUES (?1, ?2, 2, 1, x'0102')",
+                    rusqlite::params![storage.session(), std::any::type_name::<Move>()],
+                )
+                .unwrap();
+        });
+        drop(storage);
+
+        let mut storage = UndoSqliteStorage::<Move>::resume(&path).unwrap();
+        assert_eq!(storage.len(), 1);
+        assert!(storage.pop(2).is_none());
+        assert_eq!(storage.drain().len(), 1);
+        let rows: i64 = storage.with_connection(|connection| {
+            connection
+                .query_row("SELECT COUNT(*) FROM undo_entries WHERE session = ?1", [storage.session()], |row| row.get(0))
+                .unwrap()
+        });
+        assert_eq!(rows, 1);
+        drop(storage);
+        let _ = std::fs::remove_file(path);
+    }
+}
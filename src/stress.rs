@@ -0,0 +1,183 @@
+use bevy::app::{App, Plugin, Update};
+use bevy::prelude::{Message, MessageReader, MessageWriter, ResMut, Resource};
+
+use crate::channel::UndoChannel;
+use crate::extension::AppUndoEx;
+use crate::meta::UndoMeta;
+use crate::request::RequestUndoEvent;
+use crate::undo_event::UndoScheduler;
+
+/// The load generated each frame by [`UndoStressPlugin`], each count being drawn between zero and the maximum.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoStressConfig {
+    /// The seed of the random generator, so a failing run can be replayed.
+    pub seed: u64,
+
+    pub max_registrations_per_frame: usize,
+
+    /// The maximum count of events registered together as one group, registrations of a single event being redoable.
+    pub max_group_len: usize,
+
+    pub max_undos_per_frame: usize,
+
+    pub max_redos_per_frame: usize,
+}
+
+
+impl Default for UndoStressConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            seed: 0x5eed,
+            max_registrations_per_frame: 8,
+            max_group_len: 4,
+            max_undos_per_frame: 4,
+            max_redos_per_frame: 2,
+        }
+    }
+}
+
+
+/// The payload registered by [`UndoStressPlugin`], numbered in registration order.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoStressEvent(pub u64);
+
+
+/// The counts of the operations generated and handled by [`UndoStressPlugin`] so far.
+#[derive(Resource, Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoStressStats {
+    pub frames: usize,
+    pub registered: usize,
+    pub undo_requests: usize,
+    pub redo_requests: usize,
+
+    /// The count of [`UndoStressEvent`] sent by undos and redos.
+    pub handled: usize,
+}
+
+
+/// Generates random registrations, groups, undos and redos every frame to validate the performance
+/// and the correctness of the history under load.
+///
+/// The `stress` feature enables `debug_invariants`, so every frame is checked for inconsistencies,
+/// panicking with a dump of the history. Progress is reported via [`UndoStressStats`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoStressPlugin(pub UndoStressConfig);
+
+
+impl Plugin for UndoStressPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_undo_event::<UndoStressEvent>()
+            .add_undo_handler::<UndoStressEvent, _>(count_handled_system)
+            .insert_resource(UndoStressState {
+                config: self.0,
+                rng: UndoStressRng::new(self.0.seed),
+                next: 0,
+            })
+            .init_resource::<UndoStressStats>()
+            .add_systems(Update, generate_load_system);
+    }
+}
+
+
+#[derive(Resource)]
+struct UndoStressState {
+    config: UndoStressConfig,
+    rng: UndoStressRng,
+    next: u64,
+}
+
+
+/// A xorshift64* generator, enough to draw the load reproducibly without another dependency.
+struct UndoStressRng(u64);
+
+
+impl UndoStressRng {
+    #[inline]
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+
+    /// Returns a number from zero to `max` inclusive.
+    fn up_to(&mut self, max: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let n = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (n % (max as u64 + 1)) as usize
+    }
+}
+
+
+fn generate_load_system(
+    mut state: ResMut<UndoStressState>,
+    mut stats: ResMut<UndoStressStats>,
+    mut scheduler: UndoScheduler<UndoStressEvent>,
+    mut ew: MessageWriter<RequestUndoEvent>,
+) {
+    let state = &mut *state;
+    let config = state.config;
+    stats.frames += 1;
+
+    for _ in 0..state.rng.up_to(config.max_registrations_per_frame) {
+        let len = 1 + state.rng.up_to(config.max_group_len.saturating_sub(1));
+        let events: Vec<UndoStressEvent> = (state.next..state.next + len as u64).map(UndoStressEvent).collect();
+        state.next += len as u64;
+        stats.registered += len;
+        if len == 1 {
+            scheduler.push(events[0], Some(events[0]), UndoMeta::default());
+        } else {
+            scheduler.register_all_grouped(events);
+        }
+    }
+
+    let undos = state.rng.up_to(config.max_undos_per_frame);
+    let redos = state.rng.up_to(config.max_redos_per_frame);
+    ew.write_batch((0..undos).map(|_| RequestUndoEvent::Latest(UndoChannel::DEFAULT)));
+    ew.write_batch((0..redos).map(|_| RequestUndoEvent::Redo(UndoChannel::DEFAULT)));
+    stats.undo_requests += undos;
+    stats.redo_requests += redos;
+}
+
+
+fn count_handled_system(
+    mut er: MessageReader<UndoStressEvent>,
+    mut stats: ResMut<UndoStressStats>,
+) {
+    stats.handled += er.read().count();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+
+    use crate::history::UndoHistory;
+    use crate::prelude::{UndoStressConfig, UndoStressPlugin, UndoStressStats};
+    use crate::UndoPlugin;
+
+    fn run(seed: u64, frames: usize) -> (UndoStressStats, usize) {
+        let mut app = App::new();
+        app.add_plugins((UndoPlugin, UndoStressPlugin(UndoStressConfig {
+            seed,
+            ..UndoStressConfig::default()
+        })));
+        for _ in 0..frames {
+            app.update();
+        }
+        let entries = app.world().resource::<UndoHistory>().entries().count();
+        (*app.world().resource::<UndoStressStats>(), entries)
+    }
+
+
+    #[test]
+    fn generate_reproducible_load() {
+        let (stats, entries) = run(7, 200);
+        assert_eq!(stats.frames, 200);
+        assert!(0 < stats.registered && 0 < stats.undo_requests && 0 < stats.handled);
+        assert!(entries <= stats.registered);
+        assert_eq!(run(7, 200), (stats, entries));
+    }
+}
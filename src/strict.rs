@@ -0,0 +1,174 @@
+use bevy::log::warn;
+use bevy::prelude::{Res, ResMut, Resource};
+#[cfg(feature = "reserve")]
+use bevy::prelude::MessageReader;
+
+use crate::counter::UndoCounter;
+#[cfg(feature = "reserve")]
+use crate::request::RequestUndoEvent;
+#[cfg(feature = "reserve")]
+use crate::reserve::{RequestCommitReservationsEvent, RequestCommitReservationsFromSchedulerEvent, ReserveCounter};
+use crate::state::UndoState;
+
+/// How suspicious usage is reported, see [`AppUndoEx::configure_undo_strict`](crate::prelude::AppUndoEx::configure_undo_strict).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UndoStrictness {
+    #[default]
+    Off,
+
+    /// Logs a warning.
+    Warn,
+
+    /// Panics, for catching misuse in tests.
+    Panic,
+}
+
+
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoStrictMode {
+    pub strictness: UndoStrictness,
+
+    /// The counters at the end of the pipeline, compared at the end of the frame.
+    dispatched_counters: (usize, usize),
+}
+
+
+impl UndoStrictMode {
+    #[inline]
+    pub fn new(strictness: UndoStrictness) -> Self {
+        Self {
+            strictness,
+            dispatched_counters: (0, 0),
+        }
+    }
+
+
+    #[track_caller]
+    fn report(&self, message: impl FnOnce() -> String) {
+        match self.strictness {
+            UndoStrictness::Off => {}
+            UndoStrictness::Warn => warn!("undo strict mode: {}", message()),
+            UndoStrictness::Panic => panic!("undo strict mode: {}", message())
+        }
+    }
+}
+
+
+/// Reports commits requested while nothing is reserved, which are ignored.
+#[cfg(feature = "reserve")]
+pub(crate) fn check_empty_commit_system(
+    mut er: MessageReader<RequestCommitReservationsEvent>,
+    mut er2: MessageReader<RequestCommitReservationsFromSchedulerEvent>,
+    strict: Res<UndoStrictMode>,
+    reserve_counter: Res<ReserveCounter>,
+) {
+    let requested = er.read().count() + er2.read().count();
+    if 0 < requested && **reserve_counter == 0 {
+        strict.report(|| "reservations were committed while none are reserved".to_string());
+    }
+}
+
+
+/// Reports undo or redo requests resolved while reservations are left uncommitted,
+/// since the reservations are committed after the entries they were meant to precede are undone.
+#[cfg(feature = "reserve")]
+pub(crate) fn check_request_with_reservations_system(
+    mut er: MessageReader<RequestUndoEvent>,
+    strict: Res<UndoStrictMode>,
+    reserve_counter: Res<ReserveCounter>,
+) {
+    if er.read().any(RequestUndoEvent::is_operation) && 0 < **reserve_counter {
+        strict.report(|| format!("undo was requested while {} reservation(s) are uncommitted", **reserve_counter));
+    }
+}
+
+
+pub(crate) fn store_dispatched_counters_system(
+    mut strict: ResMut<UndoStrictMode>,
+    counter: Res<UndoCounter>,
+    #[cfg(feature = "reserve")]
+    reserve_counter: Res<ReserveCounter>,
+) {
+    #[cfg(feature = "reserve")]
+    let reserved = **reserve_counter;
+    #[cfg(not(feature = "reserve"))]
+    let reserved = 0;
+    strict.dispatched_counters = (**counter, reserved);
+}
+
+
+/// Reports entries registered or reserved in the frame entries are undone or redone,
+/// typically by handlers which register the changes they apply, which clears the redo history.
+pub(crate) fn check_register_during_dispatch_system(
+    strict: Res<UndoStrictMode>,
+    state: Res<UndoState>,
+    counter: Res<UndoCounter>,
+    #[cfg(feature = "reserve")]
+    reserve_counter: Res<ReserveCounter>,
+) {
+    if *state != UndoState::Dispatching {
+        return;
+    }
+    #[cfg(feature = "reserve")]
+    let reserved = **reserve_counter;
+    #[cfg(not(feature = "reserve"))]
+    let reserved = 0;
+    let (dispatched_counter, dispatched_reserved) = strict.dispatched_counters;
+    if dispatched_counter < **counter || dispatched_reserved < reserved {
+        strict.report(|| "entries were registered while undone or redone events were being handled".to_string());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Message, MessageReader, Update};
+
+    use crate::prelude::{AppUndoEx, UndoScheduler, UndoStrictness};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[test]
+    #[should_panic(expected = "entries were registered while undone or redone events were being handled")]
+    fn panic_on_register_in_handler() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.configure_undo_strict(UndoStrictness::Panic);
+            app.add_systems(Update, |mut er: MessageReader<Move>, mut scheduler: UndoScheduler<Move>| {
+                for Move(x) in er.read() {
+                    scheduler.register(Move(-x));
+                }
+            });
+        });
+        harness.register(Move(1));
+        harness.undo();
+    }
+
+
+    #[test]
+    #[cfg(feature = "reserve")]
+    #[should_panic(expected = "reservations were committed while none are reserved")]
+    fn panic_on_empty_commit() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.configure_undo_strict(UndoStrictness::Panic);
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Move>| scheduler.register_all_reserved());
+        });
+        harness.frames(2);
+    }
+
+
+    #[test]
+    fn allow_plain_usage() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.configure_undo_strict(UndoStrictness::Panic);
+        });
+        harness.register(Move(1));
+        harness.undo();
+        harness.expect([Move(1)]);
+    }
+}
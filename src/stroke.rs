@@ -0,0 +1,200 @@
+use std::hash::Hash;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Message, ResMut, Resource};
+use bevy::platform::collections::HashMap;
+
+use crate::meta::UndoMeta;
+use crate::undo_event::UndoScheduler;
+
+/// Sent when a stroke is undone or redone, the app writes each value back to its key.
+///
+/// For example, the key is a pixel coordinate and the value its color.
+#[derive(Message, Debug, Clone)]
+pub struct UndoStrokeEvent<K, V>
+    where
+        K: Clone + Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static
+{
+    pub changes: Vec<(K, V)>,
+}
+
+
+/// The changes of the stroke in progress, keyed in the order they were first modified.
+#[derive(Resource)]
+pub(crate) struct UndoStrokeBuffer<K, V> {
+    max_changes: Option<usize>,
+    indices: HashMap<K, usize>,
+    changes: Vec<(K, V, V)>,
+}
+
+
+impl<K, V> UndoStrokeBuffer<K, V> {
+    #[inline(always)]
+    pub fn new(max_changes: Option<usize>) -> Self {
+        Self {
+            max_changes,
+            indices: HashMap::default(),
+            changes: Vec::new(),
+        }
+    }
+}
+
+
+/// Accumulates many small modifications of a stroke, and registers them as one entry when it finishes.
+///
+/// Modifying the same key several times keeps its first value for undo and its last value for redo.
+/// Once the stroke reaches the size limit given to [`AppUndoEx::add_undo_stroke`](crate::prelude::AppUndoEx::add_undo_stroke),
+/// the changes so far are registered as an entry and the stroke continues with a new one.
+#[derive(SystemParam)]
+pub struct UndoStroke<'w, K, V>
+    where
+        K: Eq + Hash + Clone + Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static
+{
+    buffer: ResMut<'w, UndoStrokeBuffer<K, V>>,
+    scheduler: UndoScheduler<'w, UndoStrokeEvent<K, V>>,
+}
+
+
+impl<'w, K, V> UndoStroke<'w, K, V>
+    where
+        K: Eq + Hash + Clone + Send + Sync + 'static,
+        V: Clone + Send + Sync + 'static
+{
+    /// Records the modification of the key from `before` to `after`.
+    pub fn record(&mut self, key: K, before: V, after: V) {
+        let buffer = &mut *self.buffer;
+        if let Some(&i) = buffer.indices.get(&key) {
+            buffer.changes[i].2 = after;
+            return;
+        }
+        if buffer.max_changes.is_some_and(|max| max <= buffer.changes.len()) {
+            self.finish();
+        }
+
+        let buffer = &mut *self.buffer;
+        buffer.indices.insert(key.clone(), buffer.changes.len());
+        buffer.changes.push((key, before, after));
+    }
+
+
+    /// Returns true if any modification has been recorded since the stroke started.
+    #[inline(always)]
+    pub fn is_active(&self) -> bool {
+        !self.buffer.changes.is_empty()
+    }
+
+
+    /// Registers the recorded modifications as one entry.
+    #[inline]
+    pub fn finish(&mut self) {
+        self.finish_with_meta(UndoMeta::default());
+    }
+
+
+    /// Same as [`UndoStroke::finish`], but the entry carries the metadata.
+    pub fn finish_with_meta(&mut self, meta: UndoMeta) {
+        self.buffer.indices.clear();
+        let changes = std::mem::take(&mut self.buffer.changes);
+        if changes.is_empty() {
+            return;
+        }
+
+        let (undo, redo) = changes
+            .into_iter()
+            .map(|(key, before, after)| ((key.clone(), before), (key, after)))
+            .unzip();
+        self.scheduler.push(
+            UndoStrokeEvent { changes: undo },
+            Some(UndoStrokeEvent { changes: redo }),
+            meta,
+        );
+    }
+
+
+    /// Drops the recorded modifications without registering them, returning the values before the stroke.
+    pub fn cancel(&mut self) -> Vec<(K, V)> {
+        self.buffer.indices.clear();
+        std::mem::take(&mut self.buffer.changes)
+            .into_iter()
+            .map(|(key, before, _)| (key, before))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{MessageReader, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoStroke, UndoStrokeEvent};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Resource, Default)]
+    struct Canvas([u8; 4]);
+
+
+    fn new_app(max_changes: Option<usize>) -> App {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_stroke::<usize, u8>(max_changes);
+        app.init_resource::<Canvas>();
+        app.add_systems(Update, |mut er: MessageReader<UndoStrokeEvent<usize, u8>>, mut canvas: ResMut<Canvas>| {
+            for event in er.read() {
+                for (pixel, color) in event.changes.iter() {
+                    canvas.0[*pixel] = *color;
+                }
+            }
+        });
+        app
+    }
+
+
+    fn paint(app: &mut App, pixels: &[usize]) {
+        let mut state = SystemState::<UndoStroke<usize, u8>>::new(app.world_mut());
+        let mut stroke = state.get_mut(app.world_mut()).unwrap();
+        for &pixel in pixels {
+            stroke.record(pixel, 0, 1);
+        }
+        stroke.finish();
+        state.apply(app.world_mut());
+        app.world_mut().resource_mut::<Canvas>().0 = [1; 4];
+        app.update();
+    }
+
+
+    fn undo(app: &mut App) {
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+    }
+
+
+    #[test]
+    fn register_stroke_as_one_entry() {
+        let mut app = new_app(None);
+        paint(&mut app, &[0, 1, 1, 2, 3]);
+
+        undo(&mut app);
+        assert_eq!(app.world().resource::<Canvas>().0, [0; 4]);
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(app.world().resource::<Canvas>().0, [1; 4]);
+    }
+
+
+    #[test]
+    fn split_long_stroke() {
+        let mut app = new_app(Some(3));
+        paint(&mut app, &[0, 1, 2, 3]);
+
+        undo(&mut app);
+        assert_eq!(app.world().resource::<Canvas>().0, [1, 1, 1, 0]);
+        undo(&mut app);
+        assert_eq!(app.world().resource::<Canvas>().0, [0; 4]);
+    }
+}
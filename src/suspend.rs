@@ -0,0 +1,219 @@
+use std::any::TypeId;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Res, ResMut, Resource, State, States};
+use bevy::platform::collections::HashSet;
+
+use crate::condition::UndoEventCondition;
+use crate::payload::UndoPayload;
+
+/// Whether registrations are currently ignored.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoSuspension {
+    /// The state types whose current state is listed via [`AppUndoEx::suspend_undo_in_states`](crate::prelude::AppUndoEx::suspend_undo_in_states).
+    states: HashSet<TypeId>,
+
+    /// The count of locks taken via [`UndoLock`], which also reject requests.
+    locks: usize,
+}
+
+
+impl UndoSuspension {
+    #[inline(always)]
+    pub fn is_suspended(&self) -> bool {
+        !self.states.is_empty() || self.is_locked()
+    }
+
+
+    #[inline(always)]
+    pub fn is_locked(&self) -> bool {
+        0 < self.locks
+    }
+}
+
+
+/// Rejects registrations and holds back undo and redo requests while locked, such as while an asynchronous export
+/// or a scene save is in flight, so the history stays consistent with the saved file.
+///
+/// Registrations recorded while locked are ignored, including the ones sent earlier in the frame the lock is taken.
+/// Undo and redo requests wait in order until unlocked, leaving [`UndoState::Deferred`](crate::prelude::UndoState::Deferred)
+/// in the meantime, while the requests to discard entries or close documents are resolved as usual.
+/// Locks are counted, so operations in flight at the same time each unlock once done.
+#[derive(SystemParam)]
+pub struct UndoLock<'w> {
+    suspension: ResMut<'w, UndoSuspension>,
+}
+
+
+impl<'w> UndoLock<'w> {
+    #[inline(always)]
+    pub fn lock(&mut self) {
+        self.suspension.locks += 1;
+    }
+
+
+    /// Releases a lock taken via [`UndoLock::lock`], doing nothing if none is left.
+    #[inline(always)]
+    pub fn unlock(&mut self) {
+        self.suspension.locks = self.suspension.locks.saturating_sub(1);
+    }
+
+
+    #[inline(always)]
+    pub fn is_locked(&self) -> bool {
+        self.suspension.is_locked()
+    }
+}
+
+
+/// The states of `S` during which registrations are ignored.
+#[derive(Resource, Debug)]
+pub(crate) struct UndoSuspendedStates<S: States>(pub Vec<S>);
+
+
+/// Tells whether the entries of `E` registered in the frame are to be ignored.
+#[derive(SystemParam)]
+pub(crate) struct UndoRecording<'w, E: UndoPayload> {
+    condition: Option<Res<'w, UndoEventCondition<E>>>,
+    suspension: Res<'w, UndoSuspension>,
+}
+
+
+impl<'w, E: UndoPayload> UndoRecording<'w, E> {
+    #[inline]
+    pub fn is_suspended(&self) -> bool {
+        self.condition.as_ref().is_some_and(|condition| !condition.holds())
+            || self.suspension.is_suspended()
+    }
+}
+
+
+pub(crate) fn update_suspension_system<S: States>(
+    mut suspension: ResMut<UndoSuspension>,
+    suspended: Res<UndoSuspendedStates<S>>,
+    state: Option<Res<State<S>>>,
+) {
+    let suspend = state.is_some_and(|state| suspended.0.contains(state.get()));
+    if suspend {
+        suspension.states.insert(TypeId::of::<S>());
+    } else {
+        suspension.states.remove(&TypeId::of::<S>());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, NextState, States};
+    use bevy::state::app::{AppExtStates, StatesPlugin};
+
+    use crate::prelude::{AppUndoEx, UndoDocuments, UndoLock, UndoScheduler, UndoView};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[derive(States, Default, Debug, Clone, Eq, PartialEq, Hash)]
+    enum Phase {
+        #[default]
+        Playing,
+        Cutscene,
+        Loading,
+    }
+
+
+    #[test]
+    fn ignore_registrations_in_suspended_states() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.add_plugins(StatesPlugin);
+            app.init_state::<Phase>();
+            app.suspend_undo_in_states([Phase::Cutscene, Phase::Loading]);
+        });
+        harness.register(Move(1));
+
+        for (phase, event) in [(Phase::Cutscene, Move(2)), (Phase::Loading, Move(3)), (Phase::Playing, Move(4))] {
+            harness.app().world_mut().resource_mut::<NextState<Phase>>().set(phase);
+            harness.frames(1);
+            harness.register(event);
+        }
+        harness.frames(1);
+
+        harness.undo();
+        harness.undo();
+        harness.expect([Move(4), Move(1)]);
+    }
+
+
+    #[test]
+    fn reject_registrations_and_requests_while_locked() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        let mut lock = SystemState::<UndoLock>::new(harness.app().world_mut());
+        harness.register(Move(1));
+        lock.get_mut(harness.app().world_mut()).unwrap().lock();
+        lock.get_mut(harness.app().world_mut()).unwrap().lock();
+        harness.register(Move(2));
+        harness.undo();
+        harness.expect([]);
+
+        lock.get_mut(harness.app().world_mut()).unwrap().unlock();
+        harness.undo();
+        harness.expect([]);
+        lock.get_mut(harness.app().world_mut()).unwrap().unlock();
+        assert!(!lock.get_mut(harness.app().world_mut()).unwrap().is_locked());
+        harness.undo();
+        harness.expect([Move(1)]);
+    }
+
+
+    #[test]
+    fn close_documents_while_locked() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        let mut documents = SystemState::<UndoDocuments>::new(harness.app().world_mut());
+        let mut scheduler = SystemState::<UndoScheduler<Move>>::new(harness.app().world_mut());
+        let mut lock = SystemState::<UndoLock>::new(harness.app().world_mut());
+        documents.get_mut(harness.app().world_mut()).unwrap().create(1);
+        scheduler.get_mut(harness.app().world_mut()).unwrap().register_to(1, Move(1));
+        harness.frames(1);
+
+        lock.get_mut(harness.app().world_mut()).unwrap().lock();
+        harness.undo();
+        documents.get_mut(harness.app().world_mut()).unwrap().close(1);
+        harness.frames(1);
+        let mut view = SystemState::<UndoView>::new(harness.app().world_mut());
+        assert_eq!(view.get(harness.app().world()).unwrap().iter().count(), 0);
+
+        harness.register(Move(2));
+        lock.get_mut(harness.app().world_mut()).unwrap().unlock();
+        harness.frames(1);
+        harness.expect([]);
+    }
+
+
+    #[cfg(feature = "reserve")]
+    #[test]
+    fn drop_reservations_committed_while_suspended() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.add_plugins(StatesPlugin);
+            app.init_state::<Phase>();
+            app.suspend_undo_in_states([Phase::Cutscene]);
+        });
+        harness.register(Move(1));
+        let mut scheduler = SystemState::<UndoScheduler<Move>>::new(harness.app().world_mut());
+
+        harness.app().world_mut().resource_mut::<NextState<Phase>>().set(Phase::Cutscene);
+        harness.frames(1);
+        scheduler.get_mut(harness.app().world_mut()).unwrap().reserve(Move(2));
+        scheduler.get_mut(harness.app().world_mut()).unwrap().register_all_reserved();
+        harness.frames(1);
+        harness.app().world_mut().resource_mut::<NextState<Phase>>().set(Phase::Playing);
+        harness.frames(1);
+
+        harness.undo();
+        harness.undo();
+        harness.expect([Move(1)]);
+    }
+}
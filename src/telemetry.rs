@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use bevy::prelude::{Res, ResMut, Resource, Time};
+use bevy::platform::collections::HashMap;
+
+use crate::history::{UndoHistory, UndoHistoryEntry};
+
+/// Anonymized usage of the history since the previous report, see [`AppUndoEx::add_undo_telemetry`](crate::prelude::AppUndoEx::add_undo_telemetry).
+///
+/// Only the type names of the entries are included, never their payloads or metadata.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct UndoUsage {
+    /// The count of entries undone per type name.
+    pub undos: HashMap<&'static str, usize>,
+
+    /// The count of entries redone per type name.
+    pub redos: HashMap<&'static str, usize>,
+
+    /// The count of slots undone or redone per count of entries in them.
+    pub group_sizes: HashMap<usize, usize>,
+
+    /// The sum of the history lengths sampled once per frame.
+    pub depth_total: usize,
+
+    /// The count of frames the history length was sampled.
+    pub depth_samples: usize,
+}
+
+
+impl UndoUsage {
+    /// Returns the average count of entries in the history.
+    #[inline]
+    pub fn average_depth(&self) -> f32 {
+        if self.depth_samples == 0 {
+            return 0.;
+        }
+        self.depth_total as f32 / self.depth_samples as f32
+    }
+
+
+    /// Returns the average count of entries in the slots undone or redone.
+    pub fn average_group_size(&self) -> f32 {
+        let slots: usize = self.group_sizes.values().sum();
+        if slots == 0 {
+            return 0.;
+        }
+        let entries: usize = self.group_sizes.iter().map(|(size, count)| size * count).sum();
+        entries as f32 / slots as f32
+    }
+
+
+    fn is_empty(&self) -> bool {
+        self.group_sizes.is_empty()
+    }
+}
+
+
+type UndoTelemetryCallback = Box<dyn Fn(&UndoUsage) + Send + Sync + 'static>;
+
+
+#[derive(Resource, Default)]
+pub(crate) struct UndoTelemetry {
+    pub interval: Duration,
+    pub callbacks: Vec<UndoTelemetryCallback>,
+    usage: UndoUsage,
+    next_report: Duration,
+}
+
+
+impl UndoTelemetry {
+    pub fn dispatched<'a>(&mut self, redo: bool, entries: impl Iterator<Item = &'a UndoHistoryEntry>) {
+        let counts = if redo { &mut self.usage.redos } else { &mut self.usage.undos };
+        let mut size = 0;
+        for entry in entries {
+            *counts.entry(entry.type_name).or_default() += 1;
+            size += 1;
+        }
+        *self.usage.group_sizes.entry(size).or_default() += 1;
+    }
+}
+
+
+/// Samples the history length, and passes the usage to the callbacks once the interval elapsed and anything was undone or redone.
+pub(crate) fn report_undo_telemetry_system(
+    mut telemetry: ResMut<UndoTelemetry>,
+    history: Res<UndoHistory>,
+    time: Option<Res<Time>>,
+) {
+    telemetry.usage.depth_total += history.entries().count();
+    telemetry.usage.depth_samples += 1;
+
+    let now = time.map(|time| time.elapsed()).unwrap_or_default();
+    if now < telemetry.next_report || telemetry.usage.is_empty() {
+        return;
+    }
+    telemetry.next_report = now + telemetry.interval;
+    let usage = std::mem::take(&mut telemetry.usage);
+    for callback in &telemetry.callbacks {
+        callback(&usage);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Message;
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoScheduler, UndoUsage};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Move;
+
+
+    #[test]
+    fn report_usage_per_type() {
+        let reports = Arc::new(Mutex::new(Vec::<UndoUsage>::new()));
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        let sink = reports.clone();
+        app.add_undo_telemetry(Duration::ZERO, move |usage| sink.lock().unwrap().push(usage.clone()));
+
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.register(Move);
+        scheduler.register_all_grouped([Move, Move]);
+        state.apply(app.world_mut());
+        app.update();
+        assert!(reports.lock().unwrap().is_empty());
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].undos.get(std::any::type_name::<Move>()), Some(&3));
+        assert_eq!(reports[0].average_group_size(), 1.5);
+        assert_eq!(reports[0].average_depth(), 1.5);
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt::Debug;
+
+use bevy::app::App;
+use bevy::ecs::system::SystemState;
+use bevy::prelude::{Message, Messages};
+
+use crate::channel::UndoChannel;
+use crate::extension::AppUndoEx;
+use crate::pacing::UndoRequestQueue;
+use crate::payload::UndoPayload;
+use crate::request::RequestUndoEvent;
+use crate::state::UndoState;
+use crate::undo_event::UndoScheduler;
+use crate::UndoPlugin;
+
+/// Runs a sequence of steps against an app managing undo events of one type, see [`UndoTestHarness`].
+///
+/// Each step is a method of the harness followed by its argument, if any, and steps are separated by `;`.
+/// An optional setup of the app can be passed after the type.
+///
+/// ```ignore
+/// undo_test!(Move;
+///     register Move(1);
+///     register Move(2);
+///     undo;
+///     expect [Move(2)];
+///     redo;
+///     expect [];
+/// );
+/// ```
+#[macro_export]
+macro_rules! undo_test {
+    ($payload:ty $(, $setup:expr)?; $($step:ident $($arg:expr)?);* $(;)?) => {{
+        #[allow(unused_mut)]
+        let mut harness = $crate::prelude::UndoTestHarness::<$payload>::new();
+        $(harness.setup($setup);)?
+        $(harness.$step($($arg)?);)*
+        harness
+    }};
+}
+
+
+/// Pumps frames in tests instead of counting the updates an undo spans.
+pub trait AppUndoTestEx {
+    /// Updates the app until an event `E` is sent, such as the undo-event of a requested undo,
+    /// returning false if none was sent within `max_frames`.
+    fn update_until<E: Message>(&mut self, max_frames: usize) -> bool;
+
+
+    /// Updates the app until no undo or redo is being dispatched or deferred anymore,
+    /// returning false if it is still busy after `max_frames`.
+    fn update_until_idle(&mut self, max_frames: usize) -> bool;
+}
+
+
+impl AppUndoTestEx for App {
+    fn update_until<E: Message>(&mut self, max_frames: usize) -> bool {
+        let mut reader = self.world().resource::<Messages<E>>().get_cursor_current();
+        for _ in 0..max_frames {
+            self.update();
+            if reader.read(self.world().resource::<Messages<E>>()).next().is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+
+    fn update_until_idle(&mut self, max_frames: usize) -> bool {
+        for _ in 0..max_frames {
+            self.update();
+            let idle = *self.world().resource::<UndoState>() == UndoState::Idle;
+            if idle && self.world().resource::<UndoRequestQueue>().len() == 0 {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+
+/// Builds an app managing undo events of `E` and pumps the frames needed by each step.
+///
+/// Failed expectations report the steps run so far.
+pub struct UndoTestHarness<E: UndoPayload + Debug + PartialEq> {
+    app: App,
+    scheduler: SystemState<UndoScheduler<'static, E>>,
+    steps: Vec<String>,
+    sent: Vec<E>,
+}
+
+
+impl<E: UndoPayload + Debug + PartialEq> UndoTestHarness<E> {
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<E>();
+        let scheduler = SystemState::new(app.world_mut());
+        Self {
+            app,
+            scheduler,
+            steps: Vec::new(),
+            sent: Vec::new(),
+        }
+    }
+
+
+    /// Configures the app, such as adding handlers or other undo types.
+    #[inline]
+    pub fn setup(&mut self, setup: impl FnOnce(&mut App)) {
+        setup(&mut self.app);
+    }
+
+
+    #[inline(always)]
+    pub fn app(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+
+    pub fn register(&mut self, event: E) {
+        self.steps.push(format!("register {event:?}"));
+        self.scheduler.get_mut(self.app.world_mut()).expect("the resources of UndoPlugin are missing").register(event);
+        self.update();
+    }
+
+
+    pub fn register_grouped(&mut self, events: impl IntoIterator<Item = E>) {
+        let events: Vec<E> = events.into_iter().collect();
+        self.steps.push(format!("register_grouped {events:?}"));
+        self.scheduler.get_mut(self.app.world_mut()).expect("the resources of UndoPlugin are missing").register_all_grouped(events);
+        self.update();
+    }
+
+
+    pub fn undo(&mut self) {
+        self.request("undo", RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+    }
+
+
+    pub fn redo(&mut self) {
+        self.request("redo", RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+    }
+
+
+    /// Runs the frames without any request.
+    pub fn frames(&mut self, frames: usize) {
+        self.steps.push(format!("frames {frames}"));
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+
+    /// Asserts the events sent since the last expectation, in the order sent.
+    #[track_caller]
+    pub fn expect(&mut self, expected: impl IntoIterator<Item = E>) {
+        let expected: Vec<E> = expected.into_iter().collect();
+        let sent = std::mem::take(&mut self.sent);
+        assert_eq!(
+            sent,
+            expected,
+            "unexpected undo events after the steps:\n  {}",
+            self.steps.join("\n  ")
+        );
+        self.steps.push(format!("expect {expected:?}"));
+    }
+
+
+    fn request(&mut self, step: &str, request: RequestUndoEvent) {
+        self.steps.push(step.to_string());
+        self.app.world_mut().write_message(request);
+        self.update();
+    }
+
+
+    /// Updates the app, keeping the sent events until the next expectation since they are dropped after two frames.
+    fn update(&mut self) {
+        self.app.update();
+        self.sent.extend(self.app.world_mut().resource_mut::<Messages<E>>().drain());
+    }
+}
+
+
+impl<E: UndoPayload + Debug + PartialEq> Default for UndoTestHarness<E> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Message;
+
+    use crate::prelude::{AppUndoEx, AppUndoTestEx, UndoRequester, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Other;
+
+
+    #[test]
+    fn run_steps() {
+        undo_test!(Move;
+            register Move(1);
+            register_grouped [Move(2), Move(3)];
+            undo;
+            expect [Move(3), Move(2)];
+            undo;
+            expect [Move(1)];
+            undo;
+            expect [];
+        );
+    }
+
+
+    #[test]
+    fn run_steps_with_setup() {
+        let mut harness = undo_test!(Move, |app: &mut bevy::app::App| {
+            app.add_undo_event::<Other>();
+        };
+            register Move(1);
+        );
+        assert!(harness.app().world().contains_resource::<bevy::prelude::Messages<Other>>());
+    }
+
+
+    #[test]
+    #[should_panic(expected = "register Move(1)\n  undo")]
+    fn report_steps_on_failure() {
+        undo_test!(Move;
+            register Move(1);
+            undo;
+            expect [Move(2)];
+        );
+    }
+
+
+    #[test]
+    fn update_until_undone_and_idle() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.configure_undo_requests_per_frame(1);
+        let mut scheduler = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register_all([Move(1), Move(2)]);
+        scheduler.apply(app.world_mut());
+        assert!(!app.update_until::<Move>(3));
+
+        let mut requester = SystemState::<UndoRequester>::new(app.world_mut());
+        requester.get_mut(app.world_mut()).unwrap().undo();
+        requester.get_mut(app.world_mut()).unwrap().undo();
+        assert!(app.update_until::<Move>(3));
+        assert!(!app.update_until_idle(1));
+        assert!(app.update_until_idle(3));
+    }
+}
@@ -0,0 +1,296 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Entity, Message, MessageReader, ResMut, Resource};
+use bevy::platform::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::meta::UndoMeta;
+use crate::request::RequestUndoEvent;
+use crate::undo_event::UndoScheduler;
+
+#[cfg(feature = "egui")]
+pub mod egui;
+
+/// The text of a text field and its cursor, as a byte offset on a grapheme boundary.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct UndoTextState {
+    pub text: String,
+    pub cursor: usize,
+}
+
+
+impl UndoTextState {
+    #[inline(always)]
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        Self {
+            cursor: text.len(),
+            text,
+        }
+    }
+}
+
+
+/// Sent when a text edit is undone or redone.
+///
+/// The app writes `state` back to the text field of `target`.
+#[derive(Message, Debug, Clone)]
+pub struct UndoTextEvent {
+    pub target: Entity,
+    pub state: UndoTextState,
+}
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum TextEditKind {
+    Insert,
+    Backspace,
+    Delete,
+}
+
+
+#[derive(Debug)]
+struct PendingTextEdit {
+    kind: TextEditKind,
+    before: UndoTextState,
+    after: UndoTextState,
+    ends_with_whitespace: bool,
+}
+
+
+/// The edits of each text field which have not been registered yet.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoTextPending(HashMap<Entity, PendingTextEdit>);
+
+
+/// Edits text fields while coalescing them into undo entries.
+///
+/// Consecutive insertions are coalesced per word, a word ends at the first non-whitespace grapheme typed after whitespace.
+/// Consecutive deletions in the same direction are coalesced into one entry.
+/// Moving the cursor or editing the text elsewhere starts a new entry.
+///
+/// Pending edits are registered before undo requests are resolved, or explicitly via [`UndoText::commit`].
+#[derive(SystemParam)]
+pub struct UndoText<'w> {
+    pending: ResMut<'w, UndoTextPending>,
+    scheduler: UndoScheduler<'w, UndoTextEvent>,
+}
+
+
+impl<'w> UndoText<'w> {
+    /// Inserts the text at the cursor, then moves the cursor to its end.
+    ///
+    /// Text consisting of several graphemes, such as a paste, is registered as its own entry.
+    pub fn insert(&mut self, target: Entity, state: &mut UndoTextState, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let before = state.clone();
+        state.text.insert_str(state.cursor, text);
+        state.cursor += text.len();
+
+        if 1 < text.graphemes(true).count() {
+            self.commit(target);
+            self.push(target, before, state.clone());
+            return;
+        }
+
+        let whitespace = text.chars().all(char::is_whitespace);
+        if let Some(pending) = self.pending.0.get_mut(&target) {
+            let word_ended = pending.ends_with_whitespace && !whitespace;
+            if pending.kind == TextEditKind::Insert && pending.after == before && !word_ended {
+                pending.after = state.clone();
+                pending.ends_with_whitespace = whitespace;
+                return;
+            }
+        }
+        self.start(target, TextEditKind::Insert, before, state.clone(), whitespace);
+    }
+
+
+    /// Deletes the grapheme before the cursor.
+    ///
+    /// Returns false if the cursor is at the start of the text.
+    pub fn backspace(&mut self, target: Entity, state: &mut UndoTextState) -> bool {
+        let Some((start, _)) = state.text[..state.cursor].grapheme_indices(true).next_back() else {
+            return false;
+        };
+        let before = state.clone();
+        state.text.replace_range(start..state.cursor, "");
+        state.cursor = start;
+        self.coalesce(target, TextEditKind::Backspace, before, state);
+        true
+    }
+
+
+    /// Deletes the grapheme after the cursor.
+    ///
+    /// Returns false if the cursor is at the end of the text.
+    pub fn delete(&mut self, target: Entity, state: &mut UndoTextState) -> bool {
+        let Some(grapheme) = state.text[state.cursor..].graphemes(true).next() else {
+            return false;
+        };
+        let before = state.clone();
+        let end = state.cursor + grapheme.len();
+        state.text.replace_range(state.cursor..end, "");
+        self.coalesce(target, TextEditKind::Delete, before, state);
+        true
+    }
+
+
+    /// Replaces the whole state, such as a cut or an autocompletion, registered as its own entry.
+    pub fn replace(&mut self, target: Entity, state: &mut UndoTextState, new_state: UndoTextState) {
+        if *state == new_state {
+            return;
+        }
+        self.commit(target);
+        let before = std::mem::replace(state, new_state);
+        self.push(target, before, state.clone());
+    }
+
+
+    /// Registers the pending edits of the text field, for example when it loses focus.
+    #[inline]
+    pub fn commit(&mut self, target: Entity) {
+        if let Some(pending) = self.pending.0.remove(&target) {
+            self.push(target, pending.before, pending.after);
+        }
+    }
+
+
+    /// Registers the pending edits of all text fields.
+    pub fn commit_all(&mut self) {
+        let mut pending: Vec<(Entity, PendingTextEdit)> = self.pending.0.drain().collect();
+        pending.sort_by_key(|(target, _)| *target);
+        for (target, pending) in pending {
+            self.push(target, pending.before, pending.after);
+        }
+    }
+
+
+    fn coalesce(&mut self, target: Entity, kind: TextEditKind, before: UndoTextState, state: &UndoTextState) {
+        if let Some(pending) = self.pending.0.get_mut(&target) {
+            if pending.kind == kind && pending.after == before {
+                pending.after = state.clone();
+                return;
+            }
+        }
+        self.start(target, kind, before, state.clone(), false);
+    }
+
+
+    fn start(&mut self, target: Entity, kind: TextEditKind, before: UndoTextState, after: UndoTextState, ends_with_whitespace: bool) {
+        self.commit(target);
+        self.pending.0.insert(target, PendingTextEdit {
+            kind,
+            before,
+            after,
+            ends_with_whitespace,
+        });
+    }
+
+
+    #[inline]
+    fn push(&mut self, target: Entity, before: UndoTextState, after: UndoTextState) {
+        self.scheduler.push(
+            UndoTextEvent { target, state: before },
+            Some(UndoTextEvent { target, state: after }),
+            UndoMeta::for_entity(target),
+        );
+    }
+}
+
+
+pub(crate) fn commit_text_edits_system(
+    mut er: MessageReader<RequestUndoEvent>,
+    mut text: UndoText,
+) {
+    if er.read().count() != 0 {
+        text.commit_all();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Update};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Entity, MessageReader, Mut, ResMut, Resource};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoText, UndoTextEvent, UndoTextState};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Resource, Default)]
+    struct Field(UndoTextState);
+
+
+    fn new_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_text();
+        app.init_resource::<Field>();
+        app.add_systems(Update, |mut er: MessageReader<UndoTextEvent>, mut field: ResMut<Field>| {
+            for event in er.read() {
+                field.0 = event.state.clone();
+            }
+        });
+        app
+    }
+
+
+    fn edit(app: &mut App, f: impl Fn(&mut UndoText, &mut UndoTextState) + Send + Sync + 'static) {
+        app.world_mut().resource_scope(|world, mut field: Mut<Field>| {
+            let mut state = SystemState::<UndoText>::new(world);
+            f(&mut state.get_mut(world).unwrap(), &mut field.0);
+            state.apply(world);
+        });
+    }
+
+
+    fn undo(app: &mut App) {
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+    }
+
+
+    #[test]
+    fn coalesce_insertions_per_word() {
+        let mut app = new_app();
+        let target = Entity::from_raw_u32(0).unwrap();
+        edit(&mut app, move |text, state| {
+            for c in ["h", "i", " ", "y", "o", "u"] {
+                text.insert(target, state, c);
+            }
+        });
+        app.update();
+        assert_eq!(app.world().resource::<Field>().0, UndoTextState::new("hi you"));
+
+        undo(&mut app);
+        assert_eq!(app.world().resource::<Field>().0, UndoTextState::new("hi "));
+        undo(&mut app);
+        assert_eq!(app.world().resource::<Field>().0, UndoTextState::new(""));
+    }
+
+
+    #[test]
+    fn coalesce_deletions_by_run() {
+        let mut app = new_app();
+        let target = Entity::from_raw_u32(0).unwrap();
+        app.world_mut().resource_mut::<Field>().0 = UndoTextState::new("ae\u{301}o");
+        edit(&mut app, move |text, state| {
+            assert!(text.backspace(target, state));
+            assert!(text.backspace(target, state));
+            assert_eq!(state.text, "a");
+            state.cursor = 0;
+            assert!(text.delete(target, state));
+            assert!(!text.delete(target, state));
+        });
+        app.update();
+        assert_eq!(app.world().resource::<Field>().0.text, "");
+
+        undo(&mut app);
+        assert_eq!(app.world().resource::<Field>().0, UndoTextState { text: "a".to_string(), cursor: 0 });
+        undo(&mut app);
+        assert_eq!(app.world().resource::<Field>().0, UndoTextState::new("ae\u{301}o"));
+    }
+}
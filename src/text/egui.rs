@@ -0,0 +1,115 @@
+use bevy::prelude::Entity;
+use bevy_egui::egui::{Response, TextEdit, Ui};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::text::{UndoText, UndoTextState};
+
+impl<'w> UndoText<'w> {
+    /// Shows a single line [`TextEdit`] for the state, and feeds its edits into the history.
+    ///
+    /// The edits are coalesced the same as [`UndoText::insert`] and [`UndoText::backspace`],
+    /// and pending edits are registered when the widget loses focus.
+    /// The built-in undo of egui only knows about this widget, so the app should bind Ctrl+Z to [`UndoRequester`](crate::prelude::UndoRequester) instead.
+    #[inline]
+    pub fn egui_singleline(&mut self, ui: &mut Ui, target: Entity, state: &mut UndoTextState) -> Response {
+        self.egui_text_edit(ui, target, state, false)
+    }
+
+
+    /// Same as [`UndoText::egui_singleline`], but shows a multiline [`TextEdit`].
+    #[inline]
+    pub fn egui_multiline(&mut self, ui: &mut Ui, target: Entity, state: &mut UndoTextState) -> Response {
+        self.egui_text_edit(ui, target, state, true)
+    }
+
+
+    fn egui_text_edit(&mut self, ui: &mut Ui, target: Entity, state: &mut UndoTextState, multiline: bool) -> Response {
+        let mut text = state.text.clone();
+        let output = if multiline {
+            TextEdit::multiline(&mut text).show(ui)
+        } else {
+            TextEdit::singleline(&mut text).show(ui)
+        };
+        let cursor = output
+            .cursor_range
+            .map(|range| byte_offset(&text, range.primary.index.0))
+            .unwrap_or(text.len());
+
+        if text != state.text {
+            self.apply_edit(target, state, UndoTextState { text, cursor });
+        } else if output.response.has_focus() {
+            state.cursor = cursor;
+        }
+        if output.response.lost_focus() {
+            self.commit(target);
+        }
+        output.response.response
+    }
+
+
+    fn apply_edit(&mut self, target: Entity, state: &mut UndoTextState, new_state: UndoTextState) {
+        let (at, removed, inserted) = diff(&state.text, &new_state.text);
+        let single_grapheme = inserted.is_empty() && is_grapheme(removed);
+        let removed_end = at + removed.len();
+        if removed_end == at {
+            state.cursor = at;
+            self.insert(target, state, inserted);
+        } else if single_grapheme && state.cursor == removed_end {
+            self.backspace(target, state);
+        } else if single_grapheme && state.cursor == at {
+            self.delete(target, state);
+        } else {
+            self.replace(target, state, new_state.clone());
+        }
+        state.cursor = new_state.cursor;
+    }
+}
+
+
+/// Returns the byte offset where the texts start to differ, the removed text and the inserted text.
+fn diff<'o, 'n>(old: &'o str, new: &'n str) -> (usize, &'o str, &'n str) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, o), n)| o != n)
+        .map(|((i, _), _)| i)
+        .unwrap_or(old.len().min(new.len()));
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(o, n)| o == n)
+        .map(|(o, _)| o.len_utf8())
+        .sum();
+    (prefix, &old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix])
+}
+
+
+#[inline]
+fn is_grapheme(text: &str) -> bool {
+    text.graphemes(true).count() == 1
+}
+
+
+#[inline]
+fn byte_offset(text: &str, char_index: usize) -> usize {
+    text
+        .char_indices()
+        .nth(char_index)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::text::egui::diff;
+
+    #[test]
+    fn diff_edits() {
+        assert_eq!(diff("helo", "hello"), (3, "", "l"));
+        assert_eq!(diff("hello", "helo"), (3, "l", ""));
+        assert_eq!(diff("aé", "aéb"), (3, "", "b"));
+        assert_eq!(diff("abc", "axc"), (1, "b", "x"));
+    }
+}
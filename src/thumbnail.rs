@@ -0,0 +1,89 @@
+use bevy::prelude::{Handle, Image, Resource, World};
+
+use crate::history::UndoHistory;
+
+type UndoCaptureFn = Box<dyn Fn(&mut World, usize) -> Option<Handle<Image>> + Send + Sync + 'static>;
+
+
+/// The callback capturing the thumbnails of new slots, and the greatest slot captured so far.
+#[derive(Resource)]
+pub(crate) struct UndoThumbnailCapture {
+    capture: UndoCaptureFn,
+    captured: usize,
+}
+
+
+impl UndoThumbnailCapture {
+    #[inline(always)]
+    pub fn new(capture: UndoCaptureFn) -> Self {
+        Self {
+            capture,
+            captured: 0,
+        }
+    }
+}
+
+
+/// Captures the thumbnails of the slots recorded since the last capture.
+pub(crate) fn capture_thumbnails_system(world: &mut World) {
+    let Some(mut capture) = world.remove_resource::<UndoThumbnailCapture>() else {
+        return;
+    };
+    let mut slots: Vec<usize> = world
+        .resource::<UndoHistory>()
+        .entries()
+        .map(|entry| entry.no)
+        .filter(|no| capture.captured < *no)
+        .collect();
+    slots.dedup();
+    for no in slots {
+        capture.captured = no;
+        if let Some(thumbnail) = (capture.capture)(world, no) {
+            world.resource_mut::<UndoHistory>().set_thumbnail(no, thumbnail);
+        }
+    }
+    world.insert_resource(capture);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::asset::uuid::Uuid;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Handle, Image, Message};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoMeta, UndoScheduler, UndoView};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Paint;
+
+
+    fn thumbnail(no: usize) -> Handle<Image> {
+        Handle::from(Uuid::from_u128(no as u128))
+    }
+
+
+    #[test]
+    fn capture_thumbnail_of_each_slot() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Paint>();
+        app.set_undo_thumbnail_capture(|_, no| (no != 2).then(|| thumbnail(no)));
+        let mut scheduler = SystemState::<UndoScheduler<Paint>>::new(app.world_mut());
+        for _ in 0..3 {
+            scheduler.get_mut(app.world_mut()).unwrap().push(Paint, Some(Paint), UndoMeta::default());
+            app.update();
+        }
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+
+        let mut view = SystemState::<UndoView>::new(app.world_mut());
+        let view = view.get(app.world()).unwrap();
+        let thumbnails: Vec<Option<Handle<Image>>> = view.iter().map(|entry| entry.thumbnail.cloned()).collect();
+        assert_eq!(thumbnails, vec![Some(thumbnail(1)), None]);
+        assert_eq!(view.iter_redo().next().and_then(|entry| entry.thumbnail.cloned()), Some(thumbnail(3)));
+    }
+}
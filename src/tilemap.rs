@@ -0,0 +1,88 @@
+use bevy::ecs::component::Mutable;
+use bevy::prelude::{Component, Entity, MessageReader, Query};
+use bevy_ecs_tilemap::tiles::{TilePos, TileStorage};
+
+use crate::stroke::{UndoStroke, UndoStrokeEvent};
+
+pub(crate) type UndoTileKey = (Entity, TilePos);
+
+
+/// Records paint operations on tilemaps, keyed by the tilemap entity and the tile position.
+///
+/// The tile component `T` is typically [`TileTextureIndex`](bevy_ecs_tilemap::tiles::TileTextureIndex),
+/// and must be set up via [`AppUndoEx::add_undo_tilemap`](crate::prelude::AppUndoEx::add_undo_tilemap).
+/// A paint operation is registered as one entry by [`UndoStroke::finish`].
+pub type UndoTilemap<'w, T> = UndoStroke<'w, UndoTileKey, T>;
+
+
+pub(crate) fn restore_tiles_system<T: Component<Mutability = Mutable> + Clone>(
+    mut er: MessageReader<UndoStrokeEvent<UndoTileKey, T>>,
+    storages: Query<&TileStorage>,
+    mut tiles: Query<&mut T>,
+) {
+    for event in er.read() {
+        for ((map, pos), value) in event.changes.iter() {
+            let Some(tile) = storages
+                .get(*map)
+                .ok()
+                .and_then(|storage| storage.get(pos)) else {
+                continue;
+            };
+            if let Ok(mut component) = tiles.get_mut(tile) {
+                *component = value.clone();
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::Entity;
+    use bevy_ecs_tilemap::map::TilemapSize;
+    use bevy_ecs_tilemap::tiles::{TilePos, TileStorage, TileTextureIndex};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoTilemap};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[test]
+    fn restore_painted_tiles() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_tilemap::<TileTextureIndex>();
+
+        let mut storage = TileStorage::empty(TilemapSize { x: 2, y: 1 });
+        let tiles: Vec<Entity> = (0..2)
+            .map(|x| {
+                let pos = TilePos { x, y: 0 };
+                let tile = app.world_mut().spawn((pos, TileTextureIndex(0))).id();
+                storage.set(&pos, tile);
+                tile
+            })
+            .collect();
+        let map = app.world_mut().spawn(storage).id();
+
+        let mut state = SystemState::<UndoTilemap<TileTextureIndex>>::new(app.world_mut());
+        let mut tilemap = state.get_mut(app.world_mut()).unwrap();
+        for x in 0..2 {
+            tilemap.record((map, TilePos { x, y: 0 }), TileTextureIndex(0), TileTextureIndex(3));
+        }
+        tilemap.finish();
+        state.apply(app.world_mut());
+        for tile in tiles.iter() {
+            app.world_mut().get_mut::<TileTextureIndex>(*tile).unwrap().0 = 3;
+        }
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert!(tiles.iter().all(|tile| app.world().get::<TileTextureIndex>(*tile).unwrap().0 == 0));
+
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert!(tiles.iter().all(|tile| app.world().get::<TileTextureIndex>(*tile).unwrap().0 == 3));
+    }
+}
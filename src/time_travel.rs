@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use bevy::app::{App, Plugin, PreUpdate};
+use bevy::prelude::{AppTypeRegistry, Entity, IntoScheduleConfigs, Mut, Resource, Time, World};
+use bevy::platform::collections::HashSet;
+
+use crate::entity_snapshot::UndoEntitySnapshot;
+use crate::history::UndoHistory;
+use crate::UndoSystemSet;
+
+/// Records the reflected components of the entities affected by each registered entry,
+/// so that a debug UI can step the world through the recorded timeline via [`UndoTimeline`].
+///
+/// Only components registered in [`AppTypeRegistry`] with [`ReflectComponent`](bevy::ecs::reflect::ReflectComponent) are recorded,
+/// and the entities are taken from [`UndoMeta::entities`](crate::prelude::UndoMeta::entities).
+/// Every registration clones all of their components, so this is meant for development builds.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoTimeTravelPlugin;
+
+
+impl Plugin for UndoTimeTravelPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<UndoTimeline>()
+            .add_systems(PreUpdate, record_timeline_system
+                .after(UndoSystemSet::Record)
+                .before(UndoSystemSet::Evict),
+            );
+    }
+}
+
+
+/// The state of the affected entities right after an entry was registered.
+#[derive(Debug)]
+pub struct UndoTimelineFrame {
+    /// The slot number of the entry, see [`UndoEntryInfo::group`](crate::prelude::UndoEntryInfo::group).
+    pub group: usize,
+
+    /// The elapsed time of the app when recorded, zero without [`Time`](bevy::prelude::Time).
+    pub recorded_at: Duration,
+
+    pub entities: Vec<UndoEntitySnapshot>,
+}
+
+
+/// The recorded timeline of [`UndoTimeTravelPlugin`].
+///
+/// Seeking applies the recorded components to the world, independent of the undo history itself.
+/// An entity takes its latest snapshot at or before the position,
+/// or its earliest one if it was first recorded later.
+#[derive(Resource, Debug, Default)]
+pub struct UndoTimeline {
+    frames: Vec<UndoTimelineFrame>,
+    position: Option<usize>,
+    live: Vec<UndoEntitySnapshot>,
+    recorded_slots: usize,
+}
+
+
+impl UndoTimeline {
+    #[inline(always)]
+    pub fn frames(&self) -> &[UndoTimelineFrame] {
+        &self.frames
+    }
+
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+
+    /// Returns the index of the frame applied to the world, or [`UndoTimeline::len`] if the world is live.
+    #[inline(always)]
+    pub fn position(&self) -> usize {
+        self.position.unwrap_or(self.frames.len())
+    }
+
+
+    #[inline(always)]
+    pub fn is_live(&self) -> bool {
+        self.position.is_none()
+    }
+
+
+    /// Drops the recorded frames, keeping the world as it is.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.live.clear();
+        self.position = None;
+    }
+
+
+    /// Applies the frame at the position to the world, or the live state taken before seeking if it is [`UndoTimeline::len`] or greater.
+    ///
+    /// This takes the world, so call it from an exclusive system.
+    pub fn seek(world: &mut World, position: usize) {
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        world.resource_scope(|world, mut timeline: Mut<UndoTimeline>| {
+            if timeline.is_live() {
+                let entities: HashSet<Entity> = timeline
+                    .frames
+                    .iter()
+                    .flat_map(|frame| frame.entities.iter().map(|snapshot| snapshot.entity))
+                    .collect();
+                timeline.live = entities
+                    .into_iter()
+                    .filter_map(|entity| UndoEntitySnapshot::capture(world, &type_registry, entity))
+                    .collect();
+            }
+
+            let timeline = &mut *timeline;
+            let snapshots: Vec<&UndoEntitySnapshot> = if timeline.frames.len() <= position {
+                timeline.position = None;
+                timeline.live.iter().collect()
+            } else {
+                timeline.position = Some(position);
+                frame_state(&timeline.frames, position)
+            };
+
+            let type_registry = type_registry.read();
+            for snapshot in snapshots {
+                if let Ok(mut entity) = world.get_entity_mut(snapshot.entity) {
+                    snapshot.apply(&mut entity, &type_registry);
+                }
+            }
+        });
+    }
+
+
+    /// Seeks to the previous frame, staying at the first one.
+    #[inline]
+    pub fn step_backward(world: &mut World) {
+        let position = world.resource::<UndoTimeline>().position();
+        Self::seek(world, position.saturating_sub(1));
+    }
+
+
+    /// Seeks to the next frame, returning to the live state after the last one.
+    #[inline]
+    pub fn step_forward(world: &mut World) {
+        let position = world.resource::<UndoTimeline>().position();
+        Self::seek(world, position + 1);
+    }
+}
+
+
+/// Returns the latest snapshot of each entity at or before the position, or the earliest one after it.
+fn frame_state(frames: &[UndoTimelineFrame], position: usize) -> Vec<&UndoEntitySnapshot> {
+    let mut seen = HashSet::new();
+    let mut snapshots = Vec::new();
+    let before = frames[..=position].iter().rev();
+    let after = frames[position + 1..].iter();
+    for frame in before.chain(after) {
+        for snapshot in frame.entities.iter() {
+            if seen.insert(snapshot.entity) {
+                snapshots.push(snapshot);
+            }
+        }
+    }
+    snapshots
+}
+
+
+fn record_timeline_system(world: &mut World) {
+    let history = world.resource::<UndoHistory>();
+    let registered_slots = history.registered_slots();
+    let recorded_slots = world.resource::<UndoTimeline>().recorded_slots;
+    if registered_slots == recorded_slots {
+        return;
+    }
+
+    // New slots always take the greatest numbers, so the newest ones are the registered ones.
+    let mut slots: Vec<(usize, Vec<Entity>)> = Vec::new();
+    for entry in history.entries().rev() {
+        if slots.last().map(|(no, _)| *no) != Some(entry.no) {
+            if slots.len() == registered_slots - recorded_slots {
+                break;
+            }
+            slots.push((entry.no, Vec::new()));
+        }
+        if let Some((_, entities)) = slots.last_mut() {
+            entities.extend(entry.meta.entities.iter().copied());
+        }
+    }
+
+    let now = world
+        .get_resource::<Time>()
+        .map(|time| time.elapsed())
+        .unwrap_or_default();
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let frames: Vec<UndoTimelineFrame> = slots
+        .into_iter()
+        .rev()
+        .map(|(group, mut entities)| {
+            entities.sort_unstable();
+            entities.dedup();
+            UndoTimelineFrame {
+                group,
+                recorded_at: now,
+                entities: entities
+                    .into_iter()
+                    .filter_map(|entity| UndoEntitySnapshot::capture(world, &type_registry, entity))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let mut timeline = world.resource_mut::<UndoTimeline>();
+    timeline.recorded_slots = registered_slots;
+    timeline.frames.extend(frames);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Component, Message, Reflect, ReflectComponent};
+
+    use crate::prelude::{AppUndoEx, UndoMeta, UndoScheduler, UndoTimeline, UndoTimeTravelPlugin};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Move;
+
+    #[derive(Component, Reflect, Default, Debug, PartialEq)]
+    #[reflect(Component)]
+    struct Position(i32);
+
+
+    #[test]
+    fn step_through_timeline() {
+        let mut app = App::new();
+        app.add_plugins((UndoPlugin, UndoTimeTravelPlugin));
+        app.register_type::<Position>();
+        app.add_undo_event::<Move>();
+        let entity = app.world_mut().spawn(Position(1)).id();
+        app.add_systems(Startup, move |mut s: UndoScheduler<Move>| s.register_with_meta(Move, UndoMeta::for_entity(entity)));
+        app.update();
+
+        app.world_mut().get_mut::<Position>(entity).unwrap().0 = 2;
+        let mut state = SystemState::<UndoScheduler<Move>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register_with_meta(Move, UndoMeta::for_entity(entity));
+        state.apply(app.world_mut());
+        app.update();
+        app.world_mut().get_mut::<Position>(entity).unwrap().0 = 3;
+        assert_eq!(app.world().resource::<UndoTimeline>().len(), 2);
+
+        UndoTimeline::step_backward(app.world_mut());
+        assert_eq!(app.world().get::<Position>(entity), Some(&Position(2)));
+        UndoTimeline::step_backward(app.world_mut());
+        assert_eq!(app.world().get::<Position>(entity), Some(&Position(1)));
+
+        UndoTimeline::step_forward(app.world_mut());
+        UndoTimeline::step_forward(app.world_mut());
+        assert!(app.world().resource::<UndoTimeline>().is_live());
+        assert_eq!(app.world().get::<Position>(entity), Some(&Position(3)));
+    }
+}
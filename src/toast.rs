@@ -0,0 +1,243 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bevy::app::{App, Plugin, PostUpdate};
+use bevy::prelude::{BackgroundColor, Color, Commands, Component, Display, FontSize, IntoScheduleConfigs, JustifyContent, Local, MessageReader, Node, PositionType, Query, Res, Resource, Startup, Text, TextColor, TextFont, UiRect, Val, With};
+
+use crate::extension::AppUndoEx;
+use crate::hooks::{run_applied_hooks_system, UndoDenied, UndoHookContext, UndoHookPhase};
+use crate::weak::UndoEntrySkipped;
+
+/// Shows a brief toast at the bottom of the screen when entries have been undone or redone, such as "Undid: Delete 3 tiles",
+/// and when requests are denied or entries skipped.
+///
+/// Entries are labelled by [`UndoMeta::tag`](crate::prelude::UndoMeta::tag), falling back to the name of their type.
+/// This requires `bevy_ui` to be rendered.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UndoToastPlugin {
+    /// How long a toast stays on screen, in real time.
+    pub duration: Duration,
+    pub font_size: f32,
+    pub color: Color,
+    pub background: Color,
+
+    /// The distance from the bottom of the screen.
+    pub bottom: Val,
+}
+
+
+impl Default for UndoToastPlugin {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(2),
+            font_size: 18.,
+            color: Color::WHITE,
+            background: Color::srgba(0., 0., 0., 0.7),
+            bottom: Val::Px(32.),
+        }
+    }
+}
+
+
+impl Plugin for UndoToastPlugin {
+    fn build(&self, app: &mut App) {
+        let applied = UndoToastApplied::default();
+        let (undone, redone) = (applied.clone(), applied.clone());
+        app
+            .add_undo_hook_on_undo(move |cx| undone.push(false, cx))
+            .add_undo_hook_on_redo(move |cx| redone.push(true, cx))
+            .insert_resource(applied)
+            .insert_resource(UndoToastConfig(*self))
+            .add_systems(Startup, spawn_toast_system)
+            .add_systems(PostUpdate, update_toast_system.after(run_applied_hooks_system));
+    }
+}
+
+
+#[derive(Resource)]
+struct UndoToastConfig(UndoToastPlugin);
+
+
+/// The slots applied since the last toast, whether they are redone, and their labels, filled by the hooks.
+#[derive(Resource, Clone, Default)]
+struct UndoToastApplied(Arc<Mutex<Vec<(bool, usize, String)>>>);
+
+
+impl UndoToastApplied {
+    fn push(&self, redo: bool, cx: &UndoHookContext) {
+        if cx.phase != UndoHookPhase::AfterApply {
+            return;
+        }
+        let mut applied = self.0.lock().unwrap();
+        // The entries of a slot are applied one after another, the slot is labelled by the first.
+        if applied.last().is_some_and(|(_, no, _)| *no == cx.no) {
+            return;
+        }
+        let label = if cx.meta.tag.is_empty() {
+            cx.type_name.rsplit("::").next().unwrap_or(cx.type_name).to_string()
+        } else {
+            cx.meta.tag.clone()
+        };
+        applied.push((redo, cx.no, label));
+    }
+
+
+    fn take(&self) -> Vec<(bool, usize, String)> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+
+/// The node holding the toast, hidden while there is nothing to show.
+#[derive(Component)]
+struct UndoToast;
+
+
+#[derive(Component)]
+struct UndoToastText;
+
+
+fn spawn_toast_system(
+    mut commands: Commands,
+    config: Res<UndoToastConfig>,
+) {
+    let config = &config.0;
+    let container = Node {
+        position_type: PositionType::Absolute,
+        bottom: config.bottom,
+        width: Val::Percent(100.),
+        justify_content: JustifyContent::Center,
+        display: Display::None,
+        ..Default::default()
+    };
+    let text = (
+        Text::default(),
+        TextFont {
+            font_size: FontSize::Px(config.font_size),
+            ..Default::default()
+        },
+        TextColor(config.color),
+        Node {
+            padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+            ..Default::default()
+        },
+        BackgroundColor(config.background),
+    );
+    commands
+        .spawn((container, UndoToast))
+        .with_children(|parent| {
+            parent.spawn((text, UndoToastText));
+        });
+}
+
+
+fn update_toast_system(
+    mut toast: Query<&mut Node, With<UndoToast>>,
+    mut toast_text: Query<&mut Text, With<UndoToastText>>,
+    mut denied: MessageReader<UndoDenied>,
+    mut skipped: MessageReader<UndoEntrySkipped>,
+    mut expires: Local<Option<Instant>>,
+    applied: Res<UndoToastApplied>,
+    config: Res<UndoToastConfig>,
+) {
+    let mut message = toast_message(&applied.take());
+    if let Some(denied) = denied.read().last() {
+        message = Some(format!("Can't undo: {}", denied.reason));
+    }
+    if skipped.read().count() != 0 {
+        message.get_or_insert_with(|| "Skipped an entry whose entity no longer exists".to_string());
+    }
+
+    let display = match message {
+        Some(message) => {
+            for mut text in toast_text.iter_mut() {
+                text.0.clone_from(&message);
+            }
+            *expires = Some(Instant::now() + config.0.duration);
+            Display::Flex
+        }
+        None if expires.is_some_and(|expires| expires <= Instant::now()) => {
+            *expires = None;
+            Display::None
+        }
+        None => return
+    };
+    for mut node in toast.iter_mut() {
+        node.display = display;
+    }
+}
+
+
+/// Describes the slots applied in the frame, such as "Undid: Delete 3 tiles", or "Undid 3 actions" if there are several.
+fn toast_message(applied: &[(bool, usize, String)]) -> Option<String> {
+    let verb = |redo: bool| if redo { "Redid" } else { "Undid" };
+    match applied {
+        [] => None,
+        [(redo, _, label)] => Some(format!("{}: {label}", verb(*redo))),
+        [(redo, ..), ..] if applied.iter().all(|(other, ..)| other == redo) => Some(format!("{} {} actions", verb(*redo), applied.len())),
+        _ => Some(format!("Applied {} actions", applied.len())),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Display, Message, Node, Text, With};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoMeta, UndoScheduler, UndoToastPlugin, UndoVerdict};
+    use crate::request::RequestUndoEvent;
+    use crate::toast::{UndoToast, UndoToastText};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Paint;
+
+
+    fn toast(app: &mut App) -> (Display, String) {
+        let display = app.world_mut().query_filtered::<&Node, With<UndoToast>>().single(app.world()).unwrap().display;
+        let text = app.world_mut().query_filtered::<&Text, With<UndoToastText>>().single(app.world()).unwrap().0.clone();
+        (display, text)
+    }
+
+
+    #[test]
+    fn show_applied_and_denied_entries() {
+        let mut app = App::new();
+        app.add_plugins((UndoPlugin, UndoToastPlugin {
+            duration: Duration::ZERO,
+            ..Default::default()
+        }));
+        app.add_undo_event::<Paint>();
+        app.add_undo_veto(|cx| if cx.meta.tag == "Locked" {
+            UndoVerdict::Deny("the layer is locked".to_string())
+        } else {
+            UndoVerdict::Allow
+        });
+        let mut scheduler = SystemState::<UndoScheduler<Paint>>::new(app.world_mut());
+        scheduler.get_mut(app.world_mut()).unwrap().register_with_meta(Paint, UndoMeta::tagged("Locked"));
+        app.update();
+        scheduler.get_mut(app.world_mut()).unwrap().push(Paint, Some(Paint), UndoMeta::tagged("Delete 3 tiles"));
+        app.update();
+        assert_eq!(toast(&mut app).0, Display::None);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(toast(&mut app), (Display::Flex, "Undid: Delete 3 tiles".to_string()));
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(toast(&mut app), (Display::Flex, "Redid: Delete 3 tiles".to_string()));
+        app.update();
+        assert_eq!(toast(&mut app).0, Display::None);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        assert_eq!(toast(&mut app), (Display::Flex, "Can't undo: the layer is locked".to_string()));
+    }
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::{MessageReader, Res, ResMut, Resource};
+use bevy::platform::collections::HashMap;
+
+use crate::UndoAreas;
+use crate::collab::UndoCollabClock;
+use crate::history::UndoHistory;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoEvent;
+
+type UndoTransformFn<E, O> = Box<dyn Fn(&mut E, &O) + Send + Sync + 'static>;
+
+
+/// Rewrites the local entries of `E` against the operations of `O` merged from other clients,
+/// see [`AppUndoEx::add_undo_transform`](crate::prelude::AppUndoEx::add_undo_transform).
+#[derive(Resource)]
+pub(crate) struct UndoTransform<E: UndoPayload, O: UndoPayload>(pub UndoTransformFn<E, O>);
+
+
+/// Transforms the local entries of `E` stamped before each merged entry of `O`,
+/// so they are applied against the state left by the later edits of other clients.
+pub(crate) fn transform_local_entries_system<E: UndoPayload, O: UndoPayload>(
+    mut er: MessageReader<UndoEvent<O>>,
+    mut areas: ResMut<UndoAreas>,
+    history: Res<UndoHistory>,
+    collab: Option<Res<UndoCollabClock>>,
+    transform: Res<UndoTransform<E, O>>,
+) {
+    let area = areas.registered_mut::<E>();
+    let Some(collab) = collab else {
+        er.clear();
+        return;
+    };
+    let remote: Vec<_> = er
+        .read()
+        .filter_map(|e| e.meta.stamp.filter(|stamp| stamp.site != collab.site).map(|stamp| (stamp, &e.inner)))
+        .collect();
+    if remote.is_empty() {
+        return;
+    }
+
+    let stamps: HashMap<usize, _> = history
+        .entries()
+        .filter_map(|entry| entry.meta.stamp.filter(|stamp| stamp.site == collab.site).map(|stamp| (entry.no, stamp)))
+        .collect();
+    for mut entry in area.0.drain() {
+        if let Some(local) = stamps.get(&entry.no) {
+            for (_, op) in remote.iter().filter(|(stamp, _)| local < stamp) {
+                (transform.0)(&mut entry.inner, op);
+                if let Some(redo) = entry.redo.as_mut() {
+                    (transform.0)(redo, op);
+                }
+            }
+        }
+        area.push(entry);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoCollab, UndoMeta, UndoRemoteEntry, UndoScheduler, UndoSiteId, UndoStamp};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct RemoveAt(usize);
+
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct InsertAt(usize);
+
+
+    #[test]
+    fn transform_against_later_remote_edits() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<RemoveAt>();
+        app.add_undo_event::<InsertAt>();
+        app.enable_undo_collab(UndoSiteId(1));
+        app.add_undo_transform::<RemoveAt, InsertAt>(|remove, insert| {
+            if insert.0 <= remove.0 {
+                remove.0 += 1;
+            }
+        });
+
+        let mut state = SystemState::<UndoScheduler<RemoveAt>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(RemoveAt(5));
+        state.apply(app.world_mut());
+        app.update();
+
+        let remote = |clock, at| UndoRemoteEntry {
+            stamp: UndoStamp {
+                clock,
+                site: UndoSiteId(2),
+            },
+            event: InsertAt(at).into(),
+            meta: UndoMeta::default(),
+        };
+        let mut state = SystemState::<UndoCollab>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().merge([remote(2, 2), remote(3, 9)]);
+        app.update();
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let undone: Vec<RemoveAt> = app.world_mut().resource_mut::<Messages<RemoveAt>>().drain().collect();
+        assert_eq!(undone, vec![RemoveAt(6)]);
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::{Message, MessageReader, NextState, Res, ResMut, Resource, State};
+use bevy::state::state::FreelyMutableState;
+
+use crate::undo_event::UndoScheduler;
+
+/// Queues the transition to the state via [`NextState`] when undone or redone.
+///
+/// Registered automatically for the state types set up via
+/// [`AppUndoEx::track_state_undo`](crate::prelude::AppUndoEx::track_state_undo).
+#[derive(Message, Debug, Clone)]
+pub struct UndoTransitionEvent<S: FreelyMutableState> {
+    pub to: S,
+}
+
+
+/// The state last seen, and whether the state has just been changed by an undo or redo.
+#[derive(Resource)]
+pub(crate) struct UndoTrackedState<S: FreelyMutableState> {
+    previous: Option<S>,
+    restored: bool,
+}
+
+
+impl<S: FreelyMutableState> Default for UndoTrackedState<S> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            previous: None,
+            restored: false,
+        }
+    }
+}
+
+
+/// Registers the transition of `S` made in the frame, except the one made by undoing or redoing it.
+pub(crate) fn track_state_transition_system<S: FreelyMutableState>(
+    state: Res<State<S>>,
+    mut tracked: ResMut<UndoTrackedState<S>>,
+    mut scheduler: UndoScheduler<UndoTransitionEvent<S>>,
+) {
+    let current = state.get().clone();
+    let restored = std::mem::take(&mut tracked.restored);
+    let Some(previous) = tracked.previous.replace(current.clone()) else {
+        return;
+    };
+    if previous == current || restored {
+        return;
+    }
+    scheduler.register_with_redo(
+        UndoTransitionEvent { to: previous },
+        UndoTransitionEvent { to: current },
+    );
+}
+
+
+pub(crate) fn apply_transition_events_system<S: FreelyMutableState>(
+    mut er: MessageReader<UndoTransitionEvent<S>>,
+    mut next: ResMut<NextState<S>>,
+    mut tracked: ResMut<UndoTrackedState<S>>,
+) {
+    if let Some(event) = er.read().last() {
+        next.set(event.to.clone());
+        tracked.restored = true;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{NextState, State, States};
+    use bevy::state::app::{AppExtStates, StatesPlugin};
+
+    use crate::prelude::{AppUndoEx, UndoRequester};
+    use crate::UndoPlugin;
+
+    #[derive(States, Default, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+    enum EditorMode {
+        #[default]
+        Select,
+        Sculpt,
+        Paint,
+    }
+
+
+    fn request(app: &mut App, f: impl FnOnce(&mut UndoRequester)) {
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        f(&mut state.get_mut(app.world_mut()).unwrap());
+        app.update();
+        app.update();
+    }
+
+
+    fn switch(app: &mut App, mode: EditorMode) {
+        app.world_mut().resource_mut::<NextState<EditorMode>>().set(mode);
+        app.update();
+        app.update();
+    }
+
+
+    fn mode(app: &App) -> EditorMode {
+        *app.world().resource::<State<EditorMode>>().get()
+    }
+
+
+    #[test]
+    fn undo_mode_switches() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_plugins(StatesPlugin);
+        app.init_state::<EditorMode>();
+        app.track_state_undo::<EditorMode>();
+        app.update();
+
+        switch(&mut app, EditorMode::Sculpt);
+        switch(&mut app, EditorMode::Paint);
+
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(mode(&app), EditorMode::Sculpt);
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(mode(&app), EditorMode::Select);
+        request(&mut app, |requester| requester.redo());
+        assert_eq!(mode(&app), EditorMode::Sculpt);
+
+        switch(&mut app, EditorMode::Select);
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(mode(&app), EditorMode::Sculpt);
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(mode(&app), EditorMode::Select);
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Res, Resource};
+
+use crate::grouping::{UndoGroupingContext, UndoGroupingStrategy};
+
+/// The entries registered during the same game turn share one slot,
+/// the turn being ended by [`UndoTurns::end_turn`].
+///
+/// Selected for all channels via [`AppUndoEx::enable_undo_turns`](crate::prelude::AppUndoEx::enable_undo_turns).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoTurnGrouping;
+
+
+impl UndoGroupingStrategy for UndoTurnGrouping {
+    #[inline(always)]
+    fn joins(&self, previous: &UndoGroupingContext, now: &UndoGroupingContext) -> bool {
+        previous.turn == now.turn
+    }
+}
+
+
+/// The count of turns ended, shared with the groupings so that systems registering entries can end turns as well.
+#[derive(Resource, Debug, Default, Clone)]
+pub(crate) struct UndoTurnClock(Arc<AtomicU64>);
+
+
+impl UndoTurnClock {
+    #[inline(always)]
+    pub fn turn(&self) -> u64 {
+        self.0.load(Ordering::Acquire)
+    }
+
+
+    #[cfg(feature = "serde")]
+    #[inline(always)]
+    pub fn set(&self, turn: u64) {
+        self.0.store(turn, Ordering::Release);
+    }
+}
+
+
+/// Ends the game turns of the turn-based mode enabled via [`AppUndoEx::enable_undo_turns`](crate::prelude::AppUndoEx::enable_undo_turns).
+#[derive(SystemParam)]
+pub struct UndoTurns<'w> {
+    clock: Res<'w, UndoTurnClock>,
+}
+
+
+impl<'w> UndoTurns<'w> {
+    /// Ends the current turn, so the entries registered from then on are undone apart from the ones registered so far.
+    ///
+    /// Ending a turn without any entry has no effect on the history.
+    #[inline]
+    pub fn end_turn(&mut self) {
+        self.clock.0.fetch_add(1, Ordering::AcqRel);
+    }
+
+
+    /// Returns the count of turns ended.
+    #[inline(always)]
+    pub fn turn(&self) -> u64 {
+        self.clock.turn()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Local, Message, Update};
+
+    use crate::prelude::{AppUndoEx, UndoScheduler, UndoTurns};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[test]
+    fn undo_one_turn_at_a_time() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.enable_undo_turns();
+            app.add_systems(Update, |mut scheduler: UndoScheduler<Move>, mut turns: UndoTurns, mut frame: Local<i32>| {
+                *frame += 1;
+                match *frame {
+                    1 => scheduler.register(Move(1)),
+                    2 => {
+                        scheduler.register(Move(2));
+                        turns.end_turn();
+                        scheduler.register(Move(3));
+                        scheduler.register(Move(4));
+                        turns.end_turn();
+                        turns.end_turn();
+                    }
+                    3 => scheduler.register(Move(5)),
+                    _ => {}
+                }
+            });
+        });
+        harness.frames(3);
+        harness.undo();
+        harness.expect([Move(5)]);
+        harness.undo();
+        harness.expect([Move(4), Move(3)]);
+        harness.undo();
+        harness.expect([Move(2), Move(1)]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use bevy::ecs::component::Mutable;
+use bevy::prelude::{Commands, Component, Entity, Message, MessageReader, MessageWriter, Query, Res, Resource, Time, Transform};
+
+use crate::component::UndoComponentEvent;
+
+type UndoInterpolation<C> = Box<dyn Fn(f32, &C, &C) -> C + Send + Sync + 'static>;
+
+
+/// The duration and interpolation of the tweens of `C`, see [`AppUndoEx::animate_undo_component`](crate::prelude::AppUndoEx::animate_undo_component).
+#[derive(Resource)]
+pub(crate) struct UndoTweenConfig<C: Component<Mutability = Mutable> + Clone> {
+    pub duration: Duration,
+    pub interpolate: UndoInterpolation<C>,
+}
+
+
+/// Interpolates the component of the entity to the value restored by an undo or redo, removed once finished.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct UndoTween<C: Component<Mutability = Mutable> + Clone> {
+    pub from: C,
+    pub to: C,
+    pub elapsed: Duration,
+}
+
+
+/// Sent when the component of the entity starts moving to the value restored by an undo or redo.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoTweenStarted<C: Component<Mutability = Mutable> + Clone> {
+    pub entity: Entity,
+    marker: PhantomData<fn() -> C>,
+}
+
+
+impl<C: Component<Mutability = Mutable> + Clone> UndoTweenStarted<C> {
+    #[inline(always)]
+    pub const fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            marker: PhantomData,
+        }
+    }
+}
+
+
+/// Sent when the component of the entity reached the value restored by an undo or redo.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoTweenFinished<C: Component<Mutability = Mutable> + Clone> {
+    pub entity: Entity,
+    marker: PhantomData<fn() -> C>,
+}
+
+
+impl<C: Component<Mutability = Mutable> + Clone> UndoTweenFinished<C> {
+    #[inline(always)]
+    pub const fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            marker: PhantomData,
+        }
+    }
+}
+
+
+/// Interpolates the translation and scale linearly and the rotation spherically.
+pub(crate) fn interpolate_transform(t: f32, from: &Transform, to: &Transform) -> Transform {
+    Transform {
+        translation: from.translation.lerp(to.translation, t),
+        rotation: from.rotation.slerp(to.rotation, t),
+        scale: from.scale.lerp(to.scale, t),
+    }
+}
+
+
+/// Starts a tween from the current value for each component about to be restored,
+/// a tween already running being restarted from where it got.
+pub(crate) fn start_tweens_system<C: Component<Mutability = Mutable> + Clone>(
+    mut commands: Commands,
+    mut er: MessageReader<UndoComponentEvent<C>>,
+    mut ew: MessageWriter<UndoTweenStarted<C>>,
+    components: Query<&C>,
+) {
+    for event in er.read() {
+        if let Ok(from) = components.get(event.entity) {
+            commands.entity(event.entity).insert(UndoTween {
+                from: from.clone(),
+                to: event.value.clone(),
+                elapsed: Duration::ZERO,
+            });
+            ew.write(UndoTweenStarted::new(event.entity));
+        }
+    }
+}
+
+
+/// Moves the tweened components along, finishing the tweens at once without [`Time`].
+pub(crate) fn advance_tweens_system<C: Component<Mutability = Mutable> + Clone>(
+    mut commands: Commands,
+    mut tweens: Query<(Entity, &mut C, &mut UndoTween<C>)>,
+    mut ew: MessageWriter<UndoTweenFinished<C>>,
+    config: Res<UndoTweenConfig<C>>,
+    time: Option<Res<Time>>,
+) {
+    let delta = time.map_or(config.duration, |time| time.delta());
+    for (entity, mut component, mut tween) in tweens.iter_mut() {
+        tween.elapsed += delta;
+        if config.duration <= tween.elapsed {
+            *component = tween.to.clone();
+            commands.entity(entity).remove::<UndoTween<C>>();
+            ew.write(UndoTweenFinished::new(entity));
+            continue;
+        }
+        let t = tween.elapsed.as_secs_f32() / config.duration.as_secs_f32();
+        *component = (config.interpolate)(t, &tween.from, &tween.to);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::app::App;
+    use bevy::ecs::component::Mutable;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Component, Messages, Time, Transform};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoComponentEvent, UndoScheduler, UndoTweenFinished, UndoTweenStarted};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Opacity(f32);
+
+
+    fn new_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.init_resource::<Time>();
+        app
+    }
+
+
+    /// Undoes the change of the component from `before` to `after`, returning its value after each of the frames.
+    fn undo_change<C: Component<Mutability = Mutable> + Clone>(app: &mut App, before: C, after: C, frames: u64) -> Vec<C> {
+        let entity = app.world_mut().spawn(before.clone()).id();
+        let mut state = SystemState::<UndoScheduler<UndoComponentEvent<C>>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register(UndoComponentEvent {
+            entity,
+            value: before,
+        });
+        state.apply(app.world_mut());
+        app.update();
+        app.world_mut().entity_mut(entity).insert(after);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        (1..=frames)
+            .map(|_| {
+                app.world_mut().resource_mut::<Time>().advance_by(Duration::from_millis(100));
+                app.update();
+                app.world().get::<C>(entity).unwrap().clone()
+            })
+            .collect()
+    }
+
+
+    #[test]
+    fn interpolate_back_to_previous_pose() {
+        let mut app = new_app();
+        app.animate_undo_transforms(Duration::from_millis(400));
+        let x: Vec<f32> = undo_change(&mut app, Transform::default(), Transform::from_xyz(8., 0., 0.), 5)
+            .iter()
+            .map(|transform| transform.translation.x)
+            .collect();
+        assert_eq!(x, vec![6., 4., 2., 0., 0.]);
+        assert_eq!(app.world().resource::<Messages<UndoTweenFinished<Transform>>>().len(), 1);
+    }
+
+
+    #[test]
+    fn interpolate_any_component() {
+        let mut app = new_app();
+        app.animate_undo_component::<Opacity>(Duration::from_millis(200), |t, from, to| Opacity(from.0 + (to.0 - from.0) * t));
+        let opacity = undo_change(&mut app, Opacity(1.), Opacity(0.), 2);
+        assert_eq!(opacity, vec![Opacity(0.5), Opacity(1.)]);
+        assert_eq!(app.world().resource::<Messages<UndoTweenStarted<Opacity>>>().len(), 1);
+        assert_eq!(app.world().resource::<Messages<UndoTweenFinished<Opacity>>>().len(), 1);
+    }
+}
@@ -1,69 +1,320 @@
+#[cfg(feature = "serde")]
+use std::time::SystemTime;
+
 use bevy::ecs::system::SystemParam;
-use bevy::prelude::{Event, EventWriter, ResMut};
+use bevy::prelude::{Message, MessageWriter, Res, ResMut, SystemCondition, World};
 
+use crate::{lock, UndoAreas};
+use crate::channel::UndoChannel;
 use crate::counter::UndoCounter;
-use crate::reserve::{RequestCommitReservationsEvent, RequestCommitReservationsFromSchedulerEvent, ReserveCounter, UndoReservedArea, UndoReserveEvent};
+use crate::deferred::{UndoDeferralTrigger, UndoDeferredEvent, UndoDeferredId, UndoDeferredRegistrations};
+use crate::document::UndoDocumentState;
+use crate::grouping::UndoGroupings;
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+#[cfg(feature = "reserve")]
+use crate::reserve::{RequestCommitReservationsEvent, RequestCommitReservationsFromSchedulerEvent, ReserveCounter, UndoReserveEvent};
 
 #[cfg(feature = "callback_event")]
 pub mod callback;
 
-#[derive(Event, Clone)]
-pub(crate) struct UndoEvent<E: Event + Clone> {
+#[derive(Message)]
+pub(crate) struct UndoEvent<E: UndoPayload> {
     pub inner: E,
+    pub redo: Option<E>,
     pub no: usize,
+    pub meta: UndoMeta,
+
+    /// The wall-clock time of the original registration, for the entries replayed from an export.
+    #[cfg(feature = "serde")]
+    pub registered_on: Option<SystemTime>,
 }
 
+
+/// An event stored in the registered area, its metadata is kept in the history.
+pub struct UndoEntry<E: UndoPayload> {
+    /// The event sent when undone.
+    pub inner: E,
+
+    /// The event sent when redone, if registered with one.
+    pub redo: Option<E>,
+
+    /// The slot number, entries registered together share the same one.
+    pub no: usize,
+}
+
+#[cfg(feature = "reserve")]
 #[derive(SystemParam)]
 pub struct UndoReserveCommitter<'w> {
-    ew: EventWriter<'w, RequestCommitReservationsEvent>,
+    ew: MessageWriter<'w, RequestCommitReservationsEvent>,
 }
 
+#[cfg(feature = "reserve")]
 impl<'w> UndoReserveCommitter<'w> {
     /// Moves all events placed on the reserved area by [`reserve`](UndoScheduler::reserve) to the registered area.
     #[inline(always)]
     pub fn commit(&mut self) {
-        self.ew.send(RequestCommitReservationsEvent);
+        self.ew.write(RequestCommitReservationsEvent);
     }
 }
 
 
 #[derive(SystemParam)]
-pub struct UndoScheduler<'w, E: Event + Clone> {
+pub struct UndoScheduler<'w, E: UndoPayload> {
     counter: ResMut<'w, UndoCounter>,
-    reserve: ResMut<'w, UndoReservedArea<E>>,
+    groupings: Option<ResMut<'w, UndoGroupings>>,
+    areas: Res<'w, UndoAreas>,
+    deferred: ResMut<'w, UndoDeferredRegistrations>,
+    documents: Res<'w, UndoDocumentState>,
+    undo_writer: MessageWriter<'w, UndoEvent<E>>,
+    #[cfg(feature = "reserve")]
     reserve_counter: ResMut<'w, ReserveCounter>,
-    undo_writer: EventWriter<'w, UndoEvent<E>>,
-    reserve_writer: EventWriter<'w, RequestCommitReservationsFromSchedulerEvent>,
+    #[cfg(feature = "reserve")]
+    reserve_writer: MessageWriter<'w, RequestCommitReservationsFromSchedulerEvent>,
 }
 
 
-impl<'w, E: Event + Clone> UndoScheduler<'w, E> {
+impl<'w, E: UndoPayload> UndoScheduler<'w, E> {
     /// Register the undo-event　in the registered area.
     ///
-    /// Events can registered multiple, and when [`UndoRequester::undo`](crate::request::UndoRequester) is called,
+    /// Messages can registered multiple, and when [`UndoRequester::undo`](crate::request::UndoRequester) is called,
     /// last registered will sent
     #[inline(always)]
     pub fn register(&mut self, event: E) {
-        self.counter.increment();
-        self.undo_writer.send(UndoEvent {
+        self.register_with_meta(event, UndoMeta::default());
+    }
+
+
+    /// Register the undo-event　in the registered area together with its [`UndoMeta`].
+    ///
+    /// The metadata can be used to select the entry later,
+    /// see [`UndoRequester::undo_last_matching`](crate::request::UndoRequester::undo_last_matching).
+    #[inline]
+    pub fn register_with_meta(&mut self, event: E, meta: UndoMeta) {
+        self.push(event, None, meta);
+    }
+
+
+    /// Register the undo-event together with the event redoing it, for actions whose inverse is known up front.
+    ///
+    /// Undoing the entry keeps it waiting for redo, and redoing it sends the redo-event as is
+    /// and puts the entry back, so handlers need not register it again.
+    #[inline]
+    pub fn register_with_redo(&mut self, event: E, redo: E) {
+        self.register_with_redo_and_meta(event, redo, UndoMeta::default());
+    }
+
+
+    /// Like [`UndoScheduler::register_with_redo`], together with its [`UndoMeta`].
+    #[inline]
+    pub fn register_with_redo_and_meta(&mut self, event: E, redo: E, meta: UndoMeta) {
+        self.push(event, Some(redo), meta);
+    }
+
+
+    /// Register the events as consecutive entries in one call.
+    ///
+    /// Each event is undone separately, like calling [`UndoScheduler::register`] for each of them.
+    pub fn register_all(&mut self, events: impl IntoIterator<Item = E>) {
+        let channel = self.documents.route(UndoChannel::DEFAULT);
+        let events: Vec<(E, usize)> = events
+            .into_iter()
+            .map(|event| (event, self.next_slot(channel)))
+            .collect();
+        self.push_batch(events);
+    }
+
+
+    /// Register the events as a single group in one call.
+    ///
+    /// All of them are sent by a single [`UndoRequester::undo`](crate::request::UndoRequester::undo), the last one first.
+    pub fn register_all_grouped(&mut self, events: impl IntoIterator<Item = E>) {
+        let mut events = events.into_iter().peekable();
+        if events.peek().is_none() {
+            return;
+        }
+        let no = self.next_slot(self.documents.route(UndoChannel::DEFAULT));
+        self.push_batch(events.map(|event| (event, no)).collect());
+    }
+
+
+    fn push_batch(&mut self, events: Vec<(E, usize)>) {
+        let meta = self.routed(UndoMeta::default());
+        self.undo_writer.write_batch(events.into_iter().map(|(inner, no)| UndoEvent {
+            inner,
+            redo: None,
+            no,
+            meta: meta.clone(),
+            #[cfg(feature = "serde")]
+            registered_on: None,
+        }));
+    }
+
+
+    #[inline]
+    pub(crate) fn push(&mut self, event: E, redo: Option<E>, meta: UndoMeta) {
+        let meta = self.routed(meta);
+        let no = self.next_slot(meta.channel);
+        self.push_to_slot(no, event, redo, meta);
+    }
+
+
+    /// Sends the entry to the slot allocated via [`UndoScheduler::next_slot`], its metadata being already routed.
+    #[inline]
+    pub(crate) fn push_to_slot(&mut self, no: usize, event: E, redo: Option<E>, meta: UndoMeta) {
+        self.undo_writer.write(UndoEvent {
             inner: event,
-            no: **self.counter,
+            redo,
+            no,
+            meta,
+            #[cfg(feature = "serde")]
+            registered_on: None,
         });
     }
 
 
+    /// Resolves [`UndoChannel::DEFAULT`] to the document active at the time of the registration,
+    /// so switching documents afterwards does not move the entry.
+    #[inline]
+    pub(crate) fn routed(&self, meta: UndoMeta) -> UndoMeta {
+        let channel = self.documents.route(meta.channel);
+        meta.with_channel(channel)
+    }
+
+
+    /// Allocates the slot of the next entry of the channel, which may be shared according to its [`UndoGroupingStrategy`](crate::prelude::UndoGroupingStrategy).
+    #[inline]
+    pub(crate) fn next_slot(&mut self, channel: UndoChannel) -> usize {
+        match self.groupings.as_mut() {
+            Some(groupings) => groupings.next_slot(channel, &mut self.counter),
+            None => {
+                self.counter.increment();
+                **self.counter
+            }
+        }
+    }
+
+
+    /// Starts a gesture, such as a pointer drag, whose entries share one slot in the channels grouped by
+    /// [`UndoGestureGrouping`](crate::prelude::UndoGestureGrouping).
+    ///
+    /// This does nothing unless a grouping is configured via [`AppUndoEx::configure_undo_grouping`](crate::prelude::AppUndoEx::configure_undo_grouping).
+    #[inline]
+    pub fn begin_gesture(&mut self) {
+        if let Some(groupings) = self.groupings.as_mut() {
+            groupings.begin_gesture();
+        }
+    }
+
+
+    /// Ends the gesture started by [`UndoScheduler::begin_gesture`].
+    #[inline]
+    pub fn end_gesture(&mut self) {
+        if let Some(groupings) = self.groupings.as_mut() {
+            groupings.end_gesture();
+        }
+    }
+
+
+    /// Mutates the payload of the most recent entry of `E` in place, such as to extend a coalesced drag with its final position.
+    ///
+    /// The entry keeps its slot and metadata, and entries registered earlier in the same frame are amended as well.
+    /// This does nothing if there is no entry, or for the input types of [`AppUndoEx::add_undo_event_mapped`](crate::prelude::AppUndoEx::add_undo_event_mapped).
+    #[inline]
+    pub fn amend_latest(&mut self, amend: impl FnOnce(&mut E) + Send + Sync + 'static) {
+        if let Some(areas) = self.areas.get::<E>() {
+            lock(&areas.amendments).0.push(Box::new(amend));
+        }
+    }
+
+
+    /// Replaces the payload of the most recent entry in the history with the event, whatever the type of the former,
+    /// such as when a tool captured better what it changed.
+    ///
+    /// The entry keeps its slot and metadata but is no longer redoable, and is replaced once the entries registered
+    /// in the same frame are recorded. This does nothing for the input types of [`AppUndoEx::add_undo_event_mapped`](crate::prelude::AppUndoEx::add_undo_event_mapped).
+    #[inline]
+    pub fn replace_latest(&mut self, event: E) {
+        if let Some(areas) = self.areas.get::<E>() {
+            lock(&areas.replacement).0 = Some(event);
+        }
+    }
+
+
+    /// Registers the event once `frames` frames have passed, such as to let an operation settle first.
+    ///
+    /// The entry gets the slot following the ones registered until then.
+    #[inline]
+    pub fn register_after_frames(&mut self, event: E, frames: usize) -> UndoDeferredId {
+        let meta = self.routed(UndoMeta::default());
+        self.deferred.push(UndoDeferralTrigger::Frames(frames), UndoDeferredEvent::Ready(event.into()), meta)
+    }
+
+
+    /// Registers the event in the first frame the condition returns true, evaluated at the start of each frame,
+    /// such as once an asset finished saving, so half-finished operations never enter the history.
+    #[inline]
+    pub fn register_when<M>(&mut self, event: E, condition: impl SystemCondition<M>) -> UndoDeferredId {
+        let meta = self.routed(UndoMeta::default());
+        self.deferred.push(UndoDeferralTrigger::condition(condition), UndoDeferredEvent::Ready(event.into()), meta)
+    }
+
+
+    /// Registers the event captured once the [`Commands`](bevy::prelude::Commands) of this frame have been applied,
+    /// so the payload sees the entities spawned in the same frame instead of placeholders.
+    ///
+    /// The capture runs at the start of the next frame, and can be cancelled until then.
+    #[inline]
+    pub fn register_after_commands(&mut self, capture: impl FnOnce(&World) -> E + Send + Sync + 'static) -> UndoDeferredId {
+        let capture = UndoDeferredEvent::Capture(Box::new(move |world| capture(world).into()));
+        let meta = self.routed(UndoMeta::default());
+        self.deferred.push(UndoDeferralTrigger::Frames(0), capture, meta)
+    }
+
+
+    /// Cancels the deferred registration, returning false if it has already been registered or cancelled.
+    #[inline(always)]
+    pub fn cancel_deferred(&mut self, id: UndoDeferredId) -> bool {
+        self.deferred.cancel(id)
+    }
+
+
+    /// Register the undo-event　to the channel.
+    ///
+    /// The entry is isolated from the other channels and can be undone via [`UndoRequester::undo_channel`](crate::request::UndoRequester::undo_channel).
+    #[inline]
+    pub fn register_to(&mut self, channel: impl Into<UndoChannel>, event: E) {
+        self.register_with_meta(event, UndoMeta::default().with_channel(channel));
+    }
+}
+
+
+#[cfg(feature = "reserve")]
+impl<'w, E: UndoPayload> UndoScheduler<'w, E> {
     /// Place the undo-event in the reserved area.
     ///
-    /// Events is  in placed on same reserved area until [`reserve_commit`](UndoScheduler::register_all_reserved) is called.
+    /// Messages is  in placed on same reserved area until [`reserve_commit`](UndoScheduler::register_all_reserved) is called.
     ///
     ///
     /// This method is useful when want to sent  multiple undo-event with single call [`UndoRequest::undo`](crate::request::UndoRequester) .
     #[inline]
     pub fn reserve(&mut self, event: E) {
+        self.reserve_with_meta(event, UndoMeta::default());
+    }
+
+
+    /// Place the undo-event in the reserved area together with its [`UndoMeta`].
+    #[inline]
+    pub fn reserve_with_meta(&mut self, event: E, meta: UndoMeta) {
+        let Some(areas) = self.areas.get::<E>() else {
+            return;
+        };
+        let meta = self.routed(meta);
         self.reserve_counter.increment();
-        self.reserve.push(UndoReserveEvent {
+        lock(&areas.reservations).push(UndoReserveEvent {
             inner: event,
             reserve_no: **self.reserve_counter,
+            meta,
         });
     }
 
@@ -71,25 +322,37 @@ impl<'w, E: Event + Clone> UndoScheduler<'w, E> {
     /// Moves all events placed on the reserved area by [`reserve`](UndoScheduler::reserve) to the registered area.
     #[inline]
     pub fn register_all_reserved(&mut self) {
-        self.reserve_writer.send(RequestCommitReservationsFromSchedulerEvent);
+        self.reserve_writer.write(RequestCommitReservationsFromSchedulerEvent);
+    }
+
+
+    /// Returns the count of reserved events not committed yet, over all event types.
+    ///
+    /// Reservations are committed at the start of the next frame, so this is not reset by [`UndoScheduler::register_all_reserved`] until then.
+    #[inline(always)]
+    pub fn pending_reservations(&self) -> usize {
+        **self.reserve_counter
     }
 }
 
 
-impl<'w, E: Event + Clone + Default> UndoScheduler<'w, E> {
+impl<'w, E: UndoPayload + Default> UndoScheduler<'w, E> {
     /// Register the undo-event　in the registered area with default value.
     ///
-    /// Events can registered multiple, and when [`UndoRequester::undo`](crate::request::UndoRequester) is called,
+    /// Messages can registered multiple, and when [`UndoRequester::undo`](crate::request::UndoRequester) is called,
     /// last registered will sent
     #[inline(always)]
     pub fn register_default(&mut self) {
         self.register(E::default());
     }
+}
 
 
+#[cfg(feature = "reserve")]
+impl<'w, E: UndoPayload + Default> UndoScheduler<'w, E> {
     /// Place the undo-event in the reserved area with default value.
     ///
-    /// Events is  in placed on same reserved area until [`reserve_commit`](UndoScheduler::register_all_reserved) is called.
+    /// Messages is  in placed on same reserved area until [`reserve_commit`](UndoScheduler::register_all_reserved) is called.
     ///
     ///
     /// This method is useful when want to sent  multiple undo-event with single call [`UndoRequest::undo`](crate::request::UndoRequester) .
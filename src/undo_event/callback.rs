@@ -1,9 +1,13 @@
 use std::sync::Arc;
 
 use bevy::app::{App, Plugin, Update};
-use bevy::prelude::{Commands, Event, EventReader};
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::{Commands, Entity, IntoScheduleConfigs, Message, MessageReader, World};
 
 use crate::extension::AppUndoEx;
+use crate::meta::UndoMeta;
+use crate::prelude::UndoScheduler;
+use crate::priority::UndoHandlerSet;
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug, Default)]
 pub(crate) struct UndoCallbackEventPlugin;
@@ -13,12 +17,14 @@ impl Plugin for UndoCallbackEventPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_undo_event::<UndoCallbackEvent>()
-            .add_systems(Update, undo_callback_event_system);
+            .mark_undo_handled::<UndoCallbackEvent>()
+            .add_message::<UndoCallbackSkipped>()
+            .add_systems(Update, undo_callback_event_system.in_set(UndoHandlerSet::of::<UndoCallbackEvent>()));
     }
 }
 
 
-#[derive(Event, Clone)]
+#[derive(Message, Clone)]
 #[repr(transparent)]
 pub struct UndoCallbackEvent(Arc<dyn Fn(&mut Commands) + Send + Sync + 'static>);
 
@@ -28,17 +34,134 @@ impl UndoCallbackEvent {
     pub fn new(f: impl Fn(&mut Commands) + Send + Sync + 'static) -> Self {
         Self(Arc::new(f))
     }
+
+
+    /// Creates the callback which receives the [`EntityCommands`] of the captured entity.
+    ///
+    /// Like [`Commands::entity`], it panics if the entity does not exist when undone.
+    #[inline(always)]
+    pub fn entity(entity: Entity, f: impl Fn(&mut EntityCommands) + Send + Sync + 'static) -> Self {
+        Self::new(move |commands| f(&mut commands.entity(entity)))
+    }
+
+
+    /// Creates the callback which receives the [`EntityCommands`] of the captured entity if it still exists.
+    ///
+    /// If the entity has been despawned, the closure is not called and [`UndoCallbackSkipped`] is sent instead.
+    #[inline(always)]
+    pub fn for_entity(entity: Entity, f: impl Fn(&mut EntityCommands) + Send + Sync + 'static) -> Self {
+        Self::new(move |commands| {
+            if let Ok(mut entity_commands) = commands.get_entity(entity) {
+                f(&mut entity_commands);
+            } else {
+                commands.queue(move |world: &mut World| {
+                    world.write_message(UndoCallbackSkipped {
+                        entity,
+                    });
+                });
+            }
+        })
+    }
+}
+
+
+/// Sent when a callback created by [`UndoCallbackEvent::for_entity`] is skipped because its entity no longer exists.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoCallbackSkipped {
+    pub entity: Entity,
+}
+
+
+impl<'w> UndoScheduler<'w, UndoCallbackEvent> {
+    /// Register a pair of callbacks, `undo` is called when undone and `redo` when redone.
+    #[inline]
+    pub fn register_callbacks(
+        &mut self,
+        undo: impl Fn(&mut Commands) + Send + Sync + 'static,
+        redo: impl Fn(&mut Commands) + Send + Sync + 'static,
+    ) {
+        self.push(UndoCallbackEvent::new(undo), Some(UndoCallbackEvent::new(redo)), UndoMeta::default());
+    }
 }
 
 
 #[inline]
 pub(crate) fn undo_callback_event_system(
     mut commands: Commands,
-    mut er: EventReader<UndoCallbackEvent>,
+    mut er: MessageReader<UndoCallbackEvent>,
 ) {
-    for e in er.iter() {
+    for e in er.read() {
         e.0(&mut commands);
     }
 }
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup, Update};
+    use bevy::prelude::{Component, Local, Messages, With, World};
+
+    use crate::prelude::{UndoChannel, UndoRequester, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Component)]
+    struct Undone;
+
+    #[derive(Component)]
+    struct Redone;
+
+
+    #[test]
+    fn undo_and_redo_callbacks() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_systems(Startup, |mut s: UndoScheduler<super::UndoCallbackEvent>| {
+            s.register_callbacks(
+                |cmd| { cmd.spawn(Undone); },
+                |cmd| { cmd.spawn(Redone); },
+            );
+        });
+        app.add_systems(Update, |mut req: UndoRequester, mut frame: Local<usize>| {
+            match *frame {
+                0 | 2 => req.undo(),
+                1 => req.redo(),
+                _ => {}
+            }
+            *frame += 1;
+        });
+        for _ in 0..5 {
+            app.update();
+        }
+
+        assert_eq!(count::<Undone>(app.world_mut()), 2);
+        assert_eq!(count::<Redone>(app.world_mut()), 1);
+    }
+
+
+    #[test]
+    fn skip_despawned_entity() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        let entity = app.world_mut().spawn_empty().id();
+        app.add_systems(Startup, move |mut s: UndoScheduler<super::UndoCallbackEvent>| {
+            s.register(super::UndoCallbackEvent::for_entity(entity, |ec| { ec.insert(Undone); }));
+        });
+        app.update();
+        app.world_mut().despawn(entity);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+
+        let skipped = app.world().resource::<Messages<super::UndoCallbackSkipped>>();
+        assert_eq!(skipped.iter_current_update_messages().next(), Some(&super::UndoCallbackSkipped { entity }));
+        assert_eq!(count::<Undone>(app.world_mut()), 0);
+    }
+
+
+    fn count<C: Component>(world: &mut World) -> usize {
+        world.query_filtered::<(), With<C>>().iter(world).count()
+    }
+}
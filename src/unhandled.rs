@@ -0,0 +1,83 @@
+use bevy::log::warn;
+use bevy::prelude::{Message, MessageReader, MessageWriter, Res, Resource};
+use bevy::platform::collections::HashSet;
+
+use crate::payload::UndoPayload;
+
+/// Sent when events of a type without a registered handler were undone or redone in the frame.
+///
+/// It is only sent after [`AppUndoEx::warn_unhandled_undo`](crate::prelude::AppUndoEx::warn_unhandled_undo),
+/// and a warning is logged along with it.
+#[derive(Message, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UndoUnhandled {
+    /// The type name of the undo event.
+    pub type_name: &'static str,
+
+    /// The count of events sent in the frame.
+    pub count: usize,
+}
+
+
+/// The undo event types which are known to be consumed.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoHandlers {
+    pub warn: bool,
+    pub handled: HashSet<&'static str>,
+}
+
+
+pub(crate) fn detect_unhandled_system<E: UndoPayload>(
+    mut er: MessageReader<E>,
+    mut ew: MessageWriter<UndoUnhandled>,
+    handlers: Res<UndoHandlers>,
+) {
+    let count = er.read().count();
+    let type_name = std::any::type_name::<E>();
+    if count == 0 || !handlers.warn || handlers.handled.contains(type_name) {
+        return;
+    }
+
+    warn!("{count} undo event(s) of {type_name} were sent, but no handler is registered for them");
+    ew.write(UndoUnhandled { type_name, count });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::prelude::{Message, MessageReader, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoScheduler, UndoUnhandled};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Handled;
+
+    #[derive(Message, Clone)]
+    struct Forgotten;
+
+
+    #[test]
+    fn warn_unhandled_types() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.warn_unhandled_undo();
+        app.add_undo_event::<Handled>();
+        app.add_undo_event::<Forgotten>();
+        app.add_undo_handler::<Handled, _>(|mut er: MessageReader<Handled>| {
+            er.read().for_each(drop);
+        });
+        app.add_systems(Startup, |mut s: UndoScheduler<Handled>| s.register(Handled));
+        app.add_systems(Startup, |mut s: UndoScheduler<Forgotten>| s.register_all([Forgotten, Forgotten]));
+        app.update();
+
+        app.world_mut().write_message_batch((0..3).map(|_| RequestUndoEvent::Latest(UndoChannel::DEFAULT)));
+        app.update();
+
+        let unhandled: Vec<UndoUnhandled> = app.world_mut().resource_mut::<Messages<UndoUnhandled>>().drain().collect();
+        assert_eq!(unhandled.len(), 1);
+        assert!(unhandled[0].type_name.ends_with("Forgotten"));
+        assert_eq!(unhandled[0].count, 2);
+    }
+}
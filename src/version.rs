@@ -0,0 +1,105 @@
+use crate::payload::UndoPayload;
+
+/// Payloads which can be persisted across changes of their definition.
+///
+/// Bump [`UndoVersioned::VERSION`] when the encoding changes,
+/// and convert bytes written by older versions in [`UndoVersioned::migrate`].
+pub trait UndoVersioned: UndoPayload + Sized {
+    /// The version of the current encoding.
+    const VERSION: u32;
+
+    fn encode(&self) -> Vec<u8>;
+
+
+    /// Decodes bytes written by the current version.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+
+
+    /// Decodes bytes written by an older version, returning `None` if they can no longer be loaded.
+    #[allow(unused_variables)]
+    fn migrate(old_version: u32, bytes: &[u8]) -> Option<Self> {
+        None
+    }
+}
+
+
+/// A payload encoded together with the version it was written with.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct UndoVersionedBytes {
+    pub version: u32,
+    pub bytes: Vec<u8>,
+}
+
+
+impl UndoVersionedBytes {
+    #[inline]
+    pub fn encode<T: UndoVersioned>(payload: &T) -> Self {
+        Self {
+            version: T::VERSION,
+            bytes: payload.encode(),
+        }
+    }
+
+
+    /// Decodes the payload, migrating it if it was written by an older version.
+    ///
+    /// Bytes written by a newer version are rejected.
+    pub fn decode<T: UndoVersioned>(&self) -> Option<T> {
+        match self.version.cmp(&T::VERSION) {
+            std::cmp::Ordering::Equal => T::decode(&self.bytes),
+            std::cmp::Ordering::Less => T::migrate(self.version, &self.bytes),
+            std::cmp::Ordering::Greater => None,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Message;
+
+    use crate::prelude::{UndoVersioned, UndoVersionedBytes};
+
+    /// Version 1 stored only `x` as a single byte.
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move {
+        x: u8,
+        y: u8,
+    }
+
+
+    impl UndoVersioned for Move {
+        const VERSION: u32 = 2;
+
+        fn encode(&self) -> Vec<u8> {
+            vec![self.x, self.y]
+        }
+
+        fn decode(bytes: &[u8]) -> Option<Self> {
+            match bytes {
+                [x, y] => Some(Self { x: *x, y: *y }),
+                _ => None
+            }
+        }
+
+        fn migrate(old_version: u32, bytes: &[u8]) -> Option<Self> {
+            match (old_version, bytes) {
+                (1, [x]) => Some(Self { x: *x, y: 0 }),
+                _ => None
+            }
+        }
+    }
+
+
+    #[test]
+    fn migrate_old_versions() {
+        let current = UndoVersionedBytes::encode(&Move { x: 1, y: 2 });
+        assert_eq!(current.decode::<Move>(), Some(Move { x: 1, y: 2 }));
+
+        let old = UndoVersionedBytes { version: 1, bytes: vec![3] };
+        assert_eq!(old.decode::<Move>(), Some(Move { x: 3, y: 0 }));
+
+        let newer = UndoVersionedBytes { version: 3, bytes: vec![1, 2] };
+        assert_eq!(newer.decode::<Move>(), None);
+    }
+}
@@ -0,0 +1,282 @@
+use std::time::Duration;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::Res;
+#[cfg(feature = "thumbnails")]
+use bevy::prelude::{Handle, Image};
+
+use crate::history::{UndoHistory, UndoHistoryEntry};
+use crate::meta::UndoMeta;
+use crate::search::{fuzzy_score, UndoSearchMatch};
+use crate::snapshot::UndoHistorySnapshot;
+
+/// Read-only metadata of a registered entry.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UndoEntryInfo<'a> {
+    /// The slot number, entries registered together share the same one.
+    pub group: usize,
+
+    /// The type name of the payload.
+    pub type_name: &'static str,
+
+    /// The elapsed time of the app when registered, zero without [`Time`](bevy::prelude::Time).
+    pub registered_at: Duration,
+
+    /// True if the entry can be redone after being undone.
+    pub redoable: bool,
+
+    pub meta: &'a UndoMeta,
+
+    /// Captured via [`AppUndoEx::set_undo_thumbnail_capture`](crate::prelude::AppUndoEx::set_undo_thumbnail_capture).
+    #[cfg(feature = "thumbnails")]
+    pub thumbnail: Option<&'a Handle<Image>>,
+}
+
+
+impl<'a> UndoEntryInfo<'a> {
+    /// Returns [`UndoMeta::tag`], which is used as the label in history UIs.
+    #[inline(always)]
+    pub fn label(&self) -> &'a str {
+        &self.meta.tag
+    }
+
+
+    /// Returns [`UndoMeta::user_data`] if it is of type `T`, such as a thumbnail shown in history UIs.
+    #[inline(always)]
+    pub fn user_data<T: std::any::Any>(&self) -> Option<&'a T> {
+        self.meta.user_data()
+    }
+}
+
+
+impl<'a> From<&'a UndoHistoryEntry> for UndoEntryInfo<'a> {
+    #[inline]
+    fn from(entry: &'a UndoHistoryEntry) -> Self {
+        Self {
+            group: entry.no,
+            type_name: entry.type_name,
+            registered_at: entry.registered_at,
+            redoable: entry.redoable,
+            meta: &entry.meta,
+            #[cfg(feature = "thumbnails")]
+            thumbnail: entry.thumbnail.as_ref(),
+        }
+    }
+}
+
+
+/// Lists the entries of the history without mutable access, for building history UIs.
+#[derive(SystemParam)]
+pub struct UndoView<'w> {
+    history: Res<'w, UndoHistory>,
+}
+
+
+impl<'w> UndoView<'w> {
+    /// Iterates the entries which can be undone, from the oldest.
+    #[inline]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = UndoEntryInfo<'_>> {
+        self.history.entries().map(UndoEntryInfo::from)
+    }
+
+
+    /// Iterates the entries which can be undone, from the newest.
+    #[inline]
+    pub fn iter_rev(&self) -> impl Iterator<Item = UndoEntryInfo<'_>> {
+        self.iter().rev()
+    }
+
+
+    /// Iterates the entries carrying the tag, from the oldest.
+    ///
+    /// See [`UndoMeta::has_tag`].
+    #[inline]
+    pub fn iter_tag<'a>(&'a self, tag: &'a str) -> impl DoubleEndedIterator<Item = UndoEntryInfo<'a>> {
+        self.iter().filter(move |entry| entry.meta.has_tag(tag))
+    }
+
+
+    /// Iterates the entries whose payload is of type `E`, from the oldest.
+    #[inline]
+    pub fn iter_type<E: 'static>(&self) -> impl DoubleEndedIterator<Item = UndoEntryInfo<'_>> {
+        let type_name = std::any::type_name::<E>();
+        self.iter().filter(move |entry| entry.type_name == type_name)
+    }
+
+
+    /// Returns the count of entries of type `E` which can be undone, independent of entries of other types.
+    ///
+    /// Entries registered together are each counted.
+    #[inline]
+    pub fn depth<E: 'static>(&self) -> usize {
+        self.iter_type::<E>().count()
+    }
+
+
+    /// Returns the count of entries of type `E` which can be redone.
+    #[inline]
+    pub fn redo_depth<E: 'static>(&self) -> usize {
+        let type_name = std::any::type_name::<E>();
+        self.iter_redo().filter(|entry| entry.type_name == type_name).count()
+    }
+
+
+    /// Returns the entries which can be undone, ordered from the oldest.
+    #[inline]
+    pub fn entries(&self) -> Vec<UndoEntryInfo<'_>> {
+        self.iter().collect()
+    }
+
+
+    /// Finds the entries which can be undone whose label or tags match each word of the query,
+    /// such as for jumping back to an entry from a command palette.
+    ///
+    /// Words may match loosely, their characters only having to appear in order and ignoring case.
+    /// The matches are ordered from the closest, then from the newest.
+    pub fn find(&self, query: &str) -> Vec<UndoSearchMatch<'_>> {
+        let mut found: Vec<UndoSearchMatch> = self
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let score = query
+                    .split_whitespace()
+                    .map(|word| {
+                        std::iter::once(&entry.meta.tag)
+                            .chain(entry.meta.tags.iter())
+                            .filter_map(|text| fuzzy_score(word, text))
+                            .max()
+                    })
+                    .sum::<Option<usize>>()?;
+                Some(UndoSearchMatch {
+                    index,
+                    score,
+                    entry,
+                })
+            })
+            .collect();
+        found.sort_by(|a, b| b.score.cmp(&a.score).then(b.index.cmp(&a.index)));
+        found
+    }
+
+
+    /// Copies the metadata of the entries which can be undone, for comparing it later via [`UndoHistorySnapshot::diff`].
+    #[inline]
+    pub fn snapshot(&self) -> UndoHistorySnapshot {
+        UndoHistorySnapshot {
+            entries: self.iter().map(Into::into).collect(),
+        }
+    }
+
+
+    /// Iterates the entries which can be redone, from the most recently undone.
+    #[inline]
+    pub fn iter_redo(&self) -> impl DoubleEndedIterator<Item = UndoEntryInfo<'_>> {
+        self.history.redo_entries().map(UndoEntryInfo::from)
+    }
+
+
+    /// Returns the entries which can be redone, ordered from the most recently undone.
+    #[inline]
+    pub fn redo_entries(&self) -> Vec<UndoEntryInfo<'_>> {
+        self.iter_redo().collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::{App, Startup};
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{IntoScheduleConfigs, Message};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoMeta, UndoScheduler, UndoView};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone)]
+    struct Move;
+
+    #[derive(Message, Clone)]
+    struct Paint;
+
+
+    #[test]
+    fn list_entries_in_order() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.add_undo_event::<Paint>();
+        app.add_systems(Startup, |mut moves: UndoScheduler<Move>| {
+            moves.register_with_meta(Move, UndoMeta::tagged("move"));
+        });
+        app.update();
+        let mut state = SystemState::<UndoScheduler<Paint>>::new(app.world_mut());
+        state.get_mut(app.world_mut()).unwrap().register_with_meta(Paint, UndoMeta::tagged("paint"));
+        app.update();
+
+        let mut state = SystemState::<UndoView>::new(app.world_mut());
+        let view = state.get(app.world()).unwrap();
+        let entries = view.entries();
+        let labels: Vec<&str> = entries.iter().map(|entry| entry.label()).collect();
+        assert_eq!(labels, vec!["move", "paint"]);
+        assert!(entries[1].type_name.ends_with("Paint"));
+        assert!(entries[0].group < entries[1].group);
+        assert!(view.redo_entries().is_empty());
+
+        assert_eq!(view.iter_rev().next().map(|entry| entry.label()), Some("paint"));
+        assert_eq!(view.iter_tag("move").count(), 1);
+        assert_eq!(view.iter_type::<Paint>().map(|entry| entry.label()).collect::<Vec<_>>(), vec!["paint"]);
+    }
+
+
+    #[test]
+    fn depth_per_type() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Move>();
+        app.add_undo_event::<Paint>();
+        app.add_systems(Startup, (
+            |mut moves: UndoScheduler<Move>| moves.register_all([Move, Move]),
+            |mut paints: UndoScheduler<Paint>| paints.push(Paint, Some(Paint), UndoMeta::default()),
+        ).chain());
+        app.update();
+
+        let mut state = SystemState::<UndoView>::new(app.world_mut());
+        assert_eq!(state.get(app.world()).unwrap().depth::<Move>(), 2);
+        assert_eq!(state.get(app.world()).unwrap().depth::<Paint>(), 1);
+
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        let view = state.get(app.world()).unwrap();
+        assert_eq!((view.depth::<Move>(), view.depth::<Paint>()), (2, 0));
+        assert_eq!((view.redo_depth::<Move>(), view.redo_depth::<Paint>()), (0, 1));
+    }
+
+
+    #[test]
+    fn retrieve_user_data() {
+        #[derive(Debug, PartialEq)]
+        struct Thumbnail(Vec<u8>);
+
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Paint>();
+        app.add_systems(Startup, |mut paints: UndoScheduler<Paint>| {
+            paints.push(Paint, Some(Paint), UndoMeta::tagged("paint").with_user_data(Thumbnail(vec![1, 2])));
+            paints.register(Paint);
+        });
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().write_message(RequestUndoEvent::Redo(UndoChannel::DEFAULT));
+        app.update();
+
+        let mut state = SystemState::<UndoView>::new(app.world_mut());
+        let view = state.get(app.world()).unwrap();
+        let entries = view.entries();
+        assert_eq!(entries[0].user_data::<Thumbnail>(), Some(&Thumbnail(vec![1, 2])));
+        assert_eq!(entries[0].user_data::<String>(), None);
+        assert!(view.redo_entries().is_empty());
+    }
+}
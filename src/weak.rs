@@ -0,0 +1,200 @@
+use bevy::ecs::entity::Entities;
+use bevy::prelude::{Entity, Message, MessageWriter, ResMut, Resource};
+use bevy::platform::collections::HashSet;
+
+use crate::DispatchUndoEvent;
+use crate::counter::UndoCounter;
+use crate::history::UndoHistory;
+
+/// An entity an entry depends on, declared via [`UndoMeta::with_weak_ref`](crate::prelude::UndoMeta::with_weak_ref).
+///
+/// Once the entity is despawned, the entries holding it are invalidated and [`UndoEntryInvalidated`] is sent,
+/// so their handlers never run against a missing entity.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+#[repr(transparent)]
+pub struct WeakEntityRef(pub Entity);
+
+
+impl From<Entity> for WeakEntityRef {
+    #[inline(always)]
+    fn from(entity: Entity) -> Self {
+        Self(entity)
+    }
+}
+
+
+/// Sent once when an entry is invalidated since an entity it holds via [`WeakEntityRef`] has been despawned.
+///
+/// The entry is pruned if configured via [`AppUndoEx::prune_invalid_undo_entries`](crate::prelude::AppUndoEx::prune_invalid_undo_entries),
+/// otherwise it stays in the history and the requests reaching it are dropped with [`UndoDenied`](crate::prelude::UndoDenied).
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoEntryInvalidated {
+    /// The slot of the entry.
+    pub no: usize,
+    pub entity: Entity,
+}
+
+
+/// Sent when an entry is dropped instead of undone since an entity declared via
+/// [`UndoMeta::with_checked_entity`](crate::prelude::UndoMeta::with_checked_entity) has been despawned
+/// and its id reused by another entity.
+#[derive(Message, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UndoEntrySkipped {
+    /// The slot of the entry.
+    pub no: usize,
+    pub entity: Entity,
+}
+
+
+/// Returns the first entity of the slot whose id is now held by another live entity.
+pub(crate) fn reused_entity(entities: &Entities, history: &UndoHistory, no: usize) -> Option<Entity> {
+    history
+        .slot_entries(no)
+        .flat_map(|entry| entry.meta.checked_entities.iter().copied())
+        .find(|entity| {
+            let current = entities.resolve_from_index(entity.index());
+            current != *entity && entities.contains_spawned(current)
+        })
+}
+
+
+#[derive(Resource, Debug, Default)]
+pub(crate) struct UndoWeakRefs {
+    pub prune: bool,
+    invalid: HashSet<usize>,
+}
+
+
+impl UndoWeakRefs {
+    /// Returns the first invalidated slot among the slots.
+    #[inline]
+    pub fn invalid_among(&self, slots: &[usize]) -> Option<usize> {
+        slots.iter().copied().find(|no| self.invalid.contains(no))
+    }
+}
+
+
+/// Invalidates the entries holding despawned entities, pruning them if configured.
+pub(crate) fn invalidate_weak_refs_system(
+    mut weak_refs: ResMut<UndoWeakRefs>,
+    mut history: ResMut<UndoHistory>,
+    mut counter: ResMut<UndoCounter>,
+    mut invalidated: MessageWriter<UndoEntryInvalidated>,
+    mut ew: MessageWriter<DispatchUndoEvent>,
+    entities: &Entities,
+) {
+    let weak_refs = &mut *weak_refs;
+    weak_refs.invalid.retain(|no| history.slot_len(*no) > 0);
+
+    let despawned: Vec<(usize, Entity)> = history
+        .entries()
+        .filter(|entry| !weak_refs.invalid.contains(&entry.no))
+        .filter_map(|entry| entry
+            .meta
+            .weak_refs
+            .iter()
+            .find(|weak| !entities.contains(weak.0))
+            .map(|weak| (entry.no, weak.0)))
+        .collect();
+    if despawned.is_empty() {
+        return;
+    }
+
+    for (no, entity) in despawned {
+        if !weak_refs.invalid.insert(no) {
+            continue;
+        }
+        invalidated.write(UndoEntryInvalidated { no, entity });
+        if weak_refs.prune {
+            history.remove_slot(no);
+            ew.write(DispatchUndoEvent::Discard(no));
+        }
+    }
+    if weak_refs.prune {
+        weak_refs.invalid.clear();
+        counter.set(history.max_no().unwrap_or_default());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+
+    use crate::prelude::{AppUndoEx, UndoChannel, UndoDenied, UndoEntryInvalidated, UndoEntrySkipped, UndoMeta, UndoScheduler};
+    use crate::request::RequestUndoEvent;
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Recolor(u32);
+
+
+    fn new_app(prune: bool) -> App {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Recolor>();
+        app.prune_invalid_undo_entries(prune);
+
+        let target = app.world_mut().spawn_empty().id();
+        let mut state = SystemState::<UndoScheduler<Recolor>>::new(app.world_mut());
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.register(Recolor(1));
+        scheduler.register_with_meta(Recolor(2), UndoMeta::default().with_weak_ref(target));
+        state.apply(app.world_mut());
+        app.update();
+
+        app.world_mut().despawn(target);
+        app.update();
+        let invalidated: Vec<UndoEntryInvalidated> = app.world_mut().resource_mut::<Messages<UndoEntryInvalidated>>().drain().collect();
+        assert_eq!(invalidated, vec![UndoEntryInvalidated { no: 2, entity: target }]);
+        app
+    }
+
+
+    fn undo(app: &mut App) -> Vec<Recolor> {
+        app.world_mut().write_message(RequestUndoEvent::Latest(UndoChannel::DEFAULT));
+        app.update();
+        app.world_mut().resource_mut::<Messages<Recolor>>().drain().collect()
+    }
+
+
+    #[test]
+    fn deny_undoing_invalidated_entry() {
+        let mut app = new_app(false);
+        assert!(undo(&mut app).is_empty());
+        assert_eq!(app.world_mut().resource_mut::<Messages<UndoDenied>>().drain().count(), 1);
+    }
+
+
+    #[test]
+    fn prune_invalidated_entry() {
+        let mut app = new_app(true);
+        assert_eq!(undo(&mut app), vec![Recolor(1)]);
+    }
+
+
+    #[test]
+    fn skip_entry_with_reused_entity() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Recolor>();
+
+        let target = app.world_mut().spawn_empty().id();
+        let mut state = SystemState::<UndoScheduler<Recolor>>::new(app.world_mut());
+        let mut scheduler = state.get_mut(app.world_mut()).unwrap();
+        scheduler.register(Recolor(1));
+        scheduler.register_with_meta(Recolor(2), UndoMeta::default().with_checked_entity(target));
+        state.apply(app.world_mut());
+        app.update();
+
+        let reused = app.world_mut().despawn_no_free(target).unwrap();
+        app.world_mut().spawn_empty_at(reused).unwrap();
+        assert_eq!(reused.index(), target.index());
+        assert!(undo(&mut app).is_empty());
+        let skipped: Vec<UndoEntrySkipped> = app.world_mut().resource_mut::<Messages<UndoEntrySkipped>>().drain().collect();
+        assert_eq!(skipped, vec![UndoEntrySkipped { no: 2, entity: target }]);
+        assert_eq!(undo(&mut app), vec![Recolor(1)]);
+    }
+}
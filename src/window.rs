@@ -0,0 +1,75 @@
+use bevy::prelude::{Changed, Entity, Query, RemovedComponents};
+use bevy::window::Window;
+
+use crate::channel::UndoChannel;
+use crate::document::UndoDocuments;
+
+/// Keeps a document per window, activating the focused one and closing the ones of closed windows.
+pub(crate) fn track_focused_window_system(
+    windows: Query<(Entity, &Window), Changed<Window>>,
+    mut closed: RemovedComponents<Window>,
+    mut documents: UndoDocuments,
+) {
+    for window in closed.read() {
+        documents.close(UndoChannel::for_window(window));
+    }
+    for (entity, window) in windows.iter() {
+        let channel = UndoChannel::for_window(entity);
+        documents.create(channel);
+        if window.focused {
+            documents.set_active(channel);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Message, Messages};
+    use bevy::window::Window;
+
+    use crate::prelude::{AppUndoEx, UndoRequester, UndoScheduler};
+    use crate::UndoPlugin;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Edit(&'static str);
+
+
+    fn undone(app: &mut App) -> Vec<Edit> {
+        app.world_mut().resource_mut::<Messages<Edit>>().drain().collect()
+    }
+
+
+    #[test]
+    fn route_to_focused_window() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_event::<Edit>();
+        app.enable_undo_windows();
+        let scene = app.world_mut().spawn(Window::default()).id();
+        let material = app.world_mut().spawn(Window {
+            focused: false,
+            ..Window::default()
+        }).id();
+        let mut scheduler = SystemState::<UndoScheduler<Edit>>::new(app.world_mut());
+        let mut requester = SystemState::<UndoRequester>::new(app.world_mut());
+
+        app.update();
+        scheduler.get_mut(app.world_mut()).unwrap().register(Edit("move cube"));
+        app.update();
+        app.world_mut().get_mut::<Window>(scene).unwrap().focused = false;
+        app.world_mut().get_mut::<Window>(material).unwrap().focused = true;
+        app.update();
+        scheduler.get_mut(app.world_mut()).unwrap().register(Edit("tint"));
+        app.update();
+
+        requester.get_mut(app.world_mut()).unwrap().undo_for_window(scene);
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("move cube")]);
+        requester.get_mut(app.world_mut()).unwrap().undo();
+        app.update();
+        assert_eq!(undone(&mut app), vec![Edit("tint")]);
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::ecs::system::SystemState;
+use bevy::prelude::{Mut, Resource, World};
+
+use crate::meta::UndoMeta;
+use crate::payload::UndoPayload;
+use crate::undo_event::UndoScheduler;
+
+/// The scheduler state of `E` kept across the calls of [`WorldUndoEx::undo_scheduler`].
+#[derive(Resource)]
+struct UndoWorldScheduler<E: UndoPayload>(SystemState<UndoScheduler<'static, E>>);
+
+
+/// Registers undo-events from exclusive systems and commands, which own the [`World`] and cannot take [`UndoScheduler`].
+///
+/// The entries go through the same counter, groupings and reservations as the ones registered via [`UndoScheduler`].
+pub trait WorldUndoEx {
+    /// Calls the function with the [`UndoScheduler`] of `E`, such as to reserve entries or register them with a redo-event.
+    fn undo_scheduler<E: UndoPayload, R>(&mut self, f: impl FnOnce(&mut UndoScheduler<E>) -> R) -> R;
+
+
+    /// Like [`UndoScheduler::register`].
+    #[inline]
+    fn register_undo<E: UndoPayload>(&mut self, event: E) {
+        self.undo_scheduler(|scheduler: &mut UndoScheduler<E>| scheduler.register(event));
+    }
+
+
+    /// Like [`UndoScheduler::register_with_meta`].
+    #[inline]
+    fn register_undo_with_meta<E: UndoPayload>(&mut self, event: E, meta: UndoMeta) {
+        self.undo_scheduler(|scheduler: &mut UndoScheduler<E>| scheduler.register_with_meta(event, meta));
+    }
+}
+
+
+impl WorldUndoEx for World {
+    fn undo_scheduler<E: UndoPayload, R>(&mut self, f: impl FnOnce(&mut UndoScheduler<E>) -> R) -> R {
+        if !self.contains_resource::<UndoWorldScheduler<E>>() {
+            let state = SystemState::new(self);
+            self.insert_resource(UndoWorldScheduler::<E>(state));
+        }
+        self.resource_scope(|world, mut scheduler: Mut<UndoWorldScheduler<E>>| {
+            let output = f(&mut scheduler.0.get_mut(world).expect("the resources of UndoPlugin are missing"));
+            scheduler.0.apply(world);
+            output
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::Startup;
+    use bevy::prelude::{Message, World};
+
+    use crate::prelude::{UndoMeta, WorldUndoEx};
+    use crate::testing::UndoTestHarness;
+
+    #[derive(Message, Clone, Debug, PartialEq)]
+    struct Move(i32);
+
+
+    #[test]
+    fn register_from_exclusive_systems() {
+        let mut harness = UndoTestHarness::<Move>::new();
+        harness.setup(|app| {
+            app.add_systems(Startup, |world: &mut World| {
+                world.register_undo(Move(1));
+                world.register_undo_with_meta(Move(2), UndoMeta::tagged("second"));
+            });
+        });
+        harness.frames(1);
+        harness.app().world_mut().register_undo(Move(3));
+        harness.frames(1);
+        #[cfg(feature = "reserve")]
+        {
+            harness.app().world_mut().undo_scheduler::<Move, _>(|scheduler| {
+                scheduler.reserve(Move(4));
+                scheduler.reserve(Move(5));
+                scheduler.register_all_reserved();
+            });
+            harness.frames(1);
+            harness.undo();
+            harness.expect([Move(5), Move(4)]);
+        }
+
+        harness.undo();
+        harness.undo();
+        harness.undo();
+        harness.expect([Move(3), Move(2), Move(1)]);
+    }
+}
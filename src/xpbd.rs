@@ -0,0 +1,186 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{Commands, Entity, Message, MessageReader, Query, Transform};
+use avian3d::prelude::{AngularVelocity, ConstantForce, ConstantTorque, LinearVelocity, Position, Rotation, SleepTimer, Sleeping};
+
+use crate::meta::UndoMeta;
+use crate::undo_event::UndoScheduler;
+
+type UndoXpbdComponents<'a> = (
+    &'a mut Transform,
+    Option<&'a mut Position>,
+    Option<&'a mut Rotation>,
+    Option<&'a mut LinearVelocity>,
+    Option<&'a mut AngularVelocity>,
+    Option<&'a mut ConstantForce>,
+    Option<&'a mut ConstantTorque>,
+    Option<&'a mut SleepTimer>,
+    Option<&'a Sleeping>,
+);
+
+
+/// The transform of a rigid body together with the avian components driving its motion.
+///
+/// [`Position`] and [`Rotation`] are kept along with the transform, since avian copies them into it.
+/// Components the body does not have are left out, and are not inserted when restored, except for the [`Sleeping`] marker.
+#[derive(Debug, Clone)]
+pub struct UndoXpbdSnapshot {
+    pub entity: Entity,
+    pub transform: Transform,
+    pub position: Option<Position>,
+    pub rotation: Option<Rotation>,
+    pub linear_velocity: Option<LinearVelocity>,
+    pub angular_velocity: Option<AngularVelocity>,
+    pub force: Option<ConstantForce>,
+    pub torque: Option<ConstantTorque>,
+    pub time_sleeping: Option<SleepTimer>,
+    pub sleeping: bool,
+}
+
+
+/// Restores the rigid body to the snapshot when undone or redone.
+///
+/// Registered via [`UndoXpbdPhysics::register`], and applied automatically once set up via
+/// [`AppUndoEx::add_undo_xpbd_physics`](crate::prelude::AppUndoEx::add_undo_xpbd_physics).
+#[derive(Message, Debug, Clone)]
+pub struct UndoXpbdEvent(pub UndoXpbdSnapshot);
+
+
+/// Captures rigid bodies simulated by avian, the successor of bevy_xpbd, before they are edited, such as moved by a gizmo, and registers the edits,
+/// so undoing them restores the velocities, pending forces and the sleeping state along with the positions.
+#[derive(SystemParam)]
+pub struct UndoXpbdPhysics<'w, 's> {
+    bodies: Query<'w, 's, UndoXpbdComponents<'static>>,
+    scheduler: UndoScheduler<'w, UndoXpbdEvent>,
+}
+
+
+impl<'w, 's> UndoXpbdPhysics<'w, 's> {
+    /// Returns the current state of the body, or `None` if the entity has no [`Transform`].
+    pub fn capture(&self, entity: Entity) -> Option<UndoXpbdSnapshot> {
+        let (transform, position, rotation, linear_velocity, angular_velocity, force, torque, time_sleeping, sleeping) = self.bodies.get(entity).ok()?;
+        Some(UndoXpbdSnapshot {
+            entity,
+            transform: *transform,
+            position: position.copied(),
+            rotation: rotation.copied(),
+            linear_velocity: linear_velocity.copied(),
+            angular_velocity: angular_velocity.copied(),
+            force: force.cloned(),
+            torque: torque.cloned(),
+            time_sleeping: time_sleeping.copied(),
+            sleeping: sleeping.is_some(),
+        })
+    }
+
+
+    /// Registers the edit of the body captured before it, which is undone by restoring the snapshot
+    /// and redone by restoring the state of the body now.
+    #[inline]
+    pub fn register(&mut self, before: UndoXpbdSnapshot) {
+        self.register_with_meta(before, UndoMeta::default());
+    }
+
+
+    /// Like [`UndoXpbdPhysics::register`], together with its [`UndoMeta`], to which the entity is added.
+    pub fn register_with_meta(&mut self, before: UndoXpbdSnapshot, meta: UndoMeta) {
+        let Some(after) = self.capture(before.entity) else {
+            return;
+        };
+        let meta = if meta.entities.contains(&before.entity) {
+            meta
+        } else {
+            meta.with_entity(before.entity)
+        };
+        self.scheduler.register_with_redo_and_meta(UndoXpbdEvent(before), UndoXpbdEvent(after), meta);
+    }
+}
+
+
+pub(crate) fn restore_xpbd_system(
+    mut commands: Commands,
+    mut er: MessageReader<UndoXpbdEvent>,
+    mut bodies: Query<UndoXpbdComponents>,
+) {
+    for UndoXpbdEvent(snapshot) in er.read() {
+        let Ok((mut transform, position, rotation, linear_velocity, angular_velocity, force, torque, time_sleeping, sleeping)) = bodies.get_mut(snapshot.entity) else {
+            continue;
+        };
+        *transform = snapshot.transform;
+        if let (Some(mut position), Some(value)) = (position, snapshot.position) {
+            *position = value;
+        }
+        if let (Some(mut rotation), Some(value)) = (rotation, snapshot.rotation) {
+            *rotation = value;
+        }
+        if let (Some(mut linear_velocity), Some(value)) = (linear_velocity, snapshot.linear_velocity) {
+            *linear_velocity = value;
+        }
+        if let (Some(mut angular_velocity), Some(value)) = (angular_velocity, snapshot.angular_velocity) {
+            *angular_velocity = value;
+        }
+        if let (Some(mut force), Some(value)) = (force, snapshot.force.clone()) {
+            *force = value;
+        }
+        if let (Some(mut torque), Some(value)) = (torque, snapshot.torque.clone()) {
+            *torque = value;
+        }
+        if let (Some(mut time_sleeping), Some(value)) = (time_sleeping, snapshot.time_sleeping) {
+            *time_sleeping = value;
+        }
+        match (sleeping.is_some(), snapshot.sleeping) {
+            (false, true) => {
+                commands.entity(snapshot.entity).insert(Sleeping);
+            }
+            (true, false) => {
+                commands.entity(snapshot.entity).remove::<Sleeping>();
+            }
+            _ => {}
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use bevy::ecs::system::SystemState;
+    use bevy::prelude::{Transform, Vec3};
+    use avian3d::prelude::{LinearVelocity, Position, Sleeping};
+
+    use crate::prelude::{AppUndoEx, UndoRequester, UndoXpbdPhysics};
+    use crate::UndoPlugin;
+
+    fn request(app: &mut App, f: impl FnOnce(&mut UndoRequester)) {
+        let mut state = SystemState::<UndoRequester>::new(app.world_mut());
+        f(&mut state.get_mut(app.world_mut()).unwrap());
+        app.update();
+    }
+
+
+    #[test]
+    fn restore_position_and_wake_body() {
+        let mut app = App::new();
+        app.add_plugins(UndoPlugin);
+        app.add_undo_xpbd_physics();
+        let crate_body = app.world_mut().spawn((Transform::default(), Position::default(), LinearVelocity::ZERO)).id();
+        let mut physics = SystemState::<UndoXpbdPhysics>::new(app.world_mut());
+
+        let before = physics.get_mut(app.world_mut()).unwrap().capture(crate_body).unwrap();
+        app.world_mut().get_mut::<Position>(crate_body).unwrap().0 = Vec3::X * 4.;
+        app.world_mut().get_mut::<LinearVelocity>(crate_body).unwrap().0 = Vec3::X * 8.;
+        physics.get_mut(app.world_mut()).unwrap().register(before);
+        app.update();
+        // The body falls asleep after the edit until undone.
+        app.world_mut().get_mut::<LinearVelocity>(crate_body).unwrap().0 = Vec3::ZERO;
+        app.world_mut().entity_mut(crate_body).insert(Sleeping);
+
+        request(&mut app, |requester| requester.undo());
+        assert_eq!(*app.world().get::<Position>(crate_body).unwrap(), Position::default());
+        assert_eq!(*app.world().get::<LinearVelocity>(crate_body).unwrap(), LinearVelocity::ZERO);
+        assert!(app.world().get::<Sleeping>(crate_body).is_none());
+
+        request(&mut app, |requester| requester.redo());
+        assert_eq!(app.world().get::<Position>(crate_body).unwrap().0, Vec3::X * 4.);
+        assert_eq!(app.world().get::<LinearVelocity>(crate_body).unwrap().0, Vec3::X * 8.);
+    }
+}